@@ -0,0 +1,288 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// adbd 的 TCP 调试端口
+pub const ADB_PORT: u16 = 5555;
+/// 限制 ADB 端口只允许 LAN 访问的专用链
+pub const RESTRICT_CHAIN: &str = "ZXPING_ADB";
+/// /proc/net/tcp 中 LISTEN 状态的值
+const TCP_LISTEN: &str = "0A";
+/// 读取 iptables -S INPUT 输出的上限，超过时不判断（按没有检查处理）
+pub const MAX_RULES_BYTES: usize = 256 * 1024;
+
+/// 解析 /proc/net/tcp 或 /proc/net/tcp6，返回在 port 上监听的本地地址。
+/// local_address 为 `地址:端口`，地址是按本机字节序打印的 32 位整数（IPv6 为 4 个），端口为普通十六进制
+pub fn parse_listeners(content: &str, port: u16) -> Vec<IpAddr> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let local = fields.nth(1)?;
+            let state = fields.nth(1)?;
+            if state != TCP_LISTEN {
+                return None;
+            }
+            let (addr, local_port) = local.split_once(':')?;
+            if u16::from_str_radix(local_port, 16).ok()? != port {
+                return None;
+            }
+            parse_addr(addr)
+        })
+        .collect()
+}
+
+fn parse_addr(hex: &str) -> Option<IpAddr> {
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..hex.len()).step_by(8) {
+        let word = u32::from_str_radix(hex.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    match bytes.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ))),
+        16 => {
+            let octets: [u8; 16] = bytes.try_into().ok()?;
+            let v6 = Ipv6Addr::from(octets);
+            // ::ffff:a.b.c.d 按 IPv4 处理
+            Some(
+                v6.to_ipv4_mapped()
+                    .map(IpAddr::V4)
+                    .unwrap_or(IpAddr::V6(v6)),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// iptables -S 的输出中是否已有规则过滤这个端口：丢弃、拒绝或跳到专用链（ACCEPT 之类的不算）
+pub fn firewall_covers(rules: &str, port: u16) -> bool {
+    let port = port.to_string();
+    rules
+        .lines()
+        .filter(|line| line.starts_with("-A "))
+        .any(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let matches_port = words
+                .windows(2)
+                .any(|pair| pair[0] == "--dport" && pair[1] == port);
+            let filters = words.windows(2).any(|pair| {
+                pair[0] == "-j" && matches!(pair[1], "DROP" | "REJECT" | RESTRICT_CHAIN)
+            });
+            matches_port && filters
+        })
+}
+
+/// 安装专用链：LAN 网段和本机回环放行，其余丢弃；INPUT 中已有跳转时不重复添加
+pub fn restrict_commands(lan: &str, port: u16) -> Vec<String> {
+    let jump = format!("INPUT -p tcp --dport {} -j {}", port, RESTRICT_CHAIN);
+    vec![
+        format!(
+            "iptables -N {} 2>/dev/null; iptables -F {}",
+            RESTRICT_CHAIN, RESTRICT_CHAIN
+        ),
+        format!("iptables -A {} -i lo -j RETURN", RESTRICT_CHAIN),
+        format!("iptables -A {} -s {} -j RETURN", RESTRICT_CHAIN, lan),
+        format!("iptables -A {} -j DROP", RESTRICT_CHAIN),
+        format!("iptables -C {} 2>/dev/null || iptables -I {}", jump, jump),
+    ]
+}
+
+/// 一次检查的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    /// 在 ADB 端口上监听的本地地址
+    pub listeners: Vec<IpAddr>,
+    /// iptables 中已有过滤该端口的规则；没有检查（未启用 iptables）时为 None
+    pub covered: Option<bool>,
+    /// 同上，ip6tables（只在监听 :: 时检查）
+    pub covered6: Option<bool>,
+}
+
+impl AuditReport {
+    /// 监听在所有地址上（0.0.0.0 或 ::）
+    pub fn wildcard(&self) -> bool {
+        self.listeners.iter().any(|addr| addr.is_unspecified())
+    }
+
+    /// 监听在所有 IPv6 地址上（::）
+    pub fn wildcard6(&self) -> bool {
+        self.listeners
+            .iter()
+            .any(|addr| addr.is_ipv6() && addr.is_unspecified())
+    }
+
+    /// 监听在所有地址上且没有防火墙规则覆盖（没检查防火墙时按暴露处理）。
+    /// 监听 :: 时 IPv4 也能连上（双栈），IPv4 和 IPv6 都要过滤
+    pub fn exposed(&self) -> bool {
+        (self.wildcard() && self.covered != Some(true))
+            || (self.wildcard6() && self.covered6 != Some(true))
+    }
+
+    /// `listen=0.0.0.0:5555 firewall=none exposed=yes`
+    pub fn describe(&self) -> String {
+        let listen = if self.listeners.is_empty() {
+            "-".to_string()
+        } else {
+            self.listeners
+                .iter()
+                .map(|addr| match addr {
+                    IpAddr::V4(v4) => format!("{}:{}", v4, ADB_PORT),
+                    IpAddr::V6(v6) => format!("[{}]:{}", v6, ADB_PORT),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let firewall = |covered: Option<bool>| match covered {
+            Some(true) => "rule",
+            Some(false) => "none",
+            None => "unchecked",
+        };
+        let mut line = format!("listen={} firewall={}", listen, firewall(self.covered));
+        if self.wildcard6() {
+            line.push_str(&format!(" firewall6={}", firewall(self.covered6)));
+        }
+        line.push_str(&format!(
+            " exposed={}",
+            if self.exposed() { "yes" } else { "no" }
+        ));
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:15B3 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1234 1 c0a1 100 0 0 10 0
+   1: 0100007F:15B3 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1235 1 c0a2 100 0 0 10 0
+   2: 0100A8C0:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1236 1 c0a3 100 0 0 10 0
+   3: 0100A8C0:15B3 0200A8C0:C350 01 00000000:00000000 00:00000000 00000000     0        0 1237 1 c0a4 20 4 30 10 -1
+";
+
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000000000000:15B3 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2234 1 c0b1 100 0 0 10 0
+   1: 00000000000000000000000001000000:15B3 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2235 1 c0b2 100 0 0 10 0
+   2: 0000000000000000FFFF00000100A8C0:15B3 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2236 1 c0b3 100 0 0 10 0
+";
+
+    #[test]
+    fn test_parse_listeners() {
+        // 只要 LISTEN 状态、端口 5555 的行（第 3 行是已建立的连接）
+        assert_eq!(
+            parse_listeners(TCP, ADB_PORT),
+            vec![
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V4(Ipv4Addr::LOCALHOST)
+            ]
+        );
+        assert_eq!(
+            parse_listeners(TCP, 80),
+            vec![IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1))]
+        );
+        assert_eq!(
+            parse_listeners(TCP6, ADB_PORT),
+            vec![
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                IpAddr::V6(Ipv6Addr::LOCALHOST),
+                IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+            ]
+        );
+        assert!(parse_listeners("", ADB_PORT).is_empty());
+        assert!(parse_listeners("header\n   0: garbage\n", ADB_PORT).is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let report = AuditReport {
+            listeners: parse_listeners(TCP, ADB_PORT),
+            covered: Some(false),
+            covered6: None,
+        };
+        assert!(report.exposed());
+        assert_eq!(
+            report.describe(),
+            "listen=0.0.0.0:5555,127.0.0.1:5555 firewall=none exposed=yes"
+        );
+
+        let loopback = AuditReport {
+            listeners: vec![IpAddr::V6(Ipv6Addr::LOCALHOST)],
+            covered: None,
+            covered6: None,
+        };
+        assert!(!loopback.exposed());
+        assert_eq!(
+            loopback.describe(),
+            "listen=[::1]:5555 firewall=unchecked exposed=no"
+        );
+
+        let filtered = AuditReport {
+            covered: Some(true),
+            ..report
+        };
+        assert!(filtered.wildcard() && !filtered.exposed());
+
+        // 监听 :: 时只有 iptables 规则不够
+        let dual = AuditReport {
+            listeners: vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)],
+            covered: Some(true),
+            covered6: Some(false),
+        };
+        assert!(dual.exposed());
+        assert_eq!(
+            dual.describe(),
+            "listen=[::]:5555 firewall=rule firewall6=none exposed=yes"
+        );
+        assert!(!AuditReport {
+            covered6: Some(true),
+            ..dual.clone()
+        }
+        .exposed());
+        assert!(AuditReport {
+            covered: Some(false),
+            covered6: Some(true),
+            ..dual
+        }
+        .exposed());
+    }
+
+    #[test]
+    fn test_firewall_covers() {
+        let rules = "-P INPUT ACCEPT\n\
+                     -A INPUT -i wan1 -p tcp -m tcp --dport 80 -j DROP\n";
+        assert!(!firewall_covers(rules, ADB_PORT));
+        let rules = format!(
+            "{}-A INPUT -p tcp -m tcp --dport 5555 -j ZXPING_ADB\n",
+            rules
+        );
+        assert!(firewall_covers(&rules, ADB_PORT));
+        assert!(!firewall_covers(
+            "-A INPUT -p tcp --dport 55555 -j DROP\n",
+            ADB_PORT
+        ));
+        // 放行的规则不算过滤
+        assert!(!firewall_covers(
+            "-A INPUT -p tcp -m tcp --dport 5555 -j ACCEPT\n",
+            ADB_PORT
+        ));
+        assert!(firewall_covers(
+            "-A INPUT -i wan1 -p tcp -m tcp --dport 5555 -j REJECT --reject-with tcp-reset\n",
+            ADB_PORT
+        ));
+    }
+
+    #[test]
+    fn test_restrict_commands() {
+        let commands = restrict_commands("192.168.0.0/24", ADB_PORT);
+        assert_eq!(
+            commands[2],
+            "iptables -A ZXPING_ADB -s 192.168.0.0/24 -j RETURN"
+        );
+        assert_eq!(
+            commands.last().unwrap(),
+            "iptables -C INPUT -p tcp --dport 5555 -j ZXPING_ADB 2>/dev/null \
+             || iptables -I INPUT -p tcp --dport 5555 -j ZXPING_ADB"
+        );
+    }
+}
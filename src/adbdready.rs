@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// Android USB gadget 的连接状态（DISCONNECTED、CONNECTED、CONFIGURED）
+pub const ANDROID_USB_STATE: &str = "/sys/class/android_usb/android0/state";
+
+/// 等待期满后仍未就绪时，每隔这么久再检查一次
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// 等待期满后最多再等这么久，仍未就绪时发送 ADBD_NOT_READY
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 一次就绪检查的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Readiness {
+    /// 进程在运行（且 USB 已枚举），内容为 ADBD_READY 的参数
+    Ready(String),
+    /// 还没就绪，稍后再查
+    Waiting,
+    /// 超时仍未就绪，内容为原因
+    NotReady(String),
+}
+
+/// adbd_ready_settle_ms：adbd 重启后等 USB gadget 在主机上重新枚举，确认就绪后发送 ADBD_READY，
+/// 主机端的工具据此重新连接
+pub struct AdbdReady {
+    settle: Duration,
+    check_usb: bool,
+    /// 下一次检查的时间，None 为没有在等待
+    due: Option<Instant>,
+    /// 等待期满的时间，超过 READY_TIMEOUT 后放弃
+    settled_at: Option<Instant>,
+}
+
+impl AdbdReady {
+    pub fn new(config: &Config) -> Self {
+        AdbdReady {
+            settle: config.adbd_ready_settle,
+            check_usb: config.adbd_ready_check_usb,
+            due: None,
+            settled_at: None,
+        }
+    }
+
+    /// adbd 重启成功后开始等待；settle 为 0 时不检查也不发送 ADBD_READY
+    pub fn arm(&mut self, now: Instant) {
+        if !self.settle.is_zero() {
+            self.due = Some(now + self.settle);
+            self.settled_at = Some(now + self.settle);
+        }
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.due.is_some_and(|due| now >= due)
+    }
+
+    /// 到期时检查一次：pid 为当前 adbd 进程，usb_state 为 ANDROID_USB_STATE 的内容（读不到时为 None）
+    pub fn check(&mut self, now: Instant, pid: Option<u32>, usb_state: Option<&str>) -> Readiness {
+        let usb_state = usb_state.map(str::trim);
+        let problem = match (pid, usb_state) {
+            (None, _) => Some("adbd not running".to_string()),
+            (Some(_), state) if self.check_usb && state != Some("CONFIGURED") => {
+                Some(format!("usb state {}", state.unwrap_or("unavailable")))
+            }
+            _ => None,
+        };
+        let waited = self
+            .settled_at
+            .map(|at| now.saturating_duration_since(at))
+            .unwrap_or_default();
+        let readiness = match (problem, pid) {
+            (None, Some(pid)) => {
+                let mut detail = format!(
+                    "PID={} SETTLE={}ms",
+                    pid,
+                    (self.settle + waited).as_millis()
+                );
+                if self.check_usb {
+                    detail.push_str(" USB=CONFIGURED");
+                }
+                Readiness::Ready(detail)
+            }
+            (Some(problem), _) if waited >= READY_TIMEOUT => Readiness::NotReady(problem),
+            _ => {
+                self.due = Some(now + RETRY_INTERVAL);
+                return Readiness::Waiting;
+            }
+        };
+        self.due = None;
+        self.settled_at = None;
+        readiness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adbd_ready() {
+        let config = Config {
+            adbd_ready_settle: Duration::from_secs(5),
+            adbd_ready_check_usb: true,
+            ..Config::default()
+        };
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut ready = AdbdReady::new(&config);
+        assert!(!ready.is_due(start));
+        ready.arm(start);
+        assert!(!ready.is_due(start + secs(4)));
+        assert!(ready.is_due(start + secs(5)));
+
+        // USB 还没枚举完：稍后再查
+        assert_eq!(
+            ready.check(start + secs(5), Some(321), Some("CONNECTED\n")),
+            Readiness::Waiting
+        );
+        assert!(!ready.is_due(start + secs(5)));
+        assert!(ready.is_due(start + secs(6)));
+        assert_eq!(
+            ready.check(start + secs(7), Some(321), Some("CONFIGURED\n")),
+            Readiness::Ready("PID=321 SETTLE=7000ms USB=CONFIGURED".to_string())
+        );
+        assert!(!ready.is_due(start + secs(60)));
+
+        // 超时仍没有进程
+        ready.arm(start);
+        assert_eq!(ready.check(start + secs(5), None, None), Readiness::Waiting);
+        assert_eq!(
+            ready.check(start + secs(5) + READY_TIMEOUT, None, None),
+            Readiness::NotReady("adbd not running".to_string())
+        );
+
+        // 不检查 USB；settle 为 0 时不等待
+        let mut config = config;
+        config.adbd_ready_check_usb = false;
+        let mut ready = AdbdReady::new(&config);
+        ready.arm(start);
+        assert_eq!(
+            ready.check(start + secs(5), Some(9), None),
+            Readiness::Ready("PID=9 SETTLE=5000ms".to_string())
+        );
+        config.adbd_ready_settle = Duration::ZERO;
+        let mut ready = AdbdReady::new(&config);
+        ready.arm(start);
+        assert!(!ready.is_due(start + secs(60)));
+    }
+}
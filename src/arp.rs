@@ -0,0 +1,170 @@
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{command, routes, sockmark};
+
+/// 触发地址解析时发往目标的端口（discard，一般没有服务监听）
+const TRIGGER_PORT: u16 = 9;
+/// 轮询邻居表的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// /proc/net/arp 中的 ATF_COM：地址解析已完成
+const ATF_COM: u32 = 0x2;
+const IP_NEIGH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 邻居表中目标的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Neighbor {
+    /// 已解析（REACHABLE/STALE 等）且 MAC 有效
+    Resolved(String),
+    /// 解析中或失败（INCOMPLETE/FAILED），或表中没有
+    Unresolved,
+}
+
+fn valid_mac(mac: &str) -> bool {
+    mac.len() == 17 && mac != "00:00:00:00:00:00"
+}
+
+/// 解析 /proc/net/arp：`IP address  HW type  Flags  HW address  Mask  Device`。
+/// FAILED 的条目仍在表中，但 Flags 没有 ATF_COM、MAC 全为 0
+pub fn parse_proc_arp(content: &str, target: Ipv4Addr) -> Neighbor {
+    for line in content.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || fields[0].parse::<Ipv4Addr>().ok() != Some(target) {
+            continue;
+        }
+        let flags = u32::from_str_radix(fields[2].trim_start_matches("0x"), 16).unwrap_or(0);
+        if flags & ATF_COM != 0 && valid_mac(fields[3]) {
+            return Neighbor::Resolved(fields[3].to_string());
+        }
+    }
+    Neighbor::Unresolved
+}
+
+/// 解析 `ip neigh show <ip>`：`192.168.0.2 dev br0 lladdr aa:bb:cc:dd:ee:ff REACHABLE`
+pub fn parse_ip_neigh(output: &str, target: Ipv4Addr) -> Neighbor {
+    for line in output.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first().and_then(|w| w.parse::<Ipv4Addr>().ok()) != Some(target) {
+            continue;
+        }
+        let mac = words
+            .iter()
+            .position(|w| *w == "lladdr")
+            .and_then(|i| words.get(i + 1));
+        let state = words.last().copied().unwrap_or("");
+        if let (Some(mac), "REACHABLE" | "STALE" | "DELAY" | "PROBE" | "PERMANENT") = (mac, state) {
+            if valid_mac(mac) {
+                return Neighbor::Resolved(mac.to_string());
+            }
+        }
+    }
+    Neighbor::Unresolved
+}
+
+/// 读取目标在邻居表中的状态：优先 /proc/net/arp，读不到时用 ip neigh
+pub fn read_neighbor(target: Ipv4Addr) -> Neighbor {
+    if let Ok(content) = fs::read_to_string("/proc/net/arp") {
+        return parse_proc_arp(&content, target);
+    }
+    let target_str = target.to_string();
+    command::run_with_timeout("ip", &["neigh", "show", &target_str], IP_NEIGH_TIMEOUT)
+        .ok()
+        .filter(|result| result.success())
+        .map(|result| parse_ip_neigh(&String::from_utf8_lossy(&result.stdout), target))
+        .unwrap_or(Neighbor::Unresolved)
+}
+
+/// ARP 存活检查：向目标的关闭端口发一个 UDP 包触发地址解析，等到邻居表中出现
+/// 有效的 MAC；返回从发包到解析完成的时间。超时仍未解析（包括 FAILED）为失败
+pub fn probe(target: Ipv4Addr, timeout: Duration) -> io::Result<Duration> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    sockmark::prepare_probe(socket.as_raw_fd(), false);
+    let start = Instant::now();
+    socket.send_to(&[0u8], (target, TRIGGER_PORT))?;
+    loop {
+        if let Neighbor::Resolved(_) = read_neighbor(target) {
+            return Ok(start.elapsed());
+        }
+        if start.elapsed() >= timeout {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} not resolved in the neighbor table", target),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// target 是否在 `192.168.0.0/24` 形式的网段内
+pub fn in_subnet(target: Ipv4Addr, cidr: &str) -> bool {
+    let Some((network, prefix)) = routes::parse_network(cidr) else {
+        return false;
+    };
+    let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    u32::from(target) & mask == u32::from(network) & mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARP_HEADER: &str =
+        "IP address       HW type     Flags       HW address            Mask     Device\n";
+
+    #[test]
+    fn test_parse_proc_arp() {
+        let content = format!(
+            "{}{}{}",
+            ARP_HEADER,
+            "192.168.0.2      0x1         0x2         aa:bb:cc:dd:ee:ff     *        br0\n",
+            "192.168.0.3      0x1         0x0         00:00:00:00:00:00     *        br0\n"
+        );
+        let ip = |s: &str| s.parse::<Ipv4Addr>().unwrap();
+        assert_eq!(
+            parse_proc_arp(&content, ip("192.168.0.2")),
+            Neighbor::Resolved("aa:bb:cc:dd:ee:ff".to_string())
+        );
+        // FAILED 条目还在表中，但不算存活
+        assert_eq!(
+            parse_proc_arp(&content, ip("192.168.0.3")),
+            Neighbor::Unresolved
+        );
+        assert_eq!(
+            parse_proc_arp(&content, ip("192.168.0.4")),
+            Neighbor::Unresolved
+        );
+    }
+
+    #[test]
+    fn test_parse_ip_neigh() {
+        let ip = |s: &str| s.parse::<Ipv4Addr>().unwrap();
+        let output = "192.168.0.2 dev br0 lladdr aa:bb:cc:dd:ee:ff STALE\n\
+                      192.168.0.3 dev br0  FAILED\n\
+                      192.168.0.4 dev br0 lladdr aa:bb:cc:dd:ee:01 INCOMPLETE\n";
+        assert_eq!(
+            parse_ip_neigh(output, ip("192.168.0.2")),
+            Neighbor::Resolved("aa:bb:cc:dd:ee:ff".to_string())
+        );
+        assert_eq!(
+            parse_ip_neigh(output, ip("192.168.0.3")),
+            Neighbor::Unresolved
+        );
+        assert_eq!(
+            parse_ip_neigh(output, ip("192.168.0.4")),
+            Neighbor::Unresolved
+        );
+    }
+
+    #[test]
+    fn test_in_subnet() {
+        let ip = |s: &str| s.parse::<Ipv4Addr>().unwrap();
+        assert!(in_subnet(ip("192.168.0.77"), "192.168.0.0/24"));
+        assert!(!in_subnet(ip("192.168.1.77"), "192.168.0.0/24"));
+        assert!(in_subnet(ip("10.1.2.3"), "0.0.0.0/0"));
+        assert!(!in_subnet(ip("10.1.2.3"), "garbage"));
+    }
+}
@@ -0,0 +1,337 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 启动记录文件名（位于存储目录下，key=value 格式）
+pub const BOOT_RECORD_FILE: &str = "zxping.boot";
+
+/// 统计启动次数的滑动窗口
+pub const BOOT_LOOP_WINDOW_SECS: u64 = 3600;
+/// 窗口内启动次数超过此值视为重启循环
+const BOOT_LOOP_MAX_BOOTS: usize = 4;
+/// 重启循环后禁止 zxic-ping 主动重启的时长
+const CONSERVATIVE_MODE_DURATION: Duration = Duration::from_secs(3 * 3600);
+/// 最多保留的启动时间戳数量
+const MAX_BOOT_TIMESTAMPS: usize = 16;
+/// 最多保留的目标不可达重启记录数量
+const MAX_TARGET_REBOOTS: usize = 8;
+
+/// 持久化的启动记录
+pub struct BootRecord {
+    path: PathBuf,
+    /// 累计启动次数
+    pub count: u64,
+    /// 上次正常退出时记录的 uptime（秒），None 表示上次非正常退出
+    clean_shutdown_uptime: Option<u64>,
+    /// 最近的启动时间（Unix 秒）
+    boots: Vec<u64>,
+    /// 本次启动是否为非正常启动（上次没有记录正常退出）
+    pub unclean: bool,
+    /// 保守模式截止时间，期间不允许主动重启
+    conservative_until: Option<Instant>,
+    /// 本次保守模式期间是否已经通知过重启被拦截
+    cap_notified: bool,
+    /// 主动重启前记录的原因（link / high_load / control）
+    reboot_reason: Option<String>,
+    /// 上次主动重启的原因，上次不是由 zxic-ping 重启时为 None
+    pub last_reboot_reason: Option<String>,
+    /// 因目标不可达主动重启前记录的目标
+    reboot_target: Option<String>,
+    /// 因目标不可达而完成的重启（重启后启动时间, 目标），最早的在前
+    target_reboots: Vec<(u64, String)>,
+}
+
+impl BootRecord {
+    /// 读取启动记录并登记本次启动，文件损坏时按空记录处理
+    pub fn register_boot(path: PathBuf) -> BootRecord {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let mut record = parse_record(path, &content);
+
+        // 首次启动（没有任何记录）不算非正常
+        record.unclean = record.count > 0 && record.clean_shutdown_uptime.is_none();
+        record.count += 1;
+        record.clean_shutdown_uptime = None;
+        record.last_reboot_reason = record.reboot_reason.take();
+
+        let now = unix_now();
+        if let Some(target) = record.reboot_target.take() {
+            record.target_reboots.push((now, target));
+            if record.target_reboots.len() > MAX_TARGET_REBOOTS {
+                let excess = record.target_reboots.len() - MAX_TARGET_REBOOTS;
+                record.target_reboots.drain(..excess);
+            }
+        }
+        record.boots.retain(|ts| *ts <= now);
+        record.boots.push(now);
+        if record.boots.len() > MAX_BOOT_TIMESTAMPS {
+            let excess = record.boots.len() - MAX_BOOT_TIMESTAMPS;
+            record.boots.drain(..excess);
+        }
+
+        if record.boot_loop_suspected() {
+            record.conservative_until = Some(Instant::now() + CONSERVATIVE_MODE_DURATION);
+        }
+
+        let _ = record.save();
+        record
+    }
+
+    /// 读取已登记的启动记录（不登记启动），供主循环之外的线程在重启前记录原因
+    pub fn load(path: PathBuf) -> BootRecord {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        parse_record(path, &content)
+    }
+
+    /// 窗口内的启动次数
+    pub fn boots_in_window(&self) -> usize {
+        let now = unix_now();
+        self.boots
+            .iter()
+            .filter(|ts| now.saturating_sub(**ts) <= BOOT_LOOP_WINDOW_SECS)
+            .count()
+    }
+
+    pub fn boot_loop_suspected(&self) -> bool {
+        self.boots_in_window() > BOOT_LOOP_MAX_BOOTS
+    }
+
+    /// 最近连续 min_reboots 次以上的目标不可达重启都发生在 window_secs 内且是同一个目标时，
+    /// 返回该目标；min_reboots 为 0 时不检查
+    pub fn suspect_target(&self, window_secs: u64, min_reboots: u32) -> Option<&str> {
+        let now = unix_now();
+        let (_, last) = self.target_reboots.last()?;
+        let repeated = self
+            .target_reboots
+            .iter()
+            .rev()
+            .take_while(|(ts, target)| target == last && now.saturating_sub(*ts) <= window_secs)
+            .count();
+        (min_reboots > 0 && repeated >= min_reboots as usize).then_some(last.as_str())
+    }
+
+    /// 保守模式剩余时间，未处于保守模式时返回 None
+    pub fn conservative_remaining(&self) -> Option<Duration> {
+        self.conservative_until
+            .and_then(|until| until.checked_duration_since(Instant::now()))
+            .filter(|d| !d.is_zero())
+    }
+
+    /// 保守模式拦截了一次重启：只有本次保守模式期间第一次拦截时返回 true（需要通知）
+    pub fn note_cap_reached(&mut self) -> bool {
+        !std::mem::replace(&mut self.cap_notified, true)
+    }
+
+    /// 记录正常退出（退出或主动重启前调用）
+    pub fn mark_clean_shutdown(&mut self) {
+        self.clean_shutdown_uptime = Some(read_uptime_secs().unwrap_or(0));
+        let _ = self.save();
+    }
+
+    /// 启动后没有进入监控就退出（如还没有 WAN 地址）：记录正常退出，并撤销本次登记的
+    /// 启动时间，下次启动不算非正常启动，也不计入重启循环的判断
+    pub fn mark_early_exit(&mut self) {
+        self.boots.pop();
+        self.mark_clean_shutdown();
+    }
+
+    /// 重启前设置这次重启归咎的目标（目标不可达时），随 mark_reboot 一起保存
+    pub fn set_reboot_target(&mut self, target: Option<&str>) {
+        self.reboot_target = target.map(str::to_string);
+    }
+
+    /// 主动重启前记录正常退出和重启原因
+    pub fn mark_reboot(&mut self, reason: &str) {
+        self.reboot_reason = Some(reason.to_string());
+        self.mark_clean_shutdown();
+    }
+
+    /// 重启失败、继续运行时撤销正常退出记录和重启原因
+    pub fn clear_clean_shutdown(&mut self) {
+        self.clean_shutdown_uptime = None;
+        self.reboot_reason = None;
+        self.reboot_target = None;
+        let _ = self.save();
+    }
+
+    /// 存储目录切换后改写到新路径
+    pub fn relocate(&mut self, path: PathBuf) {
+        self.path = path;
+        let _ = self.save();
+    }
+
+    /// 原子写入：先写临时文件再 rename
+    fn save(&self) -> std::io::Result<()> {
+        let boots: Vec<String> = self.boots.iter().map(|ts| ts.to_string()).collect();
+        let mut content = format!("count={}\nboots={}\n", self.count, boots.join(","));
+        if let Some(uptime) = self.clean_shutdown_uptime {
+            content.push_str(&format!("clean_shutdown_uptime={}\n", uptime));
+        }
+        if let Some(reason) = &self.reboot_reason {
+            content.push_str(&format!("reboot_reason={}\n", reason));
+        }
+        if let Some(target) = &self.reboot_target {
+            content.push_str(&format!("reboot_target={}\n", target));
+        }
+        if !self.target_reboots.is_empty() {
+            let entries: Vec<String> = self
+                .target_reboots
+                .iter()
+                .map(|(ts, target)| format!("{}@{}", ts, target))
+                .collect();
+            content.push_str(&format!("target_reboots={}\n", entries.join(",")));
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, content.as_bytes())?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+/// 解析启动记录，无法识别的行直接忽略
+fn parse_record(path: PathBuf, content: &str) -> BootRecord {
+    let mut record = BootRecord {
+        path,
+        count: 0,
+        clean_shutdown_uptime: None,
+        boots: Vec::new(),
+        unclean: false,
+        conservative_until: None,
+        cap_notified: false,
+        reboot_reason: None,
+        last_reboot_reason: None,
+        reboot_target: None,
+        target_reboots: Vec::new(),
+    };
+    for line in content.lines() {
+        match line.split_once('=') {
+            Some(("count", v)) => record.count = v.trim().parse().unwrap_or(0),
+            Some(("clean_shutdown_uptime", v)) => {
+                record.clean_shutdown_uptime = v.trim().parse().ok()
+            }
+            Some(("reboot_reason", v)) if !v.trim().is_empty() => {
+                record.reboot_reason = Some(v.trim().to_string())
+            }
+            Some(("reboot_target", v)) if !v.trim().is_empty() => {
+                record.reboot_target = Some(v.trim().to_string())
+            }
+            Some(("target_reboots", v)) => {
+                record.target_reboots = v
+                    .split(',')
+                    .filter_map(|entry| entry.trim().split_once('@'))
+                    .filter_map(|(ts, target)| Some((ts.parse().ok()?, target.to_string())))
+                    .collect()
+            }
+            Some(("boots", v)) => {
+                record.boots = v
+                    .split(',')
+                    .filter_map(|ts| ts.trim().parse().ok())
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    record
+}
+
+/// 读取 /proc/uptime 的第一个字段（秒）
+pub fn read_uptime_secs() -> Option<u64> {
+    let content = fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = content.split_whitespace().next()?.parse().ok()?;
+    Some(secs as u64)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_record() {
+        let record = parse_record(
+            PathBuf::from("/tmp/boot"),
+            "count=7\nboots=100,200,bad,300\nclean_shutdown_uptime=3600\nreboot_reason=high_load\n",
+        );
+        assert_eq!(record.count, 7);
+        assert_eq!(record.reboot_reason.as_deref(), Some("high_load"));
+        assert_eq!(record.boots, vec![100, 200, 300]);
+        assert_eq!(record.clean_shutdown_uptime, Some(3600));
+    }
+
+    #[test]
+    fn test_parse_corrupt_record() {
+        let record = parse_record(
+            PathBuf::from("/tmp/boot"),
+            "co\u{0}unt=\ngarbage\ncount=abc\n",
+        );
+        assert_eq!(record.count, 0);
+        assert!(record.boots.is_empty());
+        assert_eq!(record.clean_shutdown_uptime, None);
+    }
+
+    #[test]
+    fn test_load_marks_reboot() {
+        let path = std::env::temp_dir().join(format!("zxic_boot_load_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        BootRecord::register_boot(path.clone());
+        // 其他线程重启前读取记录并写入原因，下次启动时能读到
+        BootRecord::load(path.clone()).mark_reboot("loop_stall");
+        let record = BootRecord::register_boot(path.clone());
+        assert_eq!(record.count, 2);
+        assert!(!record.unclean);
+        assert_eq!(record.last_reboot_reason.as_deref(), Some("loop_stall"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_early_exit() {
+        let path = std::env::temp_dir().join(format!("zxic_boot_early_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        for _ in 0..BOOT_LOOP_MAX_BOOTS + 2 {
+            BootRecord::register_boot(path.clone()).mark_early_exit();
+        }
+        let record = BootRecord::register_boot(path.clone());
+        assert!(!record.unclean);
+        assert_eq!(record.boots_in_window(), 1);
+        assert!(!record.boot_loop_suspected());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_boot_loop_window() {
+        let now = unix_now();
+        let mut record = parse_record(PathBuf::from("/tmp/boot"), "");
+        record.boots = vec![now - 7200, now - 300, now - 200, now - 100, now - 50];
+        assert_eq!(record.boots_in_window(), 4);
+        assert!(!record.boot_loop_suspected());
+        record.boots.push(now);
+        assert!(record.boot_loop_suspected());
+        assert!(record.note_cap_reached());
+        assert!(!record.note_cap_reached());
+    }
+
+    #[test]
+    fn test_suspect_target() {
+        let now = unix_now();
+        let content = format!(
+            "target_reboots={}@1.1.1.1:53,{}@8.8.8.8:53,{}@8.8.8.8:53,bad\n",
+            now - 600,
+            now - 500,
+            now - 100
+        );
+        let mut record = parse_record(PathBuf::from("/tmp/boot"), &content);
+        assert_eq!(record.target_reboots.len(), 3);
+        assert_eq!(record.suspect_target(3600, 2), Some("8.8.8.8:53"));
+        assert_eq!(record.suspect_target(3600, 3), None);
+        // 窗口外的重启不算
+        assert_eq!(record.suspect_target(200, 2), None);
+        assert_eq!(record.suspect_target(3600, 0), None);
+        // 最近一次换了目标，连续记录中断
+        record.target_reboots.push((now, "1.1.1.1:53".to_string()));
+        assert_eq!(record.suspect_target(3600, 2), None);
+    }
+}
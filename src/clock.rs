@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant, SystemTime};
+
+/// 墙上时间与单调时钟的偏差超过这个值才算跳变（忽略正常的漂移和慢速校时）
+pub const CLOCK_STEP_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// 检测墙上时间的跳变（NTP/SNTP 校时、手动改时间）。日志时间戳用的是墙上时间，
+/// 跳变前后的日志不能直接按时间戳对照
+pub struct ClockWatch {
+    wall: SystemTime,
+    mono: Instant,
+    /// 启动以来检测到的跳变次数
+    pub steps: u32,
+    /// 最近一次跳变的幅度（秒，正数为向前跳）
+    pub last_step: Option<i64>,
+}
+
+impl ClockWatch {
+    pub fn new(wall: SystemTime, mono: Instant) -> Self {
+        ClockWatch {
+            wall,
+            mono,
+            steps: 0,
+            last_step: None,
+        }
+    }
+
+    /// 与上一次记录比较，墙上时间比单调时钟多走（或少走）超过阈值时返回偏差秒数
+    pub fn check(&mut self, wall: SystemTime, mono: Instant) -> Option<i64> {
+        let expected = self.wall + mono.saturating_duration_since(self.mono);
+        self.wall = wall;
+        self.mono = mono;
+        let delta = match wall.duration_since(expected) {
+            Ok(ahead) => ahead.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        if delta.unsigned_abs() < CLOCK_STEP_THRESHOLD.as_secs() {
+            return None;
+        }
+        self.steps += 1;
+        self.last_step = Some(delta);
+        Some(delta)
+    }
+
+    /// STATUS 中的一行：`clock_steps=1 last_clock_step=+3600s`
+    pub fn status_line(&self) -> String {
+        format!(
+            "clock_steps={} last_clock_step={}",
+            self.steps,
+            self.last_step
+                .map(|delta| format!("{:+}s", delta))
+                .unwrap_or_else(|| "-".to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_clock_step() {
+        let wall = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mono = Instant::now();
+        let mut watch = ClockWatch::new(wall, mono);
+        let after = |secs| mono + Duration::from_secs(secs);
+
+        // 正常走时和小幅漂移不算跳变
+        assert_eq!(watch.check(wall + Duration::from_secs(2), after(2)), None);
+        assert_eq!(watch.check(wall + Duration::from_secs(14), after(4)), None);
+
+        // 校时向前跳一小时
+        assert_eq!(
+            watch.check(wall + Duration::from_secs(3620), after(6)),
+            Some(3604)
+        );
+        // 之后以新的时间为基准
+        assert_eq!(
+            watch.check(wall + Duration::from_secs(3622), after(8)),
+            None
+        );
+        // 向后跳
+        assert_eq!(
+            watch.check(wall + Duration::from_secs(22), after(10)),
+            Some(-3602)
+        );
+        assert_eq!(watch.status_line(), "clock_steps=2 last_clock_step=-3602s");
+    }
+}
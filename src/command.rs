@@ -0,0 +1,191 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 每个输出流最多保留的字节数（多出的读出后丢弃，避免子进程写满管道卡住）
+const OUTPUT_CAP: usize = 4096;
+/// 轮询子进程是否退出的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// 子进程退出后等待读取线程收尾的时间（后台的孙进程可能还占着管道）
+const READER_GRACE: Duration = Duration::from_millis(100);
+
+/// 外部命令的执行结果
+#[derive(Debug)]
+pub struct CommandResult {
+    pub status: ExitStatus,
+    /// 超时后被杀死
+    pub timed_out: bool,
+    pub timeout: Duration,
+    /// 最多 OUTPUT_CAP 字节
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        !self.timed_out && self.status.success()
+    }
+}
+
+/// 失败原因：超时、stderr 或退出状态
+impl fmt::Display for CommandResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.timed_out {
+            write!(f, "timed out after {}ms", self.timeout.as_millis())
+        } else if !self.stderr.trim().is_empty() {
+            write!(f, "{}", self.stderr.trim())
+        } else {
+            write!(f, "{}", self.status)
+        }
+    }
+}
+
+/// 执行外部命令，超过 timeout 时杀死它（连同它启动的子进程）。
+/// 闪存卡死时外部命令会一直挂着，不能让它拖住主循环
+pub fn run_with_timeout(cmd: &str, args: &[&str], timeout: Duration) -> io::Result<CommandResult> {
+    let mut command = Command::new(cmd);
+    command.args(args);
+    run_command(command, timeout)
+}
+
+/// 同 run_with_timeout，用于需要额外设置（如环境变量）的命令
+pub fn run_command(command: Command, timeout: Duration) -> io::Result<CommandResult> {
+    run_command_capped(command, timeout, OUTPUT_CAP)
+}
+
+/// 同 run_command，stdout 最多保留 stdout_cap 字节（用于 iptables-save 等输出较大的命令）
+pub fn run_command_capped(
+    mut command: Command,
+    timeout: Duration,
+    stdout_cap: usize,
+) -> io::Result<CommandResult> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // 单独的进程组，超时时整组杀死（sh -c 启动的命令不会留下来）
+        .process_group(0)
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| read_capped(pipe, stdout_cap));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| read_capped(pipe, OUTPUT_CAP));
+
+    let deadline = Instant::now() + timeout;
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            timed_out = true;
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            break child.wait()?;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let collect = |rx: Option<Receiver<Vec<u8>>>| {
+        rx.and_then(|rx| rx.recv_timeout(READER_GRACE).ok())
+            .unwrap_or_default()
+    };
+    Ok(CommandResult {
+        status,
+        timed_out,
+        timeout,
+        stdout: collect(stdout),
+        stderr: String::from_utf8_lossy(&collect(stderr)).into_owned(),
+    })
+}
+
+/// 通过 sh -c 执行一条命令
+pub fn run_shell(cmd: &str, timeout: Duration) -> io::Result<CommandResult> {
+    run_with_timeout("sh", &["-c", cmd], timeout)
+}
+
+/// 同 run_shell，stdout 最多保留 stdout_cap 字节
+pub fn run_shell_capped(
+    cmd: &str,
+    timeout: Duration,
+    stdout_cap: usize,
+) -> io::Result<CommandResult> {
+    let mut command = Command::new("sh");
+    command.args(["-c", cmd]);
+    run_command_capped(command, timeout, stdout_cap)
+}
+
+/// 在后台线程读取一个输出流，保留前 cap 字节
+fn read_capped(mut pipe: impl Read + Send + 'static, cap: usize) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let _ = (&mut pipe).take(cap as u64).read_to_end(&mut kept);
+        let _ = io::copy(&mut pipe, &mut io::sink());
+        let _ = tx.send(kept);
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_with_timeout() {
+        let result = run_with_timeout("/bin/sleep", &["0"], Duration::from_secs(5)).unwrap();
+        assert!(result.success());
+        assert!(!result.timed_out);
+
+        // 超时：被杀死并很快返回
+        let start = Instant::now();
+        let result = run_with_timeout("/bin/sleep", &["10"], Duration::from_millis(200)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(result.timed_out);
+        assert!(!result.success());
+        assert_eq!(result.to_string(), "timed out after 200ms");
+
+        // sh 启动的 sleep 也一起被杀死，不会拖住读取
+        let start = Instant::now();
+        let result = run_shell("/bin/sleep 10; echo done", Duration::from_millis(200)).unwrap();
+        assert!(result.timed_out);
+        assert!(result.stdout.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        assert!(run_with_timeout("/no/such/binary", &[], Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_captured_output() {
+        let result = run_shell(
+            "echo out; echo bad rule >&2; exit 3",
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(result.status.code(), Some(3));
+        assert_eq!(result.stdout, b"out\n");
+        assert_eq!(result.to_string(), "bad rule");
+
+        // stderr 只保留前 OUTPUT_CAP 字节，其余读掉丢弃
+        let result = run_shell(
+            "head -c 100000 /dev/zero | tr '\\0' x >&2",
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(result.success());
+        assert_eq!(result.stderr.len(), OUTPUT_CAP);
+
+        let result =
+            run_shell_capped("head -c 100000 /dev/zero", Duration::from_secs(5), 65536).unwrap();
+        assert_eq!(result.stdout.len(), 65536);
+    }
+}
@@ -0,0 +1,2077 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::histogram::{parse_bounds, DEFAULT_BUCKETS_MS};
+use crate::hooks::HookEvent;
+use crate::http;
+use crate::logprune::{format_time_of_day, parse_time_of_day};
+use crate::priority::{parse_priority, Condition, DEFAULT_PRIORITY};
+use crate::profile::Profile;
+use crate::routes;
+use crate::services::Service;
+use crate::udpecho::MAX_PAYLOAD;
+
+/// 默认配置文件路径（key = value 格式，# 开头为注释）
+pub const DEFAULT_CONFIG_PATH: &str = "/etc_rw/zxping.conf";
+
+/// 配置文件格式的当前版本（配置文件中写 `version = N`），增删或改变配置项含义时加 1
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 检查目标是否可达的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeMethod {
+    /// TCP 连接目标端口
+    Tcp,
+    /// 向目标发 UDP echo 请求（目标需运行 echo 服务），参数见 udp_echo_*
+    Udp,
+    /// 目标在 LAN 上时，通过邻居表确认它在线（不依赖端口是否开放）
+    Arp,
+    /// TCP 连接或 ICMP echo 任一成功即可达（运营商有时只屏蔽其中一种）
+    Hybrid,
+    /// 向目标端口发 HTTP 请求，分别测量连接时间和首字节时间（TTFB）
+    Http,
+}
+
+impl ProbeMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeMethod::Tcp => "tcp",
+            ProbeMethod::Udp => "udp",
+            ProbeMethod::Arp => "arp",
+            ProbeMethod::Hybrid => "hybrid",
+            ProbeMethod::Http => "http",
+        }
+    }
+}
+
+/// 主循环卡住时看门狗的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallAction {
+    /// 只记录日志和通知
+    Log,
+    /// 退出进程，由 init/supervisor 重新拉起
+    Exit,
+    /// 重启系统
+    Reboot,
+}
+
+impl StallAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StallAction::Log => "log",
+            StallAction::Exit => "exit",
+            StallAction::Reboot => "reboot",
+        }
+    }
+}
+
+/// 控制端口绑定失败（通常是端口被占用）时的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindConflict {
+    /// 记录错误后退出
+    Fail,
+    /// 不带控制通道继续监控，后台按退避间隔重试绑定
+    Retry,
+    /// 不带控制通道继续监控
+    Disable,
+}
+
+impl BindConflict {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BindConflict::Fail => "fail",
+            BindConflict::Retry => "retry",
+            BindConflict::Disable => "disable",
+        }
+    }
+}
+
+/// 配置项当前值的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    /// 由调优档位带出（conntrack_*）
+    Profile,
+    File,
+    Cli,
+    /// 运行中通过控制命令修改（包括重启后沿用的 PROFILE 设置）
+    Runtime,
+}
+
+impl ConfigSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::Profile => "profile",
+            ConfigSource::File => "file",
+            ConfigSource::Cli => "cli",
+            ConfigSource::Runtime => "runtime",
+        }
+    }
+}
+
+/// 运行配置：默认值 < 配置文件 < 命令行参数
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// UDP 通知 socket 的本地绑定地址（多网卡时指定出口）
+    pub udp_local_bind: String,
+    /// UDP 通知发送超时
+    pub udp_timeout: Duration,
+    /// 用于状态指示的 LED 名称（/sys/class/leds 下的目录名），None 为不控制
+    pub led: Option<String>,
+    /// 控制台日志使用 ANSI 颜色和级别前缀（仅在 stdout 为 TTY 时生效）
+    pub log_color: bool,
+    /// 正常状态（优化/恢复）下的 nf_conntrack_max
+    pub conntrack_max: u32,
+    /// 高延迟限流时的 nf_conntrack_max
+    pub conntrack_max_throttled: u32,
+    /// nf_conntrack 哈希表大小（/sys/module/nf_conntrack/parameters/hashsize）
+    pub conntrack_hashsize: u32,
+    /// 日志和状态文件的首选存储目录，不可写时退回 /tmp
+    pub storage_root: String,
+    /// 固件版本文件，首行作为固件版本上报
+    pub firmware_version_file: String,
+    /// 通知加上会话 id、序号和 CRC32 信封，便于接收端检测丢包和损坏
+    pub notify_envelope: bool,
+    /// 关键通知（重启前）等待接收端原样回显确认的时间，0 为不等待（只发送一次）
+    pub notify_ack_timeout: Duration,
+    /// 关键通知没有确认时的重发次数
+    pub notify_ack_retries: u32,
+    /// 调优档位，设置时覆盖之前的 conntrack_* 值（之后出现的 conntrack_* 仍可覆盖档位）
+    pub profile: Profile,
+    /// 可用内存低于此值（KB）时按 CPU 采样周期主动清理 page cache，0 为关闭
+    pub cache_drop_mem_kb: u64,
+    /// 两次主动清理 page cache 的最小间隔
+    pub cache_drop_min_interval: Duration,
+    /// 恢复正常时可用内存高于此值（KB）就不清理 page cache（保留热缓存），0 为总是清理
+    pub cache_drop_floor_kb: u64,
+    /// 通知接收端列表（可多次指定或逗号分隔，累加），为空时发往监控目标
+    pub notify_addrs: Vec<String>,
+    /// 只发送这些类型的通知（如 `REBOOTING,ADBD_FORCE_RESTARTED`），为空时发送全部
+    pub notify_only: Vec<String>,
+    /// 不发送这些类型的通知（如 `HIGH_LOAD`），优先于 notify_only
+    pub notify_disable: Vec<String>,
+    /// 高负载通知只在进入/退出时发送（持续期间按 high_load_report_interval 限频）
+    pub high_load_report_on_change: bool,
+    /// report_on_change 模式下持续高负载的报告间隔
+    pub high_load_report_interval: Duration,
+    /// 连续这么多次采样达到阈值才进入高负载
+    pub high_load_enter_count: u32,
+    /// 连续这么多次采样低于阈值才退出高负载
+    pub high_load_exit_count: u32,
+    /// 连续失败达到此次数时做一次路径探测并随 OUTAGE 通知上报，0 为关闭
+    pub diag_failure_threshold: u32,
+    /// 连续失败多少次后重启（需开启 auto_reboot）
+    pub max_failures: u32,
+    /// CPU 高负载期间断网时，重启前的失败次数放大到 max_failures 的这么多倍
+    pub high_load_failure_factor: u32,
+    /// 按最近这么多次检查的失败比例重启（与 max_failures 同时生效，需开启 auto_reboot），0 为关闭
+    pub failure_window: u32,
+    /// 窗口已满且失败比例超过此百分比时重启
+    pub failure_window_percent: u32,
+    /// 连续高延迟多少次后限流
+    pub max_high_latency: u32,
+    /// 连接成功但耗时超过此值（如蜂窝网络重传导致的单次秒级延迟）时记为软丢包，计入丢包率；0 为关闭
+    pub rtt_outlier: Duration,
+    /// 限流后高延迟每再持续这么多次检查升级一级（清理页缓存、重启 WAN 接口、重启），0 为关闭
+    pub latency_escalate_after: u32,
+    /// 综合健康分数的扣分倍率（百分比），调高后各阈值更早越过
+    pub health_score_scale_percent: u32,
+    /// 健康分数的衰减半衰期
+    pub health_score_half_life: Duration,
+    /// 健康分数达到此值时发送 HEALTH_SCORE 通知，0 为关闭
+    pub health_score_notify: u32,
+    /// 健康分数达到此值时限流，0 为关闭；开启后由分数代替高延迟计数决定限流和恢复
+    pub health_score_throttle: u32,
+    /// 健康分数达到此值时重启（需开启 auto_reboot），0 为关闭；开启后由分数代替连续失败、
+    /// 失败比例和高延迟升级决定重启
+    pub health_score_reboot: u32,
+    /// 输出 Debug 级别日志
+    pub log_debug: bool,
+    /// /proc/net/sockstat 中 TCP inuse 的告警阈值，0 为不检查
+    pub sock_tcp_inuse_max: u64,
+    /// TCP orphan 的告警阈值，0 为不检查
+    pub sock_tcp_orphan_max: u64,
+    /// orphan 超过阈值时提前缩短 TIME_WAIT 相关超时
+    pub sock_orphan_throttle: bool,
+    /// /proc/sys/fs/file-nr 已分配句柄占上限的百分比超过此值时告警，0 为不检查
+    pub fd_warn_percent: u32,
+    /// 超过此百分比时发送 FD_PRESSURE 通知（附打开句柄最多的进程），0 为不通知
+    pub fd_critical_percent: u32,
+    /// 连续失败达到 max_failures 时自动重启
+    pub auto_reboot: bool,
+    /// 结束持续占用 CPU 的进程（仅限 runaway_kill_list 中的进程名），关闭时只报告
+    pub runaway_kill: bool,
+    /// 允许被结束的进程名（comm，逗号分隔）
+    pub runaway_kill_list: Vec<String>,
+    /// 单个进程的 CPU 占用阈值（百分比）
+    pub runaway_cpu_threshold: f32,
+    /// 连续多少次高负载检查都是同一进程超过阈值才处理
+    pub runaway_checks: u32,
+    /// SIGTERM 后等待多久再 SIGKILL
+    pub runaway_kill_grace: Duration,
+    /// kill -9 后等待进程消失的最长时间
+    pub kill_wait_timeout: Duration,
+    /// 重启 adbd 时 kill 后等待旧进程退出的最长时间（提前退出时立即启动新进程）
+    pub adbd_kill_settle: Duration,
+    /// adbd 重启后等这么久（USB gadget 在主机上重新枚举）再确认进程在运行并发送 ADBD_READY，0 为不发送
+    pub adbd_ready_settle: Duration,
+    /// 确认就绪时还要求 /sys/class/android_usb/android0/state 为 CONFIGURED
+    pub adbd_ready_check_usb: bool,
+    /// CPU 占用率上升斜率（百分点/分钟）达到该值时提前预警，0 为关闭
+    pub cpu_velocity_threshold: f32,
+    /// 各子系统开关，关闭后对应的检查/命令不再执行（默认全部开启）
+    pub enable_cpu_monitor: bool,
+    pub enable_network_monitor: bool,
+    pub enable_control_channel: bool,
+    /// 调整参数时刷新 iptables 规则、定期更新 SNAT
+    pub enable_iptables: bool,
+    /// 启动时清空 filter/nat 表和 ip6tables 规则并把默认策略设为 ACCEPT（默认关闭）；
+    /// 清空前把原有规则保存到存储目录，可用 RESTORE_FIREWALL 恢复
+    pub iptables_flush: bool,
+    /// 启动时的网络参数优化推迟到第一次检查成功后（确认网络栈已就绪）
+    pub tune_after_first_success: bool,
+    /// 推迟优化的最长等待时间，到期仍没有成功过也照常应用
+    pub tune_deadline: Duration,
+    /// 结束/重启 adbd（控制命令和高延迟限流）
+    pub enable_adbd_control: bool,
+    /// 发现 adbd 的 TCP 端口（5555）对外暴露时装一条专用链，只允许 LAN 网段访问（默认关闭）
+    pub adbd_restrict_lan: bool,
+    /// 启动时同时调整 IPv6 的路由表、邻居表和分片参数（默认关闭）
+    pub enable_ipv6_tuning: bool,
+    /// 默认路由丢失时按最近一次正常的网关重新添加
+    pub route_repair: bool,
+    /// 启动时进入维护模式（继续监控和通知，但不重启、不限流/恢复、不结束或重启进程）；
+    /// 运行中用 MAINTENANCE:on/off 切换
+    pub maintenance: bool,
+    /// 维护模式的最长时间，到期自动退出，避免忘记关闭
+    pub maintenance_timeout: Duration,
+    /// 控制通道 HMAC 共享密钥所在的文件（应只有 root 可读），空为不使用。
+    /// 配置后 TCP 控制端口只接受签名的命令（格式见 hmac::verify_command）
+    pub hmac_key_file: String,
+    /// 控制端口监听 socket 的接收/发送缓冲区大小（字节，accept 的连接继承），0 为内核默认值
+    pub control_recv_buffer: usize,
+    pub control_send_buffer: usize,
+    /// 控制端口被占用等绑定失败时的处理：fail、retry 或 disable
+    pub control_bind_conflict: BindConflict,
+    /// 本机控制用的 Unix socket 路径（如 /var/run/zxping.sock，只有 root 可访问），命令与控制端口相同；空为关闭
+    pub control_socket: String,
+    /// HTTP 状态/控制接口的端口（GET /status、GET /metrics、POST /command/...），0 为关闭
+    pub http_port: u16,
+    /// HTTP 接口监听的地址，默认只监听本机；需要从 LAN 访问时设为 LAN 地址（如 192.168.0.1），
+    /// 设为 0.0.0.0 或 :: 会同时暴露在 WAN 上
+    pub http_address: IpAddr,
+    /// HTTP 接口执行命令所需的 Bearer token 所在的文件（应只有 root 可读），空为只读接口
+    pub http_token_file: String,
+    /// 重启命令（绝对路径，可带参数，如 `/sbin/reboot -f`），在内置的 reboot 查找之前使用；空为只用内置的。
+    /// 默认的 /sbin/reboot 不存在时直接使用内置的查找
+    pub reboot_command: String,
+    /// 主循环超过这么久没有完成一轮时看门狗报告 LOOP_STALL，0 为关闭
+    pub loop_stall_timeout: Duration,
+    /// 看门狗发现卡住后的处理：log、exit 或 reboot
+    pub loop_stall_action: StallAction,
+    /// 主循环一轮（不含睡眠和等待探测应答）超过这么久时记录 Warn 并指出最慢的阶段，0 为关闭
+    pub tick_budget: Duration,
+    /// 10 分钟内超出预算这么多次时发送 SLOW_TICK
+    pub slow_tick_count: u32,
+    /// 事件脚本（配置项 hook_<事件名>，绝对路径），事件发生时执行
+    pub hooks: HashMap<HookEvent, String>,
+    /// 通过 RESTART:<名字>、KILL:<名字> 管理的服务（配置项 service，可多次指定或逗号分隔，
+    /// 同名覆盖，空值清空）；没有 adbd 时使用内置的 adbd
+    pub services: Vec<Service>,
+    /// 自动重启还要求本次断网已持续这么久（与 max_failures 同时满足），0 为不限制
+    pub reboot_min_outage: Duration,
+    /// 厂商连接管理进程名（按程序名匹配），空为不看护
+    pub conn_manager_process: String,
+    /// 该进程不在时执行的启动命令（sh -c）
+    pub conn_manager_start_cmd: String,
+    /// 启动后依次执行的命令（如重新拨号），配置中用 `;` 分隔
+    pub conn_manager_post_start: Vec<String>,
+    /// 远程检查前先 ping 默认网关，网关不通时直接记为失败
+    pub gateway_probe: bool,
+    /// 与目标保持一条开启 TCP keepalive 的长连接代替每次重新握手，断线时立即检查；
+    /// 连接保持期间没有延迟测量，只有重新连接时记录连接耗时
+    pub keepalive_check: bool,
+    /// 检查方式：tcp、udp、arp、hybrid 或 http（arp 只能用于 LAN 网段内的目标）
+    pub probe: ProbeMethod,
+    /// probe=http 时首字节时间超过该值记为服务器慢（SLOW_SERVER），0 为不检查；
+    /// 必须小于等待响应的时间（http::RESPONSE_TIMEOUT），否则慢服务器会被记为连接失败
+    pub ttfb_threshold: Duration,
+    /// probe=udp 的负载长度（字节，1-1400），用固定图案填充
+    pub udp_echo_payload: usize,
+    /// probe=udp 的目标端口（与通知端口无关），0 为使用检查目标的端口
+    pub udp_echo_port: u16,
+    /// probe=udp 的 IP TOS 字节（DSCP 左移 2 位，IPv6 为 traffic class），0 为不设置
+    pub udp_echo_tos: u8,
+    /// probe=udp 要求回应与负载逐字节相同；关闭时收到目标的任何回应都算成功
+    pub udp_echo_strict: bool,
+    /// 探测 socket（tcp/udp/http/hybrid/keepalive）的 DSCP（0-63），None 为不标记。
+    /// 拥塞时标记过的探测优先通过，测得的延迟也是优先路径的延迟（这正是目的，不代表普通流量）
+    pub probe_dscp: Option<u8>,
+    /// 探测 socket 的 SO_PRIORITY（0-7，本机出口队列），None 为不设置
+    pub probe_priority: Option<u8>,
+    /// 通知 socket 也使用 probe_dscp/probe_priority 的标记
+    pub notify_mark: bool,
+    /// 探测 socket 绑定到该网卡（SO_BINDTODEVICE），不管默认路由走哪个上行口；
+    /// 需要 root 或 CAP_NET_RAW，绑定失败时按路由表探测。空为不绑定
+    pub bind_interface: String,
+    /// 自动重启前做本地检查（WAN 地址、carrier、默认路由、网关），全部正常时不重启
+    pub reboot_local_check: bool,
+    /// 同时出现多个保护条件时的优先级（从高到低），低优先级的恢复动作会推迟
+    pub condition_priority: Vec<Condition>,
+    /// 按固定间隔（从启动算起）清空日志文件，0 为不按间隔
+    pub log_prune_interval: Duration,
+    /// 把每次 CPU 采样和连通性检查追加到存储目录下的 zxping.samples.csv（会增加闪存写入），默认关闭
+    pub sample_log: bool,
+    /// 采样文件的大小上限（字节），超过后改名为 .1 重新开始，最多占用两倍空间
+    pub sample_log_max_bytes: u64,
+    /// 每天在本地时间几点之后清空日志（当天秒数），时钟不可信时退回按间隔
+    pub log_prune_at: Option<u32>,
+    /// 日志文件路径（追加写入，不存在时创建），空为存储目录下的 zxping.log
+    pub log_to: String,
+    /// 状态快照文件（供厂商 web 界面的调试页显示），空为不写
+    pub status_file: String,
+    /// 每隔这么久发送一次 HEARTBEAT 通知（连续成功次数、健康时长、LAN 客户端数），0 为关闭
+    pub heartbeat_interval: Duration,
+    /// 检测不到 br0 网段时使用的 LAN 网段（如 `10.0.0.0/24`），空为不假设；之后仍会继续检测，检测到后改用实际网段
+    pub assume_lan: String,
+    /// DHCP 租约文件（dnsmasq 或 udhcpd），用于统计 LAN 客户端数；空为自动查找常见位置
+    pub dhcp_leases_file: String,
+    /// WAN 正常时客户端数从非 0 降为 0 时发送 LAN_EMPTY 通知（默认关闭）
+    pub lan_empty_notify: bool,
+    /// 连续这么多次因同一目标不可达而重启后，启动时改用备用目标检查，0 为关闭（默认关闭，建议 2）
+    pub target_suspect_reboots: u32,
+    /// 上述连续重启需要发生在这个时间窗口内
+    pub target_suspect_window: Duration,
+    /// 备用检查目标（IP:PORT），空为默认网关（端口沿用原目标的）
+    pub fallback_target: String,
+    /// 原目标连续可达这么久后恢复为检查目标
+    pub target_restore_after: Duration,
+    /// 连接耗时直方图的桶上界（毫秒，严格递增），最后一个桶没有上界
+    pub latency_buckets: Vec<u32>,
+    /// 一周内某个小时的失败超过总失败次数的这个百分比时，在每日汇总和 STATS 中提示 PATTERN_SUSPECTED；0 为关闭
+    pub fail_pattern_share_percent: u32,
+    /// 非默认值的来源，key 同 KEYS
+    pub sources: HashMap<&'static str, ConfigSource>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let (conntrack_max, conntrack_max_throttled, conntrack_hashsize) =
+            Profile::Balanced.conntrack();
+        Config {
+            udp_local_bind: "0.0.0.0:0".to_string(),
+            udp_timeout: Duration::from_secs(2),
+            led: None,
+            log_color: false,
+            conntrack_max,
+            conntrack_max_throttled,
+            conntrack_hashsize,
+            storage_root: "/etc_rw".to_string(),
+            firmware_version_file: "/etc/version".to_string(),
+            notify_envelope: false,
+            notify_ack_timeout: Duration::ZERO,
+            notify_ack_retries: 2,
+            profile: Profile::Balanced,
+            cache_drop_mem_kb: 0,
+            cache_drop_min_interval: Duration::from_secs(300),
+            cache_drop_floor_kb: 16384,
+            notify_addrs: Vec::new(),
+            notify_only: Vec::new(),
+            notify_disable: Vec::new(),
+            high_load_report_on_change: false,
+            high_load_report_interval: Duration::from_secs(300),
+            high_load_enter_count: 1,
+            high_load_exit_count: 1,
+            diag_failure_threshold: 3,
+            max_failures: 15,
+            high_load_failure_factor: 2,
+            failure_window: 0,
+            failure_window_percent: 60,
+            max_high_latency: 3,
+            rtt_outlier: Duration::ZERO,
+            latency_escalate_after: 0,
+            health_score_scale_percent: 100,
+            health_score_half_life: Duration::from_secs(300),
+            health_score_notify: 40,
+            health_score_throttle: 0,
+            health_score_reboot: 0,
+            log_debug: false,
+            sock_tcp_inuse_max: 512,
+            sock_tcp_orphan_max: 64,
+            fd_warn_percent: 80,
+            fd_critical_percent: 95,
+            sock_orphan_throttle: false,
+            auto_reboot: false,
+            runaway_kill: false,
+            runaway_kill_list: Vec::new(),
+            runaway_cpu_threshold: 50.0,
+            runaway_checks: 4,
+            runaway_kill_grace: Duration::from_secs(5),
+            kill_wait_timeout: Duration::from_secs(3),
+            adbd_kill_settle: Duration::from_secs(3),
+            adbd_ready_settle: Duration::ZERO,
+            adbd_ready_check_usb: false,
+            cpu_velocity_threshold: 0.0,
+            enable_cpu_monitor: true,
+            enable_network_monitor: true,
+            enable_control_channel: true,
+            enable_iptables: true,
+            iptables_flush: false,
+            tune_after_first_success: false,
+            tune_deadline: Duration::from_secs(300),
+            enable_adbd_control: true,
+            adbd_restrict_lan: false,
+            enable_ipv6_tuning: false,
+            route_repair: false,
+            maintenance: false,
+            maintenance_timeout: Duration::from_secs(3600),
+            hmac_key_file: String::new(),
+            control_recv_buffer: 0,
+            control_send_buffer: 0,
+            control_bind_conflict: BindConflict::Disable,
+            control_socket: String::new(),
+            http_port: 0,
+            http_address: IpAddr::from([127, 0, 0, 1]),
+            http_token_file: String::new(),
+            reboot_command: "/sbin/reboot".to_string(),
+            loop_stall_timeout: Duration::from_secs(300),
+            loop_stall_action: StallAction::Log,
+            tick_budget: Duration::from_secs(1),
+            slow_tick_count: 5,
+            hooks: HashMap::new(),
+            services: Vec::new(),
+            reboot_min_outage: Duration::ZERO,
+            conn_manager_process: String::new(),
+            conn_manager_start_cmd: String::new(),
+            conn_manager_post_start: Vec::new(),
+            gateway_probe: false,
+            keepalive_check: false,
+            probe: ProbeMethod::Tcp,
+            ttfb_threshold: Duration::from_secs(2),
+            udp_echo_payload: 32,
+            udp_echo_port: 0,
+            udp_echo_tos: 0,
+            udp_echo_strict: false,
+            probe_dscp: None,
+            probe_priority: None,
+            notify_mark: false,
+            bind_interface: String::new(),
+            reboot_local_check: false,
+            condition_priority: DEFAULT_PRIORITY.to_vec(),
+            log_prune_interval: Duration::ZERO,
+            sample_log: false,
+            sample_log_max_bytes: 256 * 1024,
+            log_prune_at: None,
+            log_to: String::new(),
+            status_file: String::new(),
+            heartbeat_interval: Duration::ZERO,
+            assume_lan: String::new(),
+            dhcp_leases_file: String::new(),
+            lan_empty_notify: false,
+            target_suspect_reboots: 0,
+            target_suspect_window: Duration::from_secs(4 * 3600),
+            fallback_target: String::new(),
+            target_restore_after: Duration::from_secs(600),
+            latency_buckets: DEFAULT_BUCKETS_MS.to_vec(),
+            fail_pattern_share_percent: 40,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+/// nf_conntrack_max 相对 hashsize 的最大合理倍数
+const CONNTRACK_MAX_HASH_RATIO: u32 = 16;
+
+/// failure_window 的上限（每次检查一个结果，保存在内存中）
+const MAX_FAILURE_WINDOW: u32 = 1000;
+
+/// 所有可配置的 key，命令行中对应 `--key-name value`
+const KEYS: &[&str] = &[
+    "udp_local_bind",
+    "udp_timeout_ms",
+    "led",
+    "log_color",
+    "conntrack_max",
+    "conntrack_max_throttled",
+    "conntrack_hashsize",
+    "storage_root",
+    "firmware_version_file",
+    "notify_envelope",
+    "notify_ack_timeout_ms",
+    "notify_ack_retries",
+    "profile",
+    "cache_drop_mem_kb",
+    "cache_drop_min_interval_secs",
+    "cache_drop_floor_kb",
+    "notify_addr",
+    "notify_only",
+    "notify_disable",
+    "high_load_report_on_change",
+    "high_load_report_interval_secs",
+    "high_load_enter_count",
+    "high_load_exit_count",
+    "diag_failure_threshold",
+    "max_failures",
+    "high_load_failure_factor",
+    "failure_window",
+    "failure_window_percent",
+    "max_high_latency",
+    "rtt_outlier_ms",
+    "latency_escalate_after",
+    "health_score_scale_percent",
+    "health_score_half_life_secs",
+    "health_score_notify",
+    "health_score_throttle",
+    "health_score_reboot",
+    "log_debug",
+    "sock_tcp_inuse_max",
+    "sock_tcp_orphan_max",
+    "fd_warn_percent",
+    "fd_critical_percent",
+    "sock_orphan_throttle",
+    "auto_reboot",
+    "runaway_kill",
+    "runaway_kill_list",
+    "runaway_cpu_threshold",
+    "runaway_checks",
+    "runaway_kill_grace_secs",
+    "kill_wait_timeout_ms",
+    "adbd_kill_settle_ms",
+    "adbd_ready_settle_ms",
+    "adbd_ready_check_usb",
+    "cpu_velocity_threshold",
+    "enable_cpu_monitor",
+    "enable_network_monitor",
+    "enable_control_channel",
+    "enable_iptables",
+    "iptables_flush",
+    "tune_after_first_success",
+    "tune_deadline_secs",
+    "enable_adbd_control",
+    "adbd_restrict_lan",
+    "enable_ipv6_tuning",
+    "route_repair",
+    "maintenance",
+    "maintenance_timeout_secs",
+    "hmac_key_file",
+    "control_recv_buffer",
+    "control_send_buffer",
+    "control_bind_conflict",
+    "control_socket",
+    "http_port",
+    "http_address",
+    "http_token_file",
+    "reboot_command",
+    "loop_stall_timeout_secs",
+    "loop_stall_action",
+    "tick_budget_ms",
+    "slow_tick_count",
+    "service",
+    "hook_connectivity_lost",
+    "hook_connectivity_restored",
+    "hook_high_load_enter",
+    "hook_high_load_exit",
+    "hook_before_reboot",
+    "hook_adbd_restarted",
+    "latency_buckets_ms",
+    "fail_pattern_share_percent",
+    "reboot_min_outage_secs",
+    "conn_manager_process",
+    "conn_manager_start_cmd",
+    "conn_manager_post_start",
+    "gateway_probe",
+    "keepalive_check",
+    "probe",
+    "ttfb_threshold_ms",
+    "udp_echo_payload_bytes",
+    "udp_echo_port",
+    "udp_echo_tos",
+    "udp_echo_strict",
+    "probe_dscp",
+    "probe_priority",
+    "notify_mark",
+    "bind_interface",
+    "reboot_local_check",
+    "condition_priority",
+    "log_prune_interval_secs",
+    "sample_log",
+    "sample_log_max_bytes",
+    "log_prune_at",
+    "log_to",
+    "status_file",
+    "heartbeat_interval_secs",
+    "assume_lan",
+    "dhcp_leases_file",
+    "lan_empty_notify",
+    "target_suspect_reboots",
+    "target_suspect_window_secs",
+    "fallback_target",
+    "target_restore_secs",
+];
+
+/// 布尔型 key，命令行中可以只写 `--key-name`（等同于 true）
+const BOOL_KEYS: &[&str] = &[
+    "log_color",
+    "notify_envelope",
+    "high_load_report_on_change",
+    "log_debug",
+    "sock_orphan_throttle",
+    "auto_reboot",
+    "runaway_kill",
+    "adbd_ready_check_usb",
+    "enable_cpu_monitor",
+    "enable_network_monitor",
+    "enable_control_channel",
+    "enable_iptables",
+    "iptables_flush",
+    "tune_after_first_success",
+    "enable_adbd_control",
+    "adbd_restrict_lan",
+    "enable_ipv6_tuning",
+    "route_repair",
+    "maintenance",
+    "gateway_probe",
+    "keepalive_check",
+    "udp_echo_strict",
+    "notify_mark",
+    "reboot_local_check",
+    "sample_log",
+    "lan_empty_notify",
+];
+
+impl Config {
+    /// 加载配置：先读配置文件（`--config <path>` 可覆盖路径），再应用命令行参数
+    pub fn load(args: &[String]) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut warnings = Vec::new();
+
+        let path = flag_value(args, "--config").unwrap_or(DEFAULT_CONFIG_PATH);
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                warnings.extend(check_version(&content, path));
+                warnings.extend(config.apply_file(&content, path));
+            }
+            Err(e) => {
+                // 默认路径不存在是正常情况，只有显式指定时才告警
+                if path != DEFAULT_CONFIG_PATH {
+                    warnings.push(format!("cannot read config {}: {}", path, e));
+                }
+            }
+        }
+
+        warnings.extend(config.apply_args(args));
+        warnings.extend(config.validate());
+        (config, warnings)
+    }
+
+    /// 检查配置项之间的合理性，只告警不拒绝
+    pub fn validate(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (key, max) in [
+            ("conntrack_max", self.conntrack_max),
+            ("conntrack_max_throttled", self.conntrack_max_throttled),
+        ] {
+            // 内核默认 nf_conntrack_max = hashsize * 4，比例过大时哈希链过长
+            let ratio = max / self.conntrack_hashsize;
+            if max < self.conntrack_hashsize || ratio > CONNTRACK_MAX_HASH_RATIO {
+                warnings.push(format!(
+                    "{}={} is implausible for conntrack_hashsize={} (expected 1x-{}x)",
+                    key, max, self.conntrack_hashsize, CONNTRACK_MAX_HASH_RATIO
+                ));
+            }
+        }
+        if self.conntrack_max_throttled > self.conntrack_max {
+            warnings.push(format!(
+                "conntrack_max_throttled={} is larger than conntrack_max={}",
+                self.conntrack_max_throttled, self.conntrack_max
+            ));
+        }
+        if self.fd_critical_percent > 0 && self.fd_warn_percent > self.fd_critical_percent {
+            warnings.push(format!(
+                "fd_warn_percent={} is larger than fd_critical_percent={}",
+                self.fd_warn_percent, self.fd_critical_percent
+            ));
+        }
+        warnings
+    }
+
+    /// 设置单个配置项
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "udp_local_bind" => {
+                self.udp_local_bind = parse_bind_addr(value)?;
+            }
+            "udp_timeout_ms" => {
+                let ms = parse_u64(key, value)?;
+                if ms == 0 {
+                    return Err("udp_timeout_ms must be > 0".to_string());
+                }
+                self.udp_timeout = Duration::from_millis(ms);
+            }
+            "led" => {
+                self.led = if value.is_empty() {
+                    None
+                } else {
+                    Some(value.to_string())
+                };
+            }
+            "log_color" => self.log_color = parse_bool(key, value)?,
+            "conntrack_max" => self.conntrack_max = parse_positive_u32(key, value)?,
+            "conntrack_max_throttled" => {
+                self.conntrack_max_throttled = parse_positive_u32(key, value)?
+            }
+            "conntrack_hashsize" => self.conntrack_hashsize = parse_positive_u32(key, value)?,
+            "storage_root" => {
+                if !value.starts_with('/') {
+                    return Err(format!("storage_root must be an absolute path: {}", value));
+                }
+                self.storage_root = value.to_string();
+            }
+            "firmware_version_file" => self.firmware_version_file = value.to_string(),
+            "hmac_key_file" => {
+                if !value.is_empty() && !value.starts_with('/') {
+                    return Err(format!("hmac_key_file must be an absolute path: {}", value));
+                }
+                self.hmac_key_file = value.to_string();
+            }
+            "http_port" => self.http_port = parse_port(key, value)?,
+            "http_address" => {
+                self.http_address = value
+                    .parse()
+                    .map_err(|_| format!("{}: invalid address '{}'", key, value))?
+            }
+            "control_recv_buffer" => self.control_recv_buffer = parse_u64(key, value)? as usize,
+            "control_send_buffer" => self.control_send_buffer = parse_u64(key, value)? as usize,
+            "control_bind_conflict" => {
+                self.control_bind_conflict = match value {
+                    "fail" => BindConflict::Fail,
+                    "retry" => BindConflict::Retry,
+                    "disable" => BindConflict::Disable,
+                    _ => {
+                        return Err(format!(
+                            "{}: expected fail|retry|disable, got '{}'",
+                            key, value
+                        ))
+                    }
+                }
+            }
+            "control_socket" => {
+                if !value.is_empty() && !value.starts_with('/') {
+                    return Err(format!(
+                        "control_socket must be an absolute path: {}",
+                        value
+                    ));
+                }
+                self.control_socket = value.to_string();
+            }
+            "http_token_file" => {
+                if !value.is_empty() && !value.starts_with('/') {
+                    return Err(format!(
+                        "http_token_file must be an absolute path: {}",
+                        value
+                    ));
+                }
+                self.http_token_file = value.to_string();
+            }
+            "reboot_command" => {
+                if !value.is_empty() && !value.starts_with('/') {
+                    return Err(format!(
+                        "reboot_command must be an absolute path: {}",
+                        value
+                    ));
+                }
+                self.reboot_command = value.to_string();
+            }
+            "loop_stall_timeout_secs" => {
+                self.loop_stall_timeout = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "loop_stall_action" => {
+                self.loop_stall_action = match value {
+                    "log" => StallAction::Log,
+                    "exit" => StallAction::Exit,
+                    "reboot" => StallAction::Reboot,
+                    _ => {
+                        return Err(format!(
+                            "{}: expected log|exit|reboot, got '{}'",
+                            key, value
+                        ))
+                    }
+                }
+            }
+            "tick_budget_ms" => self.tick_budget = Duration::from_millis(parse_u64(key, value)?),
+            "slow_tick_count" => self.slow_tick_count = parse_positive_u32(key, value)?,
+            "notify_envelope" => self.notify_envelope = parse_bool(key, value)?,
+            "notify_ack_timeout_ms" => {
+                self.notify_ack_timeout = Duration::from_millis(parse_u64(key, value)?)
+            }
+            "notify_ack_retries" => self.notify_ack_retries = parse_u32_in(key, value, 0..=10)?,
+            "profile" => {
+                let profile = Profile::parse(value).ok_or_else(|| {
+                    format!(
+                        "unknown profile '{}' (expected {})",
+                        value,
+                        Profile::names()
+                    )
+                })?;
+                self.apply_profile(profile);
+            }
+            "cache_drop_mem_kb" => self.cache_drop_mem_kb = parse_u64(key, value)?,
+            "cache_drop_floor_kb" => self.cache_drop_floor_kb = parse_u64(key, value)?,
+            "cache_drop_min_interval_secs" => {
+                self.cache_drop_min_interval = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "high_load_report_on_change" => {
+                self.high_load_report_on_change = parse_bool(key, value)?
+            }
+            "high_load_report_interval_secs" => {
+                self.high_load_report_interval = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "high_load_enter_count" => self.high_load_enter_count = parse_positive_u32(key, value)?,
+            "high_load_exit_count" => self.high_load_exit_count = parse_positive_u32(key, value)?,
+            "diag_failure_threshold" => self.diag_failure_threshold = parse_u32(key, value)?,
+            "max_failures" => self.max_failures = parse_positive_u32(key, value)?,
+            "high_load_failure_factor" => {
+                self.high_load_failure_factor = parse_positive_u32(key, value)?
+            }
+            "failure_window" => {
+                self.failure_window = parse_u32_in(key, value, 0..=MAX_FAILURE_WINDOW)?
+            }
+            "failure_window_percent" => self.failure_window_percent = parse_percent(key, value)?,
+            "max_high_latency" => self.max_high_latency = parse_positive_u32(key, value)?,
+            "rtt_outlier_ms" => self.rtt_outlier = Duration::from_millis(parse_u64(key, value)?),
+            "latency_escalate_after" => self.latency_escalate_after = parse_u32(key, value)?,
+            "health_score_scale_percent" => {
+                self.health_score_scale_percent = parse_positive_u32(key, value)?
+            }
+            "health_score_half_life_secs" => {
+                self.health_score_half_life =
+                    Duration::from_secs(parse_positive_u32(key, value)? as u64)
+            }
+            "health_score_notify" => self.health_score_notify = parse_u32(key, value)?,
+            "health_score_throttle" => self.health_score_throttle = parse_u32(key, value)?,
+            "health_score_reboot" => self.health_score_reboot = parse_u32(key, value)?,
+            "log_debug" => self.log_debug = parse_bool(key, value)?,
+            "sock_tcp_inuse_max" => self.sock_tcp_inuse_max = parse_u64(key, value)?,
+            "sock_tcp_orphan_max" => self.sock_tcp_orphan_max = parse_u64(key, value)?,
+            "fd_warn_percent" => self.fd_warn_percent = parse_percent(key, value)?,
+            "fd_critical_percent" => self.fd_critical_percent = parse_percent(key, value)?,
+            "sock_orphan_throttle" => self.sock_orphan_throttle = parse_bool(key, value)?,
+            "auto_reboot" => self.auto_reboot = parse_bool(key, value)?,
+            "runaway_kill" => self.runaway_kill = parse_bool(key, value)?,
+            "cpu_velocity_threshold" => {
+                self.cpu_velocity_threshold = match value.parse::<f32>() {
+                    Ok(slope) if slope >= 0.0 => slope,
+                    _ => return Err(format!("{}: expected >= 0, got '{}'", key, value)),
+                }
+            }
+            "enable_cpu_monitor" => self.enable_cpu_monitor = parse_bool(key, value)?,
+            "enable_network_monitor" => self.enable_network_monitor = parse_bool(key, value)?,
+            "enable_control_channel" => self.enable_control_channel = parse_bool(key, value)?,
+            "enable_iptables" => self.enable_iptables = parse_bool(key, value)?,
+            "iptables_flush" => self.iptables_flush = parse_bool(key, value)?,
+            "tune_after_first_success" => self.tune_after_first_success = parse_bool(key, value)?,
+            "tune_deadline_secs" => {
+                self.tune_deadline = Duration::from_secs(parse_positive_u32(key, value)? as u64)
+            }
+            "enable_adbd_control" => self.enable_adbd_control = parse_bool(key, value)?,
+            "adbd_restrict_lan" => self.adbd_restrict_lan = parse_bool(key, value)?,
+            "enable_ipv6_tuning" => self.enable_ipv6_tuning = parse_bool(key, value)?,
+            "route_repair" => self.route_repair = parse_bool(key, value)?,
+            "maintenance" => self.maintenance = parse_bool(key, value)?,
+            "maintenance_timeout_secs" => {
+                self.maintenance_timeout =
+                    Duration::from_secs(parse_positive_u32(key, value)? as u64)
+            }
+            "runaway_kill_list" => {
+                self.runaway_kill_list = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "runaway_cpu_threshold" => {
+                self.runaway_cpu_threshold = match value.parse::<f32>() {
+                    Ok(pct) if pct > 0.0 && pct <= 100.0 => pct,
+                    _ => return Err(format!("{}: expected 1-100, got '{}'", key, value)),
+                }
+            }
+            "runaway_checks" => self.runaway_checks = parse_positive_u32(key, value)?,
+            "runaway_kill_grace_secs" => {
+                self.runaway_kill_grace = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "kill_wait_timeout_ms" => {
+                let ms = parse_u64(key, value)?;
+                if ms == 0 {
+                    return Err("kill_wait_timeout_ms must be > 0".to_string());
+                }
+                self.kill_wait_timeout = Duration::from_millis(ms);
+            }
+            "adbd_kill_settle_ms" => {
+                let ms = parse_u64(key, value)?;
+                if ms == 0 {
+                    return Err("adbd_kill_settle_ms must be > 0".to_string());
+                }
+                self.adbd_kill_settle = Duration::from_millis(ms);
+            }
+            "adbd_ready_settle_ms" => {
+                self.adbd_ready_settle = Duration::from_millis(parse_u64(key, value)?)
+            }
+            "adbd_ready_check_usb" => self.adbd_ready_check_usb = parse_bool(key, value)?,
+            "latency_buckets_ms" => self.latency_buckets = parse_bounds(value)?,
+            "fail_pattern_share_percent" => {
+                self.fail_pattern_share_percent = parse_percent(key, value)?
+            }
+            "gateway_probe" => self.gateway_probe = parse_bool(key, value)?,
+            "keepalive_check" => self.keepalive_check = parse_bool(key, value)?,
+            "probe" => {
+                self.probe = match value {
+                    "tcp" => ProbeMethod::Tcp,
+                    "udp" => ProbeMethod::Udp,
+                    "arp" => ProbeMethod::Arp,
+                    "hybrid" => ProbeMethod::Hybrid,
+                    "http" => ProbeMethod::Http,
+                    _ => {
+                        return Err(format!(
+                            "{}: expected tcp|udp|arp|hybrid|http, got '{}'",
+                            key, value
+                        ))
+                    }
+                }
+            }
+            "ttfb_threshold_ms" => {
+                let threshold = Duration::from_millis(parse_u64(key, value)?);
+                if threshold >= http::RESPONSE_TIMEOUT {
+                    return Err(format!(
+                        "{} must be below the {}ms response timeout",
+                        key,
+                        http::RESPONSE_TIMEOUT.as_millis()
+                    ));
+                }
+                self.ttfb_threshold = threshold;
+            }
+            "udp_echo_payload_bytes" => {
+                self.udp_echo_payload = parse_u32_in(key, value, 1..=MAX_PAYLOAD as u32)? as usize
+            }
+            "udp_echo_port" => self.udp_echo_port = parse_port(key, value)?,
+            "udp_echo_tos" => {
+                self.udp_echo_tos = parse_tos(value)
+                    .ok_or_else(|| format!("{}: expected 0-255 or 0xNN, got '{}'", key, value))?
+            }
+            "udp_echo_strict" => self.udp_echo_strict = parse_bool(key, value)?,
+            "probe_dscp" => self.probe_dscp = parse_optional_max(key, value, 63)?,
+            "probe_priority" => self.probe_priority = parse_optional_max(key, value, 7)?,
+            "notify_mark" => self.notify_mark = parse_bool(key, value)?,
+            "bind_interface" => {
+                // IFNAMSIZ 为 16（含结尾的 0）
+                if value.len() > 15 || value.contains(|c: char| c == '/' || c.is_whitespace()) {
+                    return Err(format!("{}: invalid interface name '{}'", key, value));
+                }
+                self.bind_interface = value.to_string();
+            }
+            "log_to" => self.log_to = value.to_string(),
+            "status_file" => self.status_file = value.to_string(),
+            "heartbeat_interval_secs" => {
+                self.heartbeat_interval = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "assume_lan" => {
+                self.assume_lan = if value.is_empty() {
+                    String::new()
+                } else {
+                    let (addr, prefix) = routes::parse_network(value).ok_or_else(|| {
+                        format!("{}: invalid network '{}', expected ADDR/PREFIX", key, value)
+                    })?;
+                    format!("{}/{}", addr, prefix)
+                }
+            }
+            "dhcp_leases_file" => {
+                if !value.is_empty() && !value.starts_with('/') {
+                    return Err(format!(
+                        "dhcp_leases_file must be an absolute path: {}",
+                        value
+                    ));
+                }
+                self.dhcp_leases_file = value.to_string();
+            }
+            "lan_empty_notify" => self.lan_empty_notify = parse_bool(key, value)?,
+            "target_suspect_reboots" => self.target_suspect_reboots = parse_u32(key, value)?,
+            "target_suspect_window_secs" => {
+                self.target_suspect_window = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "fallback_target" => {
+                if !value.is_empty() && value.parse::<std::net::SocketAddr>().is_err() {
+                    return Err(format!("{}: expected IP:PORT, got '{}'", key, value));
+                }
+                self.fallback_target = value.to_string();
+            }
+            "target_restore_secs" => {
+                self.target_restore_after = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "log_prune_interval_secs" => {
+                self.log_prune_interval = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "sample_log" => self.sample_log = parse_bool(key, value)?,
+            "sample_log_max_bytes" => {
+                self.sample_log_max_bytes = parse_positive_u32(key, value)? as u64
+            }
+            "log_prune_at" => {
+                self.log_prune_at = if value.is_empty() {
+                    None
+                } else {
+                    Some(parse_time_of_day(value)?)
+                }
+            }
+            "condition_priority" => self.condition_priority = parse_priority(value)?,
+            "reboot_local_check" => self.reboot_local_check = parse_bool(key, value)?,
+            "conn_manager_process" => self.conn_manager_process = value.trim().to_string(),
+            "conn_manager_start_cmd" => self.conn_manager_start_cmd = value.trim().to_string(),
+            "conn_manager_post_start" => {
+                self.conn_manager_post_start = value
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|cmd| !cmd.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            "reboot_min_outage_secs" => {
+                self.reboot_min_outage = Duration::from_secs(parse_u64(key, value)?)
+            }
+            "notify_addr" => {
+                for addr in value.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+                    let addr = addr
+                        .parse::<SocketAddr>()
+                        .map_err(|_| format!("invalid notify address: {}", addr))?;
+                    if !self.notify_addrs.contains(&addr.to_string()) {
+                        self.notify_addrs.push(addr.to_string());
+                    }
+                }
+            }
+            "notify_only" => self.notify_only = parse_event_list(value)?,
+            "notify_disable" => self.notify_disable = parse_event_list(value)?,
+            "service" => {
+                if value.is_empty() {
+                    self.services.clear();
+                }
+                for spec in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    let service = Service::parse(spec)?;
+                    self.services
+                        .retain(|existing| existing.name != service.name);
+                    self.services.push(service);
+                }
+            }
+            key if key.starts_with("hook_") => {
+                let event = HookEvent::from_key(key)
+                    .ok_or_else(|| format!("unknown config key: {}", key))?;
+                if value.is_empty() {
+                    self.hooks.remove(&event);
+                } else if !value.starts_with('/') {
+                    return Err(format!("{} must be an absolute path: {}", key, value));
+                } else {
+                    self.hooks.insert(event, value.to_string());
+                }
+            }
+            _ => return Err(format!("unknown config key: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// 切换调优档位并使用该档位的 conntrack 参数；已经在配置文件、命令行或运行中
+    /// 明确设置的 conntrack_* 保持不变（只替换默认值和档位带出的值）
+    pub fn apply_profile(&mut self, profile: Profile) {
+        let (max, max_throttled, hashsize) = profile.conntrack();
+        self.profile = profile;
+        for (key, field, value) in [
+            ("conntrack_max", &mut self.conntrack_max, max),
+            (
+                "conntrack_max_throttled",
+                &mut self.conntrack_max_throttled,
+                max_throttled,
+            ),
+            ("conntrack_hashsize", &mut self.conntrack_hashsize, hashsize),
+        ] {
+            let source = self.sources.get(key).unwrap_or(&ConfigSource::Default);
+            if matches!(source, ConfigSource::Default | ConfigSource::Profile) {
+                *field = value;
+                self.sources.insert(key, ConfigSource::Profile);
+            }
+        }
+    }
+
+    /// 记录 key 当前值的来源（未知 key 忽略）
+    pub fn set_source(&mut self, key: &str, source: ConfigSource) {
+        if let Some(key) = KEYS.iter().find(|k| **k == key) {
+            self.sources.insert(key, source);
+        }
+    }
+
+    fn set_from(&mut self, key: &str, value: &str, source: ConfigSource) -> Result<(), String> {
+        self.set(key, value)?;
+        self.set_source(key, source);
+        Ok(())
+    }
+
+    /// 当前生效的值，格式与配置文件中的写法一致
+    pub fn get(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "udp_local_bind" => self.udp_local_bind.clone(),
+            "udp_timeout_ms" => self.udp_timeout.as_millis().to_string(),
+            "led" => self.led.clone().unwrap_or_default(),
+            "log_color" => self.log_color.to_string(),
+            "conntrack_max" => self.conntrack_max.to_string(),
+            "conntrack_max_throttled" => self.conntrack_max_throttled.to_string(),
+            "conntrack_hashsize" => self.conntrack_hashsize.to_string(),
+            "storage_root" => self.storage_root.clone(),
+            "firmware_version_file" => self.firmware_version_file.clone(),
+            "hmac_key_file" => self.hmac_key_file.clone(),
+            "http_port" => self.http_port.to_string(),
+            "http_address" => self.http_address.to_string(),
+            "http_token_file" => self.http_token_file.clone(),
+            "control_recv_buffer" => self.control_recv_buffer.to_string(),
+            "control_send_buffer" => self.control_send_buffer.to_string(),
+            "control_bind_conflict" => self.control_bind_conflict.name().to_string(),
+            "control_socket" => self.control_socket.clone(),
+            "reboot_command" => self.reboot_command.clone(),
+            "loop_stall_timeout_secs" => self.loop_stall_timeout.as_secs().to_string(),
+            "loop_stall_action" => self.loop_stall_action.name().to_string(),
+            "tick_budget_ms" => self.tick_budget.as_millis().to_string(),
+            "slow_tick_count" => self.slow_tick_count.to_string(),
+            "latency_buckets_ms" => self
+                .latency_buckets
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            "fail_pattern_share_percent" => self.fail_pattern_share_percent.to_string(),
+            "notify_envelope" => self.notify_envelope.to_string(),
+            "notify_ack_timeout_ms" => self.notify_ack_timeout.as_millis().to_string(),
+            "notify_ack_retries" => self.notify_ack_retries.to_string(),
+            "profile" => self.profile.name().to_string(),
+            "cache_drop_mem_kb" => self.cache_drop_mem_kb.to_string(),
+            "cache_drop_floor_kb" => self.cache_drop_floor_kb.to_string(),
+            "cache_drop_min_interval_secs" => self.cache_drop_min_interval.as_secs().to_string(),
+            "notify_addr" => self.notify_addrs.join(","),
+            "notify_only" => self.notify_only.join(","),
+            "notify_disable" => self.notify_disable.join(","),
+            "service" => self
+                .services
+                .iter()
+                .map(Service::spec)
+                .collect::<Vec<_>>()
+                .join(","),
+            "high_load_report_on_change" => self.high_load_report_on_change.to_string(),
+            "high_load_report_interval_secs" => {
+                self.high_load_report_interval.as_secs().to_string()
+            }
+            "high_load_enter_count" => self.high_load_enter_count.to_string(),
+            "high_load_exit_count" => self.high_load_exit_count.to_string(),
+            "diag_failure_threshold" => self.diag_failure_threshold.to_string(),
+            "max_failures" => self.max_failures.to_string(),
+            "high_load_failure_factor" => self.high_load_failure_factor.to_string(),
+            "failure_window" => self.failure_window.to_string(),
+            "failure_window_percent" => self.failure_window_percent.to_string(),
+            "max_high_latency" => self.max_high_latency.to_string(),
+            "rtt_outlier_ms" => self.rtt_outlier.as_millis().to_string(),
+            "latency_escalate_after" => self.latency_escalate_after.to_string(),
+            "health_score_scale_percent" => self.health_score_scale_percent.to_string(),
+            "health_score_half_life_secs" => self.health_score_half_life.as_secs().to_string(),
+            "health_score_notify" => self.health_score_notify.to_string(),
+            "health_score_throttle" => self.health_score_throttle.to_string(),
+            "health_score_reboot" => self.health_score_reboot.to_string(),
+            "log_debug" => self.log_debug.to_string(),
+            "sock_tcp_inuse_max" => self.sock_tcp_inuse_max.to_string(),
+            "sock_tcp_orphan_max" => self.sock_tcp_orphan_max.to_string(),
+            "fd_warn_percent" => self.fd_warn_percent.to_string(),
+            "fd_critical_percent" => self.fd_critical_percent.to_string(),
+            "sock_orphan_throttle" => self.sock_orphan_throttle.to_string(),
+            "auto_reboot" => self.auto_reboot.to_string(),
+            "runaway_kill" => self.runaway_kill.to_string(),
+            "runaway_kill_list" => self.runaway_kill_list.join(","),
+            "runaway_cpu_threshold" => self.runaway_cpu_threshold.to_string(),
+            "runaway_checks" => self.runaway_checks.to_string(),
+            "runaway_kill_grace_secs" => self.runaway_kill_grace.as_secs().to_string(),
+            "kill_wait_timeout_ms" => self.kill_wait_timeout.as_millis().to_string(),
+            "adbd_kill_settle_ms" => self.adbd_kill_settle.as_millis().to_string(),
+            "adbd_ready_settle_ms" => self.adbd_ready_settle.as_millis().to_string(),
+            "adbd_ready_check_usb" => self.adbd_ready_check_usb.to_string(),
+            "reboot_min_outage_secs" => self.reboot_min_outage.as_secs().to_string(),
+            "conn_manager_process" => self.conn_manager_process.clone(),
+            "gateway_probe" => self.gateway_probe.to_string(),
+            "keepalive_check" => self.keepalive_check.to_string(),
+            "probe" => self.probe.name().to_string(),
+            "ttfb_threshold_ms" => self.ttfb_threshold.as_millis().to_string(),
+            "udp_echo_payload_bytes" => self.udp_echo_payload.to_string(),
+            "udp_echo_port" => self.udp_echo_port.to_string(),
+            "udp_echo_tos" => format!("0x{:02x}", self.udp_echo_tos),
+            "udp_echo_strict" => self.udp_echo_strict.to_string(),
+            "probe_dscp" => self.probe_dscp.map(|v| v.to_string()).unwrap_or_default(),
+            "probe_priority" => self
+                .probe_priority
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            "notify_mark" => self.notify_mark.to_string(),
+            "bind_interface" => self.bind_interface.clone(),
+            "log_to" => self.log_to.clone(),
+            "status_file" => self.status_file.clone(),
+            "heartbeat_interval_secs" => self.heartbeat_interval.as_secs().to_string(),
+            "assume_lan" => self.assume_lan.clone(),
+            "dhcp_leases_file" => self.dhcp_leases_file.clone(),
+            "lan_empty_notify" => self.lan_empty_notify.to_string(),
+            "target_suspect_reboots" => self.target_suspect_reboots.to_string(),
+            "target_suspect_window_secs" => self.target_suspect_window.as_secs().to_string(),
+            "fallback_target" => self.fallback_target.clone(),
+            "target_restore_secs" => self.target_restore_after.as_secs().to_string(),
+            "log_prune_interval_secs" => self.log_prune_interval.as_secs().to_string(),
+            "sample_log" => self.sample_log.to_string(),
+            "sample_log_max_bytes" => self.sample_log_max_bytes.to_string(),
+            "log_prune_at" => self
+                .log_prune_at
+                .map(format_time_of_day)
+                .unwrap_or_default(),
+            "condition_priority" => self
+                .condition_priority
+                .iter()
+                .map(Condition::name)
+                .collect::<Vec<_>>()
+                .join(","),
+            "reboot_local_check" => self.reboot_local_check.to_string(),
+            "conn_manager_start_cmd" => self.conn_manager_start_cmd.clone(),
+            "conn_manager_post_start" => self.conn_manager_post_start.join(";"),
+            "cpu_velocity_threshold" => self.cpu_velocity_threshold.to_string(),
+            "enable_cpu_monitor" => self.enable_cpu_monitor.to_string(),
+            "enable_network_monitor" => self.enable_network_monitor.to_string(),
+            "enable_control_channel" => self.enable_control_channel.to_string(),
+            "enable_iptables" => self.enable_iptables.to_string(),
+            "iptables_flush" => self.iptables_flush.to_string(),
+            "tune_after_first_success" => self.tune_after_first_success.to_string(),
+            "tune_deadline_secs" => self.tune_deadline.as_secs().to_string(),
+            "enable_adbd_control" => self.enable_adbd_control.to_string(),
+            "adbd_restrict_lan" => self.adbd_restrict_lan.to_string(),
+            "enable_ipv6_tuning" => self.enable_ipv6_tuning.to_string(),
+            "route_repair" => self.route_repair.to_string(),
+            "maintenance" => self.maintenance.to_string(),
+            "maintenance_timeout_secs" => self.maintenance_timeout.as_secs().to_string(),
+            key => self
+                .hooks
+                .get(&HookEvent::from_key(key)?)
+                .cloned()
+                .unwrap_or_default(),
+        };
+        Some(value)
+    }
+
+    /// 所有配置项的 `key=value (来源)`，用于 CONFIG 命令和 --print-config
+    pub fn dump_lines(&self) -> Vec<String> {
+        KEYS.iter()
+            .map(|key| {
+                let source = self.sources.get(key).unwrap_or(&ConfigSource::Default);
+                format!(
+                    "{}={} ({})",
+                    key,
+                    self.get(key).unwrap_or_default(),
+                    source.as_str()
+                )
+            })
+            .collect()
+    }
+
+    fn apply_file(&mut self, content: &str, path: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                // 由 check_version 检查
+                Some((key, _)) if key.trim() == "version" => {}
+                Some((key, value)) => {
+                    if let Err(e) =
+                        self.set_from(key.trim(), unquote(value.trim()), ConfigSource::File)
+                    {
+                        warnings.push(format!("{}:{}: {}", path, lineno + 1, e));
+                    }
+                }
+                None => warnings.push(format!("{}:{}: expected key = value", path, lineno + 1)),
+            }
+        }
+        warnings
+    }
+
+    fn apply_args(&mut self, args: &[String]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut i = 1;
+        while i < args.len() {
+            if let Some(key) = config_key_of_flag(&args[i]) {
+                if BOOL_KEYS.contains(&key.as_str()) && !next_is_bool(args, i) {
+                    // 布尔选项不带值
+                    let _ = self.set_from(&key, "true", ConfigSource::Cli);
+                    i += 1;
+                    continue;
+                }
+                match args.get(i + 1) {
+                    Some(value) => {
+                        if let Err(e) = self.set_from(&key, value, ConfigSource::Cli) {
+                            warnings.push(format!("{}: {}", args[i], e));
+                        }
+                        i += 1;
+                    }
+                    None => warnings.push(format!("{}: missing value", args[i])),
+                }
+            }
+            i += 1;
+        }
+        warnings
+    }
+}
+
+/// 检查配置文件的 version：缺少或较旧时提醒新增的配置项都使用默认值，
+/// 较新时提醒其中本版本不认识的配置项会被忽略
+fn check_version(content: &str, path: &str) -> Vec<String> {
+    let value = content.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once('=')?;
+        (key.trim() == "version").then(|| value.trim())
+    });
+    let version = match value.map(|v| unquote(v).parse::<u32>()) {
+        None => {
+            return vec![format!(
+            "{}: no version, assuming a pre-versioning config; options added since use defaults \
+                 (add `version = {}` after reviewing it)",
+            path, CONFIG_VERSION
+        )]
+        }
+        Some(Err(_)) => {
+            return vec![format!(
+                "{}: invalid version '{}'",
+                path,
+                value.unwrap_or_default()
+            )]
+        }
+        Some(Ok(version)) => version,
+    };
+    if version < CONFIG_VERSION {
+        vec![format!(
+            "{}: version {} is older than {}; options added since use defaults",
+            path, version, CONFIG_VERSION
+        )]
+    } else if version > CONFIG_VERSION {
+        vec![format!(
+            "{}: version {} is newer than {}; unknown keys are ignored",
+            path, version, CONFIG_VERSION
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+/// `--udp-local-bind` -> `udp_local_bind`，不是配置项则返回 None
+fn config_key_of_flag(arg: &str) -> Option<String> {
+    let key = arg.strip_prefix("--")?.replace('-', "_");
+    if KEYS.contains(&key.as_str()) {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+/// 去掉所有选项及其值后剩下的位置参数（如目标地址）
+pub fn positional_args(args: &[String]) -> Vec<&String> {
+    let mut positional = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--config" {
+            i += 1;
+        } else if let Some(key) = config_key_of_flag(arg) {
+            if !BOOL_KEYS.contains(&key.as_str()) || next_is_bool(args, i) {
+                i += 1;
+            }
+        } else if !arg.starts_with("--") {
+            positional.push(arg);
+        }
+        i += 1;
+    }
+    positional
+}
+
+fn next_is_bool(args: &[String], i: usize) -> bool {
+    args.get(i + 1).is_some_and(|v| parse_bool("", v).is_ok())
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("{}: invalid boolean '{}'", key, value)),
+    }
+}
+
+fn parse_u64(key: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_| format!("{}: invalid number '{}'", key, value))
+}
+
+fn parse_u32(key: &str, value: &str) -> Result<u32, String> {
+    u32::try_from(parse_u64(key, value)?)
+        .map_err(|_| format!("{}: number too large '{}'", key, value))
+}
+
+/// 0-100 的整数百分比，0 通常表示关闭
+fn parse_percent(key: &str, value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(pct) if pct <= 100 => Ok(pct),
+        _ => Err(format!("{}: expected 0-100, got '{}'", key, value)),
+    }
+}
+
+/// range 范围内的整数
+fn parse_u32_in(key: &str, value: &str, range: RangeInclusive<u32>) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if range.contains(&n) => Ok(n),
+        _ => Err(format!(
+            "{}: expected {}-{}, got '{}'",
+            key,
+            range.start(),
+            range.end(),
+            value
+        )),
+    }
+}
+
+/// 端口号，0 的含义由各配置项决定
+fn parse_port(key: &str, value: &str) -> Result<u16, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{}: invalid port '{}'", key, value))
+}
+
+/// TOS 字节，十进制或 0x 开头的十六进制
+fn parse_tos(value: &str) -> Option<u8> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// 0-max 的整数，空为不设置
+fn parse_optional_max(key: &str, value: &str, max: u8) -> Result<Option<u8>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match value.parse::<u8>() {
+        Ok(n) if n <= max => Ok(Some(n)),
+        _ => Err(format!(
+            "{}: expected 0-{} or empty, got '{}'",
+            key, max, value
+        )),
+    }
+}
+
+fn parse_positive_u32(key: &str, value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{}: expected a positive number, got '{}'",
+            key, value
+        )),
+    }
+}
+
+/// 逗号分隔的通知类型（通知开头的大写名称，如 `HIGH_LOAD`）
+fn parse_event_list(value: &str) -> Result<Vec<String>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            if name
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            {
+                Ok(name.to_string())
+            } else {
+                Err(format!("invalid notification type: {}", name))
+            }
+        })
+        .collect()
+}
+
+/// 接受 `IP` 或 `IP:port`，只写 IP 时端口为 0（随机）
+fn parse_bind_addr(value: &str) -> Result<String, String> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(addr.to_string());
+    }
+    match value.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, 0).to_string()),
+        Err(_) => Err(format!("invalid bind address: {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_apply_file() {
+        let mut config = Config::default();
+        let warnings = config.apply_file(
+            "# comment\nudp_timeout_ms = 500\nled = \"red\"\nbogus = 1\nnot a pair\n",
+            "test.conf",
+        );
+        assert_eq!(config.udp_timeout, Duration::from_millis(500));
+        assert_eq!(config.led.as_deref(), Some("red"));
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_check_version() {
+        assert!(check_version("version = 1\nled = red\n", "test.conf").is_empty());
+        assert!(check_version("led = red\n", "test.conf")[0].contains("no version"));
+        assert!(check_version("version = 99\n", "test.conf")[0].contains("newer"));
+        assert!(check_version("version = x\n", "test.conf")[0].contains("invalid version 'x'"));
+
+        // version 不算未知配置项
+        let mut config = Config::default();
+        let warnings = config.apply_file("version = 1\nbogus = 1\n", "test.conf");
+        assert_eq!(warnings, ["test.conf:2: unknown config key: bogus"]);
+    }
+
+    #[test]
+    fn test_hooks() {
+        let mut config = Config::default();
+        assert!(config.set("hook_before_reboot", "/etc_rw/relay.sh").is_ok());
+        assert_eq!(
+            config.get("hook_before_reboot").as_deref(),
+            Some("/etc_rw/relay.sh")
+        );
+        assert_eq!(config.get("hook_adbd_restarted").as_deref(), Some(""));
+        assert!(config.set("hook_before_reboot", "relay.sh").is_err());
+        assert!(config.set("hook_bogus", "/x").is_err());
+        assert!(config.set("hook_before_reboot", "").is_ok());
+        assert!(config.hooks.is_empty());
+    }
+
+    #[test]
+    fn test_reboot_command() {
+        let mut config = Config::default();
+        assert_eq!(config.reboot_command, "/sbin/reboot");
+        assert!(config.set("reboot_command", "/sbin/reboot -f").is_ok());
+        assert_eq!(config.reboot_command, "/sbin/reboot -f");
+        assert!(config.set("reboot_command", "reboot -f").is_err());
+        assert!(config.set("reboot_command", "").is_ok());
+    }
+
+    #[test]
+    fn test_loop_stall_options() {
+        let mut config = Config::default();
+        assert_eq!(config.loop_stall_action, StallAction::Log);
+        config.set("loop_stall_action", "exit").unwrap();
+        assert_eq!(config.get("loop_stall_action").as_deref(), Some("exit"));
+        assert!(config.set("loop_stall_action", "panic").is_err());
+        config.set("loop_stall_timeout_secs", "0").unwrap();
+        assert!(config.loop_stall_timeout.is_zero());
+    }
+
+    #[test]
+    fn test_tick_budget() {
+        let mut config = Config::default();
+        assert_eq!(config.tick_budget, Duration::from_secs(1));
+        let warnings = config.apply_args(&args(&["zxic_ping", "--tick-budget-ms", "2500"]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.tick_budget, Duration::from_millis(2500));
+        assert!(config.set("slow_tick_count", "0").is_err());
+        config.set("slow_tick_count", "3").unwrap();
+        assert_eq!(config.get("slow_tick_count").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_probe_method() {
+        let mut config = Config::default();
+        let warnings = config.apply_args(&args(&["zxic_ping", "--probe", "arp"]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.probe, ProbeMethod::Arp);
+        assert!(config.set("probe", "icmp").is_err());
+        assert!(config.set("probe", "http").is_ok());
+        assert!(config.set("probe", "hybrid").is_ok());
+        assert_eq!(config.probe, ProbeMethod::Hybrid);
+        assert!(config.set("ttfb_threshold_ms", "1500").is_ok());
+        assert_eq!(config.get("ttfb_threshold_ms").as_deref(), Some("1500"));
+        assert!(config.set("ttfb_threshold_ms", "10000").is_err());
+    }
+
+    #[test]
+    fn test_udp_echo_options() {
+        let mut config = Config::default();
+        let warnings = config.apply_args(&args(&[
+            "zxic_ping",
+            "--probe",
+            "udp",
+            "--udp-echo-port",
+            "7",
+            "--udp-echo-tos",
+            "0xb8",
+            "--udp-echo-strict",
+        ]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.probe, ProbeMethod::Udp);
+        assert_eq!(config.udp_echo_port, 7);
+        assert_eq!(config.udp_echo_tos, 0xb8);
+        assert!(config.udp_echo_strict);
+        assert_eq!(config.get("udp_echo_tos").as_deref(), Some("0xb8"));
+
+        assert!(config.set("udp_echo_payload_bytes", "1400").is_ok());
+        assert!(config.set("udp_echo_payload_bytes", "1401").is_err());
+        assert!(config.set("udp_echo_payload_bytes", "0").is_err());
+        assert_eq!(config.udp_echo_payload, 1400);
+        assert!(config.set("udp_echo_tos", "184").is_ok());
+        assert!(config.set("udp_echo_tos", "256").is_err());
+    }
+
+    #[test]
+    fn test_adbd_ready_options() {
+        let mut config = Config::default();
+        let warnings = config.apply_args(&args(&[
+            "zxic_ping",
+            "--adbd-ready-settle-ms",
+            "5000",
+            "--adbd-ready-check-usb",
+        ]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.adbd_ready_settle, Duration::from_secs(5));
+        assert!(config.adbd_ready_check_usb);
+        assert!(config.set("adbd_ready_settle_ms", "0").is_ok());
+        assert!(config.set("adbd_ready_settle_ms", "-1").is_err());
+    }
+
+    #[test]
+    fn test_failure_window() {
+        let mut config = Config::default();
+        let warnings = config.apply_args(&args(&[
+            "zxic_ping",
+            "--failure-window",
+            "10",
+            "--failure-window-percent",
+            "50",
+        ]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.failure_window, 10);
+        assert_eq!(config.failure_window_percent, 50);
+        assert!(config.set("failure_window", "1001").is_err());
+        assert!(config.set("failure_window_percent", "101").is_err());
+        assert!(config.set("failure_window", "0").is_ok());
+    }
+
+    #[test]
+    fn test_probe_marking() {
+        let mut config = Config::default();
+        let warnings = config.apply_args(&args(&[
+            "zxic_ping",
+            "--probe-dscp",
+            "46",
+            "--probe-priority",
+            "6",
+            "--notify-mark",
+        ]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.probe_dscp, Some(46));
+        assert_eq!(config.probe_priority, Some(6));
+        assert!(config.notify_mark);
+        assert_eq!(config.get("probe_dscp").as_deref(), Some("46"));
+
+        // DSCP 只有 6 位
+        assert!(config.set("probe_dscp", "64").is_err());
+        assert!(config.set("probe_dscp", "0xb8").is_err());
+        assert!(config.set("probe_priority", "8").is_err());
+        assert!(config.set("probe_dscp", "").is_ok());
+        assert_eq!(config.probe_dscp, None);
+        assert_eq!(config.get("probe_dscp").as_deref(), Some(""));
+
+        assert!(config.set("bind_interface", "wan1").is_ok());
+        assert_eq!(config.bind_interface, "wan1");
+        assert!(config.set("bind_interface", "a-very-long-ifname").is_err());
+        assert!(config.set("bind_interface", "wan 1").is_err());
+        assert!(config.set("bind_interface", "").is_ok());
+    }
+
+    #[test]
+    fn test_assume_lan() {
+        let mut config = Config::default();
+        assert!(config.set("assume_lan", "10.0.0.0/24").is_ok());
+        assert_eq!(config.assume_lan, "10.0.0.0/24");
+        assert!(config.set("assume_lan", "10.0.0.0").is_err());
+        assert!(config.set("assume_lan", "10.0.0.0/33").is_err());
+        assert!(config.set("assume_lan", "").is_ok());
+        assert!(config.assume_lan.is_empty());
+    }
+
+    #[test]
+    fn test_heartbeat_interval() {
+        let mut config = Config::default();
+        assert!(config.heartbeat_interval.is_zero());
+        config.apply_args(&args(&["zxic_ping", "--heartbeat-interval-secs", "3600"]));
+        assert_eq!(config.heartbeat_interval, Duration::from_secs(3600));
+        assert_eq!(
+            config.get("heartbeat_interval_secs").as_deref(),
+            Some("3600")
+        );
+        assert!(config.set("heartbeat_interval_secs", "-1").is_err());
+    }
+
+    #[test]
+    fn test_fallback_target() {
+        let mut config = Config::default();
+        assert!(config.set("fallback_target", "1.1.1.1:53").is_ok());
+        assert!(config.set("fallback_target", "1.1.1.1").is_err());
+        assert!(config.set("fallback_target", "").is_ok());
+        assert!(config.set("target_suspect_reboots", "0").is_ok());
+        assert_eq!(config.get("target_suspect_reboots").as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn test_services() {
+        let mut config = Config::default();
+        config.apply_file(
+            "service = dropbear:/usr/sbin/dropbear:TERM\nservice = agent:/etc_rw/agent\n",
+            "test.conf",
+        );
+        assert_eq!(config.services.len(), 2);
+        assert!(config
+            .set("service", "dropbear:/usr/sbin/dropbear:HUP:500")
+            .is_ok());
+        assert_eq!(
+            config.get("service").as_deref(),
+            Some("agent:/etc_rw/agent:KILL:3000,dropbear:/usr/sbin/dropbear:HUP:500")
+        );
+        assert!(config.set("service", "bad name:/bin/x").is_err());
+        assert!(config.set("service", "").is_ok());
+        assert!(config.services.is_empty());
+    }
+
+    #[test]
+    fn test_http_server() {
+        let mut config = Config::default();
+        assert_eq!(config.http_port, 0);
+        assert!(config.set("http_port", "8080").is_ok());
+        assert_eq!(config.get("http_port").as_deref(), Some("8080"));
+        assert!(config.set("http_port", "70000").is_err());
+        assert_eq!(config.get("http_address").as_deref(), Some("127.0.0.1"));
+        assert!(config.set("http_address", "192.168.0.1").is_ok());
+        assert!(config.set("http_address", "::").is_ok());
+        assert!(config.set("http_address", "lan").is_err());
+        assert!(config.set("http_token_file", "/etc/zxping.token").is_ok());
+        assert!(config.set("http_token_file", "zxping.token").is_err());
+    }
+
+    #[test]
+    fn test_control_bind_conflict() {
+        let mut config = Config::default();
+        assert_eq!(config.control_bind_conflict, BindConflict::Disable);
+        let warnings = config.apply_args(&args(&["zxic_ping", "--control-bind-conflict", "retry"]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.control_bind_conflict, BindConflict::Retry);
+        assert_eq!(
+            config.get("control_bind_conflict").as_deref(),
+            Some("retry")
+        );
+        assert!(config.set("control_bind_conflict", "crash").is_err());
+    }
+
+    #[test]
+    fn test_control_socket() {
+        let mut config = Config::default();
+        assert!(config.control_socket.is_empty());
+        let warnings = config.apply_args(&args(&[
+            "zxic_ping",
+            "--control-socket",
+            "/var/run/zxping.sock",
+        ]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.control_socket, "/var/run/zxping.sock");
+        assert!(config.set("control_socket", "zxping.sock").is_err());
+        assert!(config.set("control_socket", "").is_ok());
+    }
+
+    #[test]
+    fn test_positional_args() {
+        let argv = args(&[
+            "zxic_ping",
+            "--led",
+            "red",
+            "--log-color",
+            "192.168.0.2:80",
+            "--background",
+        ]);
+        assert_eq!(positional_args(&argv), vec!["192.168.0.2:80"]);
+
+        let mut config = Config::default();
+        assert!(config.apply_args(&argv).is_empty());
+        assert!(config.log_color);
+    }
+
+    #[test]
+    fn test_enable_flags() {
+        let argv = args(&[
+            "zxic_ping",
+            "--enable-cpu-monitor",
+            "false",
+            "--enable-adbd-control",
+            "no",
+            "--adbd-restrict-lan",
+            "192.168.0.2:80",
+        ]);
+        assert_eq!(positional_args(&argv), vec!["192.168.0.2:80"]);
+        let mut config = Config::default();
+        assert!(config.apply_args(&argv).is_empty());
+        assert!(!config.enable_cpu_monitor);
+        assert!(!config.enable_adbd_control);
+        assert!(config.adbd_restrict_lan);
+        assert!(config.enable_network_monitor && config.enable_control_channel);
+    }
+
+    #[test]
+    fn test_maintenance_options() {
+        let mut config = Config::default();
+        assert!(!config.maintenance);
+        config.apply_args(&args(&["zxic_ping", "--maintenance"]));
+        config.apply_file("maintenance_timeout_secs = 900\n", "test.conf");
+        assert!(config.maintenance);
+        assert_eq!(config.maintenance_timeout, Duration::from_secs(900));
+        assert!(config.set("maintenance_timeout_secs", "0").is_err());
+    }
+
+    #[test]
+    fn test_tune_after_first_success() {
+        let mut config = Config::default();
+        assert!(config.set("tune_deadline_secs", "0").is_err());
+        config.apply_args(&args(&[
+            "zxic_ping",
+            "--tune-after-first-success",
+            "--tune-deadline-secs",
+            "120",
+        ]));
+        assert!(config.tune_after_first_success);
+        assert_eq!(config.tune_deadline, Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_fd_percent() {
+        let mut config = Config::default();
+        assert!(config.set("fd_warn_percent", "101").is_err());
+        assert!(config.set("fd_critical_percent", "-1").is_err());
+        config.set("fd_warn_percent", "0").unwrap();
+        assert_eq!(config.fd_warn_percent, 0);
+        assert!(config.validate().is_empty());
+        config.set("fd_warn_percent", "90").unwrap();
+        config.set("fd_critical_percent", "85").unwrap();
+        assert_eq!(config.validate().len(), 1);
+    }
+
+    #[test]
+    fn test_dhcp_leases_file() {
+        let mut config = Config::default();
+        assert!(config.set("dhcp_leases_file", "dnsmasq.leases").is_err());
+        config.apply_args(&args(&[
+            "zxic_ping",
+            "--dhcp-leases-file",
+            "/tmp/dnsmasq.leases",
+            "--lan-empty-notify",
+        ]));
+        assert_eq!(config.dhcp_leases_file, "/tmp/dnsmasq.leases");
+        assert!(config.lan_empty_notify);
+    }
+
+    #[test]
+    fn test_log_to_override() {
+        let mut config = Config::default();
+        config.apply_file("log_to = /etc_rw/other.log\n", "test.conf");
+        config.apply_args(&args(&["zxic_ping", "--log-to", "/tmp/debug.log"]));
+        assert_eq!(config.log_to, "/tmp/debug.log");
+        assert_eq!(config.sources.get("log_to"), Some(&ConfigSource::Cli));
+    }
+
+    #[test]
+    fn test_latency_buckets() {
+        let mut config = Config::default();
+        let warnings = config.apply_file("latency_buckets_ms = 10,30,90\n", "test.conf");
+        assert!(warnings.is_empty());
+        assert_eq!(config.latency_buckets, vec![10, 30, 90]);
+        // 非法的值被拒绝，保留原值
+        let warnings = config.apply_file("latency_buckets_ms = 30,10\n", "test.conf");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(config.latency_buckets, vec![10, 30, 90]);
+    }
+
+    #[test]
+    fn test_latency_escalate_after() {
+        let mut config = Config::default();
+        // 默认关闭，需要时再开启
+        assert_eq!(config.latency_escalate_after, 0);
+        assert!(config.set("latency_escalate_after", "30").is_ok());
+        assert_eq!(config.latency_escalate_after, 30);
+        assert!(config.set("latency_escalate_after", "-1").is_err());
+        assert!(config.set("latency_escalate_after", "4294967296").is_err());
+    }
+
+    #[test]
+    fn test_sample_log() {
+        let mut config = Config::default();
+        assert!(!config.sample_log);
+        let warnings = config.apply_args(&args(&[
+            "zxic_ping",
+            "--sample-log",
+            "--sample-log-max-bytes",
+            "65536",
+        ]));
+        assert!(warnings.is_empty());
+        assert!(config.sample_log);
+        assert_eq!(config.sample_log_max_bytes, 65536);
+        assert!(config.set("sample_log_max_bytes", "0").is_err());
+    }
+
+    #[test]
+    fn test_fail_pattern_share() {
+        let mut config = Config::default();
+        assert_eq!(config.fail_pattern_share_percent, 40);
+        let warnings =
+            config.apply_args(&args(&["zxic_ping", "--fail-pattern-share-percent", "60"]));
+        assert!(warnings.is_empty());
+        assert_eq!(config.fail_pattern_share_percent, 60);
+        assert!(config.set("fail_pattern_share_percent", "120").is_err());
+    }
+
+    #[test]
+    fn test_dump_lines() {
+        let mut config = Config::default();
+        config.apply_file("profile = performance\nconntrack_max = 9000\n", "test.conf");
+        config.apply_args(&args(&["zxic_ping", "--auto-reboot"]));
+        let lines = config.dump_lines();
+        assert_eq!(lines.len(), KEYS.len());
+        for expected in [
+            "profile=performance (file)",
+            "conntrack_max=9000 (file)",
+            "conntrack_hashsize=4096 (profile)",
+            "auto_reboot=true (cli)",
+            "max_failures=15 (default)",
+            "udp_timeout_ms=2000 (default)",
+        ] {
+            assert!(lines.iter().any(|l| l == expected), "missing {}", expected);
+        }
+        // 每个 key 都能读出值，且读出的值能重新设置
+        for key in KEYS {
+            let value = config.get(key).unwrap();
+            assert!(
+                Config::default().set(key, &value).is_ok(),
+                "{}={}",
+                key,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_notify_addrs() {
+        let argv = args(&[
+            "zxic_ping",
+            "--notify-addr",
+            "192.168.0.2:9000, 10.0.0.1:9000",
+            "--notify-addr",
+            "192.168.0.2:9000",
+            "--notify-addr",
+            "10.0.0.3",
+        ]);
+        let mut config = Config::default();
+        assert_eq!(config.apply_args(&argv).len(), 1);
+        assert_eq!(
+            config.notify_addrs,
+            vec!["192.168.0.2:9000", "10.0.0.1:9000"]
+        );
+    }
+
+    #[test]
+    fn test_notify_event_lists() {
+        let mut config = Config::default();
+        config
+            .set("notify_disable", "HIGH_LOAD, HIGH_LOAD_ENTER,")
+            .unwrap();
+        assert_eq!(config.notify_disable, vec!["HIGH_LOAD", "HIGH_LOAD_ENTER"]);
+        assert!(config.set("notify_only", "high_load").is_err());
+        config.set("notify_only", "").unwrap();
+        assert!(config.notify_only.is_empty());
+    }
+
+    #[test]
+    fn test_notify_ack_options() {
+        let mut config = Config::default();
+        assert_eq!(config.notify_ack_timeout, Duration::ZERO);
+        config.apply_file(
+            "notify_ack_timeout_ms = 300\nnotify_ack_retries = 0\n",
+            "test.conf",
+        );
+        assert_eq!(config.notify_ack_timeout, Duration::from_millis(300));
+        assert_eq!(config.notify_ack_retries, 0);
+        assert!(config.set("notify_ack_retries", "11").is_err());
+    }
+
+    #[test]
+    fn test_validate_conntrack() {
+        let mut config = Config::default();
+        assert!(config.validate().is_empty());
+
+        config.conntrack_max = 100_000;
+        assert_eq!(config.validate().len(), 1);
+
+        config.conntrack_max = 1024;
+        // 小于 hashsize，且比限流值还小
+        assert_eq!(config.validate().len(), 2);
+    }
+
+    #[test]
+    fn test_profile_sets_conntrack() {
+        let mut config = Config::default();
+        let warnings = config.apply_file(
+            "profile = conservative\nconntrack_max = 3000\n",
+            "test.conf",
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(config.profile, Profile::Conservative);
+        assert_eq!(config.conntrack_max, 3000);
+        assert_eq!(config.conntrack_hashsize, 1024);
+        assert!(config.set("profile", "turbo").is_err());
+
+        // 之后切换档位（如启动时沿用 PROFILE 设置）不覆盖明确配置的值
+        let mut config = Config::default();
+        config.apply_file("conntrack_max = 3000\n", "test.conf");
+        config.apply_profile(Profile::Performance);
+        assert_eq!(config.conntrack_max, 3000);
+        assert_eq!(
+            config.sources.get("conntrack_max"),
+            Some(&ConfigSource::File)
+        );
+        assert_eq!(
+            config.conntrack_hashsize,
+            Profile::Performance.conntrack().2
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// 两次重新启动连接管理进程之间的最短间隔（启动后拨号需要时间）
+const RESTART_COOLDOWN: Duration = Duration::from_secs(300);
+
+/// 厂商连接管理进程（如 zte_cm，名字随固件不同）的看护
+///
+/// 这个进程退出后接口仍然是 up 的，但流量已经中断；连接开始失败时先检查它，
+/// 不在则重新启动并执行配置的后续命令（如重新拨号），比重启 adbd 或整机重启代价小。
+/// 未配置 conn_manager_process 时不做任何事
+pub struct ConnManagerWatch {
+    last_restart: Option<Instant>,
+}
+
+impl ConnManagerWatch {
+    pub fn new() -> Self {
+        ConnManagerWatch { last_restart: None }
+    }
+
+    /// 根据进程是否仍在运行，返回需要依次执行的命令（启动命令 + 后续命令）；
+    /// 未配置、进程仍在、没有启动命令或处于冷却期时返回空
+    pub fn plan(&mut self, config: &Config, running: bool, now: Instant) -> Vec<String> {
+        if config.conn_manager_process.is_empty()
+            || config.conn_manager_start_cmd.is_empty()
+            || running
+        {
+            return Vec::new();
+        }
+        if self
+            .last_restart
+            .is_some_and(|last| now.duration_since(last) < RESTART_COOLDOWN)
+        {
+            return Vec::new();
+        }
+        self.last_restart = Some(now);
+        let mut commands = vec![config.conn_manager_start_cmd.clone()];
+        commands.extend(config.conn_manager_post_start.iter().cloned());
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let now = Instant::now();
+        let mut watch = ConnManagerWatch::new();
+        // 没有配置时不做任何事
+        assert!(watch.plan(&Config::default(), false, now).is_empty());
+
+        let config = Config {
+            conn_manager_process: "zte_cm".to_string(),
+            conn_manager_start_cmd: "/bin/zte_cm &".to_string(),
+            conn_manager_post_start: vec!["/sbin/dial.sh".to_string()],
+            ..Config::default()
+        };
+        assert!(watch.plan(&config, true, now).is_empty());
+        assert_eq!(
+            watch.plan(&config, false, now),
+            ["/bin/zte_cm &", "/sbin/dial.sh"]
+        );
+        // 冷却期内不重复启动
+        assert!(watch
+            .plan(&config, false, now + Duration::from_secs(60))
+            .is_empty());
+        assert_eq!(watch.plan(&config, false, now + RESTART_COOLDOWN).len(), 2);
+    }
+}
@@ -0,0 +1,541 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::notify::Notifier;
+use crate::{log_debug, log_error, log_message, log_warn};
+
+/// 连续多少次非 WouldBlock 错误后重建监听 socket
+const CONTROL_RESET_THRESHOLD: u32 = 10;
+/// 同一种错误的日志最小间隔
+const CONTROL_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(600);
+/// 未知命令日志每小时最多记录几条
+const UNKNOWN_LOG_PER_HOUR: usize = 5;
+/// 未知命令日志中最多显示的字节数
+const UNKNOWN_PREVIEW_BYTES: usize = 32;
+/// 监听队列长度
+const LISTEN_BACKLOG: i32 = 16;
+/// control_bind_conflict=retry 时重试绑定的首次间隔和最大间隔（每次失败翻倍）
+const BIND_RETRY_INITIAL: Duration = Duration::from_secs(10);
+const BIND_RETRY_MAX: Duration = Duration::from_secs(600);
+/// 本机 socket 连接等待命令的最长时间
+const LOCAL_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 监听 socket 的缓冲区大小（字节），0 为内核默认值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferSizes {
+    pub recv: usize,
+    pub send: usize,
+}
+
+/// 控制命令的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    /// 控制端口或 HTTP 接口
+    Net(SocketAddr),
+    /// 本机 Unix socket（control_socket）
+    Local,
+}
+
+impl Peer {
+    /// 按来源统计未知命令时的键，本机 socket 为 None
+    fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Peer::Net(addr) => Some(addr.ip()),
+            Peer::Local => None,
+        }
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Peer::Net(addr) => write!(f, "{}", addr),
+            Peer::Local => f.write_str("local socket"),
+        }
+    }
+}
+
+/// 控制端口监听器（TCP，同时接受 IPv4 和 IPv6），持续出错时自动重建
+pub struct ControlListener {
+    port: u16,
+    buffers: BufferSizes,
+    /// enable_control_channel 关闭时不监听，accept 总是返回 WouldBlock
+    enabled: bool,
+    listener: Option<TcpListener>,
+    /// 启动时绑定失败、等待重试：(下次重试的时间, 当前间隔)
+    bind_retry: Option<(Instant, Duration)>,
+    /// 累计错误次数（不含 WouldBlock）
+    total_errors: u64,
+    /// 连续错误次数，成功 accept 或 WouldBlock 时清零
+    consecutive_errors: u32,
+    resets: u32,
+    last_error: Option<ErrorKind>,
+    /// 每种错误最近一次写日志的时间
+    last_logged: HashMap<ErrorKind, Instant>,
+    /// 收到的未知命令总数和按来源的计数
+    unknown_commands: u64,
+    unknown_by_source: HashMap<Option<IpAddr>, u64>,
+    /// 最近一小时内记录未知命令日志的时间
+    unknown_logged: VecDeque<Instant>,
+}
+
+impl ControlListener {
+    pub fn bind(port: u16, buffers: BufferSizes) -> io::Result<ControlListener> {
+        Ok(ControlListener {
+            listener: Some(bind_listener(port, buffers)?),
+            ..ControlListener::unbound(port, buffers)
+        })
+    }
+
+    /// 启动时绑定失败，稍后按退避间隔重试（见 retry_bind）
+    pub fn retrying(port: u16, buffers: BufferSizes, now: Instant) -> ControlListener {
+        ControlListener {
+            bind_retry: Some((now + BIND_RETRY_INITIAL, BIND_RETRY_INITIAL)),
+            ..ControlListener::unbound(port, buffers)
+        }
+    }
+
+    fn unbound(port: u16, buffers: BufferSizes) -> ControlListener {
+        ControlListener {
+            buffers,
+            enabled: true,
+            ..ControlListener::disabled(port)
+        }
+    }
+
+    /// 不监听任何端口的控制通道
+    pub fn disabled(port: u16) -> ControlListener {
+        ControlListener {
+            port,
+            buffers: BufferSizes::default(),
+            enabled: false,
+            listener: None,
+            bind_retry: None,
+            total_errors: 0,
+            consecutive_errors: 0,
+            resets: 0,
+            last_error: None,
+            last_logged: HashMap::new(),
+            unknown_commands: 0,
+            unknown_by_source: HashMap::new(),
+            unknown_logged: VecDeque::new(),
+        }
+    }
+
+    /// 非阻塞 accept；监听 socket 已关闭（重建失败）时返回 NotConnected
+    pub fn accept(&mut self) -> io::Result<(TcpStream, SocketAddr)> {
+        let result = match &self.listener {
+            _ if !self.enabled || self.bind_retry.is_some() => {
+                Err(io::Error::from(ErrorKind::WouldBlock))
+            }
+            Some(listener) => listener.accept(),
+            None => Err(io::Error::new(
+                ErrorKind::NotConnected,
+                "control socket closed",
+            )),
+        };
+        match &result {
+            Ok(_) => self.consecutive_errors = 0,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => self.consecutive_errors = 0,
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// 等待重试绑定时，到时间后再试一次；失败时间隔翻倍，最长 BIND_RETRY_MAX
+    pub fn retry_bind(&mut self, now: Instant, notifier: &Notifier, is_prod: bool) {
+        let Some((at, delay)) = self.bind_retry else {
+            return;
+        };
+        if now < at {
+            return;
+        }
+        match bind_listener(self.port, self.buffers) {
+            Ok(listener) => {
+                self.listener = Some(listener);
+                self.bind_retry = None;
+                log_message(
+                    &format!("Control port {} bound after retrying", self.port),
+                    is_prod,
+                );
+                notifier.send(&format!("CONTROL_BOUND: PORT={}", self.port), is_prod);
+            }
+            Err(e) => {
+                let delay = (delay * 2).min(BIND_RETRY_MAX);
+                self.bind_retry = Some((now + delay, delay));
+                log_debug(
+                    &format!(
+                        "Control port {} still unavailable ({}), retrying in {}s",
+                        self.port,
+                        e,
+                        delay.as_secs()
+                    ),
+                    is_prod,
+                );
+            }
+        }
+    }
+
+    /// 处理 accept 错误：按错误种类限频记录日志，连续出错过多时重建 socket
+    pub fn handle_error(&mut self, e: &io::Error, notifier: &Notifier, is_prod: bool) {
+        let (should_log, should_reset) = self.record_error(e.kind(), Instant::now());
+        if should_log {
+            log_warn(
+                &format!(
+                    "Control socket error: {} (consecutive: {}, total: {})",
+                    e, self.consecutive_errors, self.total_errors
+                ),
+                is_prod,
+            );
+        }
+        if should_reset {
+            self.rebuild(notifier, is_prod);
+        }
+    }
+
+    /// 记录一次错误，返回 (是否写日志, 是否重建)
+    fn record_error(&mut self, kind: ErrorKind, now: Instant) -> (bool, bool) {
+        if kind == ErrorKind::WouldBlock {
+            return (false, false);
+        }
+        self.total_errors += 1;
+        self.consecutive_errors += 1;
+        self.last_error = Some(kind);
+
+        let should_log = match self.last_logged.get(&kind) {
+            Some(last) => now.duration_since(*last) >= CONTROL_ERROR_LOG_INTERVAL,
+            None => true,
+        };
+        if should_log {
+            self.last_logged.insert(kind, now);
+        }
+        (
+            should_log,
+            self.consecutive_errors >= CONTROL_RESET_THRESHOLD,
+        )
+    }
+
+    /// 记录一条无法识别的命令，按来源计数并限频记录内容预览
+    pub fn record_unknown(&mut self, peer: &Peer, payload: &[u8], is_prod: bool) {
+        let from_source = self.count_unknown(peer.ip());
+        if self.should_log_unknown(Instant::now()) {
+            log_warn(
+                &format!(
+                    "Unknown control command from {} ({} from this source): {}",
+                    peer,
+                    from_source,
+                    preview(payload)
+                ),
+                is_prod,
+            );
+        }
+    }
+
+    /// 返回该来源累计的未知命令数
+    fn count_unknown(&mut self, ip: Option<IpAddr>) -> u64 {
+        self.unknown_commands += 1;
+        let count = self.unknown_by_source.entry(ip).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn should_log_unknown(&mut self, now: Instant) -> bool {
+        while self
+            .unknown_logged
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= Duration::from_secs(3600))
+        {
+            self.unknown_logged.pop_front();
+        }
+        if self.unknown_logged.len() >= UNKNOWN_LOG_PER_HOUR {
+            return false;
+        }
+        self.unknown_logged.push_back(now);
+        true
+    }
+
+    /// 关闭并重新绑定监听端口，失败时保持关闭状态，下次达到阈值再试
+    fn rebuild(&mut self, notifier: &Notifier, is_prod: bool) {
+        log_warn(
+            &format!(
+                "Control socket failed {} times in a row, rebinding port {}",
+                self.consecutive_errors, self.port
+            ),
+            is_prod,
+        );
+        self.consecutive_errors = 0;
+        // 先关闭旧 socket 才能重新绑定同一端口
+        self.listener = None;
+        match bind_listener(self.port, self.buffers) {
+            Ok(listener) => {
+                self.listener = Some(listener);
+                self.resets += 1;
+                log_message("Control socket rebound", is_prod);
+                notifier.send(
+                    &format!("CONTROL_SOCKET_RESET: RESETS={}", self.resets),
+                    is_prod,
+                );
+            }
+            Err(e) => log_error(
+                &format!("Failed to rebind control port {}: {}", self.port, e),
+                is_prod,
+            ),
+        }
+    }
+
+    /// STATUS 中的控制通道错误计数
+    pub fn status_line(&self) -> String {
+        if !self.enabled {
+            return "control=disabled".to_string();
+        }
+        if let Some((at, _)) = self.bind_retry {
+            return format!(
+                "control=retrying port={} next_in={}s",
+                self.port,
+                at.saturating_duration_since(Instant::now()).as_secs()
+            );
+        }
+        format!(
+            "control_errors={} consecutive={} resets={} last_error={} unknown_commands={}",
+            self.total_errors,
+            self.consecutive_errors,
+            self.resets,
+            self.last_error
+                .map(|kind| format!("{:?}", kind))
+                .unwrap_or_else(|| "-".to_string()),
+            self.unknown_commands
+        )
+    }
+}
+
+/// 本机控制 Unix socket：文件权限限制为 root，只接受本机进程的命令。
+/// 退出时删除 socket 文件
+pub struct LocalControl {
+    path: PathBuf,
+    listener: UnixListener,
+}
+
+impl LocalControl {
+    /// 绑定 path。上次异常退出留下的 socket 文件先删除，其他类型的文件不动
+    pub fn bind(path: &str) -> io::Result<LocalControl> {
+        let path = PathBuf::from(path);
+        if fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        let control = LocalControl { path, listener };
+        fs::set_permissions(&control.path, fs::Permissions::from_mode(0o600))?;
+        control.listener.set_nonblocking(true)?;
+        Ok(control)
+    }
+
+    /// 非阻塞 accept，没有新连接时返回 WouldBlock。连接本身是阻塞的，
+    /// 设置读超时，连上不发命令的客户端不会卡住主循环
+    pub fn accept(&self) -> io::Result<UnixStream> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_read_timeout(Some(LOCAL_READ_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    pub fn status_line(&self) -> String {
+        format!("control_socket={}", self.path.display())
+    }
+}
+
+impl Drop for LocalControl {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// 日志中安全显示的负载预览：可打印 ASCII 原样显示，其他字节转义为 \\xNN，
+/// 超过 UNKNOWN_PREVIEW_BYTES 截断，后面附上长度
+fn preview(payload: &[u8]) -> String {
+    let mut text = String::new();
+    for &byte in payload.iter().take(UNKNOWN_PREVIEW_BYTES) {
+        match byte {
+            b'\n' => text.push_str("\\n"),
+            b'\r' => text.push_str("\\r"),
+            b'\\' => text.push_str("\\\\"),
+            b'"' => text.push_str("\\\""),
+            0x20..=0x7e => text.push(byte as char),
+            _ => text.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    if payload.len() > UNKNOWN_PREVIEW_BYTES {
+        format!("\"{}\"... ({} bytes)", text, payload.len())
+    } else {
+        format!("\"{}\" ({} bytes)", text, payload.len())
+    }
+}
+
+/// 绑定 [::]:port。设置 SO_REUSEADDR，进程崩溃后立即重启时不会因旧连接处于 TIME_WAIT 而绑定失败
+fn bind_listener(port: u16, buffers: BufferSizes) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    // 设置 IPV6_V6ONLY 为 false，允许 IPv4 映射到 IPv6
+    let _ = socket.set_only_v6(false);
+    socket.set_reuse_address(true)?;
+    if buffers.recv > 0 {
+        socket.set_recv_buffer_size(buffers.recv)?;
+    }
+    if buffers.send > 0 {
+        socket.set_send_buffer_size(buffers.send)?;
+    }
+    socket.bind(&SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview(b"STATUS\n"), r#""STATUS\n" (7 bytes)"#);
+        assert_eq!(
+            preview(b"\x1b[2J\x00\"\\"),
+            r#""\x1b[2J\x00\"\\" (7 bytes)"#
+        );
+        let long = [b'A'; 40];
+        assert_eq!(
+            preview(&long),
+            format!("\"{}\"... (40 bytes)", "A".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_unknown_commands() {
+        let mut control = ControlListener::disabled(0);
+        let a: IpAddr = "192.168.0.2".parse().unwrap();
+        let b: IpAddr = "192.168.0.3".parse().unwrap();
+        assert_eq!(control.count_unknown(Some(a)), 1);
+        assert_eq!(control.count_unknown(Some(b)), 1);
+        assert_eq!(control.count_unknown(Some(a)), 2);
+        assert_eq!(control.count_unknown(Peer::Local.ip()), 1);
+        assert_eq!(control.unknown_commands, 4);
+
+        let now = Instant::now();
+        for _ in 0..UNKNOWN_LOG_PER_HOUR {
+            assert!(control.should_log_unknown(now));
+        }
+        assert!(!control.should_log_unknown(now + Duration::from_secs(60)));
+        assert!(control.should_log_unknown(now + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_retry_bind() {
+        let notifier = Notifier::new("127.0.0.1:80", &crate::config::Config::default());
+        let taken = bind_listener(0, BufferSizes::default()).unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let start = Instant::now();
+        let mut control = ControlListener::retrying(port, BufferSizes::default(), start);
+        assert_eq!(control.accept().unwrap_err().kind(), ErrorKind::WouldBlock);
+        assert!(control.status_line().starts_with("control=retrying"));
+
+        // 端口仍被占用：间隔翻倍
+        control.retry_bind(start + BIND_RETRY_INITIAL, &notifier, false);
+        assert_eq!(
+            control.bind_retry.map(|(_, delay)| delay),
+            Some(BIND_RETRY_INITIAL * 2)
+        );
+        drop(taken);
+        control.retry_bind(start + BIND_RETRY_INITIAL * 4, &notifier, false);
+        assert!(control.bind_retry.is_none());
+        assert!(control.status_line().starts_with("control_errors=0"));
+    }
+
+    #[test]
+    fn test_record_error() {
+        let mut control = ControlListener::disabled(0);
+        let now = Instant::now();
+        assert_eq!(
+            control.record_error(ErrorKind::WouldBlock, now),
+            (false, false)
+        );
+        assert_eq!(control.total_errors, 0);
+
+        // 同种错误只记一次日志，不同种错误各记一次
+        assert_eq!(control.record_error(ErrorKind::Other, now), (true, false));
+        assert_eq!(control.record_error(ErrorKind::Other, now), (false, false));
+        assert_eq!(
+            control.record_error(ErrorKind::ConnectionAborted, now),
+            (true, false)
+        );
+
+        for _ in 3..CONTROL_RESET_THRESHOLD - 1 {
+            control.record_error(ErrorKind::Other, now);
+        }
+        assert_eq!(
+            control.record_error(ErrorKind::Other, now + CONTROL_ERROR_LOG_INTERVAL),
+            (true, true)
+        );
+        assert_eq!(control.total_errors, CONTROL_RESET_THRESHOLD as u64);
+    }
+
+    #[test]
+    fn test_bind_listener_options() {
+        let buffers = BufferSizes {
+            recv: 32 * 1024,
+            send: 16 * 1024,
+        };
+        let listener = bind_listener(0, buffers).unwrap();
+        let socket = socket2::SockRef::from(&listener);
+        assert!(socket.reuse_address().unwrap());
+        // 内核会把设置值翻倍（并受 rmem_max 限制），只检查确实生效
+        assert!(socket.recv_buffer_size().unwrap() >= buffers.recv.min(4096));
+        let port = listener.local_addr().unwrap().port();
+
+        // 有连接处于 TIME_WAIT 时（服务端先关闭）仍可重新绑定同一端口
+        let client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut accepted = None;
+        for _ in 0..100 {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    accepted = Some(stream);
+                    break;
+                }
+                Err(_) => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        drop(accepted.expect("accept"));
+        drop(listener);
+        drop(client);
+        assert!(bind_listener(port, BufferSizes::default()).is_ok());
+    }
+
+    #[test]
+    fn test_local_control() {
+        use std::io::{Read, Write};
+
+        let path = std::env::temp_dir().join(format!("zxping-ctl-{}.sock", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let control = LocalControl::bind(path_str).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(control.accept().unwrap_err().kind(), ErrorKind::WouldBlock);
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"PING").unwrap();
+        let mut server = control.accept().unwrap();
+        let mut buf = [0u8; 16];
+        let size = server.read(&mut buf).unwrap();
+        assert_eq!(&buf[..size], b"PING");
+
+        // 残留的 socket 文件不影响重新绑定，退出时删除
+        std::mem::forget(control);
+        let control = LocalControl::bind(path_str).unwrap();
+        drop(control);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::system::SystemOps;
+
+/// /proc/stat 中 "cpu" 行的累计 jiffies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CpuTimes {
+    /// idle + iowait
+    idle: u64,
+    total: u64,
+}
+
+/// CPU 占用率采样器：两次采样之间的非空闲时间占比
+pub struct CpuMonitor {
+    prev: Option<CpuTimes>,
+    /// 最近一次计算出的占用率（0-100）
+    pub usage: Option<f32>,
+}
+
+impl CpuMonitor {
+    pub fn new() -> Self {
+        CpuMonitor {
+            prev: None,
+            usage: None,
+        }
+    }
+
+    /// 读取 /proc/stat 并更新占用率，第一次采样或读取失败时返回 None
+    pub fn sample(&mut self, sys: &mut impl SystemOps) -> Option<f32> {
+        let current = sys
+            .cpu_stat()
+            .and_then(|content| parse_cpu_times(&content))?;
+        if let Some(prev) = self.prev {
+            if let Some(usage) = usage_between(prev, current) {
+                self.usage = Some(usage);
+            }
+        }
+        self.prev = Some(current);
+        self.usage
+    }
+}
+
+/// 解析 "cpu  user nice system idle iowait irq softirq steal ..." 行
+fn parse_cpu_times(content: &str) -> Option<CpuTimes> {
+    let line = content.lines().find(|l| l.starts_with("cpu "))?;
+    let values: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    if values.len() < 4 {
+        return None;
+    }
+    // guest/guest_nice 已包含在 user/nice 中，不重复计算
+    let total = values.iter().take(8).sum();
+    let idle = values[3] + values.get(4).copied().unwrap_or(0);
+    Some(CpuTimes { idle, total })
+}
+
+fn usage_between(prev: CpuTimes, current: CpuTimes) -> Option<f32> {
+    let total = current.total.checked_sub(prev.total)?;
+    let idle = current.idle.checked_sub(prev.idle)?;
+    if total == 0 {
+        return None;
+    }
+    Some((total.saturating_sub(idle)) as f32 * 100.0 / total as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_times() {
+        let content = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 100 0 50 800 50 0 0 0 0 0\nintr 1\n";
+        assert_eq!(
+            parse_cpu_times(content),
+            Some(CpuTimes {
+                idle: 850,
+                total: 1000
+            })
+        );
+        assert_eq!(parse_cpu_times("intr 1\n"), None);
+    }
+
+    #[test]
+    fn test_usage_between() {
+        let prev = CpuTimes {
+            idle: 850,
+            total: 1000,
+        };
+        let current = CpuTimes {
+            idle: 900,
+            total: 1200,
+        };
+        assert_eq!(usage_between(prev, current), Some(75.0));
+        assert_eq!(usage_between(prev, prev), None);
+        // 计数器回绕
+        assert_eq!(usage_between(current, prev), None);
+    }
+}
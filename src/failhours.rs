@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::storage;
+
+/// 统计最近这么多天
+const WINDOW_DAYS: i64 = 7;
+/// 报告失败最多的几个小时
+const WORST_HOURS: usize = 3;
+/// 一周内失败少于这个数时不判断是否集中在某个小时
+const PATTERN_MIN_FAILURES: u32 = 10;
+/// 有新记录时最多这么久写一次文件（断网期间每次检查都失败，不能每次都写闪存）
+const SAVE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 最近一周各小时（本地时间）的连通性失败次数，用于发现每天固定时间的故障（如基站维护）。
+/// 系统时钟不可信时不记录
+pub struct FailureHours {
+    path: PathBuf,
+    /// (本地天序号, 各小时的失败次数)，最早的在前
+    days: Vec<(i64, [u32; 24])>,
+    dirty: bool,
+    last_save: Option<Instant>,
+}
+
+impl FailureHours {
+    /// 读取持久化的记录，文件不存在或损坏的行忽略
+    pub fn load(path: PathBuf) -> Self {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let mut days: Vec<(i64, [u32; 24])> = content.lines().filter_map(parse_day).collect();
+        days.sort_by_key(|(day, _)| *day);
+        days.dedup_by_key(|(day, _)| *day);
+        FailureHours {
+            path,
+            days,
+            dirty: false,
+            last_save: None,
+        }
+    }
+
+    /// 记录一次失败；clock 为 logprune::local_wall_clock() 的（天序号, 当天秒数），
+    /// 时钟不可信（None）时不记录并返回 false
+    pub fn record(&mut self, clock: Option<(i64, u32)>) -> bool {
+        let Some((day, secs)) = clock else {
+            return false;
+        };
+        self.prune(day);
+        let hour = (secs / 3600).min(23) as usize;
+        match self.days.iter_mut().find(|(d, _)| *d == day) {
+            Some((_, hours)) => hours[hour] += 1,
+            None => {
+                let mut hours = [0; 24];
+                hours[hour] = 1;
+                self.days.push((day, hours));
+                self.days.sort_by_key(|(day, _)| *day);
+            }
+        }
+        self.dirty = true;
+        true
+    }
+
+    /// 丢掉 today 往前一周以外的记录（时钟回拨后“未来”的记录也丢掉）
+    pub fn prune(&mut self, today: i64) {
+        let before = self.days.len();
+        self.days
+            .retain(|(day, _)| *day > today - WINDOW_DAYS && *day <= today);
+        self.dirty |= self.days.len() != before;
+    }
+
+    /// 一周内各小时的失败总数
+    fn totals(&self) -> [u32; 24] {
+        let mut totals = [0; 24];
+        for (_, hours) in &self.days {
+            for (total, count) in totals.iter_mut().zip(hours) {
+                *total += count;
+            }
+        }
+        totals
+    }
+
+    /// 失败最多的几个小时（次数相同时早的在前，没有失败的不列出）
+    fn worst(&self) -> Vec<(usize, u32)> {
+        let mut hours: Vec<(usize, u32)> = self
+            .totals()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        hours.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hours.truncate(WORST_HOURS);
+        hours
+    }
+
+    /// 某个小时的失败超过一周总数的 share_percent 时返回该小时；0 为不判断
+    fn pattern(&self, share_percent: u32) -> Option<usize> {
+        let totals = self.totals();
+        let sum: u32 = totals.iter().sum();
+        if share_percent == 0 || sum < PATTERN_MIN_FAILURES {
+            return None;
+        }
+        let (hour, count) = totals
+            .into_iter()
+            .enumerate()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))?;
+        (count as u64 * 100 > sum as u64 * share_percent as u64).then_some(hour)
+    }
+
+    /// 每日汇总和 STATS 中的内容：`FAIL_HOURS=03:12,04:5,17:2 PATTERN_SUSPECTED:03`
+    pub fn summary(&self, share_percent: u32) -> String {
+        let worst: Vec<String> = self
+            .worst()
+            .iter()
+            .map(|(hour, count)| format!("{:02}:{}", hour, count))
+            .collect();
+        let mut summary = format!(
+            "FAIL_HOURS={}",
+            if worst.is_empty() {
+                "-".to_string()
+            } else {
+                worst.join(",")
+            }
+        );
+        if let Some(hour) = self.pattern(share_percent) {
+            summary.push_str(&format!(" PATTERN_SUSPECTED:{:02}", hour));
+        }
+        summary
+    }
+
+    /// 有新记录且距上次写入已满 SAVE_INTERVAL 时写入
+    pub fn save_if_due(&mut self, now: Instant) {
+        if self.dirty
+            && self
+                .last_save
+                .is_none_or(|at| now.duration_since(at) >= SAVE_INTERVAL)
+        {
+            self.last_save = Some(now);
+            self.save();
+        }
+    }
+
+    /// 写入统计文件（每行 `<本地天序号>=<24 个小时的次数>`，其他统计的行保留），写失败时下次再试
+    pub fn save(&mut self) {
+        let content: String = self
+            .days
+            .iter()
+            .map(|(day, hours)| {
+                let counts: Vec<String> = hours.iter().map(|c| c.to_string()).collect();
+                format!("{}={}\n", day, counts.join(","))
+            })
+            .collect();
+        if storage::write_shared(&self.path, |line| parse_day(line).is_some(), &content).is_ok() {
+            self.dirty = false;
+        }
+    }
+
+    /// 存储目录切换后改写到新路径
+    pub fn relocate(&mut self, path: PathBuf) {
+        self.path = path;
+        self.save();
+    }
+}
+
+/// `<天序号>=<24 个逗号分隔的次数>`
+fn parse_day(line: &str) -> Option<(i64, [u32; 24])> {
+    let (day, counts) = line.trim().split_once('=')?;
+    let counts: Vec<u32> = counts
+        .split(',')
+        .map(|count| count.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    Some((day.trim().parse().ok()?, counts.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: i64, hour: u32) -> Option<(i64, u32)> {
+        Some((day, hour * 3600 + 120))
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("zxping-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_worst_hours_and_pattern() {
+        let mut hours = FailureHours::load(temp_path("failhours-missing"));
+        assert_eq!(hours.summary(50), "FAIL_HOURS=-");
+        // 时钟不可信时不记录
+        assert!(!hours.record(None));
+
+        for day in 100..103 {
+            for _ in 0..4 {
+                hours.record(at(day, 3));
+            }
+        }
+        hours.record(at(102, 17));
+        hours.record(at(102, 4));
+        hours.record(at(102, 4));
+        // 03 点 12 次，占 15 次中的 80%
+        assert_eq!(
+            hours.summary(50),
+            "FAIL_HOURS=03:12,04:2,17:1 PATTERN_SUSPECTED:03"
+        );
+        assert_eq!(hours.summary(90), "FAIL_HOURS=03:12,04:2,17:1");
+        assert_eq!(hours.summary(0), "FAIL_HOURS=03:12,04:2,17:1");
+
+        // 一周以前的记录不再计入
+        hours.record(at(108, 4));
+        assert_eq!(hours.summary(50), "FAIL_HOURS=03:4,04:3,17:1");
+    }
+
+    #[test]
+    fn test_persistence() {
+        let path = temp_path("failhours");
+        let mut hours = FailureHours::load(path.clone());
+        hours.record(at(200, 23));
+        hours.record(at(201, 0));
+        hours.save_if_due(Instant::now());
+        // 刚写过，新的记录等下一个间隔
+        hours.record(at(201, 0));
+        hours.save_if_due(Instant::now());
+
+        let loaded = FailureHours::load(path.clone());
+        assert_eq!(loaded.summary(0), "FAIL_HOURS=00:1,23:1");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(parse_day("5=1,2"), None);
+        assert_eq!(
+            parse_day("x=0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0"),
+            None
+        );
+        assert!(parse_day("5=0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,7").is_some());
+    }
+}
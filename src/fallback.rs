@@ -0,0 +1,144 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sockmark;
+
+/// 选择备用检查目标：配置了 fallback_target 时用它，否则用默认网关（端口沿用原目标的）
+pub fn fallback_target(
+    configured: &str,
+    gateway: Option<IpAddr>,
+    original: &str,
+) -> Option<String> {
+    if !configured.is_empty() {
+        return (configured != original).then(|| configured.to_string());
+    }
+    let port = original.parse::<SocketAddr>().ok()?.port();
+    Some(SocketAddr::new(gateway?, port).to_string())
+}
+
+/// 原目标是否可达（后台检查，不记录日志）
+fn probe(target: &str, timeout: Duration) -> bool {
+    target
+        .parse::<SocketAddr>()
+        .is_ok_and(|addr| sockmark::connect_tcp(addr, timeout).is_ok())
+}
+
+/// 原目标被怀疑后改用备用目标检查；每轮后台检查原目标，连续可达满 restore_after 后恢复
+pub struct TargetFallback {
+    pub original: String,
+    restore_after: Duration,
+    reachable_since: Option<Instant>,
+    /// 进行中的后台检查：(是否可达, 完成时间)
+    probe: Option<Receiver<(bool, Instant)>>,
+}
+
+impl TargetFallback {
+    pub fn new(original: String, restore_after: Duration) -> Self {
+        TargetFallback {
+            original,
+            restore_after,
+            reachable_since: None,
+            probe: None,
+        }
+    }
+
+    /// 每轮调用：取回上一次后台检查的结果，没有进行中的检查时启动新的一次
+    /// （原目标不可达时连接要等到超时，不能放在主循环里）。应当恢复原目标时返回 true
+    pub fn poll(&mut self, timeout: Duration) -> bool {
+        if let Some(rx) = &self.probe {
+            match rx.try_recv() {
+                Ok((reachable, at)) => {
+                    self.probe = None;
+                    return self.record(reachable, at);
+                }
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => self.probe = None,
+            }
+        }
+        let (tx, rx) = mpsc::channel();
+        let target = self.original.clone();
+        thread::spawn(move || {
+            let _ = tx.send((probe(&target, timeout), Instant::now()));
+        });
+        self.probe = Some(rx);
+        false
+    }
+
+    /// 记录一次原目标的检查结果；应当恢复原目标时返回 true
+    fn record(&mut self, reachable: bool, now: Instant) -> bool {
+        if !reachable {
+            self.reachable_since = None;
+            return false;
+        }
+        let since = *self.reachable_since.get_or_insert(now);
+        now.duration_since(since) >= self.restore_after
+    }
+
+    /// STATUS 中的一行：`target=192.168.0.1:53 (fallback, original 8.8.8.8:53 reachable 120s/600s)`
+    pub fn status_line(&self, current: &str, now: Instant) -> String {
+        let reachable = self
+            .reachable_since
+            .map(|since| format!("reachable {}s", now.duration_since(since).as_secs()))
+            .unwrap_or_else(|| "unreachable".to_string());
+        format!(
+            "target={} (fallback, original {} {}/{}s)",
+            current,
+            self.original,
+            reachable,
+            self.restore_after.as_secs()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_target() {
+        let gateway = "192.168.0.1".parse().ok();
+        assert_eq!(
+            fallback_target("1.1.1.1:53", gateway, "8.8.8.8:53").as_deref(),
+            Some("1.1.1.1:53")
+        );
+        assert_eq!(fallback_target("8.8.8.8:53", gateway, "8.8.8.8:53"), None);
+        assert_eq!(
+            fallback_target("", gateway, "8.8.8.8:53").as_deref(),
+            Some("192.168.0.1:53")
+        );
+        assert_eq!(fallback_target("", None, "8.8.8.8:53"), None);
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut fallback = TargetFallback::new("8.8.8.8:53".to_string(), Duration::from_secs(600));
+        let now = Instant::now();
+        assert!(!fallback.record(true, now));
+        assert!(!fallback.record(true, now + Duration::from_secs(300)));
+        assert_eq!(
+            fallback.status_line("192.168.0.1:53", now + Duration::from_secs(300)),
+            "target=192.168.0.1:53 (fallback, original 8.8.8.8:53 reachable 300s/600s)"
+        );
+        // 中途不可达时重新计时
+        assert!(!fallback.record(false, now + Duration::from_secs(400)));
+        assert!(!fallback.record(true, now + Duration::from_secs(500)));
+        assert!(!fallback.record(true, now + Duration::from_secs(1000)));
+        assert!(fallback.record(true, now + Duration::from_secs(1100)));
+    }
+
+    #[test]
+    fn test_poll_in_background() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let original = listener.local_addr().unwrap().to_string();
+        let mut fallback = TargetFallback::new(original, Duration::ZERO);
+        // 第一次只启动检查，结果在之后的轮次取回
+        assert!(!fallback.poll(Duration::from_secs(1)));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !fallback.poll(Duration::from_secs(1)) {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
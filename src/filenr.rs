@@ -0,0 +1,152 @@
+use std::fs;
+
+/// /proc/sys/fs/file-nr 的快照
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileNr {
+    /// 已分配的文件句柄数
+    pub allocated: u64,
+    /// 系统上限（fs.file-max）
+    pub max: u64,
+}
+
+impl FileNr {
+    pub fn read() -> Option<FileNr> {
+        parse_file_nr(&fs::read_to_string("/proc/sys/fs/file-nr").ok()?)
+    }
+
+    /// 已分配占上限的百分比
+    pub fn percent(&self) -> u32 {
+        if self.max == 0 {
+            return 0;
+        }
+        (self.allocated * 100 / self.max) as u32
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "allocated={} max={} ({}%)",
+            self.allocated,
+            self.max,
+            self.percent()
+        )
+    }
+}
+
+/// 文件句柄使用程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdLevel {
+    Normal,
+    /// 超过 fd_warn_percent
+    Warn,
+    /// 超过 fd_critical_percent
+    Critical,
+}
+
+/// 跟踪系统文件句柄使用量，级别变化时（边沿触发）返回新级别
+pub struct FdMonitor {
+    pub latest: Option<FileNr>,
+    level: FdLevel,
+}
+
+impl FdMonitor {
+    pub fn new() -> Self {
+        FdMonitor {
+            latest: None,
+            level: FdLevel::Normal,
+        }
+    }
+
+    /// 阈值为 0 表示不检查该级别
+    pub fn update(
+        &mut self,
+        stat: FileNr,
+        warn_percent: u32,
+        critical_percent: u32,
+    ) -> Option<FdLevel> {
+        self.latest = Some(stat);
+        let percent = stat.percent();
+        let level = if critical_percent > 0 && percent >= critical_percent {
+            FdLevel::Critical
+        } else if warn_percent > 0 && percent >= warn_percent {
+            FdLevel::Warn
+        } else {
+            FdLevel::Normal
+        };
+        if level == self.level {
+            return None;
+        }
+        self.level = level;
+        Some(level)
+    }
+
+    /// STATUS 中的最新快照
+    pub fn status_line(&self) -> String {
+        match &self.latest {
+            Some(stat) => format!("file_nr: {}", stat.summary()),
+            None => "file_nr: -".to_string(),
+        }
+    }
+}
+
+/// "<已分配> <已分配但未使用> <上限>"，2.6 以后的内核第二个字段总是 0
+fn parse_file_nr(content: &str) -> Option<FileNr> {
+    let fields: Vec<u64> = content
+        .split_whitespace()
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    match fields[..] {
+        [allocated, _, max] => Some(FileNr { allocated, max }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_nr() {
+        assert_eq!(
+            parse_file_nr("1024\t0\t8192\n"),
+            Some(FileNr {
+                allocated: 1024,
+                max: 8192
+            })
+        );
+        assert_eq!(parse_file_nr("1024\t0\n"), None);
+        assert_eq!(parse_file_nr("a b c\n"), None);
+        assert_eq!(
+            FileNr {
+                allocated: 1,
+                max: 0
+            }
+            .percent(),
+            0
+        );
+        assert_eq!(
+            FileNr {
+                allocated: 7000,
+                max: 8192
+            }
+            .summary(),
+            "allocated=7000 max=8192 (85%)"
+        );
+    }
+
+    #[test]
+    fn test_fd_levels() {
+        let mut monitor = FdMonitor::new();
+        let stat = |allocated| FileNr {
+            allocated,
+            max: 1000,
+        };
+        assert_eq!(monitor.update(stat(500), 80, 95), None);
+        assert_eq!(monitor.update(stat(800), 80, 95), Some(FdLevel::Warn));
+        assert_eq!(monitor.update(stat(850), 80, 95), None);
+        assert_eq!(monitor.update(stat(990), 80, 95), Some(FdLevel::Critical));
+        assert_eq!(monitor.update(stat(900), 80, 95), Some(FdLevel::Warn));
+        assert_eq!(monitor.update(stat(100), 80, 95), Some(FdLevel::Normal));
+        // 阈值为 0 时不检查
+        assert_eq!(monitor.update(stat(1000), 0, 0), None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::command;
+use crate::system::SystemOps;
+
+/// 缓存的默认网关多久重新读取一次
+const GATEWAY_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+/// 读取默认路由的 ip 命令超时
+const ROUTE_COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// WAN 接口名（与 get_wan_ip_address 一致）
+pub const WAN_INTERFACE: &str = "wan1";
+
+/// 从 `ip route show default` 的输出中取网关地址（`default via 192.168.0.1 dev wan1 ...`）
+pub fn parse_default_route(output: &str) -> Option<IpAddr> {
+    output.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("default") {
+            return None;
+        }
+        words
+            .skip_while(|word| *word != "via")
+            .nth(1)
+            .and_then(|gw| gw.parse().ok())
+    })
+}
+
+/// 远程检查之前的本地网关检查：网关都不通说明是本地问题，可以跳过远程检查
+pub struct GatewayProbe {
+    gateway: Option<IpAddr>,
+    refreshed_at: Option<Instant>,
+}
+
+impl GatewayProbe {
+    pub fn new() -> Self {
+        GatewayProbe {
+            gateway: None,
+            refreshed_at: None,
+        }
+    }
+
+    /// 当前默认网关，缓存超过刷新间隔时重新读取
+    pub fn gateway(&mut self, now: Instant) -> Option<IpAddr> {
+        let stale = self
+            .refreshed_at
+            .is_none_or(|at| now.duration_since(at) >= GATEWAY_REFRESH_INTERVAL);
+        if stale {
+            self.gateway = read_default_gateway();
+            self.refreshed_at = Some(now);
+        }
+        self.gateway
+    }
+
+    /// ping 网关一次；没有默认网关时返回 None（不做判断）
+    pub fn check(&mut self, sys: &mut impl SystemOps, now: Instant) -> Option<(IpAddr, bool)> {
+        let gateway = self.gateway(now)?;
+        let reachable = sys
+            .run_command(&format!("ping -c 1 -W 1 {} >/dev/null 2>&1", gateway))
+            .is_ok_and(|status| status.success());
+        if !reachable {
+            // 网关可能已变化（如重新拨号），下次重新读取
+            self.refreshed_at = None;
+        }
+        Some((gateway, reachable))
+    }
+
+    /// 重启前的本地检查（重新读取默认网关，不使用缓存）
+    pub fn local_sanity(
+        &mut self,
+        sys: &mut impl SystemOps,
+        wan_addr: String,
+        now: Instant,
+    ) -> LocalSanity {
+        self.refreshed_at = None;
+        let checked = self.check(sys, now);
+        LocalSanity {
+            wan_addr,
+            carrier: read_carrier(WAN_INTERFACE),
+            default_route: checked.map(|(gateway, _)| gateway),
+            gateway_reachable: checked.is_some_and(|(_, reachable)| reachable),
+        }
+    }
+}
+
+/// 重启前的本地检查结果：全部正常而只有远程目标不通时，问题很可能在本机（如防火墙规则）
+pub struct LocalSanity {
+    pub wan_addr: String,
+    /// 读不到 carrier 时为 None（视为不正常）
+    pub carrier: Option<bool>,
+    pub default_route: Option<IpAddr>,
+    pub gateway_reachable: bool,
+}
+
+impl LocalSanity {
+    pub fn all_ok(&self) -> bool {
+        !self.wan_addr.is_empty()
+            && self.carrier == Some(true)
+            && self.default_route.is_some()
+            && self.gateway_reachable
+    }
+
+    /// 日志中的形式：`wan_addr=10.0.0.2 carrier=up default_route=10.0.0.1 gateway=reachable`
+    pub fn describe(&self) -> String {
+        format!(
+            "wan_addr={} carrier={} default_route={} gateway={}",
+            if self.wan_addr.is_empty() {
+                "-"
+            } else {
+                &self.wan_addr
+            },
+            match self.carrier {
+                Some(true) => "up",
+                Some(false) => "down",
+                None => "unknown",
+            },
+            self.default_route
+                .map(|gw| gw.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            if self.gateway_reachable {
+                "reachable"
+            } else {
+                "unreachable"
+            }
+        )
+    }
+}
+
+fn read_carrier(interface: &str) -> Option<bool> {
+    fs::read_to_string(format!("/sys/class/net/{}/carrier", interface))
+        .ok()
+        .map(|carrier| carrier.trim() == "1")
+}
+
+fn read_default_gateway() -> Option<IpAddr> {
+    let output =
+        command::run_with_timeout("ip", &["route", "show", "default"], ROUTE_COMMAND_TIMEOUT)
+            .ok()
+            .filter(|output| output.success())?;
+    parse_default_route(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_route() {
+        assert_eq!(
+            parse_default_route("default via 192.168.0.1 dev wan1 metric 10\n"),
+            Some("192.168.0.1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_default_route("10.0.0.0/8 via 10.1.1.1 dev br0\ndefault dev ppp0 scope link\n"),
+            None
+        );
+        assert_eq!(parse_default_route(""), None);
+    }
+
+    #[test]
+    fn test_local_sanity() {
+        let mut sanity = LocalSanity {
+            wan_addr: "10.0.0.2".to_string(),
+            carrier: Some(true),
+            default_route: Some("10.0.0.1".parse().unwrap()),
+            gateway_reachable: true,
+        };
+        assert!(sanity.all_ok());
+        assert_eq!(
+            sanity.describe(),
+            "wan_addr=10.0.0.2 carrier=up default_route=10.0.0.1 gateway=reachable"
+        );
+        // 任何一项不能确认都不算正常
+        sanity.carrier = None;
+        assert!(!sanity.all_ok());
+        assert_eq!(
+            sanity.describe(),
+            "wan_addr=10.0.0.2 carrier=unknown default_route=10.0.0.1 gateway=reachable"
+        );
+    }
+}
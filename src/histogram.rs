@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant};
+
+/// 默认的桶上界（毫秒）：<20, 20-50, 50-100, 100-300, 300+
+pub const DEFAULT_BUCKETS_MS: &[u32] = &[20, 50, 100, 300];
+
+/// 每天结束时输出一次汇总并清零
+const HISTOGRAM_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 解析 `20,50,100,300` 形式的桶上界，要求非空、为正数且严格递增
+pub fn parse_bounds(value: &str) -> Result<Vec<u32>, String> {
+    let mut bounds = Vec::new();
+    for part in value.split(',').map(str::trim) {
+        let bound = match part.parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("latency_buckets_ms: invalid bound '{}'", part)),
+        };
+        if bounds.last().is_some_and(|last| *last >= bound) {
+            return Err(format!(
+                "latency_buckets_ms: bounds must be increasing: {}",
+                value
+            ));
+        }
+        bounds.push(bound);
+    }
+    Ok(bounds)
+}
+
+/// 成功连接耗时的固定桶直方图
+///
+/// 清零规则：`counts` 只统计当前这一天（每 24 小时由 rotate 输出后清零），
+/// `total` 从启动开始累计、从不清零
+pub struct LatencyHistogram {
+    /// 各桶上界（毫秒），最后还有一个无上界的桶
+    bounds: Vec<u32>,
+    counts: Vec<u64>,
+    total: Vec<u64>,
+    /// 从启动开始累计的耗时之和（毫秒）
+    total_ms: u64,
+    period_start: Instant,
+}
+
+impl LatencyHistogram {
+    pub fn new(bounds: &[u32], now: Instant) -> Self {
+        LatencyHistogram {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            total: vec![0; bounds.len() + 1],
+            total_ms: 0,
+            period_start: now,
+        }
+    }
+
+    /// 记录一次成功检查的耗时（等于上界的值计入该桶）
+    pub fn record(&mut self, rtt_ms: u128) {
+        let index = self
+            .bounds
+            .iter()
+            .position(|bound| rtt_ms <= *bound as u128)
+            .unwrap_or(self.bounds.len());
+        self.counts[index] += 1;
+        self.total[index] += 1;
+        self.total_ms = self.total_ms.saturating_add(rtt_ms as u64);
+    }
+
+    /// 已满一天时返回当天的汇总并清零当天计数
+    pub fn rotate(&mut self, now: Instant) -> Option<String> {
+        if now.duration_since(self.period_start) < HISTOGRAM_PERIOD {
+            return None;
+        }
+        let summary = self.format(&self.counts);
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.period_start = now;
+        Some(summary)
+    }
+
+    /// STATS 命令的返回内容
+    pub fn stats_lines(&self, now: Instant) -> Vec<String> {
+        vec![
+            format!(
+                "latency_today={} (since {}s ago)",
+                self.format(&self.counts),
+                now.duration_since(self.period_start).as_secs()
+            ),
+            format!("latency_total={}", self.format(&self.total)),
+        ]
+    }
+
+    /// 从启动开始的累计数据，Prometheus 直方图格式：`<name>_bucket{le="20"}` 为不超过
+    /// 该上界的累计次数（最后是 `le="+Inf"`），另有 `<name>_sum` 和 `<name>_count`
+    pub fn prometheus_lines(&self, name: &str) -> Vec<String> {
+        let mut lines = vec![format!("# TYPE {} histogram", name)];
+        let mut cumulative = 0;
+        for (index, count) in self.total.iter().enumerate() {
+            cumulative += count;
+            let le = self
+                .bounds
+                .get(index)
+                .map_or_else(|| "+Inf".to_string(), u32::to_string);
+            lines.push(format!("{}_bucket{{le=\"{}\"}} {}", name, le, cumulative));
+        }
+        lines.push(format!("{}_sum {}", name, self.total_ms));
+        lines.push(format!("{}_count {}", name, cumulative));
+        lines
+    }
+
+    /// `<20:5 20-50:3 50-100:0 100-300:1 300+:0`
+    fn format(&self, counts: &[u64]) -> String {
+        let mut parts = Vec::with_capacity(counts.len());
+        let mut lower = None;
+        for (index, count) in counts.iter().enumerate() {
+            let label = match (lower, self.bounds.get(index)) {
+                (None, Some(upper)) => format!("<{}", upper),
+                (Some(lower), Some(upper)) => format!("{}-{}", lower, upper),
+                (Some(lower), None) => format!("{}+", lower),
+                (None, None) => "all".to_string(),
+            };
+            parts.push(format!("{}:{}", label, count));
+            lower = self.bounds.get(index);
+        }
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bounds() {
+        assert_eq!(parse_bounds("20, 50,100").unwrap(), vec![20, 50, 100]);
+        assert!(parse_bounds("").is_err());
+        assert!(parse_bounds("0,50").is_err());
+        assert!(parse_bounds("50,20").is_err());
+        assert!(parse_bounds("20,20").is_err());
+        assert!(parse_bounds("20,abc").is_err());
+    }
+
+    #[test]
+    fn test_record_and_daily_reset() {
+        let start = Instant::now();
+        let mut histogram = LatencyHistogram::new(DEFAULT_BUCKETS_MS, start);
+        for rtt in [5, 20, 21, 99, 300, 301, 5000] {
+            histogram.record(rtt);
+        }
+        assert_eq!(
+            histogram.stats_lines(start)[0],
+            "latency_today=<20:2 20-50:1 50-100:1 100-300:1 300+:2 (since 0s ago)"
+        );
+        assert_eq!(histogram.rotate(start + Duration::from_secs(3600)), None);
+
+        // 满一天：返回当天汇总，当天计数清零，累计计数保留
+        let day = start + HISTOGRAM_PERIOD;
+        assert_eq!(
+            histogram.rotate(day).as_deref(),
+            Some("<20:2 20-50:1 50-100:1 100-300:1 300+:2")
+        );
+        histogram.record(30);
+        assert_eq!(
+            histogram.stats_lines(day),
+            [
+                "latency_today=<20:0 20-50:1 50-100:0 100-300:0 300+:0 (since 0s ago)",
+                "latency_total=<20:2 20-50:2 50-100:1 100-300:1 300+:2",
+            ]
+        );
+        assert_eq!(histogram.rotate(day + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn test_prometheus_lines() {
+        let start = Instant::now();
+        let mut histogram = LatencyHistogram::new(&[20, 50], start);
+        for rtt in [5, 20, 30, 400] {
+            histogram.record(rtt);
+        }
+        // 按天清零不影响累计数据
+        histogram.rotate(start + HISTOGRAM_PERIOD);
+        assert_eq!(
+            histogram.prometheus_lines("rtt_ms"),
+            [
+                "# TYPE rtt_ms histogram",
+                "rtt_ms_bucket{le=\"20\"} 2",
+                "rtt_ms_bucket{le=\"50\"} 3",
+                "rtt_ms_bucket{le=\"+Inf\"} 4",
+                "rtt_ms_sum 455",
+                "rtt_ms_count 4",
+            ]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+/// SHA-256 分组大小（字节）
+const BLOCK: usize = 64;
+
+/// 签名时间与本机时间允许的最大偏差
+pub const MAX_SKEW_SECS: u64 = 300;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+    for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *s = s.wrapping_add(v);
+    }
+}
+
+/// 依次拼接 parts 计算 SHA-256
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut state = H0;
+    let mut buf = Vec::with_capacity(BLOCK * 2);
+    let mut len = 0u64;
+    for part in parts {
+        len += part.len() as u64;
+        buf.extend_from_slice(part);
+        let full = buf.len() / BLOCK * BLOCK;
+        for block in buf[..full].chunks_exact(BLOCK) {
+            compress(&mut state, block);
+        }
+        buf.drain(..full);
+    }
+    buf.push(0x80);
+    while buf.len() % BLOCK != BLOCK - 8 {
+        buf.push(0);
+    }
+    buf.extend_from_slice(&(len * 8).to_be_bytes());
+    for block in buf.chunks_exact(BLOCK) {
+        compress(&mut state, block);
+    }
+    let mut out = [0u8; 32];
+    for (chunk, s) in out.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&s.to_be_bytes());
+    }
+    out
+}
+
+/// HMAC-SHA256（RFC 2104）
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&sha256(&[key]));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let ipad: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    let opad: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    let inner = sha256(&[&ipad, message]);
+    sha256(&[&opad, &inner])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 校验 TCP 控制端口带签名的命令，返回去掉时间和 HMAC 的命令。
+/// 格式为 `<命令>|<UNIX 秒>|<HMAC 十六进制>`，HMAC 按 `<命令>|<UNIX 秒>` 计算；
+/// 时间与本机相差超过 MAX_SKEW_SECS 的拒绝，限制重放
+pub fn verify_command<'a>(
+    key: &[u8],
+    received: &'a [u8],
+    now: u64,
+) -> Result<&'a [u8], &'static str> {
+    let mut fields = received.rsplitn(3, |b| *b == b'|');
+    let (Some(mac), Some(ts), Some(command)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err("unsigned");
+    };
+    let signed = &received[..command.len() + 1 + ts.len()];
+    let expected = hex(&hmac_sha256(key, signed));
+    let mac = mac.trim_ascii_end();
+    // 比较时间不随第一个不同字节的位置变化
+    let same = mac.len() == expected.len()
+        && mac
+            .iter()
+            .zip(expected.as_bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x.to_ascii_lowercase() ^ y))
+            == 0;
+    if !same {
+        return Err("bad signature");
+    }
+    let ts: u64 = std::str::from_utf8(ts)
+        .ok()
+        .and_then(|ts| ts.parse().ok())
+        .ok_or("bad timestamp")?;
+    if ts.abs_diff(now) > MAX_SKEW_SECS {
+        return Err("expired");
+    }
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_vectors() {
+        assert_eq!(
+            hex(&sha256(&[b"abc"])),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&sha256(&[
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklm",
+                b"klmnlmnomnopnopq"
+            ])),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        // RFC 4231 测试用例 2
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // RFC 4231 测试用例 6：密钥长于一个分组
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn test_verify_command() {
+        let key = b"s3cret";
+        let sign = |message: &str| {
+            format!(
+                "{}|{}\n",
+                message,
+                hex(&hmac_sha256(key, message.as_bytes()))
+            )
+        };
+        let signed = sign("RESTART_ADBD|1000");
+        assert_eq!(
+            verify_command(key, signed.as_bytes(), 1100),
+            Ok(&b"RESTART_ADBD"[..])
+        );
+        // 命令本身可以含 `|`
+        let signed = sign("A|B|1000");
+        assert_eq!(
+            verify_command(key, signed.as_bytes(), 1000),
+            Ok(&b"A|B"[..])
+        );
+        assert_eq!(
+            verify_command(
+                key,
+                sign("RESTART_ADBD|1000").as_bytes(),
+                1000 + MAX_SKEW_SECS + 1
+            ),
+            Err("expired")
+        );
+        assert_eq!(
+            verify_command(b"other", sign("RESTART_ADBD|1000").as_bytes(), 1000),
+            Err("bad signature")
+        );
+        assert_eq!(verify_command(key, b"RESTART_ADBD", 1000), Err("unsigned"));
+    }
+}
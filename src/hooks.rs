@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::command::{self, CommandResult};
+use crate::{log_message, log_warn};
+
+/// 一般事件脚本的超时
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+/// 同一事件两次执行脚本的最小间隔，避免事件抖动时反复执行
+const HOOK_MIN_INTERVAL: Duration = Duration::from_secs(60);
+/// before_reboot 脚本在重启前同步执行，最多占用这么久，不能无限推迟需要的重启
+const BEFORE_REBOOT_BUDGET: Duration = Duration::from_secs(15);
+
+/// 可以挂脚本的事件（配置项为 `hook_<事件名>`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    ConnectivityLost,
+    ConnectivityRestored,
+    HighLoadEnter,
+    HighLoadExit,
+    BeforeReboot,
+    AdbdRestarted,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 6] = [
+        HookEvent::ConnectivityLost,
+        HookEvent::ConnectivityRestored,
+        HookEvent::HighLoadEnter,
+        HookEvent::HighLoadExit,
+        HookEvent::BeforeReboot,
+        HookEvent::AdbdRestarted,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::ConnectivityLost => "connectivity_lost",
+            HookEvent::ConnectivityRestored => "connectivity_restored",
+            HookEvent::HighLoadEnter => "high_load_enter",
+            HookEvent::HighLoadExit => "high_load_exit",
+            HookEvent::BeforeReboot => "before_reboot",
+            HookEvent::AdbdRestarted => "adbd_restarted",
+        }
+    }
+
+    /// `hook_connectivity_lost` -> ConnectivityLost
+    pub fn from_key(key: &str) -> Option<HookEvent> {
+        let name = key.strip_prefix("hook_")?;
+        HookEvent::ALL
+            .into_iter()
+            .find(|event| event.name() == name)
+    }
+}
+
+/// 按配置执行事件脚本。事件详情通过环境变量传入（ZXP_EVENT 以及调用方给出的 ZXP_*），
+/// 脚本失败和超时只记录日志和计数，不影响主循环
+pub struct Hooks {
+    scripts: HashMap<HookEvent, String>,
+    last_run: HashMap<HookEvent, Instant>,
+    results_tx: Sender<(HookEvent, io::Result<CommandResult>)>,
+    results_rx: Receiver<(HookEvent, io::Result<CommandResult>)>,
+    pub runs: u32,
+    pub failures: u32,
+    is_prod: bool,
+}
+
+impl Hooks {
+    pub fn new(scripts: HashMap<HookEvent, String>, is_prod: bool) -> Self {
+        let (results_tx, results_rx) = mpsc::channel();
+        Hooks {
+            scripts,
+            last_run: HashMap::new(),
+            results_tx,
+            results_rx,
+            runs: 0,
+            failures: 0,
+            is_prod,
+        }
+    }
+
+    /// 事件配置了脚本且离上次执行已超过最小间隔时返回脚本，并记为已执行
+    fn due(&mut self, event: HookEvent, now: Instant) -> Option<String> {
+        let script = self.scripts.get(&event)?.clone();
+        if self
+            .last_run
+            .get(&event)
+            .is_some_and(|last| now.duration_since(*last) < HOOK_MIN_INTERVAL)
+        {
+            log_message(
+                &format!(
+                    "Hook {} skipped: ran less than {}s ago",
+                    event.name(),
+                    HOOK_MIN_INTERVAL.as_secs()
+                ),
+                self.is_prod,
+            );
+            return None;
+        }
+        self.last_run.insert(event, now);
+        self.runs += 1;
+        Some(script)
+    }
+
+    /// 在后台线程执行事件脚本，结果由 poll() 收集
+    pub fn fire(&mut self, event: HookEvent, details: &[(&str, String)], now: Instant) {
+        let Some(script) = self.due(event, now) else {
+            return;
+        };
+        let command = hook_command(&script, event, details);
+        let tx = self.results_tx.clone();
+        thread::spawn(move || {
+            let _ = tx.send((event, command::run_command(command, HOOK_TIMEOUT)));
+        });
+    }
+
+    /// 同步执行事件脚本（before_reboot），最多等待 BEFORE_REBOOT_BUDGET
+    pub fn run_blocking(&mut self, event: HookEvent, details: &[(&str, String)], now: Instant) {
+        let Some(script) = self.due(event, now) else {
+            return;
+        };
+        let command = hook_command(&script, event, details);
+        let result = command::run_command(command, BEFORE_REBOOT_BUDGET);
+        self.record(event, result);
+    }
+
+    /// 收集后台执行完的脚本结果
+    pub fn poll(&mut self) {
+        while let Ok((event, result)) = self.results_rx.try_recv() {
+            self.record(event, result);
+        }
+    }
+
+    fn record(&mut self, event: HookEvent, result: io::Result<CommandResult>) {
+        let error = match result {
+            Ok(result) if result.success() => None,
+            Ok(result) => Some(result.to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        match error {
+            None => log_message(&format!("Hook {} finished", event.name()), self.is_prod),
+            Some(e) => {
+                self.failures += 1;
+                log_warn(
+                    &format!("Hook {} failed: {}", event.name(), e),
+                    self.is_prod,
+                );
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// STATUS 中的一行：`hook_runs=3 hook_failures=1`
+    pub fn status_line(&self) -> String {
+        format!("hook_runs={} hook_failures={}", self.runs, self.failures)
+    }
+}
+
+fn hook_command(script: &str, event: HookEvent, details: &[(&str, String)]) -> Command {
+    let mut command = Command::new(script);
+    command.env("ZXP_EVENT", event.name());
+    for (name, value) in details {
+        command.env(name, value);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_key() {
+        assert_eq!(
+            HookEvent::from_key("hook_before_reboot"),
+            Some(HookEvent::BeforeReboot)
+        );
+        assert_eq!(HookEvent::from_key("hook_bogus"), None);
+        assert_eq!(HookEvent::from_key("before_reboot"), None);
+    }
+
+    #[test]
+    fn test_hooks() {
+        let dir = std::env::temp_dir().join(format!("zxping-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out");
+        let script = dir.join("hook.sh");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$ZXP_EVENT $ZXP_FAILURE_COUNT\" > {}\n",
+                out.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+
+        let scripts = HashMap::from([
+            (HookEvent::BeforeReboot, script.display().to_string()),
+            (HookEvent::HighLoadEnter, "/no/such/hook".to_string()),
+        ]);
+        let mut hooks = Hooks::new(scripts, false);
+        let now = Instant::now();
+        hooks.run_blocking(
+            HookEvent::BeforeReboot,
+            &[("ZXP_FAILURE_COUNT", "5".to_string())],
+            now,
+        );
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "before_reboot 5\n");
+        // 最小间隔内不再执行
+        hooks.run_blocking(HookEvent::BeforeReboot, &[], now + Duration::from_secs(10));
+        assert_eq!(hooks.runs, 1);
+
+        // 没有配置的事件不执行；脚本不存在时计为失败
+        hooks.fire(HookEvent::ConnectivityLost, &[], now);
+        hooks.fire(HookEvent::HighLoadEnter, &[], now);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while hooks.failures == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            hooks.poll();
+        }
+        assert_eq!(hooks.status_line(), "hook_runs=2 hook_failures=1");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,192 @@
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::sockmark;
+
+/// 一次 HTTP 检查的耗时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpTiming {
+    /// TCP 握手完成的时间
+    pub connect: Duration,
+    /// 从请求发出到收到响应第一个字节的时间（服务器处理时间）
+    pub ttfb: Duration,
+}
+
+/// 发出请求后等待响应首字节的时间。要比 ttfb_threshold 长：慢但有响应的服务器
+/// 记为 SLOW_SERVER，不算连接失败
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 向目标发一个 HEAD 请求，分别测量连接时间和首字节时间。连接最多等 connect_timeout，
+/// 响应最多等 response_timeout。只要收到任何响应字节就算成功，不关心状态码
+pub fn probe(
+    addr: SocketAddr,
+    connect_timeout: Duration,
+    response_timeout: Duration,
+) -> io::Result<HttpTiming> {
+    let start = Instant::now();
+    let mut stream = sockmark::connect_tcp(addr, connect_timeout)?;
+    let connect = start.elapsed();
+
+    stream.set_read_timeout(Some(response_timeout))?;
+    stream.set_write_timeout(Some(response_timeout))?;
+    let sent = Instant::now();
+    stream.write_all(
+        format!(
+            "HEAD / HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            addr.ip()
+        )
+        .as_bytes(),
+    )?;
+    let mut byte = [0u8; 1];
+    match stream.read(&mut byte) {
+        Ok(0) => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed before response",
+        )),
+        Ok(_) => Ok(HttpTiming {
+            connect,
+            ttfb: sent.elapsed(),
+        }),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no response within {}ms", response_timeout.as_millis()),
+            ))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// 服务器慢（TTFB 超过阈值）的计数，与连接失败、高延迟分开统计
+#[derive(Debug, Default)]
+pub struct SlowServer {
+    /// 连续超过阈值的次数
+    pub count: u32,
+    /// 累计超过阈值的次数
+    pub total: u32,
+    last: Option<HttpTiming>,
+}
+
+impl SlowServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次成功的检查。只在状态变化时返回通知：第一次超过阈值时为 SLOW_SERVER，
+    /// 之后恢复时为 SLOW_SERVER_RECOVERED（带上连续慢了几次）
+    pub fn record(&mut self, timing: HttpTiming, threshold: Duration) -> Option<String> {
+        self.last = Some(timing);
+        if threshold.is_zero() || timing.ttfb <= threshold {
+            let count = std::mem::take(&mut self.count);
+            return (count > 0).then(|| {
+                format!(
+                    "SLOW_SERVER_RECOVERED: TTFB={}ms COUNT={}",
+                    timing.ttfb.as_millis(),
+                    count
+                )
+            });
+        }
+        self.count += 1;
+        self.total += 1;
+        (self.count == 1).then(|| {
+            format!(
+                "SLOW_SERVER: TTFB={}ms CONNECT={}ms THRESHOLD={}ms",
+                timing.ttfb.as_millis(),
+                timing.connect.as_millis(),
+                threshold.as_millis()
+            )
+        })
+    }
+
+    /// 当前是否处于服务器慢的状态
+    pub fn is_slow(&self) -> bool {
+        self.count > 0
+    }
+
+    /// STATUS 中的一行：`connect_ms=12 ttfb_ms=340 slow_server=0/2`
+    pub fn status_line(&self) -> String {
+        let ms = |d: Option<Duration>| {
+            d.map(|d| d.as_millis().to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        format!(
+            "connect_ms={} ttfb_ms={} slow_server={}/{}",
+            ms(self.last.map(|t| t.connect)),
+            ms(self.last.map(|t| t.ttfb)),
+            self.count,
+            self.total
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 256];
+            let _ = stream.read(&mut buf);
+            thread::sleep(Duration::from_millis(150));
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n");
+        });
+        // 首字节时间可以超过连接超时
+        let timing = probe(addr, Duration::from_millis(100), Duration::from_secs(5)).unwrap();
+        assert!(timing.ttfb >= Duration::from_millis(150));
+        assert!(timing.connect < timing.ttfb);
+        server.join().unwrap();
+
+        // 只建立连接不回应：超时失败
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let err = probe(addr, Duration::from_secs(1), Duration::from_millis(200)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_slow_server() {
+        let timing = |connect, ttfb| HttpTiming {
+            connect: Duration::from_millis(connect),
+            ttfb: Duration::from_millis(ttfb),
+        };
+        let threshold = Duration::from_millis(1000);
+        let mut slow = SlowServer::new();
+        assert_eq!(slow.status_line(), "connect_ms=- ttfb_ms=- slow_server=0/0");
+        assert_eq!(slow.record(timing(10, 200), threshold), None);
+        assert_eq!(
+            slow.record(timing(12, 2500), threshold).as_deref(),
+            Some("SLOW_SERVER: TTFB=2500ms CONNECT=12ms THRESHOLD=1000ms")
+        );
+        // 持续慢时不重复通知
+        assert_eq!(slow.record(timing(12, 1800), threshold), None);
+        assert!(slow.is_slow());
+        assert_eq!(
+            slow.status_line(),
+            "connect_ms=12 ttfb_ms=1800 slow_server=2/2"
+        );
+        // 恢复后连续计数清零，累计保留
+        assert_eq!(
+            slow.record(timing(9, 300), threshold).as_deref(),
+            Some("SLOW_SERVER_RECOVERED: TTFB=300ms COUNT=2")
+        );
+        assert_eq!(slow.record(timing(9, 300), threshold), None);
+        assert_eq!(
+            slow.status_line(),
+            "connect_ms=9 ttfb_ms=300 slow_server=0/2"
+        );
+        // 阈值为 0 时不检查
+        assert_eq!(slow.record(timing(9, 30000), Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::histogram::LatencyHistogram;
+use crate::{log_message, log_warn};
+
+/// 请求头最多读取的字节数
+const MAX_REQUEST: usize = 4096;
+/// 读取请求、写回应答的超时（串行处理，不能让一个慢客户端占住服务）
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// 等待主循环执行命令的时间
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 可以通过 `POST /command/<名字>` 执行的命令和对应的控制通道命令
+const COMMANDS: &[(&str, &[u8])] = &[
+    ("restart_adbd", crate::RESTART_SIGNAL_ADBD),
+    ("kill_adbd", crate::KILL_SIGNAL_ADBD),
+    ("disable_adb", crate::DISABLE_ADB),
+    ("reboot", crate::RESTART_SIGNAL_SERVER),
+    ("restart_goahead", crate::RESTART_SIGNAL_GOAHEAD),
+    ("kill_goahead", crate::KILL_SIGNAL_GOAHEAD),
+    ("kill_radvd", crate::KILL_SIGNAL_RADVD),
+    ("reduce_kernel_load", crate::REDUCE_KERNEL_LOAD),
+    ("adjust_zram", crate::ADJUST_ZRAM),
+    ("restore_firewall", crate::RESTORE_FIREWALL),
+    ("maintenance_on", crate::SIGNAL_MAINTENANCE_ON),
+    ("maintenance_off", crate::SIGNAL_MAINTENANCE_OFF),
+];
+
+/// 转交主循环执行的命令：控制通道命令、来源地址和写回应答的对象
+pub type Command = (Vec<u8>, SocketAddr, Box<dyn Write + Send>);
+
+/// 主循环写入的应答，drop 时交回等待中的 HTTP 线程
+struct Reply {
+    buf: Vec<u8>,
+    tx: Sender<Vec<u8>>,
+}
+
+impl Write for Reply {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Reply {
+    fn drop(&mut self) {
+        let _ = self.tx.send(std::mem::take(&mut self.buf));
+    }
+}
+
+/// 延迟直方图在 /metrics 中的名字
+const LATENCY_METRIC: &str = "zxic_ping_latency_milliseconds";
+
+/// /status 和 /metrics 返回的快照
+#[derive(Clone, Default)]
+struct Snapshot {
+    status: Vec<String>,
+    /// Prometheus 格式的延迟直方图
+    histogram: Vec<String>,
+    /// zxic_ping_info 的标签：只放取值固定的字段（版本、探测方式、档位），
+    /// 状态行里随时变化的字符串做标签会让标签组合无限增长
+    info: Vec<(&'static str, String)>,
+}
+
+/// 简单的 HTTP/1.0 管理接口：`GET /status`（JSON）、`GET /metrics`（Prometheus），
+/// 以及带 Bearer token 的 `POST /command/<名字>`。在单独的线程中串行处理请求，
+/// 命令通过 poll_command 交给主循环，与 TCP 控制通道走同一套处理
+pub struct HttpServer {
+    status: Arc<Mutex<Snapshot>>,
+    commands: Receiver<Command>,
+}
+
+impl HttpServer {
+    /// 监听 address:port；token 为 None 时只提供只读接口
+    pub fn start(
+        address: IpAddr,
+        port: u16,
+        token: Option<Vec<u8>>,
+        is_prod: bool,
+    ) -> io::Result<HttpServer> {
+        let listener = TcpListener::bind((address, port))?;
+        let status = Arc::new(Mutex::new(Snapshot::default()));
+        let (tx, commands) = mpsc::channel();
+        let shared = Arc::clone(&status);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = handle(stream, &shared, token.as_deref(), &tx) {
+                    log_warn(&format!("HTTP request failed: {}", e), is_prod);
+                }
+            }
+        });
+        log_message(
+            &format!(
+                "HTTP server listening on {}",
+                SocketAddr::new(address, port)
+            ),
+            is_prod,
+        );
+        Ok(HttpServer { status, commands })
+    }
+
+    /// 更新 /status 和 /metrics 返回的快照
+    pub fn update(
+        &self,
+        lines: &[String],
+        histogram: &LatencyHistogram,
+        info: &[(&'static str, &str)],
+    ) {
+        if let Ok(mut status) = self.status.lock() {
+            *status = Snapshot {
+                status: lines.to_vec(),
+                histogram: histogram.prometheus_lines(LATENCY_METRIC),
+                info: info
+                    .iter()
+                    .map(|(key, value)| (*key, value.to_string()))
+                    .collect(),
+            };
+        }
+    }
+
+    /// 取一条待执行的命令（非阻塞）
+    pub fn poll_command(&mut self) -> Option<Command> {
+        self.commands.try_recv().ok()
+    }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    status: &Mutex<Snapshot>,
+    token: Option<&[u8]>,
+    commands: &Sender<Command>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let peer = stream.peer_addr()?;
+    let request = read_request(&mut stream)?;
+    let snapshot = || status.lock().map(|s| s.clone()).unwrap_or_default();
+    let (code, content_type, body) = match route(&request, token) {
+        Route::Status => (200, "application/json", status_json(&snapshot().status)),
+        Route::Metrics => (200, "text/plain; version=0.0.4", metrics(&snapshot())),
+        Route::Command(signal) => {
+            let (tx, rx) = mpsc::channel();
+            let reply = Box::new(Reply {
+                buf: Vec::new(),
+                tx,
+            });
+            let _ = commands.send((signal.to_vec(), peer, reply));
+            match rx.recv_timeout(COMMAND_TIMEOUT) {
+                Ok(reply) => (
+                    200,
+                    "text/plain",
+                    String::from_utf8_lossy(&reply).into_owned(),
+                ),
+                Err(_) => (504, "text/plain", "command timed out".to_string()),
+            }
+        }
+        Route::Error(code, message) => (code, "text/plain", message.to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.0 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason_phrase(code),
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// 读到空行（请求头结束）为止
+fn read_request(stream: &mut TcpStream) -> io::Result<String> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 512];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() >= MAX_REQUEST {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too large",
+            ));
+        }
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+#[derive(Debug, PartialEq)]
+enum Route {
+    Status,
+    Metrics,
+    Command(&'static [u8]),
+    Error(u16, &'static str),
+}
+
+fn route(request: &str, token: Option<&[u8]>) -> Route {
+    let mut words = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    match (method, path) {
+        ("GET", "/status") => Route::Status,
+        ("GET", "/metrics") => Route::Metrics,
+        ("POST", path) if path.starts_with("/command/") => {
+            let Some(token) = token else {
+                return Route::Error(403, "commands disabled: no http_token_file configured");
+            };
+            if !authorized(request, token) {
+                return Route::Error(401, "missing or wrong bearer token");
+            }
+            let name = &path["/command/".len()..];
+            COMMANDS
+                .iter()
+                .find(|(command, _)| *command == name)
+                .map(|(_, signal)| Route::Command(signal))
+                .unwrap_or(Route::Error(404, "unknown command"))
+        }
+        (_, "/status" | "/metrics") => Route::Error(405, "method not allowed"),
+        _ => Route::Error(404, "not found"),
+    }
+}
+
+/// 请求头中有 `Authorization: Bearer <token>` 且与配置的一致
+fn authorized(request: &str, token: &[u8]) -> bool {
+    request
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .filter_map(|(_, value)| value.trim().strip_prefix("Bearer "))
+        .any(|given| constant_time_eq(given.trim().as_bytes(), token))
+}
+
+/// 比较时间不随第一个不同字节的位置变化
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn reason_phrase(code: u16) -> &'static str {
+    match code {
+        200 => "OK",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        504 => "Gateway Timeout",
+        _ => "Error",
+    }
+}
+
+/// 把 STATUS 行拆成 (key, value)：每个 `key=value` 开始一个字段，
+/// 不含 `=` 的词接在前一个值后面（如 `health_score=6.0 (ok)`）。
+/// 不同行中重复的 key 加上所在行第一个 key 作为前缀（`storage_default_route`），仍然重复的丢弃
+fn status_fields(lines: &[String]) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut seen = HashSet::new();
+    for line in lines {
+        let mut lead: Option<&str> = None;
+        let mut in_line = false;
+        for word in line.split_whitespace() {
+            match word.split_once('=') {
+                Some((key, value)) if !key.is_empty() => {
+                    let lead = *lead.get_or_insert(key);
+                    let key = if seen.contains(key) {
+                        format!("{}_{}", lead, key)
+                    } else {
+                        key.to_string()
+                    };
+                    in_line = seen.insert(key.clone());
+                    if in_line {
+                        fields.push((key, value.to_string()));
+                    }
+                }
+                _ if in_line => {
+                    if let Some((_, value)) = fields.last_mut() {
+                        value.push(' ');
+                        value.push_str(word);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    fields
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// STATUS 快照转成 JSON 对象，数值字段输出为数字
+fn status_json(lines: &[String]) -> String {
+    let fields: Vec<String> = status_fields(lines)
+        .iter()
+        .map(|(key, value)| {
+            let value = match value.parse::<f64>() {
+                Ok(n) if n.is_finite() => value.clone(),
+                _ => json_string(value),
+            };
+            format!("{}:{}", json_string(key), value)
+        })
+        .collect();
+    format!("{{{}}}\n", fields.join(","))
+}
+
+/// 状态行中的数值字段（可带 s/ms/% 单位）输出为 gauge，其余字段不输出；
+/// 之后是延迟直方图和 `zxic_ping_info{version="..",probe="..",profile=".."} 1`
+fn metrics(snapshot: &Snapshot) -> String {
+    let mut out = String::new();
+    for (key, value) in status_fields(&snapshot.status) {
+        let (number, suffix) = if let Some(n) = value.strip_suffix("ms") {
+            (n, "_milliseconds")
+        } else if let Some(n) = value.strip_suffix('s') {
+            (n, "_seconds")
+        } else if let Some(n) = value.strip_suffix('%') {
+            (n, "_percent")
+        } else {
+            (value.as_str(), "")
+        };
+        let key: String = key
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        match number.parse::<f64>() {
+            Ok(n) if n.is_finite() && !key.ends_with(suffix.trim_start_matches('_')) => {
+                out.push_str(&format!("zxic_ping_{}{} {}\n", key, suffix, n))
+            }
+            Ok(n) if n.is_finite() => out.push_str(&format!("zxic_ping_{} {}\n", key, n)),
+            _ => {}
+        }
+    }
+    for line in &snapshot.histogram {
+        out.push_str(line);
+        out.push('\n');
+    }
+    if !snapshot.info.is_empty() {
+        let labels: Vec<String> = snapshot
+            .info
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}=\"{}\"",
+                    key,
+                    value.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            })
+            .collect();
+        out.push_str(&format!("zxic_ping_info{{{}}} 1\n", labels.join(",")));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_route() {
+        let token = Some(&b"s3cret"[..]);
+        assert_eq!(route("GET /status HTTP/1.0\r\n\r\n", token), Route::Status);
+        assert_eq!(route("GET /metrics HTTP/1.1\r\n\r\n", None), Route::Metrics);
+        assert_eq!(
+            route(
+                "POST /command/restart_adbd HTTP/1.0\r\nAuthorization: Bearer s3cret\r\n\r\n",
+                token
+            ),
+            Route::Command(crate::RESTART_SIGNAL_ADBD)
+        );
+        assert!(matches!(
+            route(
+                "POST /command/reboot HTTP/1.0\r\nAuthorization: Bearer wrong\r\n\r\n",
+                token
+            ),
+            Route::Error(401, _)
+        ));
+        assert!(matches!(
+            route("POST /command/reboot HTTP/1.0\r\n\r\n", None),
+            Route::Error(403, _)
+        ));
+        assert!(matches!(
+            route(
+                "POST /command/rm_rf HTTP/1.0\r\nauthorization: Bearer s3cret\r\n\r\n",
+                token
+            ),
+            Route::Error(404, _)
+        ));
+        assert!(matches!(
+            route("DELETE /status HTTP/1.0\r\n\r\n", token),
+            Route::Error(405, _)
+        ));
+    }
+
+    #[test]
+    fn test_status_json() {
+        let status = lines(&[
+            "health=HEALTHY",
+            "outage=0s",
+            "hook_runs=3 hook_failures=1",
+            "health_score=6.0 (ok)",
+            "note=say \"hi\"",
+            "storage=/tmp outage=1s",
+        ]);
+        assert_eq!(
+            status_json(&status),
+            concat!(
+                "{\"health\":\"HEALTHY\",\"outage\":\"0s\",\"hook_runs\":3,",
+                "\"hook_failures\":1,\"health_score\":\"6.0 (ok)\",\"note\":\"say \\\"hi\\\"\",",
+                "\"storage\":\"/tmp\",\"storage_outage\":\"1s\"}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut histogram = LatencyHistogram::new(&[50], std::time::Instant::now());
+        histogram.record(30);
+        let snapshot = Snapshot {
+            status: lines(&[
+                "health=HEALTHY",
+                "failure_count=2",
+                "outage=30s",
+                "loss=10%",
+                "last_rtt_ms=23",
+                "last_error=connect refused",
+            ]),
+            histogram: histogram.prometheus_lines(LATENCY_METRIC),
+            info: vec![
+                ("version", "1.2.0".to_string()),
+                ("probe", "tcp".to_string()),
+            ],
+        };
+        assert_eq!(
+            metrics(&snapshot),
+            concat!(
+                "zxic_ping_failure_count 2\n",
+                "zxic_ping_outage_seconds 30\n",
+                "zxic_ping_loss_percent 10\n",
+                "zxic_ping_last_rtt_ms 23\n",
+                "# TYPE zxic_ping_latency_milliseconds histogram\n",
+                "zxic_ping_latency_milliseconds_bucket{le=\"50\"} 1\n",
+                "zxic_ping_latency_milliseconds_bucket{le=\"+Inf\"} 1\n",
+                "zxic_ping_latency_milliseconds_sum 30\n",
+                "zxic_ping_latency_milliseconds_count 1\n",
+                "zxic_ping_info{version=\"1.2.0\",probe=\"tcp\"} 1\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_command_roundtrip() {
+        let probe = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = probe.local_addr().unwrap().port();
+        drop(probe);
+        let mut server = HttpServer::start(
+            IpAddr::from([127, 0, 0, 1]),
+            port,
+            Some(b"tok".to_vec()),
+            false,
+        )
+        .unwrap();
+        let histogram = LatencyHistogram::new(&[50], std::time::Instant::now());
+        server.update(&lines(&["health=HEALTHY"]), &histogram, &[]);
+
+        let request = |text: &'static str| {
+            thread::spawn(move || {
+                let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+                stream.write_all(text.as_bytes()).unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                response
+            })
+        };
+        let response = request("GET /status HTTP/1.0\r\n\r\n").join().unwrap();
+        assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(response.ends_with("{\"health\":\"HEALTHY\"}\n"));
+
+        // 命令交给主循环，主循环写入的应答返回给客户端
+        let client =
+            request("POST /command/kill_adbd HTTP/1.0\r\nAuthorization: Bearer tok\r\n\r\n");
+        let (signal, _, mut reply) = loop {
+            if let Some(command) = server.poll_command() {
+                break command;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(signal, crate::KILL_SIGNAL_ADBD);
+        reply.write_all(b"OK").unwrap();
+        drop(reply);
+        assert!(client.join().unwrap().ends_with("\r\n\r\nOK"));
+    }
+}
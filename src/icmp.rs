@@ -0,0 +1,205 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::sockmark;
+
+const ECHO_REQUEST: u8 = 8;
+const ECHO_REPLY: u8 = 0;
+const PAYLOAD: &[u8] = b"zxping";
+/// 每次请求递增的序号，用来区分迟到的旧应答
+static SEQ: AtomicU16 = AtomicU16::new(0);
+
+/// 检查目标时成功的方式（probe=hybrid）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Via {
+    Tcp,
+    Icmp,
+}
+
+impl Via {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Via::Tcp => "tcp",
+            Via::Icmp => "icmp",
+        }
+    }
+}
+
+/// ICMP 校验和（RFC 1071）
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn echo_request(id: u16, seq: u16) -> Vec<u8> {
+    let mut packet = vec![ECHO_REQUEST, 0, 0, 0];
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+    let sum = checksum(&packet);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    packet
+}
+
+/// 是否是我们的 echo 应答。raw socket 收到的包带 IP 头，并且会收到所有 ICMP 包，
+/// 需要比较 id；ping socket（DGRAM）由内核改写和过滤 id，只比较 seq
+fn is_reply(packet: &[u8], raw: bool, id: u16, seq: u16) -> bool {
+    let icmp = if raw {
+        let header_len = packet.first().map(|b| (b & 0x0f) as usize * 4).unwrap_or(0);
+        packet.get(header_len..).unwrap_or(&[])
+    } else {
+        packet
+    };
+    if icmp.len() < 8 || icmp[0] != ECHO_REPLY {
+        return false;
+    }
+    (!raw || icmp[4..6] == id.to_be_bytes()) && icmp[6..8] == seq.to_be_bytes()
+}
+
+/// 优先用不需要特权的 ping socket，内核不允许时（ping_group_range）退回 raw socket
+fn open_socket() -> io::Result<(Socket, bool)> {
+    match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)) {
+        Ok(socket) => Ok((socket, false)),
+        Err(_) => Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).map(|s| (s, true)),
+    }
+}
+
+/// 发一个 ICMP echo 请求并等待应答，返回往返时间
+pub fn echo(target: IpAddr, timeout: Duration) -> io::Result<Duration> {
+    let IpAddr::V4(_) = target else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ICMP echo is only supported for IPv4 targets",
+        ));
+    };
+    let (socket, raw) = open_socket()?;
+    sockmark::prepare_probe(socket.as_raw_fd(), false);
+    let id = std::process::id() as u16;
+    let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+    let addr = SocketAddr::new(target, 0);
+    let start = Instant::now();
+    socket.send_to(&echo_request(id, seq), &addr.into())?;
+
+    let mut buf = [MaybeUninit::<u8>::uninit(); 256];
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no echo reply within {}ms", timeout.as_millis()),
+            ));
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        };
+        let packet: Vec<u8> = buf[..len]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        if is_reply(&packet, raw, id, seq) {
+            return Ok(start.elapsed());
+        }
+    }
+}
+
+/// probe=hybrid 的统计：每种方式成功的次数和上一次成功的方式
+#[derive(Debug, Default)]
+pub struct HybridStats {
+    pub last: Option<Via>,
+    pub tcp_ok: u32,
+    pub icmp_ok: u32,
+}
+
+impl HybridStats {
+    /// 记录一次检查的结果；成功方式发生变化时返回要记录的日志
+    pub fn record(&mut self, via: Option<Via>) -> Option<String> {
+        match via {
+            Some(Via::Tcp) => self.tcp_ok += 1,
+            Some(Via::Icmp) => self.icmp_ok += 1,
+            None => return None,
+        }
+        if self.last == via {
+            return None;
+        }
+        let previous = std::mem::replace(&mut self.last, via);
+        Some(format!(
+            "Hybrid check now succeeding via {} (was {})",
+            via.map(|v| v.name()).unwrap_or("none"),
+            previous.map(|v| v.name()).unwrap_or("none")
+        ))
+    }
+
+    /// STATUS 中的一行：`hybrid_via=icmp tcp_ok=10 icmp_ok=3`
+    pub fn status_line(&self) -> String {
+        format!(
+            "hybrid_via={} tcp_ok={} icmp_ok={}",
+            self.last.map(|v| v.name()).unwrap_or("none"),
+            self.tcp_ok,
+            self.icmp_ok
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_packet() {
+        let packet = echo_request(0x1234, 7);
+        assert_eq!(&packet[..2], &[ECHO_REQUEST, 0]);
+        // 带校验和的整个包再算一次校验和为 0
+        assert_eq!(checksum(&packet), 0);
+
+        let mut reply = packet.clone();
+        reply[0] = ECHO_REPLY;
+        assert!(is_reply(&reply, false, 0, 7));
+        assert!(!is_reply(&reply, false, 0, 8));
+        assert!(!is_reply(&packet, false, 0x1234, 7));
+
+        // raw socket：跳过 20 字节的 IP 头，并比较 id
+        let mut ip_packet = vec![0x45];
+        ip_packet.extend_from_slice(&[0; 19]);
+        ip_packet.extend_from_slice(&reply);
+        assert!(is_reply(&ip_packet, true, 0x1234, 7));
+        assert!(!is_reply(&ip_packet, true, 0x4321, 7));
+    }
+
+    #[test]
+    fn test_hybrid_stats() {
+        let mut stats = HybridStats::default();
+        assert_eq!(
+            stats.record(Some(Via::Tcp)).as_deref(),
+            Some("Hybrid check now succeeding via tcp (was none)")
+        );
+        assert_eq!(stats.record(Some(Via::Tcp)), None);
+        assert_eq!(stats.record(None), None);
+        assert_eq!(
+            stats.record(Some(Via::Icmp)).as_deref(),
+            Some("Hybrid check now succeeding via icmp (was tcp)")
+        );
+        assert_eq!(stats.status_line(), "hybrid_via=icmp tcp_ok=2 icmp_ok=1");
+    }
+}
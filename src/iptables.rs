@@ -0,0 +1,694 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::command;
+use crate::gateway::WAN_INTERFACE;
+use crate::system::SystemOps;
+use crate::{log_message, log_warn};
+
+/// 系统中 iptables 的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// 找不到 iptables（部分精简固件）
+    Missing,
+    Legacy,
+    /// nf_tables 后端的 iptables-nft
+    Nft,
+}
+
+impl Variant {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Missing => "missing",
+            Variant::Legacy => "legacy",
+            Variant::Nft => "nft",
+        }
+    }
+
+    /// iptables 命令失败时附加在日志中的提示
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Variant::Missing => "iptables binary not found",
+            Variant::Legacy => {
+                "legacy iptables: check that the ip_tables/iptable_nat/NETMAP modules are loaded"
+            }
+            Variant::Nft => {
+                "nft-backed iptables: the kernel may lack nf_tables, an iptables-legacy build may work"
+            }
+        }
+    }
+}
+
+/// 解析 `iptables --version`：`iptables v1.8.7 (nf_tables)`、`iptables v1.8.7 (legacy)`，
+/// 旧版本只有 `iptables v1.4.21`（都是 legacy）
+pub fn parse_version(output: &str) -> Option<Variant> {
+    let line = output.lines().next()?.trim();
+    if !line.starts_with("iptables") {
+        return None;
+    }
+    Some(if line.contains("nf_tables") {
+        Variant::Nft
+    } else {
+        Variant::Legacy
+    })
+}
+
+/// 探测 iptables 版本的超时
+const VERSION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 启动时探测 iptables 是否存在及其类型
+pub fn probe() -> Variant {
+    command::run_with_timeout("iptables", &["--version"], VERSION_TIMEOUT)
+        .ok()
+        .and_then(|output| parse_version(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(Variant::Missing)
+}
+
+/// 需要按 iptables 检查结果的命令（ip6tables 同样受影响）
+pub fn is_iptables_cmd(cmd: &str) -> bool {
+    cmd.starts_with("iptables ") || cmd.starts_with("ip6tables ")
+}
+
+/// 清空规则前保存原有规则：(保存命令, 恢复命令, 快照文件名，位于存储目录下)
+pub const RULESET_SNAPSHOTS: &[(&str, &str, &str)] = &[
+    ("iptables-save", "iptables-restore", "iptables.rules"),
+    ("ip6tables-save", "ip6tables-restore", "ip6tables.rules"),
+];
+/// 快照大小上限；超过时不保存（截断的规则无法恢复）
+pub const MAX_SNAPSHOT_BYTES: usize = 64 * 1024;
+
+/// 清空规则前用 iptables-save/ip6tables-save 保存原有规则，并记录各表的规则数。
+/// 已有快照时保留不动（之前的运行可能已经清空过，这时的规则不再是原有规则），
+/// 要重新保存时先删除快照文件。没有保存命令（精简固件）或规则过大时跳过并告警
+pub fn snapshot_firewall(sys: &mut impl SystemOps, dir: &Path, is_prod: bool) {
+    for (save, _, file) in RULESET_SNAPSHOTS {
+        let path = dir.join(file);
+        if path.is_file() {
+            log_message(
+                &format!("Keeping existing {} snapshot {}", save, path.display()),
+                is_prod,
+            );
+            continue;
+        }
+        let rules = match sys.run_command_output_limit(save, MAX_SNAPSHOT_BYTES) {
+            Ok(output) if output.status.success() => output.stdout,
+            Ok(output) => {
+                log_warn(
+                    &format!(
+                        "{} failed ({}), skipping ruleset snapshot",
+                        save,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ),
+                    is_prod,
+                );
+                continue;
+            }
+            Err(e) => {
+                log_warn(
+                    &format!("{} unavailable ({}), skipping ruleset snapshot", save, e),
+                    is_prod,
+                );
+                continue;
+            }
+        };
+        if rules.len() > MAX_SNAPSHOT_BYTES {
+            log_warn(
+                &format!(
+                    "{} output exceeds {} bytes, too large to snapshot",
+                    save, MAX_SNAPSHOT_BYTES
+                ),
+                is_prod,
+            );
+            continue;
+        }
+        let digest = rule_digest(&String::from_utf8_lossy(&rules));
+        match fs::write(&path, &rules) {
+            Ok(()) => log_message(
+                &format!(
+                    "Saved {} rules before flush to {}: {}",
+                    save,
+                    path.display(),
+                    digest
+                ),
+                is_prod,
+            ),
+            Err(e) => log_warn(
+                &format!(
+                    "Failed to save {} snapshot to {}: {}",
+                    save,
+                    path.display(),
+                    e
+                ),
+                is_prod,
+            ),
+        }
+    }
+}
+
+/// RESTORE_FIREWALL：用 iptables-restore/ip6tables-restore 重新应用清空前保存的规则
+pub fn restore_firewall(
+    sys: &mut impl SystemOps,
+    dir: &Path,
+    is_prod: bool,
+) -> Result<String, String> {
+    let mut restored = Vec::new();
+    for (_, restore, file) in RULESET_SNAPSHOTS {
+        let path = dir.join(file);
+        if !path.is_file() {
+            continue;
+        }
+        let cmd = format!("{} < {}", restore, shell_quote(&path.to_string_lossy()));
+        match sys.run_command_output(&cmd) {
+            Ok(output) if output.status.success() => {
+                log_message(
+                    &format!("Restored firewall rules from {}", path.display()),
+                    is_prod,
+                );
+                restored.push(*file);
+            }
+            Ok(output) => {
+                return Err(format!(
+                    "{} failed: {}",
+                    restore,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+            Err(e) => return Err(format!("{} failed: {}", restore, e)),
+        }
+    }
+    if restored.is_empty() {
+        return Err("no saved ruleset".to_string());
+    }
+    Ok(restored.join(","))
+}
+
+/// 单引号包起来交给 sh -c，路径中的单引号写成 '\''
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// iptables-save 输出中每个表的规则数：`filter=12 nat=3`
+pub fn rule_digest(save: &str) -> String {
+    let mut tables: Vec<(&str, usize)> = Vec::new();
+    for line in save.lines() {
+        if let Some(table) = line.strip_prefix('*') {
+            tables.push((table.trim(), 0));
+        } else if line.starts_with("-A ") {
+            if let Some((_, count)) = tables.last_mut() {
+                *count += 1;
+            }
+        }
+    }
+    if tables.is_empty() {
+        return "empty".to_string();
+    }
+    tables
+        .iter()
+        .map(|(table, count)| format!("{}={}", table, count))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 安装 NAT（NETMAP）规则的命令
+fn is_nat_rule(cmd: &str) -> bool {
+    cmd.contains(" -I POSTROUTING ") && cmd.contains("NETMAP")
+}
+
+/// iptables 命令的执行结果：NAT 规则是否装上，以及一次性的失败通知
+pub struct IptablesHealth {
+    pub variant: Variant,
+    /// 最近一次安装 NAT 规则的结果，还没安装过为 None
+    nat_installed: Option<bool>,
+    failed: Vec<String>,
+    notified: bool,
+}
+
+impl IptablesHealth {
+    pub fn new(variant: Variant) -> Self {
+        IptablesHealth {
+            variant,
+            nat_installed: None,
+            failed: Vec::new(),
+            notified: false,
+        }
+    }
+
+    /// 执行一条 iptables 命令并记录结果；失败在所有模式下都告警（附带 stderr 和 iptables 类型提示）
+    pub fn run(&mut self, sys: &mut impl SystemOps, cmd: &str, is_prod: bool) -> bool {
+        let error = match sys.run_command_output(cmd) {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                Some(if stderr.is_empty() {
+                    output.status.to_string()
+                } else {
+                    stderr
+                })
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(error) = &error {
+            log_warn(
+                &format!(
+                    "iptables command failed: {}: {} ({})",
+                    cmd,
+                    error,
+                    self.variant.hint()
+                ),
+                is_prod,
+            );
+        }
+        self.record(cmd, error.is_none());
+        error.is_none()
+    }
+
+    fn record(&mut self, cmd: &str, ok: bool) {
+        if is_nat_rule(cmd) {
+            self.nat_installed = Some(ok);
+        }
+        if !ok && !self.failed.iter().any(|failed| failed == cmd) {
+            self.failed.push(cmd.to_string());
+        }
+    }
+
+    /// 第一次出现失败时返回 IPTABLES_FAILED 通知内容，之后不再返回。
+    /// RULES 为失败的命令（`|` 分隔），放在最后以便包含空格
+    pub fn take_notification(&mut self) -> Option<String> {
+        if self.notified || self.failed.is_empty() {
+            return None;
+        }
+        self.notified = true;
+        Some(format!(
+            "IPTABLES_FAILED: VARIANT={} FAILED={} RULES={}",
+            self.variant.name(),
+            self.failed.len(),
+            self.failed.join("|")
+        ))
+    }
+
+    /// STATUS 中的一行：`iptables=nft nat_installed=yes`
+    pub fn status_line(&self) -> String {
+        format!(
+            "iptables={} nat_installed={}",
+            self.variant.name(),
+            match self.nat_installed {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "-",
+            }
+        )
+    }
+}
+
+/// br0 网段经 WAN 出去的 MASQUERADE 规则（action 为 -A/-C/-D）
+fn masquerade_rule(action: &str, network: &str) -> String {
+    format!(
+        "iptables -t nat {} POSTROUTING -s {} -o {} -j MASQUERADE",
+        action, network, WAN_INTERFACE
+    )
+}
+
+/// br0 网段的 MASQUERADE 规则：记录最近一次装上的网段，网段变化（DHCP 重新分配）时
+/// 先装新规则再删掉旧规则，避免 NAT 停留在旧网段
+#[derive(Default)]
+pub struct LanMasquerade {
+    applied: Option<String>,
+}
+
+impl LanMasquerade {
+    /// 最近一次装上规则的网段
+    pub fn applied(&self) -> Option<&str> {
+        self.applied.as_deref()
+    }
+
+    /// nat 表被清空后规则已不在，下次 sync 重新安装（不删除旧规则）
+    pub fn forget(&mut self) {
+        self.applied = None;
+    }
+
+    /// 按当前网段安装规则；已有同样的规则（如上次运行装上的）时不重复添加。返回规则是否在位
+    pub fn sync(
+        &mut self,
+        sys: &mut impl SystemOps,
+        health: &mut IptablesHealth,
+        network: &str,
+        is_prod: bool,
+    ) -> bool {
+        if self.applied.as_deref() == Some(network) {
+            return true;
+        }
+        let exists = sys
+            .run_command(&masquerade_rule("-C", network))
+            .is_ok_and(|status| status.success());
+        if !exists && !health.run(sys, &masquerade_rule("-A", network), is_prod) {
+            return false;
+        }
+        if let Some(old) = self.applied.replace(network.to_string()) {
+            if health.run(sys, &masquerade_rule("-D", &old), is_prod) {
+                log_message(
+                    &format!("LAN MASQUERADE moved from {} to {}", old, network),
+                    is_prod,
+                );
+            }
+        } else {
+            log_message(
+                &format!("LAN MASQUERADE installed for {}", network),
+                is_prod,
+            );
+        }
+        true
+    }
+}
+
+/// 目标主机经 WAN 出去时映射到 WAN 地址的 NETMAP 规则（action 为 -I/-C/-D）
+fn netmap_rule(action: &str, source: &str, wan_ip: &str) -> String {
+    format!(
+        "iptables -t nat {} POSTROUTING -s {}/32 -o {} -j NETMAP --to {}",
+        action, source, WAN_INTERFACE, wan_ip
+    )
+}
+
+/// 从 `iptables -t nat -S POSTROUTING` 的输出中找出 source 的 NETMAP 规则映射到的地址
+fn netmap_targets(rules: &str, source: &str) -> Vec<String> {
+    let source = format!("{}/32", source);
+    rules
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let value = |flag: &str| {
+                let at = fields.iter().position(|field| *field == flag)?;
+                fields.get(at + 1).copied()
+            };
+            (value("-s") == Some(source.as_str())
+                && value("-o") == Some(WAN_INTERFACE)
+                && value("-j") == Some("NETMAP"))
+            .then(|| value("--to"))
+            .flatten()
+            .map(|to| to.trim_end_matches("/32").to_string())
+        })
+        .collect()
+}
+
+/// 目标主机的 NETMAP 规则：记录最近一次装上的 WAN 地址，已有同样的规则时不重复添加，
+/// WAN 地址变化时先装新规则再删掉旧规则。刚启动还没有记录时，删除上次运行留下的、
+/// 指向其他地址或重复的规则（不清空 nat 表时它们会一直留着）
+#[derive(Default)]
+pub struct WanNetmap {
+    applied: Option<String>,
+}
+
+impl WanNetmap {
+    /// nat 表被清空后规则已不在，下次 sync 重新安装
+    pub fn forget(&mut self) {
+        self.applied = None;
+    }
+
+    /// 按当前 WAN 地址安装规则，返回规则是否在位
+    pub fn sync(
+        &mut self,
+        sys: &mut impl SystemOps,
+        health: &mut IptablesHealth,
+        source: &str,
+        wan_ip: &str,
+        is_prod: bool,
+    ) -> bool {
+        if self.applied.as_deref() == Some(wan_ip) {
+            return true;
+        }
+        let exists = sys
+            .run_command(&netmap_rule("-C", source, wan_ip))
+            .is_ok_and(|status| status.success());
+        if !exists {
+            if !health.run(sys, &netmap_rule("-I", source, wan_ip), is_prod) {
+                return false;
+            }
+            log_message(
+                &format!("SNAT rule added: {} -> {}", source, wan_ip),
+                is_prod,
+            );
+        }
+        let stale = match self.applied.replace(wan_ip.to_string()) {
+            Some(old) => vec![old],
+            None => {
+                let mut stale = sys
+                    .run_command_output("iptables -t nat -S POSTROUTING")
+                    .map(|output| netmap_targets(&String::from_utf8_lossy(&output.stdout), source))
+                    .unwrap_or_default();
+                // 保留一条当前地址的规则
+                if let Some(current) = stale.iter().position(|ip| ip == wan_ip) {
+                    stale.remove(current);
+                }
+                stale
+            }
+        };
+        for old in stale {
+            if health.run(sys, &netmap_rule("-D", source, &old), is_prod) {
+                log_message(
+                    &format!("Old SNAT rule deleted: {} -> {}", source, old),
+                    is_prod,
+                );
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("iptables v1.8.7 (nf_tables)\n"),
+            Some(Variant::Nft)
+        );
+        assert_eq!(
+            parse_version("iptables v1.8.7 (legacy)\n"),
+            Some(Variant::Legacy)
+        );
+        assert_eq!(parse_version("iptables v1.4.21\n"), Some(Variant::Legacy));
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("sh: iptables: not found"), None);
+    }
+
+    #[test]
+    fn test_iptables_health() {
+        let nat =
+            "iptables -t nat -I POSTROUTING -s 192.168.0.2/32 -o wan1 -j NETMAP --to 10.0.0.2";
+        let mut health = IptablesHealth::new(Variant::Nft);
+        assert_eq!(health.status_line(), "iptables=nft nat_installed=-");
+        health.record("iptables -F -t nat", true);
+        assert_eq!(health.take_notification(), None);
+
+        health.record(nat, false);
+        health.record(nat, false);
+        health.record("ip6tables -F", false);
+        assert_eq!(health.status_line(), "iptables=nft nat_installed=no");
+        assert_eq!(
+            health.take_notification().unwrap(),
+            format!(
+                "IPTABLES_FAILED: VARIANT=nft FAILED=2 RULES={}|ip6tables -F",
+                nat
+            )
+        );
+        // 只通知一次
+        health.record("iptables -P INPUT ACCEPT", false);
+        assert_eq!(health.take_notification(), None);
+
+        health.record(nat, true);
+        assert_eq!(health.status_line(), "iptables=nft nat_installed=yes");
+        assert!(is_iptables_cmd(nat));
+        assert!(!is_iptables_cmd("ifconfig wan1 txqueuelen 100"));
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        use crate::system::MockSystem;
+
+        let dir = std::env::temp_dir().join(format!("zxping-fw '{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut sys = MockSystem::default();
+        assert_eq!(
+            restore_firewall(&mut sys, &dir, true),
+            Err("no saved ruleset".to_string())
+        );
+
+        let rules = "*filter\n-A INPUT -j DROP\nCOMMIT\n";
+        sys.outputs = vec![
+            ("iptables-save".to_string(), rules.to_string()),
+            // ip6tables 的规则超过上限：不保存
+            (
+                "ip6tables-save".to_string(),
+                "x".repeat(MAX_SNAPSHOT_BYTES + 100),
+            ),
+        ];
+        snapshot_firewall(&mut sys, &dir, true);
+        assert_eq!(sys.commands, ["iptables-save", "ip6tables-save"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("iptables.rules")).unwrap(),
+            rules
+        );
+        assert!(!dir.join("ip6tables.rules").exists());
+
+        // 已有快照时不再覆盖（这时的规则可能已经被清空过）
+        sys.outputs[0].1 = "*filter\nCOMMIT\n".to_string();
+        sys.commands.clear();
+        snapshot_firewall(&mut sys, &dir, true);
+        assert_eq!(sys.commands, ["ip6tables-save"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("iptables.rules")).unwrap(),
+            rules
+        );
+
+        // 路径带空格和单引号时也能正确传给 sh
+        sys.commands.clear();
+        assert_eq!(
+            restore_firewall(&mut sys, &dir, true),
+            Ok("iptables.rules".to_string())
+        );
+        assert_eq!(
+            sys.commands,
+            [format!(
+                "iptables-restore < '{}'",
+                dir.join("iptables.rules")
+                    .to_string_lossy()
+                    .replace('\'', "'\\''")
+            )]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lan_masquerade() {
+        use crate::system::MockSystem;
+
+        let mut sys = MockSystem::default();
+        let mut health = IptablesHealth::new(Variant::Legacy);
+        let mut masquerade = LanMasquerade::default();
+        // 上次运行没有留下规则：-C 失败后添加
+        sys.failing = vec![masquerade_rule("-C", "192.168.0.0/24")];
+        assert!(masquerade.sync(&mut sys, &mut health, "192.168.0.0/24", true));
+        assert_eq!(
+            sys.commands,
+            [
+                "iptables -t nat -C POSTROUTING -s 192.168.0.0/24 -o wan1 -j MASQUERADE",
+                "iptables -t nat -A POSTROUTING -s 192.168.0.0/24 -o wan1 -j MASQUERADE",
+            ]
+        );
+        assert_eq!(masquerade.applied(), Some("192.168.0.0/24"));
+
+        // 网段不变时不执行命令
+        sys.commands.clear();
+        assert!(masquerade.sync(&mut sys, &mut health, "192.168.0.0/24", true));
+        assert!(sys.commands.is_empty());
+
+        // 网段变化：先装新规则，再删旧规则
+        sys.failing = vec![masquerade_rule("-C", "10.0.0.0/24")];
+        assert!(masquerade.sync(&mut sys, &mut health, "10.0.0.0/24", true));
+        assert_eq!(
+            sys.commands,
+            [
+                "iptables -t nat -C POSTROUTING -s 10.0.0.0/24 -o wan1 -j MASQUERADE",
+                "iptables -t nat -A POSTROUTING -s 10.0.0.0/24 -o wan1 -j MASQUERADE",
+                "iptables -t nat -D POSTROUTING -s 192.168.0.0/24 -o wan1 -j MASQUERADE",
+            ]
+        );
+        assert_eq!(masquerade.applied(), Some("10.0.0.0/24"));
+
+        // 添加失败时保留旧规则和记录
+        sys.commands.clear();
+        sys.failing = vec!["iptables -t nat -".to_string()];
+        assert!(!masquerade.sync(&mut sys, &mut health, "172.16.0.0/16", true));
+        assert_eq!(sys.commands.len(), 2);
+        assert_eq!(masquerade.applied(), Some("10.0.0.0/24"));
+
+        // 已有同样的规则（上次运行装上的）：不重复添加；清空 nat 表后重新安装
+        sys.commands.clear();
+        sys.failing.clear();
+        masquerade.forget();
+        assert!(masquerade.sync(&mut sys, &mut health, "10.0.0.0/24", true));
+        assert_eq!(
+            sys.commands,
+            ["iptables -t nat -C POSTROUTING -s 10.0.0.0/24 -o wan1 -j MASQUERADE"]
+        );
+    }
+
+    #[test]
+    fn test_wan_netmap() {
+        use crate::system::MockSystem;
+
+        let rules = "-P POSTROUTING ACCEPT\n\
+                     -A POSTROUTING -s 192.168.0.0/24 -o wan1 -j MASQUERADE\n\
+                     -A POSTROUTING -s 192.168.0.2/32 -o wan1 -j NETMAP --to 10.1.1.1/32\n\
+                     -A POSTROUTING -s 192.168.0.2/32 -o wan1 -j NETMAP --to 10.2.2.2/32\n\
+                     -A POSTROUTING -s 192.168.0.2/32 -o wan1 -j NETMAP --to 10.2.2.2/32\n\
+                     -A POSTROUTING -s 192.168.0.3/32 -o wan1 -j NETMAP --to 10.1.1.1/32\n";
+        assert_eq!(
+            netmap_targets(rules, "192.168.0.2"),
+            ["10.1.1.1", "10.2.2.2", "10.2.2.2"]
+        );
+
+        let mut sys = MockSystem::default();
+        let mut health = IptablesHealth::new(Variant::Legacy);
+        let mut netmap = WanNetmap::default();
+        // 上次运行留下了当前地址的规则（重复两条）和旧地址的规则：不再添加，删掉多余的
+        sys.outputs = vec![("iptables -t nat -S".to_string(), rules.to_string())];
+        assert!(netmap.sync(&mut sys, &mut health, "192.168.0.2", "10.2.2.2", true));
+        assert_eq!(
+            sys.commands,
+            [
+                netmap_rule("-C", "192.168.0.2", "10.2.2.2"),
+                "iptables -t nat -S POSTROUTING".to_string(),
+                netmap_rule("-D", "192.168.0.2", "10.1.1.1"),
+                netmap_rule("-D", "192.168.0.2", "10.2.2.2"),
+            ]
+        );
+
+        // 地址不变时不执行命令
+        sys.commands.clear();
+        assert!(netmap.sync(&mut sys, &mut health, "192.168.0.2", "10.2.2.2", true));
+        assert!(sys.commands.is_empty());
+
+        // 地址变化：先装新规则，再删旧规则
+        sys.failing = vec![netmap_rule("-C", "192.168.0.2", "10.3.3.3")];
+        assert!(netmap.sync(&mut sys, &mut health, "192.168.0.2", "10.3.3.3", true));
+        assert_eq!(
+            sys.commands,
+            [
+                netmap_rule("-C", "192.168.0.2", "10.3.3.3"),
+                netmap_rule("-I", "192.168.0.2", "10.3.3.3"),
+                netmap_rule("-D", "192.168.0.2", "10.2.2.2"),
+            ]
+        );
+
+        // 添加失败时保留旧规则和记录
+        sys.commands.clear();
+        sys.failing = vec!["iptables -t nat -".to_string()];
+        assert!(!netmap.sync(&mut sys, &mut health, "192.168.0.2", "10.4.4.4", true));
+        assert_eq!(sys.commands.len(), 2);
+        sys.failing.clear();
+        sys.commands.clear();
+        assert!(netmap.sync(&mut sys, &mut health, "192.168.0.2", "10.3.3.3", true));
+        assert!(sys.commands.is_empty());
+    }
+
+    #[test]
+    fn test_rule_digest() {
+        let save = "# Generated by iptables-save v1.8.7\n\
+                    *filter\n\
+                    :INPUT ACCEPT [0:0]\n\
+                    -A INPUT -i wan1 -p tcp --dport 80 -j DROP\n\
+                    -A FORWARD -j ACCEPT\n\
+                    COMMIT\n\
+                    *nat\n\
+                    :POSTROUTING ACCEPT [0:0]\n\
+                    -A POSTROUTING -o wan1 -j MASQUERADE\n\
+                    COMMIT\n\
+                    *mangle\n\
+                    COMMIT\n";
+        assert_eq!(rule_digest(save), "filter=2 nat=1 mangle=0");
+        assert_eq!(rule_digest(""), "empty");
+    }
+}
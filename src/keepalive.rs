@@ -0,0 +1,131 @@
+use std::io::{self, ErrorKind, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+
+use crate::sockmark;
+
+/// 连接空闲多久后开始发送 keepalive 探测
+const KEEPALIVE_IDLE: Duration = Duration::from_secs(10);
+/// 探测间隔
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+/// 连续多少个探测没有应答时内核断开连接（约 25 秒发现断线）
+const KEEPALIVE_RETRIES: u32 = 3;
+/// 每轮最多读取丢弃的次数（目标主动发数据时避免一直读）
+const MAX_DRAIN_READS: usize = 16;
+
+/// 与目标保持一条开启 TCP keepalive 的长连接，断线由内核探测发现，
+/// 比每次检查都重新握手更快、更轻
+pub struct KeepaliveLink {
+    target: SocketAddr,
+    stream: Option<TcpStream>,
+}
+
+impl KeepaliveLink {
+    pub fn new(target: SocketAddr) -> Self {
+        KeepaliveLink {
+            target,
+            stream: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// 主循环每轮调用（不阻塞）：连接被 keepalive 判定断开或被对端关闭时丢弃连接并返回原因
+    pub fn poll_break(&mut self) -> Option<String> {
+        let stream = self.stream.as_mut()?;
+        let broken = match stream.take_error() {
+            Ok(Some(e)) | Err(e) => Some(e.to_string()),
+            Ok(None) => drain(stream),
+        };
+        if broken.is_some() {
+            self.stream = None;
+        }
+        broken
+    }
+
+    /// 定期检查：连接还在时返回 None（空闲连接上内核的 RTT 是最后一次有数据往来时的旧值，
+    /// 不能当作这次检查的延迟），否则重新连接并返回连接耗时
+    pub fn check(&mut self, timeout: Duration) -> io::Result<Option<Duration>> {
+        if self.stream.is_some() {
+            return Ok(None);
+        }
+        let start = Instant::now();
+        let stream = connect(self.target, timeout)?;
+        let elapsed = start.elapsed();
+        self.stream = Some(stream);
+        Ok(Some(elapsed))
+    }
+}
+
+fn connect(target: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    let keepalive = TcpKeepalive::new()
+        .with_time(KEEPALIVE_IDLE)
+        .with_interval(KEEPALIVE_INTERVAL)
+        .with_retries(KEEPALIVE_RETRIES);
+    socket.set_tcp_keepalive(&keepalive)?;
+    sockmark::prepare_probe(socket.as_raw_fd(), target.is_ipv6());
+    socket.connect_timeout(&target.into(), timeout)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// 读掉目标发来的数据；返回 Some 表示连接已断开
+fn drain(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = [0u8; 256];
+    for _ in 0..MAX_DRAIN_READS {
+        match stream.read(&mut buf) {
+            Ok(0) => return Some("closed by peer".to_string()),
+            Ok(_) => continue,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return None,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Some(e.to_string()),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_keepalive_link() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut link = KeepaliveLink::new(listener.local_addr().unwrap());
+        assert_eq!(link.poll_break(), None);
+
+        assert!(link.check(Duration::from_secs(1)).unwrap().is_some());
+        let (server, _) = listener.accept().unwrap();
+        assert!(link.is_connected());
+        assert_eq!(link.poll_break(), None);
+        // 连接还在时不重新握手，也不报告 RTT
+        assert_eq!(link.check(Duration::from_secs(1)).unwrap(), None);
+        listener.set_nonblocking(true).unwrap();
+        assert!(listener.accept().is_err());
+
+        // 对端关闭后下一轮发现，检查时重新连接
+        drop(server);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(link.poll_break().as_deref(), Some("closed by peer"));
+        assert!(!link.is_connected());
+        link.check(Duration::from_secs(1)).unwrap();
+        assert!(link.is_connected());
+
+        // 目标不在监听时连接失败
+        drop(listener);
+        let mut link = KeepaliveLink::new("127.0.0.1:1".parse().unwrap());
+        assert!(link.check(Duration::from_secs(1)).is_err());
+        assert!(!link.is_connected());
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+use std::path::Path;
+
+/// 未配置 dhcp_leases_file 时依次查找的租约文件
+pub const LEASE_FILES: &[&str] = &[
+    "/var/lib/misc/dnsmasq.leases",
+    "/tmp/dnsmasq.leases",
+    "/etc_rw/dnsmasq.leases",
+    "/var/lib/dnsmasq/dnsmasq.leases",
+    "/var/lib/misc/udhcpd.leases",
+    "/etc_rw/udhcpd.leases",
+];
+
+/// busybox udhcpd 租约文件：开头 8 字节的写入时间，之后每条 36 字节
+const UDHCPD_HEADER_LEN: usize = 8;
+const UDHCPD_RECORD_LEN: usize = 36;
+
+/// 统计未过期的租约数（now 为 UNIX 秒数）。支持 dnsmasq 的文本格式和 busybox udhcpd 的二进制格式，
+/// 都不是时返回 None
+pub fn count_active(content: &[u8], now: u64) -> Option<usize> {
+    // 二进制文件也可能恰好是合法的 UTF-8，文本解析失败时再按二进制解析
+    std::str::from_utf8(content)
+        .ok()
+        .and_then(|text| count_dnsmasq(text, now))
+        .or_else(|| count_udhcpd(content, now))
+}
+
+/// `<到期时间> <MAC> <IP> <主机名> <client-id>`，到期时间为 0 表示不过期。
+/// `duid` 行（DHCPv6）和无法解析的行跳过；一行都认不出时返回 None
+fn count_dnsmasq(text: &str, now: u64) -> Option<usize> {
+    let mut active = 0;
+    let mut recognized = false;
+    let mut unrecognized = false;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let mut fields = line.split_whitespace();
+        let first = fields.next().unwrap_or_default();
+        if first == "duid" {
+            recognized = true;
+            continue;
+        }
+        match (first.parse::<u64>(), fields.nth(1)) {
+            (Ok(expires), Some(_)) => {
+                recognized = true;
+                if expires == 0 || expires > now {
+                    active += 1;
+                }
+            }
+            _ => unrecognized = true,
+        }
+    }
+    (recognized || !unrecognized).then_some(active)
+}
+
+/// 写入时间（i64 大端）之后每条为：剩余秒数（u32 大端，相对写入时间）、IP(4)、MAC(6)、主机名(20)、填充(2)
+fn count_udhcpd(blob: &[u8], now: u64) -> Option<usize> {
+    if blob.len() < UDHCPD_HEADER_LEN
+        || !(blob.len() - UDHCPD_HEADER_LEN).is_multiple_of(UDHCPD_RECORD_LEN)
+    {
+        return None;
+    }
+    let written = i64::from_be_bytes(blob[..UDHCPD_HEADER_LEN].try_into().ok()?);
+    let written = u64::try_from(written).ok()?;
+    let active = blob[UDHCPD_HEADER_LEN..]
+        .chunks_exact(UDHCPD_RECORD_LEN)
+        .filter(|record| {
+            let remaining = u32::from_be_bytes([record[0], record[1], record[2], record[3]]);
+            record[4..8] != [0; 4] && written + remaining as u64 > now
+        })
+        .count();
+    Some(active)
+}
+
+/// 按周期读取租约文件，记录当前和当天最多的客户端数
+pub struct LeaseWatch {
+    /// 配置的租约文件，空为自动查找
+    configured: String,
+    /// 最近一次读到的文件
+    pub source: Option<String>,
+    /// 最近一次的活跃租约数，文件不存在或无法解析时为 None
+    pub clients: Option<usize>,
+    /// 本周期（每天）内最多的客户端数
+    peak: Option<usize>,
+}
+
+impl LeaseWatch {
+    pub fn new(configured: &str) -> Self {
+        LeaseWatch {
+            configured: configured.to_string(),
+            source: None,
+            clients: None,
+            peak: None,
+        }
+    }
+
+    /// 读取并统计租约文件；失败时记为未知（不记录日志）
+    pub fn read(&mut self, now: u64) -> Option<usize> {
+        let candidates: Vec<&str> = if self.configured.is_empty() {
+            LEASE_FILES.to_vec()
+        } else {
+            vec![self.configured.as_str()]
+        };
+        let found = candidates.into_iter().find_map(|path| {
+            fs::read(Path::new(path))
+                .ok()
+                .map(|content| (path, content))
+        });
+        self.source = found.as_ref().map(|(path, _)| path.to_string());
+        let count = found.and_then(|(_, content)| count_active(&content, now));
+        self.record(count)
+    }
+
+    /// 记录一次统计结果；从有客户端变为 0 时返回之前的数量
+    pub fn record(&mut self, count: Option<usize>) -> Option<usize> {
+        let previous = self.clients;
+        self.clients = count;
+        if let Some(count) = count {
+            self.peak = Some(self.peak.map_or(count, |peak| peak.max(count)));
+        }
+        match (previous, count) {
+            (Some(was), Some(0)) if was > 0 => Some(was),
+            _ => None,
+        }
+    }
+
+    /// 取出本周期的最大客户端数并重新开始统计
+    pub fn take_peak(&mut self) -> Option<usize> {
+        self.peak.take()
+    }
+
+    pub fn clients_str(&self) -> String {
+        self.clients
+            .map(|count| count.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// STATUS 中的一行：`dhcp_clients=3 lease_file=/var/lib/misc/dnsmasq.leases`
+    pub fn status_line(&self) -> String {
+        format!(
+            "dhcp_clients={} lease_file={}",
+            self.clients_str(),
+            self.source.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dnsmasq_leases() {
+        let leases = "1700000600 aa:bb:cc:dd:ee:01 192.168.0.100 phone 01:aa:bb:cc:dd:ee:01\n\
+                      1699999000 aa:bb:cc:dd:ee:02 192.168.0.101 * *\n\
+                      0 aa:bb:cc:dd:ee:03 192.168.0.102 printer *\n";
+        assert_eq!(count_active(leases.as_bytes(), 1_700_000_000), Some(2));
+        assert_eq!(count_active(b"", 1_700_000_000), Some(0));
+        assert_eq!(count_active(b"\n", 1_700_000_000), Some(0));
+        assert_eq!(count_active(b"not a lease file\n", 1_700_000_000), None);
+        assert_eq!(count_active(b"1700000600 aa:bb\n", 1_700_000_000), None);
+        // duid 行和个别损坏的行不影响其余的租约
+        let leases = format!(
+            "duid 00:01:00:01:2c:aa:bb:cc:dd:ee:ff:00\n{}garbage\n",
+            leases
+        );
+        assert_eq!(count_active(leases.as_bytes(), 1_700_000_000), Some(2));
+    }
+
+    fn udhcpd_record(remaining: u32, ip: [u8; 4]) -> Vec<u8> {
+        let mut record = remaining.to_be_bytes().to_vec();
+        record.extend_from_slice(&ip);
+        record.extend_from_slice(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01]);
+        record.extend_from_slice(&[0u8; 22]);
+        record
+    }
+
+    #[test]
+    fn test_udhcpd_leases() {
+        let mut blob = 1_700_000_000i64.to_be_bytes().to_vec();
+        blob.extend(udhcpd_record(3600, [192, 168, 0, 100]));
+        blob.extend(udhcpd_record(60, [192, 168, 0, 101]));
+        blob.extend(udhcpd_record(3600, [0, 0, 0, 0]));
+        // 写入 120 秒后：第二条已过期，第三条是空记录
+        assert_eq!(count_active(&blob, 1_700_000_120), Some(1));
+        assert_eq!(count_active(&blob[..blob.len() - 1], 1_700_000_120), None);
+    }
+
+    #[test]
+    fn test_lease_watch() {
+        let mut watch = LeaseWatch::new("/nonexistent/leases");
+        assert_eq!(watch.read(0), None);
+        assert_eq!(watch.status_line(), "dhcp_clients=unknown lease_file=-");
+
+        assert_eq!(watch.record(Some(3)), None);
+        assert_eq!(watch.record(Some(5)), None);
+        // 有客户端变为 0 时报告，未知变为 0 时不报告
+        assert_eq!(watch.record(Some(0)), Some(5));
+        assert_eq!(watch.record(None), None);
+        assert_eq!(watch.record(Some(0)), None);
+        assert_eq!(watch.take_peak(), Some(5));
+        assert_eq!(watch.take_peak(), None);
+        assert_eq!(watch.clients_str(), "0");
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::log_message;
+
+const LED_SYSFS_DIR: &str = "/sys/class/leds";
+
+/// LED 显示模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedPattern {
+    /// 常亮：一切正常
+    Solid,
+    /// 慢闪：连续失败中
+    SlowBlink,
+    /// 快闪：高负载/限流中
+    FastBlink,
+    /// 短闪：即将重启
+    RebootPending,
+}
+
+impl LedPattern {
+    /// timer 触发器的 (delay_on, delay_off) 毫秒，常亮返回 None
+    fn timer_delays(&self) -> Option<(u32, u32)> {
+        match self {
+            LedPattern::Solid => None,
+            LedPattern::SlowBlink => Some((1000, 1000)),
+            LedPattern::FastBlink => Some((150, 150)),
+            LedPattern::RebootPending => Some((50, 950)),
+        }
+    }
+}
+
+/// /sys/class/leds 下的单个 LED，所有写入失败都忽略
+pub struct Led {
+    dir: PathBuf,
+    current: Option<LedPattern>,
+    original_trigger: Option<String>,
+    original_brightness: Option<String>,
+    /// 原来就是 timer 触发器时的 (delay_on, delay_off)
+    original_delays: Option<(String, String)>,
+}
+
+impl Led {
+    /// 打开 LED 并记录原始 trigger/brightness（timer 触发器还有闪烁间隔），LED 不存在时返回 None
+    pub fn open(name: &str, is_prod: bool) -> Option<Led> {
+        let dir = PathBuf::from(LED_SYSFS_DIR).join(name);
+        if !dir.is_dir() {
+            log_message(
+                &format!("LED {} not found, LED signaling disabled", name),
+                is_prod,
+            );
+            return None;
+        }
+
+        let original_trigger = fs::read_to_string(dir.join("trigger"))
+            .ok()
+            .and_then(|content| selected_trigger(&content));
+        let original_brightness = fs::read_to_string(dir.join("brightness"))
+            .ok()
+            .map(|s| s.trim().to_string());
+        // delay_on/delay_off 只在 timer 触发器下存在
+        let read_delay = |file: &str| {
+            fs::read_to_string(dir.join(file))
+                .ok()
+                .map(|s| s.trim().to_string())
+        };
+        let original_delays = match original_trigger.as_deref() {
+            Some("timer") => read_delay("delay_on").zip(read_delay("delay_off")),
+            _ => None,
+        };
+
+        Some(Led {
+            dir,
+            current: None,
+            original_trigger,
+            original_brightness,
+            original_delays,
+        })
+    }
+
+    /// 切换显示模式，模式未变化时不写 sysfs
+    pub fn set(&mut self, pattern: LedPattern) {
+        if self.current == Some(pattern) {
+            return;
+        }
+        self.current = Some(pattern);
+
+        match pattern.timer_delays() {
+            Some((delay_on, delay_off)) => {
+                self.write("trigger", "timer");
+                self.write("delay_on", &delay_on.to_string());
+                self.write("delay_off", &delay_off.to_string());
+            }
+            None => {
+                self.write("trigger", "none");
+                let max = fs::read_to_string(self.dir.join("max_brightness"))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_else(|_| "1".to_string());
+                self.write("brightness", &max);
+            }
+        }
+    }
+
+    /// 恢复启动时的 trigger、brightness 和闪烁间隔
+    pub fn restore(&self) {
+        if let Some(trigger) = &self.original_trigger {
+            self.write("trigger", trigger);
+        }
+        // 切换到 timer 时内核会重置间隔，需要在 trigger 之后写
+        if let Some((delay_on, delay_off)) = &self.original_delays {
+            self.write("delay_on", delay_on);
+            self.write("delay_off", delay_off);
+        }
+        if self.original_trigger.as_deref() == Some("none") {
+            if let Some(brightness) = &self.original_brightness {
+                self.write("brightness", brightness);
+            }
+        }
+    }
+
+    fn write(&self, file: &str, value: &str) {
+        let _ = fs::write(self.dir.join(file), value.as_bytes());
+    }
+}
+
+/// 解析 trigger 文件中被 [] 选中的项，如 "none [timer] heartbeat" -> "timer"
+fn selected_trigger(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .find(|t| t.starts_with('[') && t.ends_with(']'))
+        .map(|t| t.trim_matches(|c| c == '[' || c == ']').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selected_trigger() {
+        assert_eq!(
+            selected_trigger("none [timer] heartbeat\n").as_deref(),
+            Some("timer")
+        );
+        assert_eq!(
+            selected_trigger("[none] timer heartbeat").as_deref(),
+            Some("none")
+        );
+        assert_eq!(selected_trigger("none timer heartbeat"), None);
+        assert_eq!(selected_trigger(""), None);
+    }
+
+    #[test]
+    fn test_restore_timer_delays() {
+        let dir = std::env::temp_dir().join(format!("zxic_led_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("trigger"), "none [timer] heartbeat\n").unwrap();
+        fs::write(dir.join("delay_on"), "500\n").unwrap();
+        fs::write(dir.join("delay_off"), "250\n").unwrap();
+        let mut led = Led::open(dir.to_str().unwrap(), true).unwrap();
+
+        led.set(LedPattern::FastBlink);
+        assert_eq!(fs::read_to_string(dir.join("delay_on")).unwrap(), "150");
+        led.restore();
+        assert_eq!(fs::read_to_string(dir.join("trigger")).unwrap(), "timer");
+        assert_eq!(fs::read_to_string(dir.join("delay_on")).unwrap(), "500");
+        assert_eq!(fs::read_to_string(dir.join("delay_off")).unwrap(), "250");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
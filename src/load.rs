@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// 高负载状态变化（每次 CPU 采样后产生）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadEvent {
+    /// 进入高负载
+    Enter(f32),
+    /// 持续高负载中的定期报告
+    Update(f32),
+    /// 退出高负载，附带持续时长
+    Exit(f32, Duration),
+    /// 尚未达到阈值，但占用率快速上升（附带斜率，百分点/分钟）
+    Rising(f32, f32),
+}
+
+/// 计算上升斜率用的最近采样数
+const VELOCITY_SAMPLES: usize = 4;
+
+/// CPU 高负载模式：占用率连续 enter_count 次达到阈值进入，连续 exit_count 次低于阈值退出
+pub struct HighLoad {
+    threshold: f32,
+    /// 只在进入/退出时通知，持续期间最多每 report_interval 报告一次
+    report_on_change: bool,
+    report_interval: Duration,
+    active_since: Option<Instant>,
+    last_report: Option<Instant>,
+    /// 上升斜率阈值（百分点/分钟），0 为关闭
+    velocity_threshold: f32,
+    history: VecDeque<(Instant, f32)>,
+    /// 已因快速上升进入预警（达到阈值或斜率回落后清除）
+    rising: bool,
+    enter_count: u32,
+    exit_count: u32,
+    /// 连续越过阈值（未进入时为达到，进入后为低于）的采样次数
+    crossings: u32,
+}
+
+impl HighLoad {
+    pub fn new(threshold: f32, config: &Config) -> Self {
+        HighLoad {
+            threshold,
+            report_on_change: config.high_load_report_on_change,
+            report_interval: config.high_load_report_interval,
+            active_since: None,
+            last_report: None,
+            velocity_threshold: config.cpu_velocity_threshold,
+            history: VecDeque::with_capacity(VELOCITY_SAMPLES),
+            rising: false,
+            enter_count: config.high_load_enter_count,
+            exit_count: config.high_load_exit_count,
+            crossings: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active_since.is_some()
+    }
+
+    /// 根据最新的 CPU 占用率更新状态，返回需要通知的事件
+    pub fn update(&mut self, usage: f32, now: Instant) -> Option<LoadEvent> {
+        if self.history.len() == VELOCITY_SAMPLES {
+            self.history.pop_front();
+        }
+        self.history.push_back((now, usage));
+
+        let crossed = match self.active_since {
+            None => usage >= self.threshold,
+            Some(_) => usage < self.threshold,
+        };
+        self.crossings = if crossed { self.crossings + 1 } else { 0 };
+
+        match self.active_since {
+            // 达到阈值但还没有确认，等待下一次采样
+            None if crossed && self.crossings < self.enter_count => None,
+            None if crossed => {
+                self.crossings = 0;
+                self.active_since = Some(now);
+                self.last_report = Some(now);
+                self.rising = false;
+                Some(LoadEvent::Enter(usage))
+            }
+            None => {
+                let slope = self.slope().filter(|slope| {
+                    self.velocity_threshold > 0.0 && *slope >= self.velocity_threshold
+                });
+                match slope {
+                    Some(slope) if !self.rising => {
+                        self.rising = true;
+                        Some(LoadEvent::Rising(usage, slope))
+                    }
+                    Some(_) => None,
+                    None => {
+                        self.rising = false;
+                        None
+                    }
+                }
+            }
+            Some(since) if crossed && self.crossings >= self.exit_count => {
+                self.crossings = 0;
+                self.active_since = None;
+                self.last_report = None;
+                Some(LoadEvent::Exit(usage, now.duration_since(since)))
+            }
+            Some(_) => {
+                let due = !self.report_on_change
+                    || self
+                        .last_report
+                        .is_none_or(|last| now.duration_since(last) >= self.report_interval);
+                if due {
+                    self.last_report = Some(now);
+                    Some(LoadEvent::Update(usage))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// 最近几次采样的占用率变化斜率（最小二乘，百分点/分钟），样本不足 3 个时为 None
+    pub fn slope(&self) -> Option<f32> {
+        if self.history.len() < 3 {
+            return None;
+        }
+        let (start, _) = self.history[0];
+        let points: Vec<(f32, f32)> = self
+            .history
+            .iter()
+            .map(|(at, usage)| (at.duration_since(start).as_secs_f32() / 60.0, *usage))
+            .collect();
+        let n = points.len() as f32;
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f32>() / n;
+        let mean_u = points.iter().map(|(_, u)| u).sum::<f32>() / n;
+        let var_t: f32 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+        if var_t == 0.0 {
+            return None;
+        }
+        let cov: f32 = points
+            .iter()
+            .map(|(t, u)| (t - mean_t) * (u - mean_u))
+            .sum();
+        Some(cov / var_t)
+    }
+
+    /// STATUS 中的高负载状态
+    pub fn status_line(&self, now: Instant) -> String {
+        match self.active_since {
+            Some(since) => format!("high_load=yes for {}s", now.duration_since(since).as_secs()),
+            None if self.rising => "high_load=rising".to_string(),
+            None => "high_load=no".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high_load(report_on_change: bool) -> HighLoad {
+        let config = Config {
+            high_load_report_on_change: report_on_change,
+            high_load_report_interval: Duration::from_secs(300),
+            ..Config::default()
+        };
+        HighLoad::new(85.0, &config)
+    }
+
+    #[test]
+    fn test_report_every_check() {
+        let mut load = high_load(false);
+        let start = Instant::now();
+        assert_eq!(load.update(50.0, start), None);
+        assert_eq!(load.update(90.0, start), Some(LoadEvent::Enter(90.0)));
+        assert_eq!(
+            load.update(95.0, start + Duration::from_secs(30)),
+            Some(LoadEvent::Update(95.0))
+        );
+        assert_eq!(
+            load.update(40.0, start + Duration::from_secs(60)),
+            Some(LoadEvent::Exit(40.0, Duration::from_secs(60)))
+        );
+        assert!(!load.is_active());
+    }
+
+    #[test]
+    fn test_velocity_trigger() {
+        let config = Config {
+            cpu_velocity_threshold: 30.0,
+            ..Config::default()
+        };
+        let mut load = HighLoad::new(85.0, &config);
+        let start = Instant::now();
+        let at = |i: u64| start + Duration::from_secs(i * 30);
+        // 每 30 秒上升 10 个百分点 = 20 点/分钟，不触发
+        for (i, usage) in [20.0, 30.0, 40.0].into_iter().enumerate() {
+            assert_eq!(load.update(usage, at(i as u64)), None);
+        }
+        assert_eq!(load.slope(), Some(20.0));
+
+        // 每 30 秒上升 20 个百分点
+        let mut load = HighLoad::new(85.0, &config);
+        assert_eq!(load.update(20.0, at(0)), None);
+        assert_eq!(load.update(40.0, at(1)), None);
+        assert_eq!(
+            load.update(60.0, at(2)),
+            Some(LoadEvent::Rising(60.0, 40.0))
+        );
+        assert_eq!(load.status_line(at(2)), "high_load=rising");
+        // 预警期间不重复通知，达到阈值时正常进入高负载
+        assert_eq!(load.update(80.0, at(3)), None);
+        assert_eq!(load.update(90.0, at(4)), Some(LoadEvent::Enter(90.0)));
+
+        // 默认关闭
+        let mut load = high_load(false);
+        for (i, usage) in [10.0, 40.0, 70.0].into_iter().enumerate() {
+            assert_eq!(load.update(usage, at(i as u64)), None);
+        }
+    }
+
+    #[test]
+    fn test_confirmation_counts() {
+        let config = Config {
+            high_load_enter_count: 3,
+            high_load_exit_count: 2,
+            ..Config::default()
+        };
+        let mut load = HighLoad::new(85.0, &config);
+        let start = Instant::now();
+        let at = |i: u64| start + Duration::from_secs(i * 30);
+        assert_eq!(load.update(90.0, at(0)), None);
+        assert_eq!(load.update(90.0, at(1)), None);
+        // 中间有一次低于阈值，重新计数
+        assert_eq!(load.update(50.0, at(2)), None);
+        assert_eq!(load.update(90.0, at(3)), None);
+        assert_eq!(load.update(90.0, at(4)), None);
+        assert!(!load.is_active());
+        assert_eq!(load.update(91.0, at(5)), Some(LoadEvent::Enter(91.0)));
+
+        // 退出同样需要连续确认，确认期间照常报告
+        assert_eq!(load.update(50.0, at(6)), Some(LoadEvent::Update(50.0)));
+        assert_eq!(load.update(90.0, at(7)), Some(LoadEvent::Update(90.0)));
+        assert_eq!(load.update(50.0, at(8)), Some(LoadEvent::Update(50.0)));
+        assert_eq!(
+            load.update(40.0, at(9)),
+            Some(LoadEvent::Exit(40.0, Duration::from_secs(120)))
+        );
+    }
+
+    #[test]
+    fn test_report_on_change() {
+        let mut load = high_load(true);
+        let start = Instant::now();
+        assert_eq!(load.update(90.0, start), Some(LoadEvent::Enter(90.0)));
+        for i in 1..10 {
+            assert_eq!(load.update(90.0, start + Duration::from_secs(i * 30)), None);
+        }
+        assert_eq!(
+            load.update(92.0, start + Duration::from_secs(300)),
+            Some(LoadEvent::Update(92.0))
+        );
+        assert_eq!(load.update(92.0, start + Duration::from_secs(330)), None);
+        assert!(matches!(
+            load.update(10.0, start + Duration::from_secs(360)),
+            Some(LoadEvent::Exit(..))
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 早于这个时间（2020-01-01）的系统时间视为未同步，锚定模式退回按间隔清理
+const MIN_SANE_UNIX_SECS: i64 = 1_577_836_800;
+
+/// 锚定模式下时钟不可信、且未配置间隔时使用的间隔
+const DEFAULT_PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 解析 `HH:MM`，返回当天的秒数
+pub fn parse_time_of_day(value: &str) -> Result<u32, String> {
+    let parsed = value.split_once(':').and_then(|(h, m)| {
+        let (h, m) = (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?);
+        (h < 24 && m < 60).then_some(h * 3600 + m * 60)
+    });
+    parsed.ok_or_else(|| format!("log_prune_at: expected HH:MM, got '{}'", value))
+}
+
+/// 当天秒数格式化为 `HH:MM`
+pub fn format_time_of_day(secs: u32) -> String {
+    format!("{:02}:{:02}", secs / 3600, secs / 60 % 60)
+}
+
+/// 本地时间 (天序号, 当天秒数)；系统时间明显不对时返回 None
+pub fn local_wall_clock() -> Option<(i64, u32)> {
+    let unix = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    if unix < MIN_SANE_UNIX_SECS {
+        return None;
+    }
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let t = unix as libc::time_t;
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return None;
+    }
+    let local = unix + tm.tm_gmtoff as i64;
+    Some((local.div_euclid(86400), local.rem_euclid(86400) as u32))
+}
+
+/// 日志清理的调度：按固定间隔（从启动算起），或每天在本地时间 `anchor` 之后清理一次
+pub struct PruneSchedule {
+    interval: Option<Duration>,
+    anchor: Option<u32>,
+    last_prune: Instant,
+    /// 锚定模式下最近一次清理（或启动时已过锚点）的天序号
+    last_anchor_day: Option<i64>,
+}
+
+impl PruneSchedule {
+    /// interval 为 0 且没有 anchor 时不清理
+    pub fn new(interval: Duration, anchor: Option<u32>, now: Instant) -> Self {
+        PruneSchedule {
+            interval: (!interval.is_zero()).then_some(interval),
+            anchor,
+            last_prune: now,
+            last_anchor_day: None,
+        }
+    }
+
+    /// 现在是否应该清理；wall 为 local_wall_clock() 的结果。返回 true 时视为已清理
+    pub fn due(&mut self, now: Instant, wall: Option<(i64, u32)>) -> bool {
+        let due = match (self.anchor, wall) {
+            (Some(anchor), Some((day, secs))) => {
+                // 第一次拿到可信时间时已过当天锚点的，当天不再清理（避免启动后立刻清空）
+                let last =
+                    *self
+                        .last_anchor_day
+                        .get_or_insert(if secs >= anchor { day } else { day - 1 });
+                // 时钟回拨到之前的日期时不重复清理
+                secs >= anchor && day > last
+            }
+            // 没有锚点，或时钟不可信时按间隔
+            (anchor, _) => {
+                let interval = self.interval.or(anchor.map(|_| DEFAULT_PRUNE_INTERVAL));
+                interval.is_some_and(|interval| now.duration_since(self.last_prune) >= interval)
+            }
+        };
+        if due {
+            self.last_prune = now;
+            if let Some((day, _)) = wall {
+                self.last_anchor_day = Some(day);
+            }
+        }
+        due
+    }
+}
+
+/// 清空日志文件，返回清理的字节数
+pub fn truncate_log(path: &Path) -> io::Result<u64> {
+    let size = fs::metadata(path)?.len();
+    fs::OpenOptions::new().write(true).open(path)?.set_len(0)?;
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u32 = 3600;
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(parse_time_of_day("03:30"), Ok(3 * HOUR + 1800));
+        assert_eq!(format_time_of_day(3 * HOUR + 1800), "03:30");
+        assert!(parse_time_of_day("24:00").is_err());
+        assert!(parse_time_of_day("3").is_err());
+    }
+
+    #[test]
+    fn test_interval_mode() {
+        let start = Instant::now();
+        let mut schedule = PruneSchedule::new(Duration::from_secs(3600), None, start);
+        assert!(!schedule.due(start + Duration::from_secs(3599), Some((100, 0))));
+        assert!(schedule.due(start + Duration::from_secs(3600), Some((100, 0))));
+        // 从上次清理重新计时
+        assert!(!schedule.due(start + Duration::from_secs(7000), None));
+        assert!(schedule.due(start + Duration::from_secs(7200), None));
+
+        // 都没有配置时从不清理
+        let mut off = PruneSchedule::new(Duration::ZERO, None, start);
+        assert!(!off.due(start + Duration::from_secs(86400 * 10), None));
+    }
+
+    #[test]
+    fn test_anchored_mode() {
+        let anchor = parse_time_of_day("03:30").unwrap();
+        let start = Instant::now();
+        let at = |hours: u64| start + Duration::from_secs(hours * 3600);
+        let mut schedule = PruneSchedule::new(Duration::ZERO, Some(anchor), start);
+
+        // 16:00 启动：当天不清理，第二天 03:30 之后清理一次
+        assert!(!schedule.due(at(0), Some((100, 16 * HOUR))));
+        assert!(!schedule.due(at(11), Some((101, 3 * HOUR))));
+        assert!(schedule.due(at(12), Some((101, 4 * HOUR))));
+        assert!(!schedule.due(at(13), Some((101, 5 * HOUR))));
+
+        // 时钟回拨到前一天：不重复清理；向前跳一天：清理一次
+        assert!(!schedule.due(at(14), Some((100, 20 * HOUR))));
+        assert!(schedule.due(at(15), Some((102, 4 * HOUR))));
+        assert!(!schedule.due(at(16), Some((102, 5 * HOUR))));
+    }
+
+    #[test]
+    fn test_anchored_mode_bogus_clock() {
+        let anchor = parse_time_of_day("03:30").unwrap();
+        let start = Instant::now();
+        let at = |hours: u64| start + Duration::from_secs(hours * 3600);
+        let mut schedule = PruneSchedule::new(Duration::ZERO, Some(anchor), start);
+
+        // 时钟不可信时按 24 小时间隔
+        assert!(!schedule.due(at(23), None));
+        assert!(schedule.due(at(24), None));
+
+        // 时钟同步后（凌晨 1 点）当天 03:30 仍会清理
+        assert!(!schedule.due(at(25), Some((200, HOUR))));
+        assert!(schedule.due(at(28), Some((200, 4 * HOUR))));
+    }
+}
@@ -3,10 +3,13 @@ use std::env;
 use std::fs::{self};
 use std::io::{self, Read, Write};
 use std::net::UdpSocket;
-use std::net::{SocketAddr, TcpListener};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::thread;
 use std::time::UNIX_EPOCH;
 use std::time::{Duration, Instant, SystemTime};
@@ -14,13 +17,120 @@ use std::time::{Duration, Instant, SystemTime};
 use libc;
 
 use daemonize::Daemonize;
+mod adbaudit;
+mod adbdready;
+mod arp;
+mod boot;
+mod clock;
+mod command;
+mod config;
+mod connmgr;
+mod control;
+mod crc32;
+mod cpu;
+mod failhours;
+mod fallback;
+mod filenr;
+mod gateway;
+mod histogram;
+mod hmac;
+mod hooks;
+mod http;
+mod httpd;
+mod icmp;
+mod iptables;
+mod keepalive;
+mod leases;
+mod led;
+mod load;
+mod logprune;
+mod maintenance;
+mod monitor;
+mod notify;
+mod outages;
+mod pathprobe;
+mod ports;
+mod priority;
+mod procs;
+mod profile;
 mod radvd; // 声明模块
+mod reboot;
+mod resume;
+mod routes;
+mod runaway;
+mod samples;
+mod scan;
+mod score;
+mod secret;
+mod services;
+mod severity;
+mod sockmark;
+mod sockstat;
+mod statusfile;
+mod storage;
+mod sysinfo;
+mod sysctl;
+mod system;
+mod ticktime;
+mod tunegate;
+mod tuning;
+mod udpecho;
+mod vmtune;
+mod watchdog;
+
+use adbaudit::AuditReport;
+use boot::BootRecord;
+use clock::ClockWatch;
+use config::{BindConflict, Config, ConfigSource, ProbeMethod};
+use connmgr::ConnManagerWatch;
+use control::{ControlListener, LocalControl, Peer};
+use cpu::CpuMonitor;
+use failhours::FailureHours;
+use fallback::TargetFallback;
+use filenr::{FdLevel, FdMonitor, FileNr};
+use gateway::GatewayProbe;
+use histogram::LatencyHistogram;
+use httpd::HttpServer;
+use hooks::{HookEvent, Hooks};
+use iptables::{IptablesHealth, LanMasquerade, WanNetmap};
+use keepalive::KeepaliveLink;
+use leases::LeaseWatch;
+use led::{Led, LedPattern};
+use load::{HighLoad, LoadEvent};
+use logprune::PruneSchedule;
+use maintenance::Maintenance;
+use monitor::{Action, CycleInputs, FailureReason, MonitorState};
+use notify::Notifier;
+use outages::{OutageLog, Recovery};
+use pathprobe::PathProbe;
+use ports::MultiPort;
+use priority::{Arbiter, Condition};
+use profile::Profile;
+use procs::{TopProcess, TopTracker};
+use reboot::RebootGuard;
+use resume::RuntimeState;
+use routes::{RouteAnomaly, RouteWatch};
+use runaway::{RunawayAction, RunawayGuard};
+use services::{Service, Verb};
+use severity::Severity;
+use sockstat::{PressureEvent, SockStat, SockStatMonitor};
+use statusfile::StatusFile;
+use storage::Storage;
+use sysinfo::SystemIdentity;
+use system::{RealSystem, SystemOps};
+use ticktime::{Phase, TickBudget, TickEvent, TickTimer};
+use tunegate::{TuneGate, TuneTrigger};
+use tuning::{Intent, TuningQueue, TuningSet};
+use vmtune::{VmChange, VmThrottle};
+use watchdog::Watchdog;
 
 const DEFAULT_TARGET_IP: &str = "127.0.0.1:80";
 const PING_INTERVAL: u64 = 60; // 网络检查间隔60秒
 const SNAT_CHECK_INTERVAL: u64 = 300;
 const DNS_CONFIG_CHECK_INTERVAL: u64 = 300; // DNS配置检查间隔120秒
 const RADVD_PREFIX_CHECK_INTERVAL: u64 = 120;
+const ROUTE_CHECK_INTERVAL: u64 = 60; // 默认路由检查间隔
+const LOG_FILE_NAME: &str = "zxping.log"; // 日志文件名（位于存储目录下）
 const SNTP_SYNC_INTERVAL: u64 = 3600; // SNTP同步间隔1小时
 const SNTP_TIMEOUT: Duration = Duration::from_secs(5); // SNTP超时时间
 const SNTP_SERVERS: &[&str] = &[
@@ -34,25 +144,33 @@ const MEMORY_LOW_THRESHOLD_KB: u64 = 2000; // 内存临界阈值2MB（小于此
 const MEMORY_CRITICAL_THRESHOLD_KB: u64 = 1600; // 内存临界阈值1600KB（小于此值杀进程）
 
 const WARN_FAILURES: u32 = 10;
-const MAX_FAILURES: u32 = 15;
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
-const MAX_HIGH_LATENCY: u32 = 3;
 const HIGH_LATENCY_THRESHOLD: u128 = 300; // 50ms
 const HIGH_LATENCY_THRESHOLD_MIN: u128 = 100; // 50ms
 const HIGH_LATENCY_THRESHOLD_MAX: u128 = 2000; // 50ms
 
 // CPU占用率监控配置
-// const CPU_USAGE_THRESHOLD: f32 = 85.0; // CPU占用率阈值 80%
+const CPU_USAGE_THRESHOLD: f32 = 85.0; // CPU占用率阈值 85%
 // const HIGH_LOAD_CHECK_INTERVAL: u64 = 15; // 高负载时网络检查间隔（秒）
 // const NORMAL_CHECK_INTERVAL: u64 = 30; // 正常负载时网络检查间隔（秒）
+const CPU_CHECK_INTERVAL: u64 = 30; // CPU占用率采样间隔（秒）
 
-// UDP通知配置
+// UDP通知配置（本地绑定地址、发送超时见 config.rs）
 // const UDP_SERVER: &str = DEFAULT_TARGET_IP; // UDP服务器地址
-const UDP_LOCAL_BIND: &str = "0.0.0.0:0"; // 本地绑定地址
-const UDP_TIMEOUT: Duration = Duration::from_secs(2); // UDP发送超时时间
 
 // 信号监听配置
 const SIGNAL_LISTEN_PORT: u16 = 1300; // 信号监听端口
+
+// 孤儿连接过多时提前应用的 TIME_WAIT 相关限流：(路径, 限流值, 正常值)
+// 正常值与 optimize_network_parameters 中的设置一致
+const TIME_WAIT_THROTTLE: &[(&str, &str, &str)] = &[
+    ("/proc/sys/net/ipv4/tcp_fin_timeout", "5", "15"),
+    ("/proc/sys/net/netfilter/nf_conntrack_tcp_timeout_time_wait", "10", "30"),
+    ("/proc/sys/net/netfilter/nf_conntrack_tcp_timeout_fin_wait", "10", "30"),
+];
+
+// 依次尝试的 reboot 程序路径（不同固件位置不同）
+const REBOOT_BINARIES: &[&str] = &["/sbin/reboot", "/bin/reboot", "/usr/sbin/reboot"];
 const RESTART_SIGNAL_ADBD: &[u8] = b"RESTART_ADBD";
 const KILL_SIGNAL_ADBD: &[u8] = b"KILL_ADBD";
 const DISABLE_ADB: &[u8] = b"DISABLE_ADB";
@@ -60,13 +178,68 @@ const RESTART_SIGNAL_SERVER: &[u8] = b"RESTART_SERVER";
 const RESTART_SIGNAL_GOAHEAD: &[u8] = b"RESTART_GOAHEAD";
 const REDUCE_KERNEL_LOAD: &[u8] = b"REDUCE_KERNEL_LOAD";
 const SIGNAL_PING: &[u8] = b"PING";
+// 单行健康摘要，字段顺序见 MonitorState::summary_line
+const SIGNAL_PING2: &[u8] = b"PING2";
 const ENABLE_MEMORY_MONITOR: &[u8] = b"ENABLE_MEMORY_MONITOR";
 const DISABLE_MEMORY_MONITOR: &[u8] = b"DISABLE_MEMORY_MONITOR";
 const KILL_SIGNAL_RADVD: &[u8] = b"KILL_RADVD";
 const KILL_SIGNAL_GOAHEAD: &[u8] = b"KILL_GOAHEAD";
 const ADJUST_ZRAM: &[u8] = b"ADJUST_ZRAM";
+// 重新应用 iptables_flush 清空前保存的规则
+const RESTORE_FIREWALL: &[u8] = b"RESTORE_FIREWALL";
+const SIGNAL_MAINTENANCE_ON: &[u8] = b"MAINTENANCE:on";
+const SIGNAL_MAINTENANCE_OFF: &[u8] = b"MAINTENANCE:off";
 const USB_FUNCTIONS: &[u8] = b"USB_FUNCTIONS";
 const WAN_IP_ADDR: &[u8] = b"WAN_IP_ADDR";
+const SIGNAL_STATUS: &[u8] = b"STATUS";
+const SIGNAL_SYSINFO: &[u8] = b"SYSINFO";
+const SIGNAL_PROFILE: &[u8] = b"PROFILE";
+const SIGNAL_PROFILE_SET: &[u8] = b"PROFILE:";
+const SIGNAL_ADBD_STATUS: &[u8] = b"ADBD_STATUS";
+const SIGNAL_ADBD_AUDIT: &[u8] = b"ADBD_AUDIT";
+// 所有配置项的生效值和来源（每行 key=value (source)）
+const SIGNAL_CONFIG: &[u8] = b"CONFIG";
+// 本程序调整过的所有 /proc/sys、/sys 参数的当前值（限流中还有原值）
+const SIGNAL_SYSCTL_DUMP: &[u8] = b"SYSCTL_DUMP";
+// 连接耗时直方图（当天和启动以来累计）
+const SIGNAL_STATS: &[u8] = b"STATS";
+// 最近几次断网的开始、结束、时长和恢复方式（每行一次，最近的在前）
+const SIGNAL_OUTAGES: &[u8] = b"OUTAGES";
+// 服务（adbd 等）启动不满这个时间不再强制重启（避免检查和启动过程撞在一起）
+const SERVICE_MIN_RESTART_AGE: Duration = Duration::from_secs(60);
+// 重新检测 LAN 网段的间隔
+const LAN_SUBNET_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// DHCP 租约统计间隔
+const LEASE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// FD_PRESSURE 通知中列出的进程数
+const FD_TOP_HOLDERS: usize = 3;
+/// 统计单个进程的 fd 时最多数到这么多
+const FD_COUNT_CAP: usize = 4096;
+/// adbd TCP 端口暴露检查间隔
+const ADBD_AUDIT_INTERVAL: Duration = Duration::from_secs(300);
+// ip/nv/brctl 等网络配置命令的超时
+const IP_COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+// kill 命令的超时
+const KILL_TIMEOUT: Duration = Duration::from_secs(2);
+// zram 每条命令的超时（swapoff 要把换出的页面读回内存，可能很慢）
+const ZRAM_COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+// 重新挂载存储目录的超时（闪存出错时 mount 可能卡住）
+const REMOUNT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 收到 SIGTERM/SIGINT 后置位，主循环退出前做清理
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_shutdown_signal(_sig: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+fn install_shutdown_handler() {
+    let handler = handle_shutdown_signal as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
 
 // 内存监控配置
 const MEMORY_MONITOR_INTERVAL: Duration = Duration::from_secs(6); // 内存检查间隔10秒
@@ -74,42 +247,72 @@ const MEMORY_MONITOR_INTERVAL: Duration = Duration::from_secs(6); // 内存检
 // echo -n "REDUCE_KERNEL_LOAD" | nc <TARGETIP> 1300
 
 // 处理信号命令，直接在接收处执行对应操作
-fn handle_restart_adb(target_ip: &str, is_prod: bool) {
-    match force_restart_adbd_process(is_prod) {
+
+/// 在后台线程重启服务（等待旧进程退出期间主循环不阻塞），结果由主循环取回后报告
+fn spawn_service_restart(service: Service, is_prod: bool) -> Receiver<Result<(), String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(force_restart_service(&service, is_prod));
+    });
+    rx
+}
+
+fn report_service_restart(
+    service: &Service,
+    result: Result<(), String>,
+    notifier: &Notifier,
+    is_prod: bool,
+) {
+    match result {
         Ok(_) => {
-            log_message("adbd force restarted successfully", is_prod);
-            send_udp_notification("ADBD_FORCE_RESTARTED", target_ip.to_string(), is_prod);
+            log_message(&format!("{} force restarted successfully", service.name), is_prod);
+            notifier.send(&format!("{}_FORCE_RESTARTED", service.event_prefix()), is_prod);
         }
         Err(e) => {
-            log_message(&format!("❌ Failed to force restart adbd: {}", e), is_prod);
+            log_error(
+                &format!("❌ Failed to force restart {}: {}", service.name, e),
+                is_prod,
+            );
         }
     }
 }
 
-fn handle_kill_adb(target_ip: &str, is_prod: bool) {
-    match force_kill_process(is_prod, "adbd") {
+fn handle_kill_service(service: &Service, notifier: &Notifier, is_prod: bool) {
+    for pid in procs::find_by_name(service.process_name()) {
+        kill_pid("kill", service.signal, pid, is_prod);
+        log_message(&format!("Killed {} process (PID: {})", service.name, pid), is_prod);
+    }
+    match wait_for_kill(service.process_name(), is_prod) {
         Ok(_) => {
-            log_message("adbd killed successfully", is_prod);
-            send_udp_notification("ADBD_FORCE_KILLED", target_ip.to_string(), is_prod);
+            log_message(&format!("{} killed successfully", service.name), is_prod);
+            notifier.send(&format!("{}_FORCE_KILLED", service.event_prefix()), is_prod);
         }
         Err(e) => {
-            log_message(&format!("❌ Failed to kill adbd: {}", e), is_prod);
+            log_error(&format!("❌ Failed to kill {}: {}", service.name, e), is_prod);
         }
     }
 }
 
-fn handle_restart_server(is_prod: bool) {
-    reboot_system(is_prod);
+fn handle_restart_server(
+    sys: &mut impl SystemOps,
+    boot_record: &mut BootRecord,
+    reboot_guard: &mut RebootGuard,
+    hooks: &mut Hooks,
+    notifier: &Notifier,
+    is_prod: bool,
+) {
+    boot_record.set_reboot_target(None);
+    reboot_system(sys, boot_record, reboot_guard, hooks, notifier, "control", is_prod);
 }
 
-fn handle_disable_adb(target_ip: &str, is_prod: bool) {
+fn handle_disable_adb(notifier: &Notifier, is_prod: bool) {
     match disable_adb_function(is_prod) {
         Ok(_) => {
             log_message("adb function disabled successfully", is_prod);
-            send_udp_notification("ADB_FUNCTION_DISABLED", target_ip.to_string(), is_prod);
+            notifier.send("ADB_FUNCTION_DISABLED", is_prod);
         }
         Err(e) => {
-            log_message(
+            log_error(
                 &format!("❌ Failed to disable adb function: {}", e),
                 is_prod,
             );
@@ -117,14 +320,14 @@ fn handle_disable_adb(target_ip: &str, is_prod: bool) {
     }
 }
 
-fn handle_restart_goahead(target_ip: &str, is_prod: bool) {
+fn handle_restart_goahead(notifier: &Notifier, is_prod: bool) {
     match force_start_goahead_process(is_prod) {
         Ok(_) => {
             log_message("goahead force restarted successfully", is_prod);
-            send_udp_notification("GOAHEAD_FORCE_RESTARTED", target_ip.to_string(), is_prod);
+            notifier.send("GOAHEAD_FORCE_RESTARTED", is_prod);
         }
         Err(e) => {
-            log_message(
+            log_error(
                 &format!("❌ Failed to force restart goahead: {}", e),
                 is_prod,
             );
@@ -132,7 +335,7 @@ fn handle_restart_goahead(target_ip: &str, is_prod: bool) {
     }
 }
 
-fn handle_reduce_kernel_load(target_ip: &str, is_prod: bool) {
+fn handle_reduce_kernel_load(notifier: &Notifier, is_prod: bool) {
     let mut zte_count = 0;
     let high_prio_count = 0;
     let mut cpu_hog_count = 0;
@@ -234,42 +437,41 @@ fn handle_reduce_kernel_load(target_ip: &str, is_prod: bool) {
         ),
         is_prod,
     );
-    send_udp_notification(
+    notifier.send(
         &format!(
             "KERNEL_LOAD_REDUCED: ZTE={} HIGH_PRIO={} CPU_HOGS={}",
             zte_count, high_prio_count, cpu_hog_count
         ),
-        target_ip.to_string(),
         is_prod,
     );
 }
 
-fn handle_kill_goahead(target_ip: &str, is_prod: bool) {
+fn handle_kill_goahead(notifier: &Notifier, is_prod: bool) {
     match force_kill_process(is_prod, "goahead") {
         Ok(_) => {
             log_message("goahead killed successfully", is_prod);
-            send_udp_notification("GOAHEAD_KILLED", target_ip.to_string(), is_prod);
+            notifier.send("GOAHEAD_KILLED", is_prod);
         }
         Err(e) => {
-            log_message(&format!("❌ Failed to kill goahead: {}", e), is_prod);
+            log_error(&format!("❌ Failed to kill goahead: {}", e), is_prod);
         }
     }
 }
 
-fn handle_kill_radvd(target_ip: &str, is_prod: bool) {
+fn handle_kill_radvd(notifier: &Notifier, is_prod: bool) {
     let _ = force_kill_process(is_prod, "dhcp6s");
     match force_kill_process(is_prod, "radvd") {
         Ok(_) => {
             log_message("radvd killed successfully", is_prod);
-            send_udp_notification("RADVD_KILLED", target_ip.to_string(), is_prod);
+            notifier.send("RADVD_KILLED", is_prod);
         }
         Err(e) => {
-            log_message(&format!("❌ Failed to kill radvd: {}", e), is_prod);
+            log_error(&format!("❌ Failed to kill radvd: {}", e), is_prod);
         }
     }
 }
 
-fn handle_adjust_zram(target_ip: &str, is_prod: bool) {
+fn handle_adjust_zram(notifier: &Notifier, is_prod: bool) {
     log_message("Adjusting zram configuration...", is_prod);
 
     let commands = [
@@ -285,11 +487,11 @@ fn handle_adjust_zram(target_ip: &str, is_prod: bool) {
     ];
 
     for cmd in commands.iter() {
-        match Command::new("sh").arg("-c").arg(cmd).status() {
-            Ok(status) => {
-                if !status.success() {
-                    log_message(
-                        &format!("Warning: command may have failed: {}", cmd),
+        match command::run_shell(cmd, ZRAM_COMMAND_TIMEOUT) {
+            Ok(result) => {
+                if !result.success() {
+                    log_warn(
+                        &format!("Warning: command may have failed: {}: {}", cmd, result),
                         is_prod,
                     );
                 }
@@ -301,13 +503,16 @@ fn handle_adjust_zram(target_ip: &str, is_prod: bool) {
     }
 
     log_message("ZRAM configuration adjusted successfully", is_prod);
-    send_udp_notification("ZRAM_ADJUSTED", target_ip.to_string(), is_prod);
+    notifier.send("ZRAM_ADJUSTED", is_prod);
 }
 
 /// 内存监控状态 - 极简设计，无线程
 struct MemoryMonitor {
     enabled: AtomicBool,
     last_check_time: Option<Instant>,
+    last_cache_drop: Option<Instant>,
+    /// 最近一次检查时空闲内存低于 MEMORY_LOW_THRESHOLD_KB
+    low_memory: bool,
 }
 
 impl MemoryMonitor {
@@ -315,6 +520,8 @@ impl MemoryMonitor {
         MemoryMonitor {
             enabled: AtomicBool::new(false),
             last_check_time: None,
+            last_cache_drop: None,
+            low_memory: false,
         }
     }
 
@@ -352,8 +559,9 @@ impl MemoryMonitor {
         self.last_check_time = Some(now);
 
         if let Some(free_kb) = get_free_memory_kb() {
+            self.low_memory = free_kb < MEMORY_LOW_THRESHOLD_KB;
             if free_kb < MEMORY_LOW_THRESHOLD_KB {
-                log_message(
+                log_error(
                     &format!(
                         "CRITICAL: Free memory {}KB is below threshold {}KB! Killing adbd and goahead...",
                         free_kb, MEMORY_LOW_THRESHOLD_KB
@@ -378,6 +586,42 @@ impl MemoryMonitor {
             log_message("Failed to get memory info via sysinfo", is_prod);
         }
     }
+
+    /// 按 CPU 采样周期调用：可用内存低于阈值时主动清理 page cache（不受 enabled 控制）
+    fn check_cache_pressure(&mut self, config: &Config, is_prod: bool) {
+        if config.cache_drop_mem_kb == 0 {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last_drop) = self.last_cache_drop {
+            if now.duration_since(last_drop) < config.cache_drop_min_interval {
+                return;
+            }
+        }
+        let Some(available_kb) = get_available_memory_kb() else {
+            return;
+        };
+        if available_kb >= config.cache_drop_mem_kb {
+            return;
+        }
+
+        self.last_cache_drop = Some(now);
+        clear_page_cache(0, is_prod);
+        log_message(
+            &format!(
+                "Low memory: available {}KB < {}KB, page cache dropped (available now {}KB)",
+                available_kb,
+                config.cache_drop_mem_kb,
+                get_available_memory_kb().unwrap_or(0)
+            ),
+            is_prod,
+        );
+    }
+}
+
+/// 可用内存：优先 MemAvailable（3.14+ 内核），否则退回 MemFree
+fn get_available_memory_kb() -> Option<u64> {
+    sysinfo::read_meminfo_kb("MemAvailable").or_else(|| sysinfo::read_meminfo_kb("MemFree"))
 }
 
 // use signal_hook::{
@@ -441,25 +685,15 @@ fn handle_hotplug_event() -> bool {
     
     if action_str == "online" && devpath_str.contains("usblan0") && subsystem_str == "net" {
         // 检查是否为桥接模式
-        let lan_enable = Command::new("nv")
-            .args(["get", "LanEnable"])
-            .output()
-            .ok()
-            .and_then(|o| if o.status.success() { Some(String::from_utf8_lossy(&o.stdout).trim().to_string()) } else { None })
-            .unwrap_or_default();
+        let lan_enable = nv_get("LanEnable");
         
-        let need_jilian = Command::new("nv")
-            .args(["get", "need_jilian"])
-            .output()
-            .ok()
-            .and_then(|o| if o.status.success() { Some(String::from_utf8_lossy(&o.stdout).trim().to_string()) } else { None })
-            .unwrap_or_default();
+        let need_jilian = nv_get("need_jilian");
         
         if lan_enable == "0" && need_jilian == "0" {
             // 检查 usblan0 是否在 br0 网桥中
-            let in_bridge = match Command::new("brctl").args(["show"]).output() {
+            let in_bridge = match command::run_with_timeout("brctl", &["show"], IP_COMMAND_TIMEOUT) {
                 Ok(output) => {
-                    if output.status.success() {
+                    if output.success() {
                         String::from_utf8_lossy(&output.stdout)
                             .lines()
                             .any(|line| line.contains("usblan0"))
@@ -478,11 +712,11 @@ fn handle_hotplug_event() -> bool {
                 //     .and_then(|mut f| f.write_all(b"[hotplug] usblan0 not in br0, re-adding...\n"));
                 
                 // 重新加入网桥
-                let _ = Command::new("brctl").args(["addif", "br0", "usblan0"]).status();
+                let _ = command::run_with_timeout("brctl", &["addif", "br0", "usblan0"], IP_COMMAND_TIMEOUT);
                 // thread::sleep(Duration::from_millis(1000));
-                let _ = Command::new("ip").args(["link", "set", "usblan0", "up"]).status();
-                let _ = Command::new("ifconfig").args(["br0", "up"]).status();
-                let _ = Command::new("ifconfig").args(["usblan0", "up"]).status();
+                let _ = command::run_with_timeout("ip", &["link", "set", "usblan0", "up"], IP_COMMAND_TIMEOUT);
+                let _ = command::run_with_timeout("ifconfig", &["br0", "up"], IP_COMMAND_TIMEOUT);
+                let _ = command::run_with_timeout("ifconfig", &["usblan0", "up"], IP_COMMAND_TIMEOUT);
                 
                 // let _ = fs::OpenOptions::new()
                 //     .create(true)
@@ -507,14 +741,58 @@ fn main() {
 
     let args: Vec<String> = env::args().collect();
 
+    // ctl-scan: 查询一批设备上 zxic-ping 的状态后退出，不做任何初始化
+    if args.get(1).map(String::as_str) == Some("ctl-scan") {
+        std::process::exit(scan::run(&args[2..], SIGNAL_LISTEN_PORT));
+    }
+
     // 检查是否需要后台运行
     let mut is_prod = false;
     if args.iter().any(|arg| arg == "--isprod") {
         is_prod = true;
     }
 
-    if args.iter().any(|arg| arg == "--background" || arg == "-b") {
-        daemonize_simple(is_prod);
+    // 配置和存储目录需要在后台化之前确定（日志文件位于存储目录下）
+    let (mut config, config_warnings) = Config::load(&args);
+    let mut storage = Storage::resolve(&config.storage_root);
+    // 运行时通过 PROFILE 命令切换的档位优先于配置文件
+    let persisted_profile = profile::load_persisted(&storage.path(profile::PROFILE_FILE));
+    if let Some(profile) = persisted_profile {
+        config.apply_profile(profile);
+        config.set_source("profile", ConfigSource::Runtime);
+    }
+
+    // --print-config: 打印生效的配置及来源后退出，有配置告警时退出码为 1（可用于检查配置文件）
+    if args.iter().any(|arg| arg == "--print-config") {
+        for line in config.dump_lines() {
+            println!("{}", line);
+        }
+        for warning in &config_warnings {
+            eprintln!("Config warning: {}", warning);
+        }
+        std::process::exit(if config_warnings.is_empty() { 0 } else { 1 });
+    }
+
+    // 探测 socket 的 DSCP/SO_PRIORITY 标记和绑定的网卡，--validate-target 也使用
+    sockmark::set_probe_marking(sockmark::Marking::from_config(&config));
+    sockmark::set_probe_interface(&config.bind_interface);
+
+    // --validate-target ADDR: 按守护进程的方式连接一次目标，打印耗时或错误后退出
+    // （不改动系统状态、不写日志），可达时退出码为 0
+    if let Some(i) = args.iter().position(|arg| arg == "--validate-target") {
+        let Some(target) = args.get(i + 1) else {
+            eprintln!("--validate-target: missing address");
+            std::process::exit(2);
+        };
+        std::process::exit(validate_target(target, &config));
+    }
+
+    let is_background = args.iter().any(|arg| arg == "--background" || arg == "-b");
+
+    if is_background {
+        daemonize_simple(is_prod, &log_file_path(&config, &storage));
+    } else if !config.log_to.is_empty() {
+        reopen_log_output(Path::new(&config.log_to), is_prod);
     }
 
     // let running = Arc::new(AtomicBool::new(true));
@@ -550,13 +828,52 @@ fn main() {
     // eprintln!("Shutting down gracefully...");
     // return;
 
-    let target_ip = get_target_ip();
+    let stdout_is_tty = unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 };
+    LOG_COLOR.store(config.log_color && stdout_is_tty, Ordering::Relaxed);
+    LOG_DEBUG.store(config.log_debug, Ordering::Relaxed);
+    KILL_WAIT_TIMEOUT_MS.store(config.kill_wait_timeout.as_millis() as u64, Ordering::Relaxed);
+    for warning in &config_warnings {
+        log_warn(&format!("Config warning: {}", warning), is_prod);
+    }
+    if let Some(e) = &storage.probe_error {
+        log_warn(
+            &format!(
+                "Storage {} is not writable ({}), falling back to {} (volatile)",
+                config.storage_root,
+                e,
+                storage.root().display()
+            ),
+            is_prod,
+        );
+    }
+    if let Some(profile) = persisted_profile {
+        log_message(
+            &format!("Using persisted tuning profile: {}", profile.name()),
+            is_prod,
+        );
+    }
+
+    // 目标可以写成 `IP:PORT,PORT,...`，target_ip 只保留第一个端口
+    let (target_ip, target_ports) = match ports::split_target_ports(&get_target_ip()) {
+        Ok(split) => split,
+        Err(e) => {
+            log_message(&e, is_prod);
+            return;
+        }
+    };
+    let reboot_cmd = reboot_command(&config, is_prod);
+    let has_reboot_command = !reboot_cmd.is_empty();
+    let mut system = RealSystem::new(is_prod, reboot_cmd.clone());
 
     if !is_prod {
         println!("Network monitor started for {}", target_ip);
         println!("Network check interval: {} seconds", PING_INTERVAL);
-        println!("Reboot after {} consecutive failures", MAX_FAILURES);
-        println!("Usage: {} [TARGET_IP:PORT] [--background] [--isprod]", args[0]);
+        println!("Reboot after {} consecutive failures", config.max_failures);
+        println!(
+            "Usage: {} [TARGET_IP:PORT[,PORT...]] [--background] [--isprod] [--config PATH] [--udp-local-bind IP[:PORT]] [--udp-timeout-ms MS] [--notify-addr IP:PORT[,...]] [--led NAME] [--log-color] [--log-to PATH] [--status-file PATH] [--notify-envelope] [--tune-only] [--tune-after-first-success] [--print-config] [--validate-target ADDR]",
+            args[0]
+        );
+        println!("       {} ctl-scan <CIDR|IP|FILE>... [-v]", args[0]);
     }
 
     let target_sock_ip = match target_ip.parse::<SocketAddr>() {
@@ -566,60 +883,485 @@ fn main() {
             return;
         }
     };
+
+    // --tune-only: 只应用一次网络参数优化然后退出（用于初始化脚本）
+    if args.iter().any(|arg| arg == "--tune-only") {
+        let mut iptables = IptablesHealth::new(iptables::probe());
+        let report = optimize_network_parameters(
+            &mut system,
+            &config,
+            &mut iptables,
+            storage.root(),
+            is_prod,
+            target_ip.clone(),
+        );
+        sync_wan_netmap(
+            &mut system,
+            &config,
+            &mut iptables,
+            &mut WanNetmap::default(),
+            &target_sock_ip,
+            is_prod,
+        );
+        let lan = get_br_network()
+            .or_else(|| (!config.assume_lan.is_empty()).then(|| config.assume_lan.clone()));
+        sync_lan_masquerade(
+            &mut system,
+            &config,
+            &mut iptables,
+            &mut LanMasquerade::default(),
+            lan.as_deref(),
+            is_prod,
+        );
+        println!(
+            "Tuning applied: {} ok, {} failed",
+            report.applied,
+            report.failed.len()
+        );
+        for cmd in &report.failed {
+            println!("  failed: {}", cmd);
+        }
+        std::process::exit(if report.failed.is_empty() { 0 } else { 1 });
+    }
+
     log_message(
         &format!("Network monitor started for {}", target_ip),
         is_prod,
     );
+    // 在临时 socket 上试一次标记，之后设置失败时不再记录（内核或权限不支持时探测照常进行）
+    let ipv6 = target_ip.parse::<SocketAddr>().is_ok_and(|addr| addr.is_ipv6());
+    let marking = sockmark::probe_marking();
+    if !marking.is_empty() {
+        match sockmark::probe_marking_check(ipv6) {
+            Ok(result) => log_message(
+                &format!(
+                    "Probe socket marking: {}{}",
+                    result.describe(&marking),
+                    if config.notify_mark { " (also on notifications)" } else { "" }
+                ),
+                is_prod,
+            ),
+            Err(e) => log_warn(&format!("Probe socket marking not checked: {}", e), is_prod),
+        }
+    }
+    if !config.bind_interface.is_empty() {
+        match sockmark::probe_interface_check(ipv6) {
+            Ok(()) => log_message(
+                &format!("Probes bound to interface {}", config.bind_interface),
+                is_prod,
+            ),
+            Err(e) => log_warn(
+                &format!(
+                    "Cannot bind probes to interface {} ({}), probing via the routing table",
+                    config.bind_interface, e
+                ),
+                is_prod,
+            ),
+        }
+    }
+    let notifier = Notifier::new(&target_ip, &config);
+    let mut led = config
+        .led
+        .as_deref()
+        .and_then(|name| Led::open(name, is_prod));
+    install_shutdown_handler();
+
+    // 登记本次启动（启动计数、非正常退出检测、重启循环检测）
+    let mut boot_record = BootRecord::register_boot(storage.path(boot::BOOT_RECORD_FILE));
+    log_message(
+        &format!(
+            "Boot #{} (unclean: {}, boots in last {}s: {})",
+            boot_record.count,
+            boot_record.unclean,
+            boot::BOOT_LOOP_WINDOW_SECS,
+            boot_record.boots_in_window()
+        ),
+        is_prod,
+    );
 
     let wan1_ip_check = get_wan_ip_address(is_prod);
     if wan1_ip_check.is_empty() {
+        boot_record.mark_early_exit();
         return
     }
 
+    if let Some(reason) = &boot_record.last_reboot_reason {
+        log_message(&format!("Last reboot by zxic-ping, reason: {}", reason), is_prod);
+    }
+
+    // 启动时确认重启这条最后的恢复手段可用
+    match find_reboot_binary() {
+        Some(reboot) => log_message(&format!("Reboot binary: {}", reboot), is_prod),
+        None if has_reboot_command => {}
+        None => {
+            log_error(
+                &format!(
+                    "CRITICAL: no usable reboot binary ({}), automatic recovery by reboot will not work",
+                    REBOOT_BINARIES.join(", ")
+                ),
+                is_prod,
+            );
+            notifier.send("REBOOT_UNAVAILABLE", is_prod);
+        }
+    }
+
+    // 固件/内核信息只在启动时读取一次
+    let sysinfo = SystemIdentity::collect(&config.firmware_version_file);
+    log_message(&format!("System: {}", sysinfo.full_lines().join(", ")), is_prod);
+
+    // 控制通道的 HMAC 密钥放在单独的文件中，不出现在配置文件和 CONFIG 输出里；
+    // 只用于 TCP 控制端口（本机 socket 只有 root 可访问，HTTP 接口用自己的 token）
+    let hmac_key = load_secret("HMAC key", &config.hmac_key_file, is_prod);
+
+    // 可选的 HTTP 接口；没有 token 时只提供 /status 和 /metrics
+    let mut http_server = if config.http_port > 0 {
+        let token = load_secret("HTTP token", &config.http_token_file, is_prod);
+        HttpServer::start(config.http_address, config.http_port, token, is_prod)
+            .map_err(|e| {
+                log_error(
+                    &format!("Failed to start HTTP server on port {}: {}", config.http_port, e),
+                    is_prod,
+                )
+            })
+            .ok()
+    } else {
+        None
+    };
+
     // 创建内存监控器（极简设计，无线程）
     let mut memory_monitor = MemoryMonitor::new();
 
     // 启动信号监听（同时支持 IPv4 和 IPv6）
-    let signal_listener = TcpListener::bind(("::", SIGNAL_LISTEN_PORT)).expect("bind signal port");
-    // 设置 IPV6_V6ONLY 为 false，允许 IPv4 映射到 IPv6
-    let socket_fd = signal_listener.as_raw_fd();
-    unsafe {
-        let opt: libc::c_int = 0;
-        libc::setsockopt(
-            socket_fd,
-            libc::IPPROTO_IPV6,
-            libc::IPV6_V6ONLY,
-            &opt as *const _ as *const libc::c_void,
-            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+    let mut signal_listener = if config.enable_control_channel {
+        let buffers = control::BufferSizes {
+            recv: config.control_recv_buffer,
+            send: config.control_send_buffer,
+        };
+        match ControlListener::bind(SIGNAL_LISTEN_PORT, buffers) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let action = config.control_bind_conflict;
+                log_error(
+                    &format!(
+                        "Cannot bind control port {}: {} (control_bind_conflict={})",
+                        SIGNAL_LISTEN_PORT,
+                        e,
+                        action.name()
+                    ),
+                    is_prod,
+                );
+                if action == BindConflict::Fail {
+                    std::process::exit(1);
+                }
+                notifier.send(
+                    &format!(
+                        "CONTROL_BIND_FAILED: PORT={} ACTION={}",
+                        SIGNAL_LISTEN_PORT,
+                        action.name()
+                    ),
+                    is_prod,
+                );
+                if action == BindConflict::Retry {
+                    ControlListener::retrying(SIGNAL_LISTEN_PORT, buffers, Instant::now())
+                } else {
+                    ControlListener::disabled(SIGNAL_LISTEN_PORT)
+                }
+            }
+        }
+    } else {
+        log_message("Control channel disabled by config", is_prod);
+        ControlListener::disabled(SIGNAL_LISTEN_PORT)
+    };
+    // 本机 Unix socket 控制通道（可选），绑定失败不影响控制端口
+    let local_control = if config.control_socket.is_empty() {
+        None
+    } else {
+        match LocalControl::bind(&config.control_socket) {
+            Ok(local) => {
+                log_message(
+                    &format!("Local control socket listening on {}", config.control_socket),
+                    is_prod,
+                );
+                Some(local)
+            }
+            Err(e) => {
+                log_error(
+                    &format!(
+                        "Failed to bind control socket {}: {}",
+                        config.control_socket, e
+                    ),
+                    is_prod,
+                );
+                None
+            }
+        }
+    };
+
+    let mut state = MonitorState::new();
+    // 进程重启（而不是设备重启）时接着使用上次保存的计数
+    match RuntimeState::take(Path::new(resume::STATE_FILE), resume::STATE_MAX_AGE) {
+        Ok(Some(saved)) => {
+            saved.apply(&mut state, config.max_high_latency);
+            notifier.resume_sequence(saved.notify_seq);
+            log_message(
+                &format!(
+                    "Resumed runtime state: failures={} high_latency={} score={:.1} seq={}",
+                    state.failure_count,
+                    state.high_latency_count,
+                    state.score.value(),
+                    saved.notify_seq
+                ),
+                is_prod,
+            );
+        }
+        Ok(None) => {}
+        Err(e) => log_warn(&format!("Ignoring saved runtime state: {}", e), is_prod),
+    }
+    let started_at = Instant::now();
+    let mut cpu_monitor = CpuMonitor::new();
+    cpu_monitor.sample(&mut system);
+    let mut last_cpu_check = Instant::now();
+    let mut high_load = HighLoad::new(CPU_USAGE_THRESHOLD, &config);
+    // 连续失败达到 diag_failure_threshold 时启动的后台路径探测
+    let mut path_probe: Option<PathProbe> = None;
+    // 后台进行中的服务重启（adbd 等）
+    let mut service_restarts: Vec<(Service, Receiver<Result<(), String>>)> = Vec::new();
+    // adbd 重启后等 USB 重新枚举，确认就绪后发送 ADBD_READY
+    let mut adbd_ready = adbdready::AdbdReady::new(&config);
+    let mut sockstat_monitor = SockStatMonitor::new();
+    let mut fd_monitor = FdMonitor::new();
+    let mut reboot_guard = RebootGuard::new();
+    let mut top_tracker = TopTracker::new();
+    // 高负载期间最近一次采样到的最耗 CPU 进程（断网时卸载负载用于报告）
+    let mut last_top: Option<TopProcess> = None;
+    let mut runaway_guard = RunawayGuard::new();
+    let mut conn_manager = ConnManagerWatch::new();
+    let mut gateway_probe = GatewayProbe::new();
+    // 日志时间戳用墙上时间，校时造成的跳变记录下来方便对照日志
+    let mut clock_watch = ClockWatch::new(SystemTime::now(), Instant::now());
+    let mut maintenance = Maintenance::load(
+        storage.path(maintenance::MAINTENANCE_FILE),
+        unix_now(),
+        config.maintenance_timeout,
+    );
+    if maintenance.is_active() {
+        log_warn(
+            &format!("Resuming maintenance mode: {}", maintenance.status_line(unix_now())),
+            is_prod,
         );
+    } else if config.maintenance {
+        match maintenance.enable(unix_now(), config.maintenance_timeout) {
+            Ok(_) => log_warn("Maintenance mode enabled by config", is_prod),
+            Err(e) => log_warn(&format!("Failed to persist maintenance mode: {}", e), is_prod),
+        }
     }
-    signal_listener
-        .set_nonblocking(true)
-        .expect("set_nonblocking");
-
-    let mut failure_count = 0;
-    let mut high_latency_count = 0;
+    // 连续几次重启都归咎于同一个目标时不再信任它：改用备用目标检查，原目标在后台继续检查
+    let mut check_target = target_ip.clone();
+    let mut target_fallback: Option<TargetFallback> = None;
+    let suspect = boot_record
+        .suspect_target(
+            config.target_suspect_window.as_secs(),
+            config.target_suspect_reboots,
+        )
+        .filter(|suspect| *suspect == target_ip);
+    if suspect.is_some() {
+        let fallback = fallback::fallback_target(
+            &config.fallback_target,
+            gateway_probe.gateway(Instant::now()),
+            &target_ip,
+        );
+        match fallback {
+            _ if config.probe == ProbeMethod::Arp => log_warn(
+                &format!(
+                    "Target {} caused {} reboots in a row, but probe=arp has no fallback",
+                    target_ip, config.target_suspect_reboots
+                ),
+                is_prod,
+            ),
+            None => log_warn(
+                &format!(
+                    "Target {} caused {} reboots in a row, but no fallback target is available",
+                    target_ip, config.target_suspect_reboots
+                ),
+                is_prod,
+            ),
+            Some(fallback) => {
+                log_warn(
+                    &format!(
+                        "Target {} caused {} reboots in a row, checking {} until it recovers",
+                        target_ip, config.target_suspect_reboots, fallback
+                    ),
+                    is_prod,
+                );
+                notifier.send(
+                    &format!(
+                        "TARGET_SUSPECT: TARGET={} FALLBACK={} REBOOTS={}",
+                        target_ip, fallback, config.target_suspect_reboots
+                    ),
+                    is_prod,
+                );
+                target_fallback = Some(TargetFallback::new(
+                    target_ip.clone(),
+                    config.target_restore_after,
+                ));
+                check_target = fallback;
+            }
+        }
+    }
+    let mut keepalive_link = config
+        .keepalive_check
+        .then(|| check_target.parse::<SocketAddr>().ok())
+        .flatten()
+        .map(KeepaliveLink::new);
+    let mut arbiter = Arbiter::new();
+    let mut hooks = Hooks::new(config.hooks.clone(), is_prod);
+    let mut latency_histogram = LatencyHistogram::new(&config.latency_buckets, Instant::now());
+    let mut fail_hours = FailureHours::load(storage.path(storage::STATS_FILE));
+    let (mut outages, closed_outage) = OutageLog::load(storage.path(storage::STATS_FILE));
+    if let Some(outage) = closed_outage {
+        log_message(
+            &format!("Outage still ongoing at last exit closed: {}", outage.describe()),
+            is_prod,
+        );
+    }
+    let mut sample_log = config.sample_log.then(|| {
+        samples::SampleLog::new(
+            storage.path(samples::SAMPLES_FILE),
+            config.sample_log_max_bytes,
+            Instant::now(),
+        )
+    });
     let mut last_network_check = Instant::now();
     let mut last_snat_check = Instant::now();
-    let mut current_snat_wan_ip = String::new();
+    // 目标主机映射到 WAN 地址的 NETMAP 规则（启动优化时安装，WAN 地址变化时更新）
+    let mut wan_netmap = WanNetmap::default();
     // let mut last_udp_notification = Instant::now();
     // let mut last_adbd_check = Instant::now();
-    // let mut last_log_prune = Instant::now();
+    let mut log_prune =
+        PruneSchedule::new(config.log_prune_interval, config.log_prune_at, Instant::now());
     let mut last_dns_config_check = Instant::now();
+    let mut route_watch = RouteWatch::new();
+    let mut last_route_check = Instant::now();
+    let mut status_file = (!config.status_file.is_empty())
+        .then(|| StatusFile::start(PathBuf::from(&config.status_file)));
     // 初始化为很早以前的时间，确保第一次 loop 就执行 radvd prefix 检查
     let mut last_radvdprefix_check =
         Instant::now() - Duration::from_secs(RADVD_PREFIX_CHECK_INTERVAL + 1);
     // SNTP同步时间检查
     let mut last_sntp_check = Instant::now() - Duration::from_secs(SNTP_SYNC_INTERVAL + 1);
 
-    thread::sleep(Duration::from_secs(30));
-    optimize_network_parameters(is_prod, target_ip.clone());
+    let mut iptables = IptablesHealth::new(iptables::probe());
+    if config.enable_iptables {
+        log_message(&format!("iptables: {}", iptables.variant.name()), is_prod);
+    }
+    let mut lan_masquerade = LanMasquerade::default();
+    // tune_after_first_success 时推迟到第一次检查成功（或等待超时）再优化，避免和厂商的网络初始化撞在一起
+    // （不做连通性检查时无从判断，照常优化）
+    let mut tune_gate = if config.tune_after_first_success && config.enable_network_monitor {
+        log_message(
+            &format!(
+                "Network tuning deferred until the first successful check (deadline {}s)",
+                config.tune_deadline.as_secs()
+            ),
+            is_prod,
+        );
+        TuneGate::deferred(Instant::now(), config.tune_deadline)
+    } else {
+        thread::sleep(Duration::from_secs(30));
+        apply_startup_tuning(
+            &mut system,
+            &config,
+            &mut iptables,
+            storage.root(),
+            &notifier,
+            is_prod,
+            target_ip.clone(),
+        );
+        sync_wan_netmap(
+            &mut system,
+            &config,
+            &mut iptables,
+            &mut wan_netmap,
+            &target_sock_ip,
+            is_prod,
+        );
+        TuneGate::applied_at_startup(Instant::now())
+    };
+    let mut last_adbd_audit: Option<Instant> = None;
+    let mut lease_watch = LeaseWatch::new(&config.dhcp_leases_file);
+    let mut last_lease_check: Option<Instant> = None;
+    let mut last_heartbeat = Instant::now();
+    // 上一次检查时 adbd 端口是否对外暴露（只在变化时通知）
+    let mut adbd_exposed = false;
+    // probe=http：连接时间作为 rtt 参与高延迟判断，首字节时间单独计入 SlowServer
+    let mut http_target = match config.probe {
+        ProbeMethod::Http => check_target.parse::<SocketAddr>().ok(),
+        _ => None,
+    };
+    let mut slow_server = http::SlowServer::new();
+    // probe=udp：echo 请求，回应内容不符与超时分开统计
+    let udp_echo = udpecho::EchoSettings::from_config(&config);
+    let mut udp_target = match config.probe {
+        ProbeMethod::Udp => udp_echo.target(&check_target),
+        _ => None,
+    };
+    if let Some(addr) = udp_target {
+        log_message(&udp_echo.describe(addr), is_prod);
+    }
+    let mut udp_stats = udpecho::EchoStats::default();
+    // 多个端口时按顺序检查，任一端口可连接即成功（只用于 probe=tcp 且没有 keepalive_check）
+    let mut multi_port = None;
+    if target_ports.len() > 1 {
+        if config.probe == ProbeMethod::Tcp && !config.keepalive_check {
+            multi_port = target_ip
+                .parse::<SocketAddr>()
+                .ok()
+                .map(|addr| MultiPort::new(addr.ip(), &target_ports));
+        } else {
+            log_warn(
+                "Extra target ports are only used with probe=tcp without keepalive_check",
+                is_prod,
+            );
+        }
+    }
+    let mut hybrid_stats = icmp::HybridStats::default();
+
+    notifier.send(
+        &format!(
+            "BOOTED: COUNT={} UPTIME={}s CLEAN={} STORAGE={} {}",
+            boot_record.count,
+            boot::read_uptime_secs().unwrap_or(0),
+            if boot_record.unclean { "no" } else { "yes" },
+            if storage.is_degraded() { "degraded" } else { "ok" },
+            sysinfo.compact()
+        ),
+        is_prod,
+    );
+    if let Some(remaining) = boot_record.conservative_remaining() {
+        log_error(
+            &format!(
+                "Boot loop suspected ({} boots within {}s), zxic-ping reboots disabled for {}s",
+                boot_record.boots_in_window(),
+                boot::BOOT_LOOP_WINDOW_SECS,
+                remaining.as_secs()
+            ),
+            is_prod,
+        );
+        notifier.send(
+            &format!(
+                "BOOT_LOOP_SUSPECTED: BOOTS={} WINDOW={}s",
+                boot_record.boots_in_window(),
+                boot::BOOT_LOOP_WINDOW_SECS
+            ),
+            is_prod,
+        );
+    }
     let _ = force_kill_process(is_prod, "dnsmasq");
     let _ = force_kill_process(is_prod, "dhcp6s");
     let _ = force_kill_process(is_prod, "radvd");
 
-    let _ = Command::new("nv").args(["set", "default_wan_rel="]).status();
-    let _ = Command::new("nv").args(["set", "default_wan6_rel="]).status();
+    let _ = command::run_with_timeout("nv", &["set", "default_wan_rel="], IP_COMMAND_TIMEOUT);
+    let _ = command::run_with_timeout("nv", &["set", "default_wan6_rel="], IP_COMMAND_TIMEOUT);
 
 
     // 检查 /etc/resolv.conf，如果为空或最后一行是 nameserver 127.0.0.1，则追加 DNS
@@ -648,26 +1390,8 @@ fn main() {
     }
 
     // 检测 nv get LanEnable 和 nv get need_jilian，如果都返回0则配置网桥
-    let lan_enable = match Command::new("nv").arg("get").arg("LanEnable").output() {
-        Ok(output) => {
-            if output.status.success() {
-                String::from_utf8_lossy(&output.stdout).trim().to_string()
-            } else {
-                String::new()
-            }
-        }
-        Err(_) => String::new(),
-    };
-    let need_jilian = match Command::new("nv").arg("get").arg("need_jilian").output() {
-        Ok(output) => {
-            if output.status.success() {
-                String::from_utf8_lossy(&output.stdout).trim().to_string()
-            } else {
-                String::new()
-            }
-        }
-        Err(_) => String::new(),
-    };
+    let lan_enable = nv_get("LanEnable");
+    let need_jilian = nv_get("need_jilian");
     let radvd_iface_name = "br0";
 
     if lan_enable == "0" && need_jilian == "0" {
@@ -675,27 +1399,18 @@ fn main() {
         let _ = std::fs::write("/proc/sys/kernel/hotplug", b"/etc_rw/zxic_ping\n");
 
         log_message("LanEnable=0 and need_jilian=0, configuring bridge...", is_prod);
-        let _ = Command::new("brctl").args(["addbr", "br0"]).status();
-        let _ = Command::new("brctl").args(["stp", "br0", "off"]).status();
-        let _ = Command::new("brctl").args(["addif", "br0", "usblan0"]).status();
-        let _ = Command::new("ifconfig").args(["br0", "up"]).status();
-        let _ = Command::new("ifconfig").args(["usblan0", "up"]).status();
+        let _ = command::run_with_timeout("brctl", &["addbr", "br0"], IP_COMMAND_TIMEOUT);
+        let _ = command::run_with_timeout("brctl", &["stp", "br0", "off"], IP_COMMAND_TIMEOUT);
+        let _ = command::run_with_timeout("brctl", &["addif", "br0", "usblan0"], IP_COMMAND_TIMEOUT);
+        let _ = command::run_with_timeout("ifconfig", &["br0", "up"], IP_COMMAND_TIMEOUT);
+        let _ = command::run_with_timeout("ifconfig", &["usblan0", "up"], IP_COMMAND_TIMEOUT);
 
         // 获取 IPv6 前缀并配置 br0
-        let wan1_ipv6_prefix = match Command::new("nv").arg("get").arg("wan1_ipv6_prefix_info").output() {
-            Ok(output) => {
-                if output.status.success() {
-                    String::from_utf8_lossy(&output.stdout).trim().to_string()
-                } else {
-                    String::new()
-                }
-            }
-            Err(_) => String::new(),
-        };
+        let wan1_ipv6_prefix = nv_get("wan1_ipv6_prefix_info");
         if !wan1_ipv6_prefix.is_empty() {
             let ipv6_addr = format!("{}:2/64", wan1_ipv6_prefix);
             log_message(&format!("Adding IPv6 address {} to br0", ipv6_addr), is_prod);
-            let _ = Command::new("ip").args(["addr", "add", &ipv6_addr, "dev", "br0"]).status();
+            let _ = command::run_with_timeout("ip", &["addr", "add", &ipv6_addr, "dev", "br0"], IP_COMMAND_TIMEOUT);
         }
 
         // 根据 target_sock_ip 计算 br0 的 IP 地址（将最后一位改为1）
@@ -703,12 +1418,49 @@ fn main() {
             let base_ip = &target_sock_ip[..last_dot + 1];
             let br0_ip = format!("{}1", base_ip);
             log_message(&format!("Adding IPv4 address {}/24 to br0", br0_ip), is_prod);
-            let _ = Command::new("ip")
-                .args(["addr", "add", &format!("{}/24", br0_ip), "dev", "br0"])
-                .status();
+            let _ = command::run_with_timeout(
+                "ip",
+                &["addr", "add", &format!("{}/24", br0_ip), "dev", "br0"],
+                IP_COMMAND_TIMEOUT,
+            );
         }
     }
 
+    // br0 的地址在上面的网桥配置中才加上，之后再检测 LAN 网段
+    let (mut lan_subnet, mut lan_subnet_assumed) = resolve_lan_subnet(&config, &notifier, is_prod);
+    let mut last_lan_subnet_check = Instant::now();
+    // 推迟的优化应用时再按当时的网段安装
+    if !tune_gate.is_pending() {
+        sync_lan_masquerade(
+            &mut system,
+            &config,
+            &mut iptables,
+            &mut lan_masquerade,
+            lan_subnet.as_deref(),
+            is_prod,
+        );
+    }
+    let arp_target = match config.probe {
+        ProbeMethod::Tcp | ProbeMethod::Udp | ProbeMethod::Hybrid | ProbeMethod::Http => None,
+        ProbeMethod::Arp => match arp_probe_target(&target_sock_ip, lan_subnet.as_deref()) {
+            Ok(ip) => Some(ip),
+            // 网段暂时未知时照常检查，主循环检测到网段后再核对目标
+            Err(e) => match target_sock_ip.parse().ok().filter(|_| lan_subnet.is_none()) {
+                Some(ip) => {
+                    log_warn(
+                        &format!("probe=arp: {}, target checked once it is detected", e),
+                        is_prod,
+                    );
+                    Some(ip)
+                }
+                None => {
+                    log_error(&format!("probe=arp: {}", e), is_prod);
+                    return;
+                }
+            },
+        },
+    };
+
     let mut recv_buf = vec![0u8; 200];
 
     let icmp_socket_option = match open_icmpv6_socket() {
@@ -722,9 +1474,60 @@ fn main() {
     };
     let mut radvd_conf_option = None;
     let mut current_radvd_pfx = String::new();
+    let mut restore_guard = NetworkRestoreGuard {
+        config: config.clone(),
+        tuning: TuningQueue::start(),
+        vm_throttle: VmThrottle::new(),
+        severity: Severity::Normal,
+        time_wait_throttled: false,
+        is_prod,
+    };
+
+    // 主循环自身卡住（命令或 socket 阻塞）时由独立线程报告并处理
+    let watchdog = (!config.loop_stall_timeout.is_zero()).then(|| {
+        Watchdog::start(
+            &config,
+            Notifier::new(&target_ip, &config),
+            RealSystem::new(is_prod, reboot_cmd),
+            storage.path(boot::BOOT_RECORD_FILE),
+            is_prod,
+        )
+    });
+
+    let mut tick_budget = TickBudget::new(config.tick_budget, config.slow_tick_count);
 
     loop {
+        if SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+            break;
+        }
+        if let Some(watchdog) = &watchdog {
+            watchdog.pet(
+                !maintenance.is_active()
+                    && reboot_guard.allowed(Instant::now())
+                    && boot_record.conservative_remaining().is_none(),
+            );
+        }
         let now = Instant::now();
+        // 各阶段的耗时，本轮结束时检查是否超出 tick_budget_ms
+        let mut tick = TickTimer::start(now);
+        hooks.poll();
+        tick.lap(Phase::Hooks);
+        notifier.poll(is_prod);
+        tick.lap(Phase::Notify);
+        if let Some(delta) = clock_watch.check(SystemTime::now(), now) {
+            log_warn(
+                &format!("Wall clock stepped by {:+}s, log timestamps jump here", delta),
+                is_prod,
+            );
+            notifier.send(&format!("CLOCK_STEP: DELTA={:+}s", delta), is_prod);
+        }
+        for outcome in restore_guard.tuning.poll() {
+            report_tuning(&outcome, &notifier, is_prod);
+        }
+        if maintenance.expire(unix_now()) {
+            log_warn("Maintenance mode expired, protective actions resumed", is_prod);
+            notifier.send("MAINTENANCE_EXPIRED", is_prod);
+        }
 
         if now.duration_since(last_radvdprefix_check)
             >= Duration::from_secs(RADVD_PREFIX_CHECK_INTERVAL)
@@ -755,7 +1558,7 @@ fn main() {
                 // 同时更新 br0 的 IPv6 地址（复制569行的逻辑）
                 let ipv6_addr = format!("{}2/64", new_pfx);
                 log_message(&format!("Updating IPv6 address {} to br0", ipv6_addr), is_prod);
-                let _ = Command::new("ip").args(["addr", "add", &ipv6_addr, "dev", "br0"]).status();
+                let _ = command::run_with_timeout("ip", &["addr", "add", &ipv6_addr, "dev", "br0"], IP_COMMAND_TIMEOUT);
             }
 
             last_radvdprefix_check = now;
@@ -769,290 +1572,1113 @@ fn main() {
             radvd::process_radvd_socket(radvd_conf, icmp_socket, &mut recv_buf);
         }
 
-        // 处理 TCP 连接
-        match signal_listener.accept() {
-            // Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-            //     // 非阻塞，没有新连接
-            // }
-            Err(_e) => {
-                // if !is_prod {
-                //     log_message(&format!("❌ Signal listener error: {}", e), is_prod);
-                // }
-            }
-            Ok((mut stream, addr)) => {
-                let mut buf = [0u8; 64];
-                match stream.read(&mut buf) {
-                    Ok(size) if size > 0 => {
-                        let received = &buf[..size];
-
-                        if received == RESTART_SIGNAL_ADBD {
-                            log_message(
-                                &format!("Received restart signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_restart_adb(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == KILL_SIGNAL_ADBD {
-                            log_message(&format!("Received kill signal from {}", addr), is_prod);
-                            handle_kill_adb(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == DISABLE_ADB {
-                            log_message(
-                                &format!("Received disable adb signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_disable_adb(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == RESTART_SIGNAL_SERVER {
-                            log_message(
-                                &format!("Received reboot signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_restart_server(is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == RESTART_SIGNAL_GOAHEAD {
-                            log_message(
-                                &format!("Received restart goahead signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_restart_goahead(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == REDUCE_KERNEL_LOAD {
-                            log_message(
-                                &format!("Received reduce kernel load signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_reduce_kernel_load(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == ENABLE_MEMORY_MONITOR {
-                            log_message(
-                                &format!("Received enable memory monitor signal from {}", addr),
-                                is_prod,
-                            );
-                            memory_monitor.enable(is_prod);
-                            send_udp_notification(
-                                "MEMORY_MONITOR_ENABLED",
-                                target_ip.clone(),
-                                is_prod,
-                            );
-                            let _ = stream.write_all(b"OK");
-                        } else if received == DISABLE_MEMORY_MONITOR {
-                            log_message(
-                                &format!("Received disable memory monitor signal from {}", addr),
-                                is_prod,
-                            );
-                            memory_monitor.disable(is_prod);
-                            send_udp_notification(
-                                "MEMORY_MONITOR_DISABLED",
-                                target_ip.clone(),
-                                is_prod,
-                            );
-                            let _ = stream.write_all(b"OK");
-                        } else if received == SIGNAL_PING {
-                            let _ = stream.write_all(b"OK");
-                        } else if received == KILL_SIGNAL_RADVD {
-                            log_message(
-                                &format!("Received kill radvd signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_kill_radvd(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == ADJUST_ZRAM {
-                            log_message(
-                                &format!("Received adjust zram signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_adjust_zram(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == KILL_SIGNAL_GOAHEAD {
-                            log_message(
-                                &format!("Received kill goahead signal from {}", addr),
-                                is_prod,
-                            );
-                            handle_kill_goahead(&target_ip, is_prod);
-                            let _ = stream.write_all(b"OK");
-                        } else if received == USB_FUNCTIONS {
-                            log_message(
-                                &format!("Received usb functions query from {}", addr),
-                                is_prod,
-                            );
-                            match fs::read_to_string("/sys/class/android_usb/android0/functions") {
-                                Ok(content) => {
-                                    let _ = stream.write_all(content.trim().as_bytes());
+        tick.lap(Phase::Other);
+        // STATUS 回复和状态文件共用的快照
+        let status_lines = {
+            let mut lines = state.status_lines();
+            lines.push(storage.status_line());
+            lines.push(high_load.status_line(now));
+            lines.push(sockstat_monitor.status_line());
+            lines.push(fd_monitor.status_line());
+            lines.push(tune_gate.status_line(now));
+            lines.push(tick_budget.status_line());
+            lines.push(arbiter.status_line());
+            lines.push(route_watch.status_line());
+            lines.push(iptables.status_line());
+            if !hooks.is_empty() {
+                lines.push(hooks.status_line());
+            }
+            lines.push(format!(
+                "lan_subnet={} lan_masquerade={}",
+                lan_subnet.as_deref().unwrap_or("unknown"),
+                lan_masquerade.applied().unwrap_or("-")
+            ));
+            lines.push(lease_watch.status_line());
+            if let Some(fallback) = &target_fallback {
+                lines.push(fallback.status_line(&check_target, now));
+            }
+            if let Some(multi) = multi_port.as_mut() {
+                lines.push(multi.status_line());
+            }
+            if udp_target.is_some() {
+                lines.push(udp_stats.status_line());
+            }
+            if http_target.is_some() {
+                lines.push(slow_server.status_line());
+            }
+            if config.probe == ProbeMethod::Hybrid {
+                lines.push(hybrid_stats.status_line());
+            }
+            if let Some(link) = &keepalive_link {
+                lines.push(format!(
+                    "keepalive={}",
+                    if link.is_connected() { "connected" } else { "down" }
+                ));
+            }
+            lines.push(reboot_guard.status_line(now));
+            lines.extend(notifier.status_lines());
+            lines.push(signal_listener.status_line());
+            if let Some(local) = &local_control {
+                lines.push(local.status_line());
+            }
+            lines.push(clock_watch.status_line());
+            lines.push(restore_guard.tuning.status_line());
+            lines.push(maintenance.status_line(unix_now()));
+            lines.push(format!(
+                "hmac_key={}",
+                if hmac_key.is_some() { "loaded" } else { "none" }
+            ));
+            lines
+        };
+        if let Some(status_file) = status_file.as_mut() {
+            status_file.update(&status_lines, now);
+        }
+        if let Some(http_server) = http_server.as_ref() {
+            http_server.update(
+                &status_lines,
+                &latency_histogram,
+                &[
+                    ("version", env!("CARGO_PKG_VERSION")),
+                    ("probe", config.probe.name()),
+                    ("profile", config.profile.name()),
+                ],
+            );
+        }
+
+        signal_listener.retry_bind(now, &notifier, is_prod);
+        // 处理 TCP 连接；本机 Unix socket 和 HTTP 接口转来的命令走同一套处理
+        let request: Option<(Vec<u8>, Peer, Box<dyn Write + Send>)> =
+            match signal_listener.accept() {
+                Err(e) => {
+                    // WouldBlock（没有新连接）在内部忽略，其他错误计数并限频记录
+                    signal_listener.handle_error(&e, &notifier, is_prod);
+                    None
+                }
+                Ok((mut stream, addr)) => {
+                    // 签名的命令多出时间和 64 字节的 HMAC
+                    let mut buf = [0u8; 256];
+                    match stream.read(&mut buf) {
+                        Ok(size) if size > 0 => match &hmac_key {
+                            None => {
+                                Some((buf[..size].to_vec(), Peer::Net(addr), Box::new(stream)))
+                            }
+                            Some(key) => match hmac::verify_command(key, &buf[..size], unix_now())
+                            {
+                                Ok(command) => {
+                                    Some((command.to_vec(), Peer::Net(addr), Box::new(stream)))
                                 }
-                                Err(_) => {
-                                    let _ = stream.write_all(b"ERROR");
+                                Err(reason) => {
+                                    log_warn(
+                                        &format!(
+                                            "Rejected control command from {}: {}",
+                                            addr, reason
+                                        ),
+                                        is_prod,
+                                    );
+                                    let _ = stream.write_all(b"ERR:AUTH");
+                                    None
                                 }
-                            }
-                        } else if received == WAN_IP_ADDR {
+                            },
+                        },
+                        _ => None,
+                    }
+                }
+            };
+        let request = request.or_else(|| {
+            let mut stream = local_control.as_ref()?.accept().ok()?;
+            let mut buf = [0u8; 64];
+            match stream.read(&mut buf) {
+                Ok(size) if size > 0 => Some((buf[..size].to_vec(), Peer::Local, Box::new(stream))),
+                _ => None,
+            }
+        });
+        let request = request.or_else(|| {
+            let (received, addr, stream) = http_server.as_mut()?.poll_command()?;
+            Some((received, Peer::Net(addr), stream))
+        });
+        if let Some((received, addr, mut stream)) = request {
+            let received = received.as_slice();
+            // RESTART:<服务>、KILL:<服务>，RESTART_ADBD、KILL_ADBD 是 adbd 的别名
+            let service_command = services::parse_command(received);
+
+            if !config.enable_adbd_control
+                && (received == DISABLE_ADB
+                    || service_command.is_some_and(|(_, name)| name == "adbd"))
+            {
+                log_message(
+                    &format!("Ignoring adbd command from {}: adbd control disabled", addr),
+                    is_prod,
+                );
+                let _ = stream.write_all(b"DISABLED");
+            } else if let Some((verb, name)) = service_command {
+                let service = services::effective(&config.services, config.adbd_kill_settle)
+                    .into_iter()
+                    .find(|service| service.name == name);
+                match (service, verb) {
+                    (None, _) => {
+                        log_warn(&format!("Unknown service '{}' from {}", name, addr), is_prod);
+                        let _ = stream.write_all(b"ERR:UNKNOWN_SERVICE");
+                    }
+                    (Some(service), Verb::Restart) => {
+                        log_message(
+                            &format!("Received restart signal for {} from {}", name, addr),
+                            is_prod,
+                        );
+                        let young = procs::youngest_by_name(service.process_name())
+                            .filter(|(_, age)| *age < SERVICE_MIN_RESTART_AGE);
+                        if service_restarts.iter().any(|(s, _)| s.name == service.name) {
+                            log_message(&format!("{} restart already in progress", name), is_prod);
+                            let _ = stream.write_all(b"BUSY");
+                        } else if let Some((pid, age)) = young {
+                            // 重启在后台线程里执行，最短运行时间的检查要在回复前做，否则会回复 OK
                             log_message(
-                                &format!("Received get wanip query from {}", addr),
+                                &format!(
+                                    "Not restarting {} (PID: {}): started {}s ago",
+                                    name,
+                                    pid,
+                                    age.as_secs()
+                                ),
                                 is_prod,
                             );
-                            let wan1_ip = get_wan_ip_address(is_prod);
-                            let _ = stream.write_all(wan1_ip.trim().as_bytes());
+                            let _ = stream.write_all(b"REFUSED: too young");
+                        } else {
+                            let rx = spawn_service_restart(service.clone(), is_prod);
+                            service_restarts.push((service, rx));
+                            let _ = stream.write_all(b"OK");
                         }
                     }
-                    _ => {}
+                    (Some(service), Verb::Kill) => {
+                        log_message(
+                            &format!("Received kill signal for {} from {}", name, addr),
+                            is_prod,
+                        );
+                        handle_kill_service(&service, &notifier, is_prod);
+                        let _ = stream.write_all(b"OK");
+                    }
                 }
+            } else if received == DISABLE_ADB {
+                log_message(
+                    &format!("Received disable adb signal from {}", addr),
+                    is_prod,
+                );
+                handle_disable_adb(&notifier, is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == RESTART_SIGNAL_SERVER {
+                log_message(
+                    &format!("Received reboot signal from {}", addr),
+                    is_prod,
+                );
+                fail_hours.save();
+                if let Some(log) = sample_log.as_mut() {
+                    let _ = log.flush();
+                }
+                handle_restart_server(
+                    &mut system,
+                    &mut boot_record,
+                    &mut reboot_guard,
+                    &mut hooks,
+                    &notifier,
+                    is_prod,
+                );
+                let _ = stream.write_all(b"OK");
+            } else if received == RESTART_SIGNAL_GOAHEAD {
+                log_message(
+                    &format!("Received restart goahead signal from {}", addr),
+                    is_prod,
+                );
+                handle_restart_goahead(&notifier, is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == REDUCE_KERNEL_LOAD {
+                log_message(
+                    &format!("Received reduce kernel load signal from {}", addr),
+                    is_prod,
+                );
+                handle_reduce_kernel_load(&notifier, is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == ENABLE_MEMORY_MONITOR {
+                log_message(
+                    &format!("Received enable memory monitor signal from {}", addr),
+                    is_prod,
+                );
+                memory_monitor.enable(is_prod);
+                notifier.send("MEMORY_MONITOR_ENABLED", is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == DISABLE_MEMORY_MONITOR {
+                log_message(
+                    &format!("Received disable memory monitor signal from {}", addr),
+                    is_prod,
+                );
+                memory_monitor.disable(is_prod);
+                notifier.send("MEMORY_MONITOR_DISABLED", is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == SIGNAL_PING {
+                let _ = stream.write_all(b"OK");
+            } else if received == SIGNAL_PING2 {
+                let load = if high_load.is_active() {
+                    "high"
+                } else if state.high_latency_count >= config.max_high_latency {
+                    "throttled"
+                } else {
+                    "normal"
+                };
+                let reply = state.summary_line(
+                    started_at.elapsed().as_secs(),
+                    cpu_monitor.usage,
+                    load,
+                    &check_target,
+                    lease_watch.clients,
+                );
+                let _ = stream.write_all(reply.as_bytes());
+            } else if received == KILL_SIGNAL_RADVD {
+                log_message(
+                    &format!("Received kill radvd signal from {}", addr),
+                    is_prod,
+                );
+                handle_kill_radvd(&notifier, is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == ADJUST_ZRAM {
+                log_message(
+                    &format!("Received adjust zram signal from {}", addr),
+                    is_prod,
+                );
+                handle_adjust_zram(&notifier, is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == RESTORE_FIREWALL {
+                log_message(
+                    &format!("Received restore firewall signal from {}", addr),
+                    is_prod,
+                );
+                let reply = match iptables::restore_firewall(&mut system, storage.root(), is_prod) {
+                    Ok(files) => format!("OK restored={}", files),
+                    Err(e) => {
+                        log_warn(&format!("Firewall restore failed: {}", e), is_prod);
+                        format!("ERR:{}", e)
+                    }
+                };
+                let _ = stream.write_all(reply.as_bytes());
+            } else if received == KILL_SIGNAL_GOAHEAD {
+                log_message(
+                    &format!("Received kill goahead signal from {}", addr),
+                    is_prod,
+                );
+                handle_kill_goahead(&notifier, is_prod);
+                let _ = stream.write_all(b"OK");
+            } else if received == SIGNAL_ADBD_STATUS {
+                let reply = match procs::youngest_by_name("adbd") {
+                    Some((pid, age)) => format!(
+                        "adbd pid={} age={} age_secs={}",
+                        pid,
+                        procs::format_age(age),
+                        age.as_secs()
+                    ),
+                    None => "adbd not running".to_string(),
+                };
+                let _ = stream.write_all(reply.as_bytes());
+            } else if received == SIGNAL_ADBD_AUDIT {
+                log_message(&format!("Received adbd audit request from {}", addr), is_prod);
+                let check_firewall = config.enable_iptables
+                    && iptables.variant != iptables::Variant::Missing;
+                let report = audit_adbd(&mut system, check_firewall);
+                let restrict_to = lan_subnet.as_deref().filter(|_| config.adbd_restrict_lan);
+                handle_adbd_audit(
+                    &report,
+                    &mut adbd_exposed,
+                    restrict_to,
+                    &mut maintenance,
+                    &mut system,
+                    &notifier,
+                    is_prod,
+                );
+                last_adbd_audit = Some(now);
+                let _ = stream.write_all(report.describe().as_bytes());
+            } else if received == USB_FUNCTIONS {
+                log_message(
+                    &format!("Received usb functions query from {}", addr),
+                    is_prod,
+                );
+                match fs::read_to_string("/sys/class/android_usb/android0/functions") {
+                    Ok(content) => {
+                        let _ = stream.write_all(content.trim().as_bytes());
+                    }
+                    Err(_) => {
+                        let _ = stream.write_all(b"ERROR");
+                    }
+                }
+            } else if received == WAN_IP_ADDR {
+                log_message(
+                    &format!("Received get wanip query from {}", addr),
+                    is_prod,
+                );
+                let wan1_ip = get_wan_ip_address(is_prod);
+                let _ = stream.write_all(wan1_ip.trim().as_bytes());
+            } else if received == SIGNAL_STATUS {
+                let _ = stream.write_all(status_lines.join("\n").as_bytes());
+            } else if received == SIGNAL_CONFIG {
+                let _ = stream.write_all(config.dump_lines().join("\n").as_bytes());
+            } else if received == SIGNAL_SYSCTL_DUMP {
+                let lines = sysctl::dump_lines(
+                    &managed_sysctl_paths(&config),
+                    restore_guard.vm_throttle.originals(),
+                    sysctl::read_current,
+                );
+                let _ = stream.write_all(lines.join("\n").as_bytes());
+            } else if received == SIGNAL_STATS {
+                let mut lines = latency_histogram.stats_lines(Instant::now());
+                lines.push(fail_hours.summary(config.fail_pattern_share_percent));
+                lines.push(tick_budget.status_line());
+                let _ = stream.write_all(lines.join("\n").as_bytes());
+            } else if received == SIGNAL_OUTAGES {
+                let lines = outages.reply_lines(unix_now(), Instant::now());
+                let _ = stream.write_all(lines.join("\n").as_bytes());
+            } else if received == SIGNAL_SYSINFO {
+                let _ = stream.write_all(sysinfo.full_lines().join("\n").as_bytes());
+            } else if received == SIGNAL_PROFILE {
+                let reply = format!("OK profile={}", config.profile.name());
+                let _ = stream.write_all(reply.as_bytes());
+            } else if received == SIGNAL_MAINTENANCE_ON {
+                log_message(&format!("Received maintenance on from {}", addr), is_prod);
+                let reply = match maintenance.enable(unix_now(), config.maintenance_timeout) {
+                    Ok(_) => {
+                        let timeout = config.maintenance_timeout.as_secs();
+                        log_warn(
+                            &format!("Maintenance mode on for {}s, actions paused", timeout),
+                            is_prod,
+                        );
+                        notifier.send(&format!("MAINTENANCE_ON: TIMEOUT={}s", timeout), is_prod);
+                        format!("OK maintenance=on timeout={}s", timeout)
+                    }
+                    Err(e) => {
+                        log_warn(&format!("Failed to persist maintenance mode: {}", e), is_prod);
+                        format!("OK maintenance=on (not persisted: {})", e)
+                    }
+                };
+                let _ = stream.write_all(reply.as_bytes());
+            } else if received == SIGNAL_MAINTENANCE_OFF {
+                log_message(&format!("Received maintenance off from {}", addr), is_prod);
+                let suppressed = maintenance.suppressed;
+                let was_active = maintenance.disable().unwrap_or_else(|e| {
+                    log_warn(&format!("Failed to remove maintenance file: {}", e), is_prod);
+                    true
+                });
+                if was_active {
+                    log_warn(
+                        &format!("Maintenance mode off, {} action(s) were skipped", suppressed),
+                        is_prod,
+                    );
+                    notifier.send(&format!("MAINTENANCE_OFF: SUPPRESSED={}", suppressed), is_prod);
+                }
+                let _ = stream.write_all(b"OK maintenance=off");
+            } else if let Some(name) = received.strip_prefix(SIGNAL_PROFILE_SET) {
+                log_message(
+                    &format!("Received profile change from {}", addr),
+                    is_prod,
+                );
+                let throttled = state.high_latency_count >= config.max_high_latency;
+                let reply = handle_set_profile(
+                    name,
+                    &mut config,
+                    &storage.path(profile::PROFILE_FILE),
+                    throttled,
+                    &mut restore_guard.tuning,
+                    &notifier,
+                    is_prod,
+                );
+                restore_guard.config = config.clone();
+                let _ = stream.write_all(reply.as_bytes());
+            } else {
+                signal_listener.record_unknown(&addr, received, is_prod);
+                let _ = stream.write_all(b"ERR:UNKNOWN_CMD");
             }
         }
 
-        if now.duration_since(last_snat_check) >= Duration::from_secs(SNAT_CHECK_INTERVAL) {
-            let wan1_ip = get_wan_ip_address(is_prod);
+        tick.lap(Phase::Commands);
 
-            if !wan1_ip.is_empty() && wan1_ip != current_snat_wan_ip {
-                // 先添加新规则到第一行（确保新规则立即生效，对运行系统影响最小）
-                let source = format!("{}/32", target_sock_ip);
-                if Command::new("iptables")
-                    .args(["-t", "nat", "-I", "POSTROUTING", "-s", &source, "-o", "wan1", "-j", "NETMAP", "--to", &wan1_ip])
-                    .status()
-                    .is_ok()
-                {
-                    log_message(
-                        &format!("SNAT rule added: {} -> {}", target_sock_ip, wan1_ip),
-                        is_prod,
-                    );
-                    
-                    // 新规则添加成功后，删除旧规则（如果有）
-                    if !current_snat_wan_ip.is_empty() {
-                        if Command::new("iptables")
-                            .args(["-t", "nat", "-D", "POSTROUTING", "-s", &source, "-o", "wan1", "-j", "NETMAP", "--to", &current_snat_wan_ip])
-                            .status()
-                            .is_ok()
-                        {
-                            log_message(
-                                &format!("Old SNAT rule deleted: {} -> {}", target_sock_ip, current_snat_wan_ip),
-                                is_prod,
-                            );
+        if now.duration_since(last_cpu_check) >= Duration::from_secs(CPU_CHECK_INTERVAL) {
+            if config.enable_cpu_monitor {
+                if let Some(usage) = cpu_monitor.sample(&mut system) {
+                    if let Some(log) = sample_log.as_mut() {
+                        if let Err(e) = log.cpu(unix_now(), usage, now) {
+                            log_warn(&format!("Failed to write samples: {}", e), is_prod);
                         }
                     }
-                    
-                    // 更新当前记录的 WAN IP
-                    current_snat_wan_ip = wan1_ip;
+                    if let Some(event) = high_load.update(usage, now) {
+                        match event {
+                            LoadEvent::Enter(usage) => hooks.fire(
+                                HookEvent::HighLoadEnter,
+                                &[("ZXP_CPU", format!("{:.1}", usage))],
+                                now,
+                            ),
+                            LoadEvent::Exit(usage, duration) => hooks.fire(
+                                HookEvent::HighLoadExit,
+                                &[
+                                    ("ZXP_CPU", format!("{:.1}", usage)),
+                                    ("ZXP_DURATION_SECS", duration.as_secs().to_string()),
+                                ],
+                                now,
+                            ),
+                            _ => {}
+                        }
+                        handle_load_event(event, &notifier, is_prod);
+                    }
+                }
+                // 高负载期间找出最耗 CPU 的进程
+                if high_load.is_active() {
+                    let top = top_tracker.sample(now);
+                    last_top = top.clone();
+                    match runaway_guard.update(top, &config, now) {
+                        Some(RunawayAction::Kill(top)) if maintenance.is_active() => {
+                            let what = format!("RUNAWAY_KILL:{}", top.name);
+                            suppress_action(&mut maintenance, &what, &notifier, is_prod);
+                        }
+                        Some(action) => {
+                            handle_runaway(action, &mut runaway_guard, &config, &notifier, is_prod)
+                        }
+                        None => {}
+                    }
                 } else {
-                    log_message(&format!("Failed to add SNAT rule to {}", wan1_ip), is_prod);
+                    top_tracker = TopTracker::new();
+                    last_top = None;
+                    runaway_guard.update(None, &config, now);
+                }
+            }
+            if !maintenance.is_active() {
+                memory_monitor.check_cache_pressure(&config, is_prod);
+            }
+            if let Some(stat) = SockStat::read() {
+                log_debug(&format!("sockstat: {}", stat.summary()), is_prod);
+                if let Some(event) = sockstat_monitor.update(
+                    stat,
+                    config.sock_tcp_inuse_max,
+                    config.sock_tcp_orphan_max,
+                ) {
+                    let tune = !maintenance.is_active();
+                    handle_socket_pressure(
+                        event,
+                        &config,
+                        tune,
+                        &mut restore_guard.time_wait_throttled,
+                        &notifier,
+                        is_prod,
+                    );
+                }
+            }
+            if let Some(stat) = FileNr::read() {
+                log_debug(&format!("file-nr: {}", stat.summary()), is_prod);
+                if let Some(level) =
+                    fd_monitor.update(stat, config.fd_warn_percent, config.fd_critical_percent)
+                {
+                    handle_fd_level(level, stat, &notifier, is_prod);
                 }
             }
+            last_cpu_check = now;
+        }
+
+        tick.lap(Phase::Cpu);
+        // 推迟的优化应用之前也不更新 SNAT 规则
+        if config.enable_iptables
+            && !tune_gate.is_pending()
+            && now.duration_since(last_snat_check) >= Duration::from_secs(SNAT_CHECK_INTERVAL)
+        {
+            let wan1_ip = get_wan_ip_address(is_prod);
+            if !wan1_ip.is_empty()
+                && !wan_netmap.sync(&mut system, &mut iptables, &target_sock_ip, &wan1_ip, is_prod)
+            {
+                log_message(&format!("Failed to add SNAT rule to {}", wan1_ip), is_prod);
+            }
+            if let Some(message) = iptables.take_notification() {
+                notifier.send(&message, is_prod);
+            }
             last_snat_check = now;
         }
 
+        // 长连接被 keepalive 判定断开时不等下一个检查周期，立即重连检查
+        let keepalive_broken = match keepalive_link.as_mut().and_then(KeepaliveLink::poll_break) {
+            Some(reason) => {
+                log_warn(
+                    &format!("Keepalive connection to {} broken: {}", check_target, reason),
+                    is_prod,
+                );
+                true
+            }
+            None => false,
+        };
+
+        tick.lap(Phase::Other);
         // 网络连通性检查 - 根据负载模式调整间隔
-        if now.duration_since(last_network_check) >= Duration::from_secs(PING_INTERVAL) {
-            match check_connectivity(&target_ip, is_prod) {
-                (true, Some(connect_duration)) => {
-                    if connect_duration.as_millis() > HIGH_LATENCY_THRESHOLD {
-                        high_latency_count += 1;
+        if config.enable_network_monitor
+            && (keepalive_broken
+                || now.duration_since(last_network_check) >= Duration::from_secs(PING_INTERVAL))
+        {
+            // 网关不通说明是本地问题，不再等待远程检查超时
+            let gateway_down = config.gateway_probe
+                && match gateway_probe.check(&mut system, now) {
+                    Some((gateway, false)) => {
                         log_message(
-                            &format!(
-                                "High latency detected: {}ms (> {}ms)",
-                                connect_duration.as_millis(),
-                                HIGH_LATENCY_THRESHOLD
-                            ),
+                            &format!("Gateway {} unreachable, skipping remote check", gateway),
                             is_prod,
                         );
+                        true
+                    }
+                    _ => false,
+                };
+            let mut rtt_measured = true;
+            let check = if gateway_down {
+                Err(FailureReason::GatewayDown)
+            } else if let Some(ip) = arp_target {
+                arp::probe(ip, CONNECT_TIMEOUT).map_err(|e| {
+                    log_message(&format!("ARP probe failed: {}", e), is_prod);
+                    FailureReason::from_io_error(&e)
+                })
+            } else if let Some(addr) = http_target {
+                match http::probe(addr, CONNECT_TIMEOUT, http::RESPONSE_TIMEOUT) {
+                    Ok(timing) => {
+                        if let Some(message) = slow_server.record(timing, config.ttfb_threshold) {
+                            if slow_server.is_slow() {
+                                log_warn(&message, is_prod);
+                            } else {
+                                log_message(&message, is_prod);
+                            }
+                            notifier.send(&message, is_prod);
+                        }
+                        Ok(timing.connect)
+                    }
+                    Err(e) => {
+                        log_message(&format!("HTTP probe failed: {}", e), is_prod);
+                        Err(FailureReason::from_io_error(&e))
+                    }
+                }
+            } else if let Some(addr) = udp_target {
+                let result = udpecho::probe(addr, &udp_echo, CONNECT_TIMEOUT);
+                udp_stats.record(&result);
+                match result {
+                    Ok(udpecho::EchoReply::Matched(rtt)) => Ok(rtt),
+                    Ok(udpecho::EchoReply::Mismatched(rtt)) => {
                         log_message(
                             &format!(
-                                "High latency count: {}/{}",
-                                high_latency_count, MAX_HIGH_LATENCY
+                                "UDP echo from {} did not match the payload (mismatched: {})",
+                                addr, udp_stats.mismatched
                             ),
                             is_prod,
                         );
-
-                        send_udp_notification(
-                            &format!("HIGH_LATENCY: LATENCY={:.1}", connect_duration.as_millis()),
-                            target_ip.clone(),
-                            is_prod,
-                        );
-                        if connect_duration.as_millis() > HIGH_LATENCY_THRESHOLD_MAX
-                            && high_latency_count < MAX_HIGH_LATENCY
-                        {
-                            high_latency_count = MAX_HIGH_LATENCY
+                        if udp_echo.strict {
+                            Err(FailureReason::Other)
+                        } else {
+                            Ok(rtt)
                         }
+                    }
+                    Err(e) => {
+                        log_message(&format!("UDP echo failed: {}", e), is_prod);
+                        Err(FailureReason::from_io_error(&e))
+                    }
+                }
+            } else if config.probe == ProbeMethod::Hybrid {
+                let result = hybrid_check(&mut system, &check_target, is_prod);
+                if let Some(message) = hybrid_stats.record(result.ok().map(|(via, _)| via)) {
+                    log_message(&message, is_prod);
+                }
+                result.map(|(_, rtt)| rtt)
+            } else if let Some(multi) = multi_port.as_mut().filter(|_| target_fallback.is_none()) {
+                let previous = multi.satisfied_by;
+                let result = multi.check(CONNECT_TIMEOUT);
+                match result {
+                    Ok((port, _)) if previous != Some(port) => log_message(
+                        &format!("Port {} of {} satisfied the check", port, target_sock_ip),
+                        is_prod,
+                    ),
+                    Ok((port, _)) => {
+                        log_debug(&format!("Port {} satisfied the check", port), is_prod)
+                    }
+                    Err(reason) => log_message(
+                        &format!(
+                            "All ports of {} failed (last: {})",
+                            target_sock_ip,
+                            reason.as_str()
+                        ),
+                        is_prod,
+                    ),
+                }
+                result.map(|(_, rtt)| rtt)
+            } else if let Some(link) = keepalive_link.as_mut() {
+                link.check(CONNECT_TIMEOUT)
+                    .map(|rtt| {
+                        // 长连接还在时没有这次检查的 RTT
+                        rtt_measured = rtt.is_some();
+                        rtt.unwrap_or_default()
+                    })
+                    .map_err(|e| {
+                        log_message(&format!("TCP connect failed: {}", e), is_prod);
+                        FailureReason::from_io_error(&e)
+                    })
+            } else {
+                system.check_connectivity(&check_target)
+            };
+            tick.lap(Phase::Probe);
+            let (connected, rtt) = (check.is_ok(), check.ok().filter(|_| rtt_measured));
+            if let Some(trigger) = tune_gate.update(connected, now) {
+                if trigger == TuneTrigger::Deadline {
+                    log_warn(
+                        &format!(
+                            "No successful check within {}s, applying deferred network tuning anyway",
+                            config.tune_deadline.as_secs()
+                        ),
+                        is_prod,
+                    );
+                } else {
+                    log_message(
+                        "First successful check, applying deferred network tuning",
+                        is_prod,
+                    );
+                }
+                apply_startup_tuning(
+                    &mut system,
+                    &config,
+                    &mut iptables,
+                    storage.root(),
+                    &notifier,
+                    is_prod,
+                    target_ip.clone(),
+                );
+                sync_wan_netmap(
+                    &mut system,
+                    &config,
+                    &mut iptables,
+                    &mut wan_netmap,
+                    &target_sock_ip,
+                    is_prod,
+                );
+                sync_lan_masquerade(
+                    &mut system,
+                    &config,
+                    &mut iptables,
+                    &mut lan_masquerade,
+                    lan_subnet.as_deref(),
+                    is_prod,
+                );
+            }
+            if let Some(rtt) = rtt {
+                latency_histogram.record(rtt.as_millis());
+            }
+            if let Some(log) = sample_log.as_mut() {
+                let result = check.err().map_or("ok", |reason| reason.as_str());
+                if let Err(e) = log.check(unix_now(), rtt, result, now) {
+                    log_warn(&format!("Failed to write samples: {}", e), is_prod);
+                }
+            }
+            if !connected {
+                // 时钟不可信时不计入，避免把失败记到错误的小时
+                fail_hours.record(logprune::local_wall_clock());
+            }
+            if let Some(summary) = latency_histogram.rotate(now) {
+                let peak = lease_watch.take_peak();
+                let peak = peak.map_or("unknown".to_string(), |peak| peak.to_string());
+                if let Some((today, _)) = logprune::local_wall_clock() {
+                    fail_hours.prune(today);
+                }
+                let fail_summary = format!(
+                    "{} {}",
+                    fail_hours.summary(config.fail_pattern_share_percent),
+                    outages.last_summary()
+                );
+                log_message(
+                    &format!(
+                        "Daily latency histogram: {} (peak clients {}), {}",
+                        summary, peak, fail_summary
+                    ),
+                    is_prod,
+                );
+                notifier.send(
+                    &format!(
+                        "LATENCY_DAILY: {} CLIENTS_PEAK={} {}",
+                        summary, peak, fail_summary
+                    ),
+                    is_prod,
+                );
+            }
+            fail_hours.save_if_due(now);
+            let inputs = CycleInputs {
+                target: &check_target,
+                connected,
+                rtt,
+                failure: check.err(),
+                reboot_allowed: reboot_guard.allowed(now),
+                probe_running: path_probe.is_some(),
+                high_load: high_load.is_active(),
+                now,
+            };
+            let previous_failures = state.failure_count;
+            let outage = state.outage(now);
+            let actions = monitor::step(&mut state, &config, inputs);
+            if !connected && previous_failures == 0 {
+                hooks.fire(
+                    HookEvent::ConnectivityLost,
+                    &[
+                        ("ZXP_TARGET", check_target.clone()),
+                        (
+                            "ZXP_REASON",
+                            check.err().map_or("", |reason| reason.as_str()).to_string(),
+                        ),
+                    ],
+                    now,
+                );
+            } else if connected && previous_failures > 0 {
+                hooks.fire(
+                    HookEvent::ConnectivityRestored,
+                    &[
+                        ("ZXP_TARGET", check_target.clone()),
+                        ("ZXP_FAILURE_COUNT", previous_failures.to_string()),
+                        ("ZXP_OUTAGE_SECS", outage.as_secs().to_string()),
+                        (
+                            "ZXP_LATENCY_MS",
+                            rtt.map(|d| d.as_millis().to_string()).unwrap_or_default(),
+                        ),
+                    ],
+                    now,
+                );
+            }
+            // 断网开始和恢复时写入断网记录（进程重启后恢复的失败计数也按新的断网记录）
+            if !connected {
+                if !outages.is_ongoing() {
+                    outages.begin(unix_now(), state.failure_count, now);
+                }
+                outages.update(state.failure_count);
+            } else if let Some(outage) = outages.end(unix_now(), now) {
+                log_message(&format!("Outage recorded: {}", outage.describe()), is_prod);
+            }
+            // 备用目标检查期间，原目标连续可达满 target_restore_secs 后恢复
+            if let Some(fallback) = target_fallback.take_if(|f| f.poll(CONNECT_TIMEOUT)) {
+                log_message(
+                    &format!(
+                        "Original target {} reachable for {}s, switching back from {}",
+                        fallback.original,
+                        config.target_restore_after.as_secs(),
+                        check_target
+                    ),
+                    is_prod,
+                );
+                notifier.send(
+                    &format!(
+                        "TARGET_RESTORED: TARGET={} FALLBACK={}",
+                        fallback.original, check_target
+                    ),
+                    is_prod,
+                );
+                check_target = fallback.original;
+                if keepalive_link.is_some() {
+                    keepalive_link =
+                        check_target.parse::<SocketAddr>().ok().map(KeepaliveLink::new);
+                }
+                if http_target.is_some() {
+                    http_target = check_target.parse::<SocketAddr>().ok();
+                }
+                if udp_target.is_some() {
+                    udp_target = udp_echo.target(&check_target);
+                }
+            }
 
-                        if high_latency_count == MAX_HIGH_LATENCY {
+            // 同时有多个保护条件时只让优先级最高的一个主导，避免相互抵消
+            let mut active = Vec::new();
+            if memory_monitor.low_memory {
+                active.push(Condition::Memory);
+            }
+            if high_load.is_active() {
+                active.push(Condition::Load);
+            }
+            if sockstat_monitor.in_pressure() {
+                active.push(Condition::Socket);
+            }
+            if state.high_latency_count >= config.max_high_latency {
+                active.push(Condition::Latency);
+            }
+            if arbiter.update(active, &config.condition_priority) {
+                log_message(&format!("Dominant condition: {}", arbiter.status_line()), is_prod);
+            }
+            if arbiter.pending_restore
+                && !maintenance.is_active()
+                && !arbiter.outranked(Condition::Latency, &config.condition_priority)
+            {
+                log_message("Running deferred latency restore", is_prod);
+                arbiter.pending_restore = false;
+                restore_after_latency(
+                    &config,
+                    &mut restore_guard.vm_throttle,
+                    &mut restore_guard.tuning,
+                    is_prod,
+                );
+            }
+
+            for action in actions {
+                if let Some(name) = action.protective_name().filter(|_| maintenance.is_active()) {
+                    suppress_action(&mut maintenance, name, &notifier, is_prod);
+                    if action == Action::Restore {
+                        // 维护结束后再恢复，避免之前的限流一直保留
+                        arbiter.pending_restore = true;
+                    }
+                    continue;
+                }
+                match action {
+                    Action::Log(level, message) => log_at(level, &message, is_prod),
+                    Action::Notify(message) => notifier.send(&message, is_prod),
+                    Action::Throttle => {
+                        if config.enable_adbd_control {
+                            let _ = force_kill_process(is_prod, "adbd");
+                        }
+                        let _ = force_kill_process(is_prod, "goahead");
+                        let set = conntrack_set(&config, Intent::Throttle, "THROTTLE");
+                        restore_guard.tuning.submit(set);
+                        let changes = restore_guard.vm_throttle.enter(vmtune::read_value);
+                        apply_vm_changes(&changes, is_prod);
+                        arbiter.pending_restore = false;
+                    }
+                    Action::Restore => {
+                        if arbiter.outranked(Condition::Latency, &config.condition_priority) {
+                            // 重新启动 goahead 会抵消更高优先级条件的处理，等它结束再恢复
                             log_message(
-                                &format!(
-                                    "WARN: {} consecutive high latency connections detected",
-                                    MAX_HIGH_LATENCY
-                                ),
+                                &format!("Deferring latency restore: {}", arbiter.status_line()),
                                 is_prod,
                             );
-                            let _ = force_kill_process(is_prod, "adbd");
-                            let _ = force_kill_process(is_prod, "goahead");
-                            throttle_network_parameters(is_prod);
-                        }
-                    } else {
-                        if high_latency_count >= MAX_HIGH_LATENCY {
-                            if connect_duration.as_millis() < HIGH_LATENCY_THRESHOLD_MIN {
-                                restore_network_parameters(is_prod);
-                                let _ = force_start_goahead_process(is_prod);
-                                clear_page_cache(is_prod);
-                                high_latency_count = 1
-                            } else {
-                                high_latency_count = MAX_HIGH_LATENCY
-                            }
+                            arbiter.pending_restore = true;
                         } else {
-                            high_latency_count = high_latency_count.saturating_sub(1);
+                            restore_after_latency(
+                                &config,
+                                &mut restore_guard.vm_throttle,
+                                &mut restore_guard.tuning,
+                                is_prod,
+                            );
+                        }
+                    }
+                    Action::ShedLoad => {
+                        outages.escalate(Recovery::Escalation, unix_now(), now);
+                        // 断网很可能是负载造成的：先卸载负载，保留现场
+                        if config.enable_adbd_control {
+                            let _ = force_kill_process(is_prod, "adbd");
                         }
-                        send_udp_notification(
+                        let _ = force_kill_process(is_prod, "goahead");
+                        let set = conntrack_set(&config, Intent::Throttle, "SHED_LOAD");
+                        restore_guard.tuning.submit(set);
+                        let changes = restore_guard.vm_throttle.enter(vmtune::read_value);
+                        apply_vm_changes(&changes, is_prod);
+                        clear_page_cache(0, is_prod);
+                        let top = last_top
+                            .as_ref()
+                            .map(|top| {
+                                format!("{}({}) CPU={:.0}", top.name, top.pid, top.cpu_percent)
+                            })
+                            .unwrap_or_else(|| "-".to_string());
+                        log_warn(
+                            &format!("Load shed during outage, top process: {}", top),
+                            is_prod,
+                        );
+                        notifier.send(
                             &format!(
-                                "NORMAL_LATENCY: LATENCY={:.1}",
-                                connect_duration.as_millis()
+                                "LOAD_SHED: FAILURES={} BUDGET={} TOP={}",
+                                state.failure_count,
+                                monitor::failure_budget(true, &config),
+                                top
                             ),
-                            target_ip.clone(),
                             is_prod,
                         );
                     }
-                    failure_count = 0;
+                    Action::RebootSystem(reason) => {
+                        if config.reboot_local_check {
+                            let sanity = gateway_probe.local_sanity(
+                                &mut system,
+                                get_wan_ip_address(is_prod),
+                                now,
+                            );
+                            log_message(
+                                &format!("Pre-reboot local check: {}", sanity.describe()),
+                                is_prod,
+                            );
+                            if sanity.all_ok() {
+                                // 不退避的话之后每轮都会重新检查并通知
+                                let retry_in = reboot_guard.record_skip(Instant::now());
+                                log_message(
+                                    &format!(
+                                        "Local network looks healthy, only the target fails; skipping reboot, next attempt in {}s",
+                                        retry_in.as_secs()
+                                    ),
+                                    is_prod,
+                                );
+                                notifier.send(
+                                    &format!(
+                                        "REBOOT_SKIPPED_LOCAL_OK: {} RETRY_IN={}s",
+                                        sanity.describe(),
+                                        retry_in.as_secs()
+                                    ),
+                                    is_prod,
+                                );
+                                continue;
+                            }
+                        }
+                        let blamed = matches!(
+                            reason,
+                            monitor::RebootReason::Link
+                                | monitor::RebootReason::HighLoad
+                                | monitor::RebootReason::FailureRatio
+                        );
+                        boot_record.set_reboot_target(blamed.then_some(check_target.as_str()));
+                        // 断网记录要在重启前写入；被拦截或重启失败时退回
+                        let previous = outages.escalate(Recovery::Reboot, unix_now(), now);
+                        fail_hours.save();
+                        // 重启前写入缓存的采样，断网前后的记录最有用
+                        if let Some(log) = sample_log.as_mut() {
+                            let _ = log.flush();
+                        }
+                        reboot_system(
+                            &mut system,
+                            &mut boot_record,
+                            &mut reboot_guard,
+                            &mut hooks,
+                            &notifier,
+                            reason.as_str(),
+                            is_prod,
+                        );
+                        // 返回说明没有重启
+                        if let Some(previous) = previous {
+                            outages.withdraw(previous, unix_now(), Instant::now());
+                        }
+                    }
+                    Action::SetSeverity(severity) => {
+                        apply_severity_params(severity, is_prod);
+                        restore_guard.severity = severity;
+                    }
+                    Action::ClearPageCache => clear_page_cache(0, is_prod),
+                    Action::BounceInterface => {
+                        outages.escalate(Recovery::InterfaceRestart, unix_now(), now);
+                        log_warn(&format!("Bouncing {}", gateway::WAN_INTERFACE), is_prod);
+                        let cmd = format!(
+                            "ip link set {0} down && sleep 1 && ip link set {0} up",
+                            gateway::WAN_INTERFACE
+                        );
+                        match system.run_command(&cmd) {
+                            Ok(status) if status.success() => {}
+                            Ok(status) => {
+                                log_error(&format!("'{}' exited with {}", cmd, status), is_prod)
+                            }
+                            Err(e) => {
+                                log_error(&format!("Failed to run '{}': {}", cmd, e), is_prod)
+                            }
+                        }
+                    }
+                    Action::CheckConnManager => {
+                        let name = &config.conn_manager_process;
+                        let running = !procs::find_by_name(name).is_empty();
+                        let commands = conn_manager.plan(&config, running, now);
+                        if !commands.is_empty() {
+                            outages.escalate(Recovery::Escalation, unix_now(), now);
+                            log_message(&format!("{} is not running, restarting it", name), is_prod);
+                            for cmd in &commands {
+                                if let Err(e) = system.run_command(cmd) {
+                                    log_error(&format!("Failed to run '{}': {}", cmd, e), is_prod);
+                                }
+                            }
+                            notifier.send(&format!("CONN_MANAGER_RESTARTED: NAME={}", name), is_prod);
+                        }
+                    }
+                    Action::StartPathProbe => {
+                        if let Ok(ip) = target_sock_ip.parse::<IpAddr>() {
+                            log_message(&format!("Starting path probe to {}", ip), is_prod);
+                            path_probe = Some(PathProbe::start(ip));
+                        }
+                    }
                 }
-                (true, None) => {
-                    // 连接成功但没有获取到时间（理论上不应该发生，但需要处理）
-                    log_message(
-                        &format!(
-                            "✓ Connection to {} successful, but duration not measured",
-                            target_ip
-                        ),
-                        is_prod,
-                    );
-                    high_latency_count = 0;
-                    failure_count = 0;
+            }
+            // if failure_count == WARN_FAILURES {
+            //     log_message(
+            //         &format!(
+            //             "Critical: {} consecutive pre failure detected",
+            //             WARN_FAILURES
+            //         ),
+            //         is_prod,
+            //     );
+            //     log_message("try reset android usb...", is_prod);
+            //     reset_android_usb(is_prod);
+            // } else if failure_count == config.max_failures {
+            //     log_message(
+            //         &format!("Critical: {} consecutive failures detected", config.max_failures),
+            //         is_prod,
+            //     );
+            //     log_message("Initiating system reboot...", is_prod);
+            //     reboot_system(is_prod);
+            // }
+            last_network_check = now;
+        }
+
+        // 路径探测在后台线程进行，这里只取结果
+        if let Some(result) = path_probe.as_ref().and_then(|probe| probe.poll(now)) {
+            log_message(&format!("Path to {}: {}", target_sock_ip, result), is_prod);
+            notifier.send(
+                &format!("OUTAGE: FAILURES={} {}", state.failure_count, result),
+                is_prod,
+            );
+            path_probe = None;
+        }
+
+        tick.lap(Phase::Net);
+        service_restarts.retain(|(service, rx)| match rx.try_recv() {
+            Ok(result) => {
+                if result.is_ok() && service.name == "adbd" {
+                    hooks.fire(HookEvent::AdbdRestarted, &[], now);
+                    adbd_ready.arm(now);
                 }
-                (false, _) => {
-                    log_message(&format!("✗ Connection to {} failed", target_ip), is_prod);
-                    failure_count += 1;
-                    log_message(
-                        &format!("Failure count: {}/{}", failure_count, MAX_FAILURES),
-                        is_prod,
-                    );
-                    // if failure_count == WARN_FAILURES {
-                    //     log_message(
-                    //         &format!(
-                    //             "Critical: {} consecutive pre failure detected",
-                    //             WARN_FAILURES
-                    //         ),
-                    //         is_prod,
-                    //     );
-                    //     log_message("try reset android usb...", is_prod);
-                    //     reset_android_usb(is_prod);
-                    // } else if failure_count == MAX_FAILURES {
-                    //     log_message(
-                    //         &format!("Critical: {} consecutive failures detected", MAX_FAILURES),
-                    //         is_prod,
-                    //     );
-                    //     log_message("Initiating system reboot...", is_prod);
-                    //     reboot_system(is_prod);
-                    // }
+                report_service_restart(service, result, &notifier, is_prod);
+                false
+            }
+            Err(TryRecvError::Disconnected) => false,
+            Err(TryRecvError::Empty) => true,
+        });
+        if adbd_ready.is_due(now) {
+            let pid = procs::youngest_by_name("adbd").map(|(pid, _)| pid);
+            let usb_state = fs::read_to_string(adbdready::ANDROID_USB_STATE).ok();
+            match adbd_ready.check(now, pid, usb_state.as_deref()) {
+                adbdready::Readiness::Ready(detail) => {
+                    log_message(&format!("adbd ready: {}", detail), is_prod);
+                    notifier.send(&format!("ADBD_READY: {}", detail), is_prod);
                 }
+                adbdready::Readiness::NotReady(reason) => {
+                    log_warn(&format!("adbd not ready after restart: {}", reason), is_prod);
+                    notifier.send(&format!("ADBD_NOT_READY: REASON={}", reason), is_prod);
+                }
+                adbdready::Readiness::Waiting => {}
             }
-            last_network_check = now;
         }
 
         // KMSG 监控检查（在主循环中处理，无线程开销）
         // kmsg_monitor.check(&target_ip, is_prod);
 
-        // 内存监控检查（在主循环中处理，无线程开销）
-        memory_monitor.check(is_prod, &target_ip);
+        // 内存监控检查（在主循环中处理，无线程开销）；维护模式下不结束进程
+        if !maintenance.is_active() {
+            memory_monitor.check(is_prod, &target_ip);
+        }
+
+        // LED 状态指示（仅在模式变化时写 sysfs）
+        if let Some(led) = led.as_mut() {
+            led.set(led_pattern(&state, &config, high_load.is_active()));
+        }
+
+        // 默认路由检查：拨号脚本可能重复添加或删掉默认路由
+        if now.duration_since(last_route_check) >= Duration::from_secs(ROUTE_CHECK_INTERVAL) {
+            let anomaly = routes::read_default_routes().and_then(|r| route_watch.check(r));
+            if let Some(anomaly) = anomaly {
+                log_warn(&format!("Default route anomaly: {}", anomaly.describe()), is_prod);
+                notifier.send(&format!("ROUTE_ANOMALY: {}", anomaly.describe()), is_prod);
+                let repair = config.route_repair && !maintenance.is_active();
+                if let (RouteAnomaly::Missing(Some(last)), true) = (&anomaly, repair) {
+                    let cmd = format!(
+                        "ip route add default via {} dev {}",
+                        last.gateway, last.iface
+                    );
+                    match system.run_command(&cmd) {
+                        Ok(status) if status.success() => log_message(
+                            &format!("Default route restored: {}", last.describe()),
+                            is_prod,
+                        ),
+                        _ => log_warn(&format!("Failed to restore default route: {}", cmd), is_prod),
+                    }
+                }
+            }
+            last_route_check = now;
+        }
 
         // DNS配置检查 - 每隔120秒读取并发送dnsmasq.conf内容
         if now.duration_since(last_dns_config_check)
@@ -1062,7 +2688,7 @@ fn main() {
             match fs::read_to_string("/etc_rw/dnsmasq.conf") {
                 Ok(content) => {
                     let msg = format!("DNS_CONF: {}", content);
-                    send_udp_notification(&msg, target_ip.clone(), is_prod);
+                    notifier.send(&msg, is_prod);
                 }
                 Err(e) => {
                     log_message(
@@ -1085,30 +2711,219 @@ fn main() {
                         ),
                         is_prod,
                     );
-                    send_udp_notification(
+                    notifier.send(
                         &format!(
                             "SNTP_SYNC_OK: {} (server: {}, offset: {}s)",
                             time_str, server_used, offset_secs
                         ),
-                        target_ip.clone(),
                         is_prod,
                     );
                 }
                 Err(e) => {
                     log_message(&format!("SNTP sync failed: {}", e), is_prod);
-                    send_udp_notification(
-                        &format!("SNTP_SYNC_FAILED: {}", e),
-                        target_ip.clone(),
-                        is_prod,
-                    );
+                    notifier.send(&format!("SNTP_SYNC_FAILED: {}", e), is_prod);
                 }
             }
             last_sntp_check = now;
         }
 
+        // 按配置清空日志文件（只有后台运行且非生产模式时才写日志文件）；
+        // 存储目录下的日志连续清理失败时尝试修复存储，仍不可写则切到后备目录
+        let mut storage_switched = false;
+        if log_prune.due(now, logprune::local_wall_clock()) && is_background && !is_prod {
+            let result = logprune::truncate_log(&log_file_path(&config, &storage));
+            match &result {
+                Ok(bytes) => log_debug(&format!("Log pruned: {} bytes reclaimed", bytes), is_prod),
+                Err(e) => log_warn(&format!("Failed to prune log: {}", e), is_prod),
+            }
+            if storage.record_prune(result.is_ok()) && config.log_to.is_empty() {
+                storage_switched = remediate_storage(&mut storage, now, &notifier, is_prod);
+            }
+        }
+
+        // 存储降级时每天重新探测首选目录，恢复后切回
+        if storage.reprobe(now) {
+            log_message(
+                &format!("Storage {} is writable again, switching back", config.storage_root),
+                is_prod,
+            );
+            notifier.send("STORAGE_RESTORED", is_prod);
+            storage_switched = true;
+        }
+        if storage_switched {
+            boot_record.relocate(storage.path(boot::BOOT_RECORD_FILE));
+            if let Some(watchdog) = &watchdog {
+                watchdog.relocate(storage.path(boot::BOOT_RECORD_FILE));
+            }
+            fail_hours.relocate(storage.path(storage::STATS_FILE));
+            outages.relocate(storage.path(storage::STATS_FILE));
+            if let Some(log) = sample_log.as_mut() {
+                log.relocate(storage.path(samples::SAMPLES_FILE));
+            }
+            maintenance.relocate(storage.path(maintenance::MAINTENANCE_FILE));
+            if is_background && !is_prod && config.log_to.is_empty() {
+                reopen_log_output(&storage.path(LOG_FILE_NAME), is_prod);
+            }
+        }
+
+        // 固件可能让 adbd 监听在所有地址上且不需要认证，定期检查 5555 端口是否对外开放
+        if config.enable_adbd_control
+            && last_adbd_audit.is_none_or(|last| now.duration_since(last) >= ADBD_AUDIT_INTERVAL)
+        {
+            last_adbd_audit = Some(now);
+            let check_firewall =
+                config.enable_iptables && iptables.variant != iptables::Variant::Missing;
+            let report = audit_adbd(&mut system, check_firewall);
+            log_debug(&format!("adbd audit: {}", report.describe()), is_prod);
+            let restrict_to = lan_subnet.as_deref().filter(|_| config.adbd_restrict_lan);
+            handle_adbd_audit(
+                &report,
+                &mut adbd_exposed,
+                restrict_to,
+                &mut maintenance,
+                &mut system,
+                &notifier,
+                is_prod,
+            );
+        }
+
+        // 统计 DHCP 租约作为 LAN 客户端数；读不到时为 unknown，不记录日志
+        if last_lease_check.is_none_or(|last| now.duration_since(last) >= LEASE_CHECK_INTERVAL) {
+            last_lease_check = Some(now);
+            if let Some(was) = lease_watch.read(unix_now()) {
+                log_debug(&format!("DHCP clients dropped from {} to 0", was), is_prod);
+                if config.lan_empty_notify && state.failure_count == 0 {
+                    notifier.send(&format!("LAN_EMPTY: WAS={}", was), is_prod);
+                }
+            }
+        }
+
+        // 定期心跳：接收端没收到就说明设备或 zxic-ping 已经停了
+        if !config.heartbeat_interval.is_zero()
+            && now.duration_since(last_heartbeat) >= config.heartbeat_interval
+        {
+            last_heartbeat = now;
+            notifier.send(
+                &format!(
+                    "HEARTBEAT: UPTIME={}s TARGET={} FAILURES={} {} CLIENTS={}",
+                    started_at.elapsed().as_secs(),
+                    check_target,
+                    state.failure_count,
+                    state.heartbeat_fields(now),
+                    lease_watch.clients_str()
+                ),
+                is_prod,
+            );
+        }
+
+        // 定期重新检测 LAN 网段：未知或使用 assume_lan 时检测到后报告，DHCP 重新分配后报告变化
+        // （暂时检测不到时保留上次的网段）
+        if now.duration_since(last_lan_subnet_check) >= LAN_SUBNET_CHECK_INTERVAL {
+            last_lan_subnet_check = now;
+            if let Some(network) =
+                get_br_network().filter(|n| lan_subnet_assumed || lan_subnet.as_ref() != Some(n))
+            {
+                match &lan_subnet {
+                    None => {
+                        log_message(&format!("br0 network detected: {}", network), is_prod);
+                        notifier.send(&format!("LAN_SUBNET_FOUND: NETWORK={}", network), is_prod);
+                    }
+                    Some(old) if lan_subnet_assumed => {
+                        log_message(
+                            &format!("br0 network detected: {} (was assuming {})", network, old),
+                            is_prod,
+                        );
+                        notifier.send(
+                            &format!("LAN_SUBNET_FOUND: NETWORK={} ASSUMED={}", network, old),
+                            is_prod,
+                        );
+                    }
+                    Some(old) => {
+                        log_warn(&format!("br0 network changed: {} -> {}", old, network), is_prod);
+                        notifier.send(
+                            &format!("LAN_SUBNET_CHANGED: FROM={} TO={}", old, network),
+                            is_prod,
+                        );
+                    }
+                }
+                if let Some(ip) = arp_target {
+                    if !arp::in_subnet(ip, &network) {
+                        log_error(
+                            &format!("probe=arp target {} is outside the new LAN subnet", ip),
+                            is_prod,
+                        );
+                    } else if lan_subnet.is_none() {
+                        log_message(&format!("probe=arp target {} is in the LAN subnet", ip), is_prod);
+                    }
+                }
+                // 推迟的优化应用之前不装规则，应用时按当时的网段安装
+                if config.enable_iptables && !tune_gate.is_pending() {
+                    lan_masquerade.sync(&mut system, &mut iptables, &network, is_prod);
+                }
+                lan_subnet = Some(network);
+                lan_subnet_assumed = false;
+            }
+        }
+
+        tick.lap(Phase::Other);
+        let (total, phase, slowest) = tick.finish();
+        if let Some(event) = tick_budget.update(total, phase, Instant::now()) {
+            log_warn(
+                &format!(
+                    "Loop iteration took {}ms (budget {}ms), slowest phase: {} {}ms",
+                    total.as_millis(),
+                    config.tick_budget.as_millis(),
+                    phase.name(),
+                    slowest.as_millis()
+                ),
+                is_prod,
+            );
+            if let TickEvent::Repeated(count) = event {
+                notifier.send(
+                    &format!(
+                        "SLOW_TICK: COUNT={} TICK={}ms PHASE={} BUDGET={}ms",
+                        count,
+                        total.as_millis(),
+                        phase.name(),
+                        config.tick_budget.as_millis()
+                    ),
+                    is_prod,
+                );
+            }
+        }
+
         // 睡眠1秒后继续检查，避免忙等待
         thread::sleep(Duration::from_millis(2000));
     }
+
+    log_message("Shutdown signal received, cleaning up...", is_prod);
+    if let Err(e) = RuntimeState::capture(&state, notifier.sequence())
+        .save(Path::new(resume::STATE_FILE))
+    {
+        log_warn(&format!("Failed to save runtime state: {}", e), is_prod);
+    }
+    boot_record.mark_clean_shutdown();
+    fail_hours.save();
+    outages.save(unix_now(), Instant::now());
+    if let Some(log) = sample_log.as_mut() {
+        let _ = log.flush();
+    }
+    if let Some(led) = &led {
+        led.restore();
+    }
+}
+
+/// 根据监控状态和 CPU 高负载模式选择 LED 显示模式
+fn led_pattern(state: &MonitorState, config: &Config, high_load: bool) -> LedPattern {
+    if state.failure_count >= config.max_failures {
+        LedPattern::RebootPending
+    } else if high_load || state.high_latency_count >= config.max_high_latency {
+        LedPattern::FastBlink
+    } else if state.failure_count > 0 {
+        LedPattern::SlowBlink
+    } else {
+        LedPattern::Solid
+    }
 }
 
 pub struct ProcessPriority;
@@ -1142,35 +2957,398 @@ fn reset_android_usb(_is_prod: bool) {
     let _ = std::fs::write("/sys/class/android_usb/android0/enable", b"1\n");
 }
 
-fn throttle_network_parameters(is_prod: bool) {
-    // 调整TCP参数来减轻网络栈负担
-    if let Err(e) = std::fs::write("/proc/sys/net/nf_conntrack_max", b"4096\n") {
-        if !is_prod {
+/// 限流或恢复时写入的参数组，交给 TuningQueue 在后台线程一次写完
+fn conntrack_set(config: &Config, intent: Intent, stage: &'static str) -> TuningSet {
+    let conntrack_max = match intent {
+        Intent::Throttle => config.conntrack_max_throttled,
+        Intent::Restore => config.conntrack_max,
+    };
+    TuningSet {
+        intent,
+        stage,
+        profile: config.profile.name(),
+        writes: vec![("/proc/sys/net/nf_conntrack_max", conntrack_max.to_string())],
+    }
+}
+
+/// 维护模式下跳过一个保护动作：记录并通知
+fn suppress_action(maintenance: &mut Maintenance, what: &str, notifier: &Notifier, is_prod: bool) {
+    // 同一种动作在一次维护期间只通知一次，之后每轮跳过只记调试日志
+    if maintenance.suppress(what) {
+        log_warn(&format!("Maintenance mode: skipping {}", what), is_prod);
+        notifier.send(&format!("MAINTENANCE_SUPPRESSED: ACTION={}", what), is_prod);
+    } else {
+        log_debug(&format!("Maintenance mode: skipping {}", what), is_prod);
+    }
+}
+
+/// 后台写入完成后发送 TUNING 通知；与上一次相同而跳过的不通知
+fn report_tuning(outcome: &tuning::TuningOutcome, notifier: &Notifier, is_prod: bool) {
+    if outcome.superseded > 0 {
+        log_debug(
+            &format!(
+                "{} superseded {} queued tuning request(s)",
+                outcome.stage, outcome.superseded
+            ),
+            is_prod,
+        );
+    }
+    if outcome.skipped {
+        return;
+    }
+    for failed in &outcome.failed {
+        log_warn(&format!("Failed to adjust {}", failed), is_prod);
+    }
+    notifier.send(&outcome.notification(), is_prod);
+}
+
+/// 离开主循环时（SIGTERM 或 panic 展开）恢复被限流改过的网络参数，
+/// 避免异常退出后系统一直保持限流值。限流状态由主循环通过这里的字段维护
+struct NetworkRestoreGuard {
+    /// 与主循环的配置保持一致（切换档位后更新）
+    config: Config,
+    /// 后台写入 nf_conntrack_max 等参数组
+    tuning: TuningQueue,
+    vm_throttle: VmThrottle,
+    /// 最近一次写入的链路质量级别参数
+    severity: Severity,
+    /// 是否已为孤儿连接缩短 TIME_WAIT（压力解除时只恢复自己改过的）
+    time_wait_throttled: bool,
+    is_prod: bool,
+}
+
+impl Drop for NetworkRestoreGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            log_error("Panicked, restoring network parameters before exit", self.is_prod);
+        }
+        // 先停下后台写入，排队中的限流不会在恢复之后再写入
+        self.tuning.stop();
+        apply_vm_changes(&self.vm_throttle.exit(vmtune::read_value), self.is_prod);
+        if self.severity != Severity::Normal {
+            apply_severity_params(Severity::Normal, self.is_prod);
+        }
+        if self.time_wait_throttled {
+            apply_time_wait_throttle(false, self.is_prod);
+        }
+        let outcome = self
+            .tuning
+            .applier()
+            .apply(conntrack_set(&self.config, Intent::Restore, "EXIT"));
+        log_message(
+            &format!(
+                "Network parameters restored on exit: applied={} failed={}",
+                outcome.applied,
+                outcome.failed.len()
+            ),
+            self.is_prod,
+        );
+    }
+}
+
+/// 延迟恢复正常后的恢复：网络和 vm 参数、goahead，并清理页缓存
+fn restore_after_latency(
+    config: &Config,
+    vm_throttle: &mut VmThrottle,
+    tuning: &mut TuningQueue,
+    is_prod: bool,
+) {
+    tuning.submit(conntrack_set(config, Intent::Restore, "RESTORE"));
+    apply_vm_changes(&vm_throttle.exit(vmtune::read_value), is_prod);
+    let _ = force_start_goahead_process(is_prod);
+    clear_page_cache(config.cache_drop_floor_kb, is_prod);
+}
+
+/// 写入链路质量级别对应的网络参数
+fn apply_severity_params(severity: Severity, is_prod: bool) {
+    for (path, value) in severity.params() {
+        if let Err(e) = std::fs::write(path, format!("{}\n", value)) {
+            if !is_prod {
+                log_message(&format!("Failed to set {} to {}: {}", path, value, e), is_prod);
+            }
+        }
+    }
+}
+
+/// 写入限流/恢复时的 vm 参数，记录修改前后的值
+fn apply_vm_changes(changes: &[VmChange], is_prod: bool) {
+    for change in changes {
+        match std::fs::write(change.path, format!("{}\n", change.to)) {
+            Ok(()) => log_message(&format!("vm: {}", change.describe()), is_prod),
+            Err(e) => log_warn(
+                &format!("Failed to set vm {}: {}", change.describe(), e),
+                is_prod,
+            ),
+        }
+    }
+}
+
+/// 记录并通知高负载状态变化
+fn handle_load_event(event: LoadEvent, notifier: &Notifier, is_prod: bool) {
+    match event {
+        LoadEvent::Enter(usage) => {
+            log_warn(
+                &format!(
+                    "High CPU load: {:.1}% (>= {:.0}%), entering high-load mode",
+                    usage, CPU_USAGE_THRESHOLD
+                ),
+                is_prod,
+            );
+            notifier.send(&format!("HIGH_LOAD_ENTER: CPU={:.1}", usage), is_prod);
+        }
+        LoadEvent::Update(usage) => {
+            notifier.send(&format!("HIGH_LOAD: CPU={:.1}", usage), is_prod);
+        }
+        LoadEvent::Rising(usage, slope) => {
+            log_warn(
+                &format!(
+                    "CPU load rising fast: {:.1}% (+{:.1}%/min), below high-load threshold",
+                    usage, slope
+                ),
+                is_prod,
+            );
+            notifier.send(
+                &format!("HIGH_LOAD_RISING: CPU={:.1} SLOPE={:.1}", usage, slope),
+                is_prod,
+            );
+        }
+        LoadEvent::Exit(usage, duration) => {
             log_message(
-                &format!("Failed to adjust nf_conntrack_max to 4096: {}", e),
+                &format!(
+                    "CPU load back to {:.1}%, leaving high-load mode after {}s",
+                    usage,
+                    duration.as_secs()
+                ),
+                is_prod,
+            );
+            notifier.send(
+                &format!(
+                    "HIGH_LOAD_EXIT: CPU={:.1} DURATION={}s",
+                    usage,
+                    duration.as_secs()
+                ),
                 is_prod,
             );
         }
     }
 }
 
-fn restore_network_parameters(is_prod: bool) {
-    // 调整TCP参数来减轻网络栈负担
-    thread::sleep(Duration::from_millis(200));
-    if let Err(e) = std::fs::write("/proc/sys/net/nf_conntrack_max", b"8192\n") {
-        if !is_prod {
+/// 报告持续占用 CPU 的进程，允许列表中的进程在开启强制时被结束
+fn handle_runaway(
+    action: RunawayAction,
+    runaway_guard: &mut RunawayGuard,
+    config: &Config,
+    notifier: &Notifier,
+    is_prod: bool,
+) {
+    match action {
+        RunawayAction::Report(top, checks) => {
+            log_warn(
+                &format!(
+                    "Runaway process {} (PID: {}) at {:.0}% CPU for {} high-load checks",
+                    top.name, top.pid, top.cpu_percent, checks
+                ),
+                is_prod,
+            );
+            notifier.send(
+                &format!("RUNAWAY_PROCESS:{} CPU={:.0}", top.name, top.cpu_percent),
+                is_prod,
+            );
+        }
+        RunawayAction::Kill(top) => {
+            log_warn(
+                &format!(
+                    "Killing runaway process {} (PID: {}) at {:.0}% CPU",
+                    top.name, top.pid, top.cpu_percent
+                ),
+                is_prod,
+            );
+            let forced = procs::graceful_kill(top.pid, config.runaway_kill_grace);
+            let kills = runaway_guard.record_kill(&top.name, Instant::now());
             log_message(
-                &format!("Failed to adjust nf_conntrack_max to 8192: {}", e),
+                &format!(
+                    "Runaway process {} terminated ({}), killed {} time(s) so far",
+                    top.name,
+                    if forced { "SIGKILL" } else { "SIGTERM" },
+                    kills
+                ),
+                is_prod,
+            );
+            notifier.send(
+                &format!(
+                    "RUNAWAY_KILLED:{} CPU={:.0} KILLS={}",
+                    top.name, top.cpu_percent, kills
+                ),
+                is_prod,
+            );
+        }
+    }
+}
+
+/// 记录并通知 socket 压力变化，孤儿连接过多时可提前缩短 TIME_WAIT（tune 为 false 时只报告）；
+/// 压力解除时只在之前缩短过（throttled）时恢复
+fn handle_socket_pressure(
+    event: PressureEvent,
+    config: &Config,
+    tune: bool,
+    throttled: &mut bool,
+    notifier: &Notifier,
+    is_prod: bool,
+) {
+    match event {
+        PressureEvent::Enter {
+            counter,
+            value,
+            limit,
+        } => {
+            log_warn(
+                &format!("Socket pressure: {}={} exceeds {}", counter, value, limit),
+                is_prod,
+            );
+            notifier.send(
+                &format!("SOCKET_PRESSURE: {}={} (> {})", counter, value, limit),
+                is_prod,
+            );
+            if counter == "tcp_orphan" && config.sock_orphan_throttle && tune && !*throttled {
+                log_message("Applying TIME_WAIT throttle for orphaned sockets", is_prod);
+                apply_time_wait_throttle(true, is_prod);
+                *throttled = true;
+            }
+        }
+        PressureEvent::Exit => {
+            log_message("Socket pressure cleared", is_prod);
+            notifier.send("SOCKET_PRESSURE_CLEARED", is_prod);
+            if tune && std::mem::take(throttled) {
+                log_message("Restoring TIME_WAIT timeouts", is_prod);
+                apply_time_wait_throttle(false, is_prod);
+            }
+        }
+    }
+}
+
+fn handle_fd_level(level: FdLevel, stat: FileNr, notifier: &Notifier, is_prod: bool) {
+    match level {
+        FdLevel::Normal => log_message(
+            &format!("File descriptor usage back to normal: {}", stat.summary()),
+            is_prod,
+        ),
+        FdLevel::Warn => log_warn(
+            &format!("File descriptor usage high: {}", stat.summary()),
+            is_prod,
+        ),
+        FdLevel::Critical => {
+            let top = procs::top_fd_holders(FD_TOP_HOLDERS, FD_COUNT_CAP)
+                .iter()
+                .map(|holder| format!("{}({})={}", holder.name, holder.pid, holder.fds))
+                .collect::<Vec<_>>()
+                .join(",");
+            log_warn(
+                &format!(
+                    "File descriptors nearly exhausted: {}, top holders: {}",
+                    stat.summary(),
+                    top
+                ),
+                is_prod,
+            );
+            notifier.send(
+                &format!(
+                    "FD_PRESSURE: ALLOCATED={} MAX={} TOP={}",
+                    stat.allocated, stat.max, top
+                ),
                 is_prod,
             );
         }
     }
 }
 
+fn apply_time_wait_throttle(throttled: bool, is_prod: bool) {
+    for (path, throttled_value, normal_value) in TIME_WAIT_THROTTLE {
+        let value = if throttled {
+            throttled_value
+        } else {
+            normal_value
+        };
+        if let Err(e) = std::fs::write(path, format!("{}\n", value)) {
+            if !is_prod {
+                log_message(&format!("Failed to set {} to {}: {}", path, value, e), is_prod);
+            }
+        }
+    }
+}
+
+/// hashsize 加上按当前是否限流对应的 nf_conntrack_max
+fn profile_set(config: &Config, throttled: bool) -> TuningSet {
+    let intent = if throttled {
+        Intent::Throttle
+    } else {
+        Intent::Restore
+    };
+    let mut set = conntrack_set(config, intent, "PROFILE");
+    set.writes.insert(
+        0,
+        (
+            "/sys/module/nf_conntrack/parameters/hashsize",
+            config.conntrack_hashsize.to_string(),
+        ),
+    );
+    set
+}
+
+/// 处理 PROFILE:<name>：校验档位名，立即生效并持久化，返回应答
+fn handle_set_profile(
+    name: &[u8],
+    config: &mut Config,
+    profile_path: &Path,
+    throttled: bool,
+    tuning: &mut TuningQueue,
+    notifier: &Notifier,
+    is_prod: bool,
+) -> String {
+    let name = String::from_utf8_lossy(name);
+    let profile = match Profile::parse(name.trim()) {
+        Some(profile) => profile,
+        None => {
+            return format!(
+                "ERROR unknown profile: {} (expected {})",
+                name.trim(),
+                Profile::names()
+            )
+        }
+    };
+
+    let previous = config.profile;
+    config.apply_profile(profile);
+    config.set_source("profile", ConfigSource::Runtime);
+    tuning.submit(profile_set(config, throttled));
+    if let Err(e) = profile::persist(profile_path, profile) {
+        log_warn(
+            &format!("Failed to persist profile to {}: {}", profile_path.display(), e),
+            is_prod,
+        );
+    }
+    log_message(
+        &format!("Tuning profile changed: {} -> {}", previous.name(), profile.name()),
+        is_prod,
+    );
+    notifier.send(
+        &format!("PROFILE_CHANGED: {} (was {})", profile.name(), previous.name()),
+        is_prod,
+    );
+    format!("OK profile={}", profile.name())
+}
+
+/// 读取 nv 配置项，失败或超时时返回空字符串
+fn nv_get(key: &str) -> String {
+    match command::run_with_timeout("nv", &["get", key], IP_COMMAND_TIMEOUT) {
+        Ok(output) if output.success() => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        _ => String::new(),
+    }
+}
+
 fn get_wan_ip_address(is_prod: bool) -> String {
     // 方法1: 使用 ip 命令获取 wan1 接口的 IP
-    if let Ok(output) = Command::new("ip").args(["addr", "show", "wan1"]).output() {
-        if output.status.success() {
+    if let Ok(output) = command::run_with_timeout("ip", &["addr", "show", "wan1"], IP_COMMAND_TIMEOUT) {
+        if output.success() {
             let output_str = String::from_utf8_lossy(&output.stdout);
             for line in output_str.lines() {
                 if line.trim().starts_with("inet ") {
@@ -1188,192 +3366,457 @@ fn get_wan_ip_address(is_prod: bool) -> String {
             }
         }
     }
-
-    // log_message("Could not determine wan1 IP address", is_prod);
-    String::new()
+
+    // log_message("Could not determine wan1 IP address", is_prod);
+    String::new()
+}
+
+/// 读取 /proc/net/tcp、/proc/net/tcp6 中 adbd 端口上的监听；check_firewall 时再看 INPUT 链中
+/// 有没有过滤该端口的规则（监听 :: 时 iptables 和 ip6tables 都看）
+fn audit_adbd(sys: &mut impl SystemOps, check_firewall: bool) -> AuditReport {
+    let mut listeners = Vec::new();
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(content) = fs::read_to_string(path) {
+            listeners.extend(adbaudit::parse_listeners(&content, adbaudit::ADB_PORT));
+        }
+    }
+    // 输出超过上限（规则极多）时不判断，按没有检查处理
+    let mut covers = |program: &str| {
+        let output = sys
+            .run_command_output_limit(
+                &format!("{} -S INPUT", program),
+                adbaudit::MAX_RULES_BYTES,
+            )
+            .ok()?;
+        if output.stdout.len() > adbaudit::MAX_RULES_BYTES {
+            return None;
+        }
+        Some(
+            output.status.success()
+                && adbaudit::firewall_covers(
+                    &String::from_utf8_lossy(&output.stdout),
+                    adbaudit::ADB_PORT,
+                ),
+        )
+    };
+    let covered = check_firewall.then(|| covers("iptables")).flatten();
+    let mut report = AuditReport {
+        listeners,
+        covered,
+        covered6: None,
+    };
+    if check_firewall && report.wildcard6() {
+        report.covered6 = covers("ip6tables");
+    }
+    report
+}
+
+/// 刚发现 adbd 端口暴露时记录并通知 ADBD_EXPOSED；指定了 restrict_to（LAN 网段）且防火墙中
+/// 没有相关规则时装专用链，只允许 LAN 访问
+fn handle_adbd_audit(
+    report: &AuditReport,
+    exposed: &mut bool,
+    restrict_to: Option<&str>,
+    maintenance: &mut Maintenance,
+    sys: &mut impl SystemOps,
+    notifier: &Notifier,
+    is_prod: bool,
+) {
+    if !report.exposed() {
+        if *exposed {
+            log_message(&format!("adbd port no longer exposed: {}", report.describe()), is_prod);
+        }
+        *exposed = false;
+        return;
+    }
+    if !*exposed {
+        log_warn(&format!("adbd port reachable from outside: {}", report.describe()), is_prod);
+        notifier.send(&format!("ADBD_EXPOSED: {}", report.describe()), is_prod);
+        *exposed = true;
+    }
+    let Some(lan) = restrict_to.filter(|_| report.covered == Some(false)) else {
+        return;
+    };
+    if maintenance.is_active() {
+        suppress_action(maintenance, "ADBD_RESTRICT", notifier, is_prod);
+        return;
+    }
+    for cmd in adbaudit::restrict_commands(lan, adbaudit::ADB_PORT) {
+        match sys.run_command(&cmd) {
+            Ok(status) if status.success() => {}
+            _ => {
+                log_warn(&format!("Failed to restrict adbd port: {}", cmd), is_prod);
+                return;
+            }
+        }
+    }
+    log_message(
+        &format!("adbd port {} restricted to {}", adbaudit::ADB_PORT, lan),
+        is_prod,
+    );
+    notifier.send(&format!("ADBD_RESTRICTED: LAN={}", lan), is_prod);
+}
+
+/// 获取 br0 接口的网络地址 (如 192.168.0.0/24)：优先读 /proc/net/route，
+/// 读不到时才调用 ip（busybox 精简版的输出格式不同）
+fn get_br_network() -> Option<String> {
+    let from_proc = fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|content| routes::parse_iface_networks(&content, "br0").into_iter().next());
+    from_proc.or_else(|| {
+        command::run_with_timeout("ip", &["route", "show", "dev", "br0"], IP_COMMAND_TIMEOUT)
+            .ok()
+            .filter(|result| result.success())
+            .and_then(|result| {
+                routes::parse_ip_route_network(&String::from_utf8_lossy(&result.stdout))
+            })
+    })
+}
+
+/// 启动时确定 LAN 网段。检测不到时不再假设 192.168.0.0/24（按错误网段装 NAT 比不装更糟），
+/// 而是报错并发送 LAN_SUBNET_UNKNOWN，只有配置了 assume_lan 时才使用它。
+/// 返回 (网段, 是否来自 assume_lan)，来自 assume_lan 时主循环继续检测
+fn resolve_lan_subnet(
+    config: &Config,
+    notifier: &Notifier,
+    is_prod: bool,
+) -> (Option<String>, bool) {
+    if let Some(network) = get_br_network() {
+        log_message(&format!("br0 network: {}", network), is_prod);
+        return (Some(network), false);
+    }
+    let assumed = (!config.assume_lan.is_empty()).then(|| config.assume_lan.clone());
+    log_error(
+        &format!(
+            "Could not determine br0 network, {}",
+            match &assumed {
+                Some(network) => format!("assuming {} (assume_lan)", network),
+                None => "will retry; set --assume-lan to override".to_string(),
+            }
+        ),
+        is_prod,
+    );
+    notifier.send(
+        &format!(
+            "LAN_SUBNET_UNKNOWN: ASSUMED={}",
+            assumed.as_deref().unwrap_or("-")
+        ),
+        is_prod,
+    );
+    let is_assumed = assumed.is_some();
+    (assumed, is_assumed)
+}
+
+/// 启动调整、限流/恢复、链路分级和 vm 限流会写入的所有路径
+fn managed_sysctl_paths(config: &Config) -> Vec<&'static str> {
+    let mut paths = vec![
+        "/sys/module/nf_conntrack/parameters/hashsize",
+        "/proc/sys/net/nf_conntrack_max",
+    ];
+    paths.extend(NETWORK_TUNING_COMMANDS.iter().filter_map(|cmd| sysctl::target_path(cmd)));
+    if config.enable_ipv6_tuning {
+        paths.extend(IPV6_TUNING_COMMANDS.iter().filter_map(|cmd| sysctl::target_path(cmd)));
+    }
+    paths.extend(TIME_WAIT_THROTTLE.iter().map(|(path, _, _)| *path));
+    paths.extend(Severity::managed_paths());
+    paths.extend(vmtune::managed_paths());
+    paths
+}
+
+/// 启动时执行的参数调整命令（conntrack 相关的来自配置，单独生成）
+const NETWORK_TUNING_COMMANDS: &[&str] = &[
+    "echo zixc_ping > /sys/power/wake_lock", 
+    "echo performance > /sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
+    "echo 2200 > /sys/module/net_ext_modul/parameters/skb_num_limit",
+    "echo 1400 > /sys/module/net_ext_modul/parameters/skb_max_panic",
+    "echo 1000 > /proc/sys/net/core/netdev_max_backlog",
+    "echo 5000 > /proc/sys/net/unix/max_dgram_qlen",
+    "echo 128 > /proc/sys/net/ipv4/tcp_max_syn_backlog",
+
+    "echo 5 > /proc/sys/net/ipv4/tcp_retries2",
+    "echo 15 > /proc/sys/net/ipv4/tcp_fin_timeout",
+    "echo 300 > /proc/sys/net/ipv4/tcp_keepalive_time",
+
+    "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_time_wait",
+    "echo 300 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_established",
+    "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_syn_sent2",
+    "echo 20 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_close",
+
+    "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_udp_timeout",
+    "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_udp_timeout_stream",
+    "echo 450 > /proc/sys/net/netfilter/nf_conntrack_expect_max",
+    // "echo 0 > /proc/sys/net/netfilter/nf_conntrack_log_invalid",
+    // "echo 0 > /proc/sys/net/netfilter/nf_conntrack_checksum",
+    "echo 1 > /proc/sys/net/netfilter/nf_conntrack_tcp_loose",
+
+    "echo 600 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_established",
+    "echo 10 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_syn_sent",
+    "echo 10 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_syn_recv",
+
+    "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_fin_wait",
+    "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_last_ack",
+    "echo 10 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_close",
+    "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_close_wait",
+
+    "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_time_wait",
+    "echo 3 > /proc/sys/net/netfilter/nf_conntrack_tcp_max_retrans",
+    "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_max_retrans",
+    "echo 10 > /proc/sys/net/netfilter/nf_conntrack_udp_timeout",
+    "echo 60 > /proc/sys/net/netfilter/nf_conntrack_udp_timeout_stream",
+    // "echo 10 > /proc/sys/net/netfilter/nf_conntrack_icmp_timeout",
+
+    "echo 100 > /proc/sys/net/netfilter/nf_conntrack_generic_timeout",
+    //"echo 0 > /proc/sys/net/ipv4/tcp_window_scaling"
+    // "echo 1 > /proc/net/fastnat_level"
+
+    // ========== IP分片重组优化 ==========
+    "echo 131072 > /proc/sys/net/ipv4/ipfrag_low_thresh",
+    "echo 196608 > /proc/sys/net/ipv4/ipfrag_high_thresh",
+    "echo 20 > /proc/sys/net/ipv4/ipfrag_time",
+
+    // ========== TCP内存极致压缩 ==========
+    "echo 256 512 768 > /proc/sys/net/ipv4/tcp_mem",
+    "echo 4096 8192 32768 > /proc/sys/net/ipv4/tcp_rmem",
+    "echo 4096 8192 32768 > /proc/sys/net/ipv4/tcp_wmem",
+    "echo 64 > /proc/sys/net/ipv4/tcp_max_orphans",
+    "echo 128 > /proc/sys/net/ipv4/tcp_max_tw_buckets",
+
+    // ========== TCP保活与重传 ==========
+    "echo 3 > /proc/sys/net/ipv4/tcp_keepalive_probes",
+    "echo 5 > /proc/sys/net/ipv4/tcp_syn_retries",
+    "echo 5 > /proc/sys/net/ipv4/tcp_synack_retries",
+    "echo 0 > /proc/sys/net/ipv4/tcp_slow_start_after_idle",
+
+    // ========== 路由表精简 ==========
+    "echo 4096 > /proc/sys/net/ipv4/route/max_size",
+    "echo 256 > /proc/sys/net/ipv4/route/gc_thresh",
+    "echo 60 > /proc/sys/net/ipv4/route/gc_timeout",
+
+    // ========== ARP/邻居表压缩 ==========
+    "echo 256 > /proc/sys/net/ipv4/neigh/default/gc_thresh1",
+    "echo 512 > /proc/sys/net/ipv4/neigh/default/gc_thresh2",
+    "echo 2048 > /proc/sys/net/ipv4/neigh/default/gc_thresh3",
+    "echo 15 > /proc/sys/net/ipv4/neigh/default/base_reachable_time",
+
+    // ========== UDP内存压缩 ==========
+    "echo 256 512 768 > /proc/sys/net/ipv4/udp_mem",
+    "echo 2048 > /proc/sys/net/ipv4/udp_rmem_min",
+    "echo 2048 > /proc/sys/net/ipv4/udp_wmem_min",
+
+    // ========== 杂项精简 ==========
+    "echo 5 > /proc/sys/net/ipv4/igmp_max_memberships",
+    "echo 8192 > /proc/sys/net/ipv4/inet_peer_threshold",
+    "echo 300 > /proc/sys/net/ipv4/inet_peer_maxttl",
+
+    // ========== ICMP限速 ==========
+    "echo 100 > /proc/sys/net/ipv4/icmp_ratelimit",
+    "echo 1 > /proc/sys/net/ipv4/icmp_echo_ignore_broadcasts",
+
+    // ========== Kernel核心参数 ==========
+    "echo 0 > /proc/sys/kernel/randomize_va_space",
+    "echo 0 > /proc/sys/kernel/panic_on_oops",
+    "echo '|/bin/false' > /proc/sys/kernel/core_pattern",
+    "echo 0 > /proc/sys/kernel/core_uses_pid",
+    "echo 1 1 1 1 > /proc/sys/kernel/printk",
+    "echo 0 > /proc/sys/kernel/sysrq",
+    "echo 256 > /proc/sys/kernel/threads-max",
+    "echo 4096 > /proc/sys/kernel/msgmnb",
+    "echo 96 > /proc/sys/kernel/msgmni",
+
+    // ========== VM内存管理 ==========
+    "echo 0 > /proc/sys/vm/panic_on_oom",
+    "echo 2048 > /proc/sys/vm/min_free_kbytes",
+
+    // ========== 实时内核优化 ==========
+    "echo 200000 > /proc/sys/kernel/sched_rt_period_us",
+
+    "echo 8192 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/limit_max",
+    "echo 4096 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/limit",
+    "echo 1024 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/limit_min",
+    "echo 500 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/hold_time"
+];
+
+/// IPv6 对应的调整（enable_ipv6_tuning）；TCP 的 keepalive/重传参数在 net.ipv4 下，对 IPv6 同样生效
+const IPV6_TUNING_COMMANDS: &[&str] = &[
+    // ========== 路由表精简 ==========
+    "echo 4096 > /proc/sys/net/ipv6/route/max_size",
+    "echo 256 > /proc/sys/net/ipv6/route/gc_thresh",
+    "echo 60 > /proc/sys/net/ipv6/route/gc_timeout",
+    // ========== 邻居表压缩 ==========
+    "echo 256 > /proc/sys/net/ipv6/neigh/default/gc_thresh1",
+    "echo 512 > /proc/sys/net/ipv6/neigh/default/gc_thresh2",
+    "echo 2048 > /proc/sys/net/ipv6/neigh/default/gc_thresh3",
+    "echo 15 > /proc/sys/net/ipv6/neigh/default/base_reachable_time",
+    // ========== 分片重组 ==========
+    "echo 131072 > /proc/sys/net/ipv6/ip6frag_low_thresh",
+    "echo 196608 > /proc/sys/net/ipv6/ip6frag_high_thresh",
+    "echo 20 > /proc/sys/net/ipv6/ip6frag_time",
+];
+
+/// 启动时的网络参数优化，完成后发送 TUNING: OPTIMIZE 和 iptables 变体的通知
+fn apply_startup_tuning(
+    sys: &mut impl SystemOps,
+    config: &Config,
+    iptables: &mut IptablesHealth,
+    snapshot_dir: &Path,
+    notifier: &Notifier,
+    is_prod: bool,
+    addr: String,
+) {
+    optimize_network_parameters(sys, config, iptables, snapshot_dir, is_prod, addr)
+        .notify("OPTIMIZE", config, notifier, is_prod);
+    if let Some(message) = iptables.take_notification() {
+        notifier.send(&message, is_prod);
+    }
+}
+
+/// 启动优化之后按 LAN 网段装 MASQUERADE（iptables_flush 已清空 nat 表，需要重新安装）。
+/// 网段未知时不装（按错误网段装比不装更糟），之后检测到时由主循环安装
+fn sync_lan_masquerade(
+    sys: &mut impl SystemOps,
+    config: &Config,
+    iptables: &mut IptablesHealth,
+    masquerade: &mut LanMasquerade,
+    lan_subnet: Option<&str>,
+    is_prod: bool,
+) {
+    if !config.enable_iptables {
+        return;
+    }
+    if config.iptables_flush {
+        masquerade.forget();
+    }
+    if let Some(network) = lan_subnet {
+        masquerade.sync(sys, iptables, network, is_prod);
+    }
+}
+
+/// 启动优化之后装目标主机的 NETMAP 规则（iptables_flush 已清空 nat 表，需要重新安装；
+/// 不清空时上次运行的规则还在，只在缺少时添加），之后 WAN 地址变化时由主循环更新
+fn sync_wan_netmap(
+    sys: &mut impl SystemOps,
+    config: &Config,
+    iptables: &mut IptablesHealth,
+    netmap: &mut WanNetmap,
+    source: &str,
+    is_prod: bool,
+) {
+    if !config.enable_iptables {
+        return;
+    }
+    if config.iptables_flush {
+        netmap.forget();
+    }
+    let wan1_ip = get_wan_ip_address(is_prod);
+    if !wan1_ip.is_empty() && !netmap.sync(sys, iptables, source, &wan1_ip, is_prod) {
+        log_message(&format!("Failed to add SNAT rule to {}", wan1_ip), is_prod);
+    }
 }
 
-// fn get_br_network(is_prod: bool) -> String {
-//     // 获取 br0 接口的网络地址 (如 192.168.0.0/24)
-//     if let Ok(output) = Command::new("ip")
-//         .args(["route", "show", "dev", "br0"])
-//         .output()
-//     {
-//         if output.status.success() {
-//             let output_str = String::from_utf8_lossy(&output.stdout);
-//             for line in output_str.lines() {
-//                 let parts: Vec<&str> = line.trim().split_whitespace().collect();
-//                 // 查找类似 "192.168.0.0/24" 的网络路由
-//                 if parts.len() >= 1 && parts[0].contains('/') {
-//                     let network = parts[0];
-//                     if network != "default" && !network.starts_with("169.254") {
-//                         // log_message(&format!("Found br0 network: {}", network), is_prod);
-//                         return network.to_string();
-//                     }
-//                 }
-//             }
-//         }
-//     }
-
-//     // 如果无法获取网络地址，使用默认的 192.168.0.0/24
-//     log_message(
-//         "Could not determine br0 network, using default 192.168.0.0/24",
-//         is_prod,
-//     );
-//     "192.168.0.0/24".to_string()
-// }
-
-fn optimize_network_parameters(is_prod: bool, addr: String) {
+fn optimize_network_parameters(
+    sys: &mut impl SystemOps,
+    config: &Config,
+    iptables: &mut IptablesHealth,
+    snapshot_dir: &Path,
+    is_prod: bool,
+    addr: String,
+) -> TuningReport {
+    let mut report = TuningReport::default();
     // 调整TCP参数来减轻网络栈负担
-    let ip_only = match addr.parse::<SocketAddr>() {
-        Ok(sock) => sock.ip().to_string(),
-        Err(_) => {
-            log_message(&format!("invalid addr: {}", addr), is_prod);
-            return;
-        }
-    };
-    // let br_network = get_br_network(is_prod);
+    if addr.parse::<SocketAddr>().is_err() {
+        log_message(&format!("invalid addr: {}", addr), is_prod);
+        return report;
+    }
     let wan1_ip = get_wan_ip_address(is_prod);
 
-    let commands = [
-        "echo zixc_ping > /sys/power/wake_lock", 
-        "echo performance > /sys/devices/system/cpu/cpu0/cpufreq/scaling_governor",
-        "echo 2200 > /sys/module/net_ext_modul/parameters/skb_num_limit",
-        "echo 1400 > /sys/module/net_ext_modul/parameters/skb_max_panic",
-        "echo 1000 > /proc/sys/net/core/netdev_max_backlog",
-        "echo 5000 > /proc/sys/net/unix/max_dgram_qlen",
-        "echo 128 > /proc/sys/net/ipv4/tcp_max_syn_backlog",
-
-        "echo 5 > /proc/sys/net/ipv4/tcp_retries2",
-        "echo 15 > /proc/sys/net/ipv4/tcp_fin_timeout",
-        "echo 300 > /proc/sys/net/ipv4/tcp_keepalive_time",
-
-        "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_time_wait",
-        "echo 300 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_established",
-        "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_syn_sent2",
-        "echo 20 > /proc/sys/net/ipv4/netfilter/ip_conntrack_tcp_timeout_close",
-
-        "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_udp_timeout",
-        "echo 10 > /proc/sys/net/ipv4/netfilter/ip_conntrack_udp_timeout_stream",
-        "echo 2048 > /sys/module/nf_conntrack/parameters/hashsize",
-        "echo 8192 > /proc/sys/net/nf_conntrack_max",
-        "echo 450 > /proc/sys/net/netfilter/nf_conntrack_expect_max",
-        // "echo 0 > /proc/sys/net/netfilter/nf_conntrack_log_invalid",
-        // "echo 0 > /proc/sys/net/netfilter/nf_conntrack_checksum",
-        "echo 1 > /proc/sys/net/netfilter/nf_conntrack_tcp_loose",
-
-        "echo 600 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_established",
-        "echo 10 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_syn_sent",
-        "echo 10 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_syn_recv",
-
-        "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_fin_wait",
-        "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_last_ack",
-        "echo 10 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_close",
-        "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_close_wait",
-
-        "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_time_wait",
-        "echo 3 > /proc/sys/net/netfilter/nf_conntrack_tcp_max_retrans",
-        "echo 30 > /proc/sys/net/netfilter/nf_conntrack_tcp_timeout_max_retrans",
-        "echo 10 > /proc/sys/net/netfilter/nf_conntrack_udp_timeout",
-        "echo 60 > /proc/sys/net/netfilter/nf_conntrack_udp_timeout_stream",
-        // "echo 10 > /proc/sys/net/netfilter/nf_conntrack_icmp_timeout",
-
-        "echo 100 > /proc/sys/net/netfilter/nf_conntrack_generic_timeout",
-        //"echo 0 > /proc/sys/net/ipv4/tcp_window_scaling"
-        // "echo 1 > /proc/net/fastnat_level"
-
-        // ========== IP分片重组优化 ==========
-        "echo 131072 > /proc/sys/net/ipv4/ipfrag_low_thresh",
-        "echo 196608 > /proc/sys/net/ipv4/ipfrag_high_thresh",
-        "echo 20 > /proc/sys/net/ipv4/ipfrag_time",
-
-        // ========== TCP内存极致压缩 ==========
-        "echo 256 512 768 > /proc/sys/net/ipv4/tcp_mem",
-        "echo 4096 8192 32768 > /proc/sys/net/ipv4/tcp_rmem",
-        "echo 4096 8192 32768 > /proc/sys/net/ipv4/tcp_wmem",
-        "echo 64 > /proc/sys/net/ipv4/tcp_max_orphans",
-        "echo 128 > /proc/sys/net/ipv4/tcp_max_tw_buckets",
-
-        // ========== TCP保活与重传 ==========
-        "echo 3 > /proc/sys/net/ipv4/tcp_keepalive_probes",
-        "echo 5 > /proc/sys/net/ipv4/tcp_syn_retries",
-        "echo 5 > /proc/sys/net/ipv4/tcp_synack_retries",
-        "echo 0 > /proc/sys/net/ipv4/tcp_slow_start_after_idle",
-
-        // ========== 路由表精简 ==========
-        "echo 4096 > /proc/sys/net/ipv4/route/max_size",
-        "echo 256 > /proc/sys/net/ipv4/route/gc_thresh",
-        "echo 60 > /proc/sys/net/ipv4/route/gc_timeout",
-
-        // ========== ARP/邻居表压缩 ==========
-        "echo 256 > /proc/sys/net/ipv4/neigh/default/gc_thresh1",
-        "echo 512 > /proc/sys/net/ipv4/neigh/default/gc_thresh2",
-        "echo 2048 > /proc/sys/net/ipv4/neigh/default/gc_thresh3",
-        "echo 15 > /proc/sys/net/ipv4/neigh/default/base_reachable_time",
-
-        // ========== UDP内存压缩 ==========
-        "echo 256 512 768 > /proc/sys/net/ipv4/udp_mem",
-        "echo 2048 > /proc/sys/net/ipv4/udp_rmem_min",
-        "echo 2048 > /proc/sys/net/ipv4/udp_wmem_min",
-
-        // ========== 杂项精简 ==========
-        "echo 5 > /proc/sys/net/ipv4/igmp_max_memberships",
-        "echo 8192 > /proc/sys/net/ipv4/inet_peer_threshold",
-        "echo 300 > /proc/sys/net/ipv4/inet_peer_maxttl",
-
-        // ========== ICMP限速 ==========
-        "echo 100 > /proc/sys/net/ipv4/icmp_ratelimit",
-        "echo 1 > /proc/sys/net/ipv4/icmp_echo_ignore_broadcasts",
-
-        // ========== Kernel核心参数 ==========
-        "echo 0 > /proc/sys/kernel/randomize_va_space",
-        "echo 0 > /proc/sys/kernel/panic_on_oops",
-        "echo '|/bin/false' > /proc/sys/kernel/core_pattern",
-        "echo 0 > /proc/sys/kernel/core_uses_pid",
-        "echo 1 1 1 1 > /proc/sys/kernel/printk",
-        "echo 0 > /proc/sys/kernel/sysrq",
-        "echo 256 > /proc/sys/kernel/threads-max",
-        "echo 4096 > /proc/sys/kernel/msgmnb",
-        "echo 96 > /proc/sys/kernel/msgmni",
-
-        // ========== VM内存管理 ==========
-        "echo 0 > /proc/sys/vm/panic_on_oom",
-        "echo 2048 > /proc/sys/vm/min_free_kbytes",
-
-        // ========== 实时内核优化 ==========
-        "echo 200000 > /proc/sys/kernel/sched_rt_period_us",
-
-        "echo 8192 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/limit_max",
-        "echo 4096 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/limit",
-        "echo 1024 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/limit_min",
-        "echo 500 > /sys/devices/platform/zx29_hsotg.0/gadget/net/usblan0/queues/tx-0/byte_queue_limits/hold_time"
-    ];
-
-    if !wan1_ip.is_empty() {
-        let ipt_cmds = [
-            "iptables -P INPUT ACCEPT".to_string(),
-            "iptables -P FORWARD ACCEPT".to_string(),
-            "iptables -P OUTPUT ACCEPT".to_string(),
-            "iptables -F -t filter".to_string(),
-            "iptables -F -t nat".to_string(),
+    if config.enable_iptables && !wan1_ip.is_empty() {
+        let mut ipt_cmds = Vec::new();
+        // 清空已有规则需要显式开启（iptables_flush），清空前先保存原有规则
+        if config.iptables_flush {
+            iptables::snapshot_firewall(sys, snapshot_dir, is_prod);
+            ipt_cmds.extend([
+                "iptables -P INPUT ACCEPT".to_string(),
+                "iptables -P FORWARD ACCEPT".to_string(),
+                "iptables -P OUTPUT ACCEPT".to_string(),
+                "iptables -F -t filter".to_string(),
+                "iptables -F -t nat".to_string(),
+                "ip6tables -F".to_string(),
+            ]);
+        }
+        ipt_cmds.extend([
             // "iptables -t nat -A POSTROUTING -s 192.168.8.2/32 -o wan1 -j MASQUERADE",
             // format!("iptables -t nat -A POSTROUTING -s {}/32 -o wan1 -j MASQUERADE", ip_only),
             // format!(
             //     "iptables -t nat -I POSTROUTING -s {}/32 -o wan1 -j SNAT --to-source {}",
             //     ip_only, wan1_ip
             // ),
-            format!(
-                "iptables -t nat -I POSTROUTING -s {}/32 -o wan1 -j NETMAP --to {}",
-                ip_only, wan1_ip
-            ),
             //&format!("iptables -t nat -A POSTROUTING -s {} -o wan1 -j MASQUERADE", br_network),
-            "ip6tables -F".to_string(),
             "ifconfig wan1 txqueuelen 100".to_string(),
             // "ifconfig br0 txqueuelen 500".to_string(),
             "ifconfig usblan0 txqueuelen 500".to_string(),
-        ];
+        ]);
         for cmd in &ipt_cmds {
-            if let Err(e) = Command::new("sh").arg("-c").arg(cmd).status() {
+            if !iptables::is_iptables_cmd(cmd) {
+                report.run(sys, cmd, is_prod);
+            } else if iptables.run(sys, cmd, is_prod) {
+                report.applied += 1;
+            } else {
+                report.failed.push(cmd.clone());
+            }
+        }
+    }
+
+    // conntrack 哈希表和上限来自配置
+    let conntrack_cmds = [
+        format!(
+            "echo {} > /sys/module/nf_conntrack/parameters/hashsize",
+            config.conntrack_hashsize
+        ),
+        format!("echo {} > /proc/sys/net/nf_conntrack_max", config.conntrack_max),
+    ];
+
+    for cmd in conntrack_cmds
+        .iter()
+        .map(|c| c.as_str())
+        .chain(NETWORK_TUNING_COMMANDS.iter().copied())
+    {
+        report.run(sys, cmd, is_prod);
+    }
+
+    if config.enable_ipv6_tuning {
+        // 内核未启用 IPv6 或没有某个参数时跳过，不计为失败
+        for cmd in IPV6_TUNING_COMMANDS {
+            if sysctl::target_path(cmd).is_some_and(|path| Path::new(path).exists()) {
+                report.run(sys, cmd, is_prod);
+            }
+        }
+    }
+    report
+}
+
+/// 一组参数调整命令的执行结果
+#[derive(Default)]
+struct TuningReport {
+    applied: usize,
+    failed: Vec<String>,
+}
+
+impl TuningReport {
+    /// 通过 sh -c 执行一条调整命令并记录结果
+    fn run(&mut self, sys: &mut impl SystemOps, cmd: &str, is_prod: bool) {
+        match sys.run_command(cmd) {
+            Ok(status) if status.success() => self.applied += 1,
+            Ok(status) => {
+                self.failed.push(cmd.to_string());
+                if !is_prod {
+                    log_message(
+                        &format!("Failed to adjust network parameter {}: {}", cmd, status),
+                        is_prod,
+                    );
+                }
+            }
+            Err(e) => {
+                self.failed.push(cmd.to_string());
                 if !is_prod {
                     log_message(
                         &format!("Failed to adjust network parameter {}: {}", cmd, e),
@@ -1384,36 +3827,69 @@ fn optimize_network_parameters(is_prod: bool, addr: String) {
         }
     }
 
-    for cmd in commands.iter() {
-        if let Err(e) = Command::new("sh").arg("-c").arg(cmd).status() {
-            if !is_prod {
-                log_message(
-                    &format!("Failed to adjust network parameter {}: {}", cmd, e),
-                    is_prod,
-                );
-            }
-        }
+    /// 发送 TUNING 通知：`TUNING: THROTTLE PROFILE=balanced APPLIED=1 FAILED=0`
+    fn notify(&self, stage: &str, config: &Config, notifier: &Notifier, is_prod: bool) {
+        notifier.send(
+            &format!(
+                "TUNING: {} PROFILE={} APPLIED={} FAILED={}",
+                stage,
+                config.profile.name(),
+                self.applied,
+                self.failed.len()
+            ),
+            is_prod,
+        );
     }
 }
 
-fn clear_page_cache(_is_prod: bool) {
-    let _ = std::fs::write("/proc/sys/vm/drop_caches", b"1\n");
+/// 清理 page cache。可用内存高于 floor_kb 时跳过，避免刚恢复时丢掉热缓存造成 iowait，
+/// floor_kb 为 0 时总是清理。清理前先 sync，记录前后的 MemFree 以便判断效果
+fn clear_page_cache(floor_kb: u64, is_prod: bool) {
+    if floor_kb > 0 {
+        if let Some(available_kb) = get_available_memory_kb().filter(|kb| *kb > floor_kb) {
+            log_message(
+                &format!("Page cache drop skipped, {}MB available", available_kb / 1024),
+                is_prod,
+            );
+            return;
+        }
+    }
+    let free_before = sysinfo::read_meminfo_kb("MemFree");
+    unsafe { libc::sync() };
+    match fs::write("/proc/sys/vm/drop_caches", b"1\n") {
+        Ok(()) => {
+            let kb = |kb: Option<u64>| kb.map_or("-".to_string(), |kb| format!("{}KB", kb));
+            log_message(
+                &format!(
+                    "Page cache dropped: MemFree {} -> {}",
+                    kb(free_before),
+                    kb(sysinfo::read_meminfo_kb("MemFree"))
+                ),
+                is_prod,
+            );
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            log_warn("Cannot drop page cache: permission denied (not running as root?)", is_prod)
+        }
+        Err(e) => log_warn(&format!("Failed to drop page cache: {}", e), is_prod),
+    }
 }
 
-fn daemonize_simple(is_prod: bool) {
+fn daemonize_simple(is_prod: bool, log_path: &Path) {
     let stdout = if is_prod {
-        "/dev/null"
+        Path::new("/dev/null")
     } else {
-        "/etc_rw/zxping.log"
+        log_path
     };
 
     let dev_null = std::fs::OpenOptions::new()
         .read(true)
-        .write(true)
+        .create(true)
+        .append(true)
         .open(stdout)
         // .open("/dev/null")
         // .open("/etc_rw/zxping.log")
-        .expect(&format!("cannot open {}", stdout));
+        .expect(&format!("cannot open {}", stdout.display()));
 
     Daemonize::new()
         .stdout(dev_null.try_clone().unwrap())
@@ -1422,13 +3898,93 @@ fn daemonize_simple(is_prod: bool) {
         .expect("daemonize failed");
 }
 
+/// 日志清理连续失败：先把存储目录所在的分区重新挂载为读写，再确认是否可写，
+/// 仍不可写时切到后备目录并发送 STORAGE_DEGRADED。切换了目录时返回 true
+fn remediate_storage(
+    storage: &mut Storage,
+    now: Instant,
+    notifier: &Notifier,
+    is_prod: bool,
+) -> bool {
+    let preferred = storage.preferred().to_path_buf();
+    let mounts = fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let mount = storage::mount_point(&preferred, &mounts).unwrap_or_else(|| preferred.clone());
+    let mount = mount.to_string_lossy();
+    log_warn(
+        &format!(
+            "Log prune keeps failing, remounting {} read-write for {}",
+            mount,
+            preferred.display()
+        ),
+        is_prod,
+    );
+    match command::run_with_timeout("mount", &["-o", "remount,rw", &mount], REMOUNT_TIMEOUT) {
+        Ok(result) if result.success() => {
+            log_message(&format!("Remounted {} read-write", mount), is_prod)
+        }
+        Ok(result) => log_warn(&format!("Failed to remount {}: {}", mount, result), is_prod),
+        Err(e) => log_warn(&format!("Failed to run mount: {}", e), is_prod),
+    }
+    match storage.probe_preferred() {
+        Ok(()) => {
+            log_message(
+                &format!("Storage {} is writable after remount", preferred.display()),
+                is_prod,
+            );
+            false
+        }
+        Err(e) => {
+            storage.degrade(now, e.to_string());
+            log_error(
+                &format!(
+                    "Storage {} is still not writable ({}), switching to {} (volatile)",
+                    preferred.display(),
+                    e,
+                    storage.root().display()
+                ),
+                is_prod,
+            );
+            notifier.send(
+                &format!(
+                    "STORAGE_DEGRADED: PATH={} FALLBACK={}",
+                    preferred.display(),
+                    storage.root().display()
+                ),
+                is_prod,
+            );
+            true
+        }
+    }
+}
+
+/// 日志文件位置：--log-to / log_to 优先，否则为存储目录下的默认文件
+fn log_file_path(config: &Config, storage: &Storage) -> PathBuf {
+    if config.log_to.is_empty() {
+        storage.path(LOG_FILE_NAME)
+    } else {
+        PathBuf::from(&config.log_to)
+    }
+}
+
+/// 将 stdout/stderr 重新指向新的日志文件（存储目录切换后使用）
+fn reopen_log_output(log_path: &Path, is_prod: bool) {
+    match fs::OpenOptions::new().create(true).append(true).open(log_path) {
+        Ok(file) => unsafe {
+            libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO);
+            libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO);
+        },
+        Err(e) => log_warn(
+            &format!("Failed to reopen log {}: {}", log_path.display(), e),
+            is_prod,
+        ),
+    }
+}
+
 fn get_target_ip() -> String {
     let args: Vec<String> = env::args().collect();
 
-    for arg in &args[1..] {
-        if !arg.starts_with("--") {
-            return arg.clone();
-        }
+    if let Some(arg) = config::positional_args(&args).first() {
+        return arg.to_string();
     }
 
     if let Ok(env_ip) = env::var("TARGET_IP") {
@@ -1440,140 +3996,449 @@ fn get_target_ip() -> String {
     DEFAULT_TARGET_IP.to_string()
 }
 
-fn check_connectivity(target_ip: &str, is_prod: bool) -> (bool, Option<std::time::Duration>) {
+fn check_connectivity(target_ip: &str, is_prod: bool) -> Result<Duration, FailureReason> {
     let start = Instant::now();
+    tcp_connect_check(target_ip, is_prod).map(|()| start.elapsed())
+}
+
+/// probe=arp 的目标：必须是 LAN 网段内的 IPv4 地址
+fn arp_probe_target(target_ip: &str, lan_subnet: Option<&str>) -> Result<Ipv4Addr, String> {
+    let ip: Ipv4Addr = target_ip
+        .parse()
+        .map_err(|_| format!("target {} is not an IPv4 address", target_ip))?;
+    match lan_subnet {
+        Some(lan) if arp::in_subnet(ip, lan) => Ok(ip),
+        Some(lan) => Err(format!("target {} is outside the LAN subnet {}", ip, lan)),
+        None => Err("LAN subnet unknown, set --assume-lan".to_string()),
+    }
+}
 
-    match tcp_connect_check(target_ip, is_prod) {
-        true => {
-            let duration = start.elapsed();
-            (true, Some(duration))
+/// 解析目标后连接一次，连接方式和超时与守护进程的检查相同（keepalive_check 时使用带
+/// keepalive 的连接，probe=udp 时发 UDP echo，probe=arp 时检查邻居表，
+/// probe=hybrid 时 TCP 失败后再试 ICMP）。
+/// 返回退出码：0 可达，1 不可达，2 地址无法解析
+fn validate_target(target: &str, config: &Config) -> i32 {
+    use std::net::ToSocketAddrs;
+
+    let addr = match target.to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(addr)) => addr,
+        Ok(None) => {
+            eprintln!("{}: no address found", target);
+            return 2;
+        }
+        Err(e) => {
+            eprintln!("{}: cannot resolve: {}", target, e);
+            return 2;
+        }
+    };
+    let mut method = match config.probe {
+        ProbeMethod::Arp => "arp",
+        ProbeMethod::Hybrid => "hybrid",
+        ProbeMethod::Http => "http",
+        ProbeMethod::Udp => "udp",
+        ProbeMethod::Tcp if config.keepalive_check => "keepalive",
+        ProbeMethod::Tcp => "tcp",
+    };
+    let mut ttfb = None;
+    let result = if config.probe == ProbeMethod::Arp {
+        let lan = get_br_network()
+            .or_else(|| (!config.assume_lan.is_empty()).then(|| config.assume_lan.clone()));
+        match arp_probe_target(&addr.ip().to_string(), lan.as_deref()) {
+            Ok(ip) => arp::probe(ip, CONNECT_TIMEOUT),
+            Err(e) => {
+                eprintln!("{}: {}", target, e);
+                return 2;
+            }
+        }
+    } else if config.probe == ProbeMethod::Hybrid {
+        let start = Instant::now();
+        sockmark::connect_tcp(addr, CONNECT_TIMEOUT)
+            .map(|_| {
+                method = "hybrid (tcp)";
+                start.elapsed()
+            })
+            .or_else(|_| {
+                icmp::echo(addr.ip(), CONNECT_TIMEOUT).inspect(|_| method = "hybrid (icmp)")
+            })
+    } else if config.probe == ProbeMethod::Udp {
+        let settings = udpecho::EchoSettings::from_config(config);
+        let mut echo_addr = addr;
+        if settings.port != 0 {
+            echo_addr.set_port(settings.port);
+        }
+        println!("{}", settings.describe(echo_addr));
+        match udpecho::probe(echo_addr, &settings, CONNECT_TIMEOUT) {
+            Ok(udpecho::EchoReply::Matched(rtt)) => Ok(rtt),
+            Ok(udpecho::EchoReply::Mismatched(rtt)) if !settings.strict => {
+                method = "udp (payload mismatch)";
+                Ok(rtt)
+            }
+            Ok(udpecho::EchoReply::Mismatched(_)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "echo payload mismatch",
+            )),
+            Err(e) => Err(e),
+        }
+    } else if config.probe == ProbeMethod::Http {
+        http::probe(addr, CONNECT_TIMEOUT, http::RESPONSE_TIMEOUT).map(|timing| {
+            ttfb = Some(timing.ttfb);
+            timing.connect
+        })
+    } else if config.keepalive_check {
+        // 新的长连接总会握手一次，返回的是连接耗时
+        KeepaliveLink::new(addr).check(CONNECT_TIMEOUT).map(Option::unwrap_or_default)
+    } else {
+        let start = Instant::now();
+        sockmark::connect_tcp(addr, CONNECT_TIMEOUT).map(|_| start.elapsed())
+    };
+    match result {
+        Ok(rtt) => {
+            println!(
+                "{} ({}) reachable via {}: rtt={}ms{}",
+                target,
+                addr,
+                method,
+                rtt.as_millis(),
+                ttfb.map(|d| format!(" ttfb={}ms", d.as_millis())).unwrap_or_default()
+            );
+            0
+        }
+        Err(e) => {
+            println!(
+                "{} ({}) unreachable via {}: {} (timeout {}ms)",
+                target,
+                addr,
+                method,
+                e,
+                CONNECT_TIMEOUT.as_millis()
+            );
+            1
         }
-        false => (false, None),
     }
 }
 
-fn tcp_connect_check(target_ip: &str, is_prod: bool) -> bool {
-    use std::net::TcpStream;
+/// probe=hybrid：先 TCP 连接，失败时再发 ICMP echo，返回成功的方式和往返时间；
+/// 都失败时返回 TCP 的失败原因
+fn hybrid_check(
+    sys: &mut impl SystemOps,
+    target_ip: &str,
+    is_prod: bool,
+) -> Result<(icmp::Via, Duration), FailureReason> {
+    let reason = match sys.check_connectivity(target_ip) {
+        Ok(rtt) => return Ok((icmp::Via::Tcp, rtt)),
+        Err(reason) => reason,
+    };
+    let ip = target_ip
+        .parse::<SocketAddr>()
+        .map_err(|_| reason)?
+        .ip();
+    match icmp::echo(ip, CONNECT_TIMEOUT) {
+        Ok(rtt) => Ok((icmp::Via::Icmp, rtt)),
+        Err(e) => {
+            log_message(&format!("ICMP echo failed: {}", e), is_prod);
+            Err(reason)
+        }
+    }
+}
 
-    match TcpStream::connect_timeout(&target_ip.parse().unwrap(), CONNECT_TIMEOUT) {
+fn tcp_connect_check(target_ip: &str, is_prod: bool) -> Result<(), FailureReason> {
+    match sockmark::connect_tcp(target_ip.parse().unwrap(), CONNECT_TIMEOUT) {
         Ok(stream) => {
             drop(stream);
-            true
+            Ok(())
         }
         Err(e) => {
-            log_message(&format!("TCP connect failed: {}", e), is_prod);
-            false
+            let reason = FailureReason::from_io_error(&e);
+            log_message(
+                &format!("TCP connect failed: {} ({})", e, reason.as_str()),
+                is_prod,
+            );
+            Err(reason)
         }
     }
 }
 
-fn reboot_system(is_prod: bool) {
-    log_message("Attempting system reboot...", is_prod);
-
-    let _ = Command::new("/sbin/reboot").status();
+/// 主动重启；只有没有重启（保守模式拦截或重启失败）时才会返回
+fn reboot_system(
+    sys: &mut impl SystemOps,
+    boot_record: &mut BootRecord,
+    reboot_guard: &mut RebootGuard,
+    hooks: &mut Hooks,
+    notifier: &Notifier,
+    reason: &str,
+    is_prod: bool,
+) {
+    if let Some(remaining) = boot_record.conservative_remaining() {
+        log_warn(
+            &format!(
+                "Reboot suppressed: boot loop conservative mode active for another {}s",
+                remaining.as_secs()
+            ),
+            is_prod,
+        );
+        // 保守模式期间每轮都会走到这里，只在第一次拦截时通知；不等确认，避免阻塞主循环
+        if boot_record.note_cap_reached() {
+            notifier.send(
+                &format!(
+                    "REBOOT_CAP_REACHED: REASON={} REMAINING={}s",
+                    reason,
+                    remaining.as_secs()
+                ),
+                is_prod,
+            );
+        }
+        return;
+    }
 
-    log_message(
-        "All reboot attempts failed! Continuing monitoring...",
+    hooks.run_blocking(
+        HookEvent::BeforeReboot,
+        &[("ZXP_REASON", reason.to_string())],
+        Instant::now(),
+    );
+    log_warn(&format!("Attempting system reboot (reason: {})...", reason), is_prod);
+    // 主动重启前记录正常退出，避免下次启动被误判为非正常启动；重启原因在下次启动时报告
+    boot_record.mark_reboot(reason);
+    // 重启前最后一条通知：配置了 notify_ack_timeout_ms 时等接收端确认再重启
+    notifier.send_critical(&format!("REBOOTING: REASON={}", reason), is_prod);
+
+    sys.reboot();
+
+    // 仍在运行说明重启失败
+    boot_record.clear_clean_shutdown();
+    let retry_in = reboot_guard.record_failure(Instant::now());
+    log_error(
+        &format!(
+            "All reboot attempts failed ({} so far)! Continuing monitoring, next automatic attempt in {}s",
+            reboot_guard.failed_attempts(),
+            retry_in.as_secs()
+        ),
+        is_prod,
+    );
+    notifier.send(
+        &format!(
+            "REBOOT_FAILED: ATTEMPTS={} RETRY_IN={}s",
+            reboot_guard.failed_attempts(),
+            retry_in.as_secs()
+        ),
         is_prod,
     );
-    // thread::sleep(Duration::from_secs(PING_INTERVAL));
 }
 
-fn send_udp_notification(message: &str, addr: String, is_prod: bool) {
-    // 获取设备标识（可以使用主机名或自定义标识）
-    // let hostname = get_hostname().unwrap_or_else(|_| "unknown".to_string());
-    // let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+/// 读取 path 指定的密钥（hmac_key_file、http_token_file），文件其他用户可读时告警
+fn load_secret(name: &str, path: &str, is_prod: bool) -> Option<Vec<u8>> {
+    if path.is_empty() {
+        return None;
+    }
+    match secret::load_key_file(Path::new(path)) {
+        Ok((key, world_readable)) => {
+            if world_readable {
+                log_warn(
+                    &format!(
+                        "{} file {} is world-readable, restrict it with chmod 600",
+                        name, path
+                    ),
+                    is_prod,
+                );
+            }
+            log_message(&format!("Loaded {} from {}", name, path), is_prod);
+            Some(key)
+        }
+        Err(e) => {
+            log_error(&format!("Failed to load {}: {}", name, e), is_prod);
+            None
+        }
+    }
+}
 
-    let full_message = format!("[{}] {}", "zxic", message);
+/// 返回第一个存在且可执行的 reboot 程序
+fn find_reboot_binary() -> Option<&'static str> {
+    REBOOT_BINARIES.iter().copied().find(|path| is_executable(path))
+}
 
-    match UdpSocket::bind(UDP_LOCAL_BIND) {
-        Ok(socket) => {
-            // 设置超时时间
-            let _ = socket.set_write_timeout(Some(UDP_TIMEOUT));
+fn is_executable(path: &str) -> bool {
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
 
-            match socket.send_to(full_message.as_bytes(), addr) {
-                Ok(_) => {
-                    if !is_prod {
-                        // log_message(&format!("UDP notification sent: {}", full_message), is_prod);
-                    }
-                }
-                Err(e) => {
-                    if !is_prod {
-                        log_message(&format!("Failed to send UDP notification: {}", e), is_prod);
-                    }
-                }
-            }
+/// 拆分配置的 reboot_command；程序不存在或不可执行时只使用内置的 reboot
+/// （明确配置的才报错，默认的 /sbin/reboot 在只有 /bin/reboot 的设备上不存在是正常的）
+fn reboot_command(config: &Config, is_prod: bool) -> Vec<String> {
+    let command: Vec<String> = config
+        .reboot_command
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    match command.first() {
+        None => Vec::new(),
+        Some(program) if is_executable(program) => {
+            log_message(&format!("Reboot command: {}", command.join(" ")), is_prod);
+            command
         }
-        Err(e) => {
-            if !is_prod {
-                log_message(&format!("Failed to create UDP socket: {}", e), is_prod);
-            }
+        Some(program)
+            if config
+                .sources
+                .get("reboot_command")
+                .is_none_or(|source| *source == ConfigSource::Default) =>
+        {
+            log_debug(
+                &format!("{} not found, using the built-in reboot", program),
+                is_prod,
+            );
+            Vec::new()
+        }
+        Some(program) => {
+            log_error(
+                &format!(
+                    "reboot_command {} is not an executable file, using the built-in reboot",
+                    program
+                ),
+                is_prod,
+            );
+            Vec::new()
         }
     }
 }
 
+/// 日志级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+// 控制台日志是否带颜色和级别前缀（log_color 配置开启且 stdout 为 TTY）
+static LOG_COLOR: AtomicBool = AtomicBool::new(false);
+// 是否输出 Debug 级别日志（log_debug 配置）
+static LOG_DEBUG: AtomicBool = AtomicBool::new(false);
+// kill 后等待进程退出的最长时间（kill_wait_timeout_ms 配置）
+static KILL_WAIT_TIMEOUT_MS: AtomicU64 = AtomicU64::new(3000);
+
+fn log_debug(message: &str, is_prod: bool) {
+    log_at(LogLevel::Debug, message, is_prod);
+}
+
 fn log_message(message: &str, is_prod: bool) {
+    log_at(LogLevel::Info, message, is_prod);
+}
+
+fn log_warn(message: &str, is_prod: bool) {
+    log_at(LogLevel::Warn, message, is_prod);
+}
+
+fn log_error(message: &str, is_prod: bool) {
+    log_at(LogLevel::Error, message, is_prod);
+}
+
+/// 当前墙上时间（UNIX 秒）
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn log_at(level: LogLevel, message: &str, is_prod: bool) {
+    if level == LogLevel::Debug && !LOG_DEBUG.load(Ordering::Relaxed) {
+        return;
+    }
     if !is_prod {
         let duration = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default();
         let timestamp = duration.as_secs();
-        println!("[{}] {}", timestamp, message);
+        let styled = match level {
+            LogLevel::Warn => Some(("\x1b[33m", "[WARN] ")),
+            LogLevel::Error => Some(("\x1b[31m", "[ERROR] ")),
+            LogLevel::Debug => Some(("\x1b[2m", "[DEBUG] ")),
+            LogLevel::Info => None,
+        };
+        match styled {
+            Some((color, prefix)) if LOG_COLOR.load(Ordering::Relaxed) => {
+                println!("[{}] {}{}{}\x1b[0m", timestamp, color, prefix, message);
+            }
+            _ => println!("[{}] {}", timestamp, message),
+        }
     }
 }
 
-// 强制重启adbd进程
-fn force_restart_adbd_process(is_prod: bool) -> Result<(), String> {
-    log_message("Force restart adbd process...", is_prod);
+// 强制重启服务进程（adbd 等）
+fn force_restart_service(service: &Service, is_prod: bool) -> Result<(), String> {
+    let name = &service.name;
+    let process = service.process_name();
+    log_message(&format!("Force restart {} process...", name), is_prod);
+    if let Some((pid, age)) = procs::youngest_by_name(process) {
+        if age < SERVICE_MIN_RESTART_AGE {
+            return Err(format!(
+                "{} (PID: {}) started {}s ago, not restarting",
+                name,
+                pid,
+                age.as_secs()
+            ));
+        }
+        log_message(
+            &format!(
+                "restarting {}, current instance age {}",
+                name,
+                procs::format_age(age)
+            ),
+            is_prod,
+        );
+    }
 
-    // 1. 查找并杀死所有adbd进程
-    if let Ok(entries) = fs::read_dir("/proc") {
-        for entry in entries.flatten() {
-            let file_name = entry.file_name();
-            let name_str = file_name.to_string_lossy();
+    // 1. 查找并结束所有同名进程
+    let pids = procs::find_by_name(process);
+    for pid in &pids {
+        kill_pid("/bin/kill", service.signal, *pid, is_prod);
+        log_message(&format!("Killed {} process (PID: {})", name, pid), is_prod);
+    }
 
-            if name_str.chars().all(|c| c.is_ascii_digit()) {
-                let cmdline_path = format!("/proc/{}/cmdline", name_str);
-                if let Ok(cmdline_content) = fs::read_to_string(&cmdline_path) {
-                    if cmdline_content.contains("adbd") {
-                        // 修复：将 Cow<'_, str> 转换为 String
-                        let pid = name_str.to_string();
-                        // 杀死adbd进程
-                        let _ = Command::new("/bin/kill").arg("-9").arg(&pid).status();
-                        log_message(&format!("Killed adbd process (PID: {})", pid), is_prod);
-                    }
-                }
-            }
+    // 2. 确认旧进程全部退出后再启动，避免两个 adbd 争用 USB gadget
+    match procs::wait_for_pids_exit(&pids, service.grace) {
+        Ok(waited) => log_message(
+            &format!("{} processes gone after {}ms", name, waited.as_millis()),
+            is_prod,
+        ),
+        Err(pids) => {
+            return Err(format!(
+                "{} still running after {}ms (PIDs: {:?})",
+                name,
+                service.grace.as_millis(),
+                pids
+            ))
         }
     }
 
-    // 2. 等待一段时间确保进程完全终止
-    thread::sleep(Duration::from_secs(3));
-
-    // 3. 启动新的adbd进程
-    let child = Command::new("/etc_rw/adbd")
+    // 3. 启动新的进程
+    let child = Command::new(&service.exec)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .spawn()
-        .map_err(|e| format!("Failed to start adbd: {}", e))?;
+        .map_err(|e| format!("Failed to start {}: {}", name, e))?;
 
     // 4. 设置子进程优先级
-    let pid = child.id();
-    log_message(&format!("set adbd pid={} pri", pid), is_prod);
-    if let Err(e) = ProcessPriority::set_nice(pid, 15) {
-        log_message(
-            &format!("Warning: Could not set priority for adbd: {}", e),
-            is_prod,
-        );
-    } else {
-        log_message(
-            &format!("Set adbd (PID: {}) priority to nice={}", pid, 15),
-            is_prod,
-        );
+    if let Some(nice) = service.nice {
+        let pid = child.id();
+        log_message(&format!("set {} pid={} pri", name, pid), is_prod);
+        if let Err(e) = ProcessPriority::set_nice(pid, nice) {
+            log_warn(
+                &format!("Warning: Could not set priority for {}: {}", name, e),
+                is_prod,
+            );
+        } else {
+            log_message(
+                &format!("Set {} (PID: {}) priority to nice={}", name, pid, nice),
+                is_prod,
+            );
+        }
     }
 
-    log_message("adbd force restarted successfully", is_prod);
-    let _ = re_enable_adb_function(is_prod);
+    log_message(&format!("{} force restarted successfully", name), is_prod);
+    if name == "adbd" {
+        let _ = re_enable_adb_function(is_prod);
+    }
     Ok(())
 }
 
@@ -1592,7 +4457,7 @@ pub fn force_start_goahead_process(is_prod: bool) -> Result<(), String> {
 
     log_message(&format!("set goahead pid={} pri", pid), is_prod);
     if let Err(e) = ProcessPriority::set_nice(pid, 15) {
-        log_message(
+        log_warn(
             &format!("Warning: Could not set priority for goahead: {}", e),
             is_prod,
         );
@@ -1613,31 +4478,48 @@ pub fn force_start_goahead_process(is_prod: bool) -> Result<(), String> {
 fn force_kill_process(is_prod: bool, process_name: &str) -> Result<(), String> {
     log_message("Force restarting process...", is_prod);
 
-    // 1. 查找并杀死所有adbd进程
-    if let Ok(entries) = fs::read_dir("/proc") {
-        for entry in entries.flatten() {
-            let file_name = entry.file_name();
-            let name_str = file_name.to_string_lossy();
-
-            if name_str.chars().all(|c| c.is_ascii_digit()) {
-                let cmdline_path = format!("/proc/{}/cmdline", name_str);
-                if let Ok(cmdline_content) = fs::read_to_string(&cmdline_path) {
-                    if cmdline_content.contains(process_name) {
-                        // 修复：将 Cow<'_, str> 转换为 String
-                        let pid = name_str.to_string();
-                        // 杀死adbd进程
-                        let _ = Command::new("kill").arg("-9").arg(&pid).status();
-                        log_message(&format!("force Killed process (PID: {})", pid), is_prod);
-                    }
-                }
-            }
-        }
+    // 1. 查找并杀死所有同名进程
+    for pid in procs::find_by_name(process_name) {
+        kill_pid("kill", 9, pid, is_prod);
+        log_message(&format!("force Killed process (PID: {})", pid), is_prod);
     }
 
-    // 2. 等待一段时间确保进程完全终止
-    thread::sleep(Duration::from_secs(1));
+    // 2. 确认进程已经全部退出
+    wait_for_kill(process_name, is_prod)
+}
+
+/// 向一个进程发送信号（kill -<signal>）；kill 本身卡住或失败时只记录，之后的退出确认会发现进程还在
+fn kill_pid(kill: &str, signal: i32, pid: u32, is_prod: bool) {
+    let signal = format!("-{}", signal);
+    match command::run_with_timeout(kill, &[&signal, &pid.to_string()], KILL_TIMEOUT) {
+        Ok(result) if result.success() => {}
+        Ok(result) => log_warn(&format!("{} {} {} failed: {}", kill, signal, pid, result), is_prod),
+        Err(e) => log_warn(&format!("{} {} {} failed: {}", kill, signal, pid, e), is_prod),
+    }
+}
 
-    return Ok(());
+/// kill 之后重新扫描进程，直到全部退出或超过 kill_wait_timeout
+fn wait_for_kill(process_name: &str, is_prod: bool) -> Result<(), String> {
+    let timeout = Duration::from_millis(KILL_WAIT_TIMEOUT_MS.load(Ordering::Relaxed));
+    match procs::wait_for_exit(process_name, timeout) {
+        Ok(waited) => {
+            log_message(
+                &format!(
+                    "{} processes gone after {}ms",
+                    process_name,
+                    waited.as_millis()
+                ),
+                is_prod,
+            );
+            Ok(())
+        }
+        Err(pids) => Err(format!(
+            "{} still running after {}ms (PIDs: {:?})",
+            process_name,
+            timeout.as_millis(),
+            pids
+        )),
+    }
 }
 
 /// 使用 libc::sysinfo 获取空闲内存（KB）
@@ -1662,7 +4544,7 @@ fn disable_adb_function(is_prod: bool) -> Result<(), String> {
             log_message("adbd killed successfully", is_prod);
         }
         Err(e) => {
-            log_message(&format!("❌ Failed to kill adbd: {}", e), is_prod);
+            log_error(&format!("❌ Failed to kill adbd: {}", e), is_prod);
         }
     }
 
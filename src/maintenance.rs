@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// 维护模式状态文件名（位于存储目录下，内容为到期时间的 UNIX 秒数）
+pub const MAINTENANCE_FILE: &str = "zxping.maintenance";
+
+/// 维护模式：现场操作时继续监控、记录和通知，但跳过所有保护动作。
+/// 到期时间按墙上时间保存，进程重启后继续生效直到到期
+pub struct Maintenance {
+    path: PathBuf,
+    /// 到期时间（UNIX 秒），None 为未开启
+    until: Option<u64>,
+    /// 本次开启以来跳过的动作数
+    pub suppressed: u32,
+    /// 本次开启以来已经通知过的动作种类
+    notified: HashSet<String>,
+}
+
+impl Maintenance {
+    /// 读取持久化的状态；已经到期的删除。剩余时间超过 timeout 的（时钟被往回调过）按 timeout 计
+    pub fn load(path: PathBuf, now: u64, timeout: Duration) -> Self {
+        let until = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| content.trim().parse::<u64>().ok());
+        let mut maintenance = Maintenance {
+            path,
+            until: None,
+            suppressed: 0,
+            notified: HashSet::new(),
+        };
+        match until {
+            Some(until) if until > now => {
+                maintenance.until = Some(until.min(now + timeout.as_secs()));
+            }
+            Some(_) => {
+                let _ = fs::remove_file(&maintenance.path);
+            }
+            None => {}
+        }
+        maintenance
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.until.is_some()
+    }
+
+    /// 开启（已开启时重新计时），返回到期时间
+    pub fn enable(&mut self, now: u64, timeout: Duration) -> io::Result<u64> {
+        let until = now + timeout.as_secs();
+        if self.until.is_none() {
+            self.suppressed = 0;
+            self.notified.clear();
+        }
+        self.until = Some(until);
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, format!("{}\n", until))?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(until)
+    }
+
+    /// 关闭，返回之前是否开启
+    pub fn disable(&mut self) -> io::Result<bool> {
+        let was_active = self.until.take().is_some();
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(was_active),
+        }
+    }
+
+    /// 到期时自动关闭并返回 true（每次开启只返回一次）
+    pub fn expire(&mut self, now: u64) -> bool {
+        if self.until.is_some_and(|until| now >= until) {
+            let _ = self.disable();
+            return true;
+        }
+        false
+    }
+
+    /// 记录一次被跳过的动作，本次开启以来第一次跳过这种动作时返回 true（需要通知）
+    pub fn suppress(&mut self, what: &str) -> bool {
+        self.suppressed += 1;
+        self.notified.insert(what.to_string())
+    }
+
+    /// 存储目录切换后改用新路径（状态文件一并迁移）
+    pub fn relocate(&mut self, path: PathBuf) {
+        let _ = fs::remove_file(&self.path);
+        self.path = path;
+        if let Some(until) = self.until {
+            let _ = fs::write(&self.path, format!("{}\n", until));
+        }
+    }
+
+    /// STATUS 中的一行：`maintenance=on remaining=1800s maintenance_suppressed=2`
+    pub fn status_line(&self, now: u64) -> String {
+        match self.until {
+            Some(until) => format!(
+                "maintenance=on remaining={}s maintenance_suppressed={}",
+                until.saturating_sub(now),
+                self.suppressed
+            ),
+            None => "maintenance=off".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("zxping-maint-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_enable_persist_expire() {
+        let path = temp_path("persist");
+        let timeout = Duration::from_secs(600);
+        let mut maintenance = Maintenance::load(path.clone(), 1_000, timeout);
+        assert!(!maintenance.is_active());
+        assert_eq!(maintenance.status_line(1_000), "maintenance=off");
+
+        assert_eq!(maintenance.enable(1_000, timeout).unwrap(), 1_600);
+        assert!(maintenance.suppress("reboot"));
+        assert!(!maintenance.suppress("reboot"));
+        assert!(maintenance.suppress("throttle"));
+        assert_eq!(
+            maintenance.status_line(1_100),
+            "maintenance=on remaining=500s maintenance_suppressed=3"
+        );
+
+        // 重启后继续生效
+        let reloaded = Maintenance::load(path.clone(), 1_200, timeout);
+        assert!(reloaded.is_active());
+        assert_eq!(
+            reloaded.status_line(1_200),
+            "maintenance=on remaining=400s maintenance_suppressed=0"
+        );
+
+        assert!(!maintenance.expire(1_599));
+        assert!(maintenance.expire(1_600));
+        assert!(!maintenance.expire(1_700));
+        assert!(!maintenance.is_active());
+        // 下次开启时重新通知
+        maintenance.enable(1_700, timeout).unwrap();
+        assert!(maintenance.suppress("reboot"));
+        maintenance.disable().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_load_expired_and_disable() {
+        let path = temp_path("expired");
+        let timeout = Duration::from_secs(600);
+        let mut maintenance = Maintenance::load(path.clone(), 1_000, timeout);
+        maintenance.enable(1_000, timeout).unwrap();
+
+        // 重启时已经到期
+        assert!(!Maintenance::load(path.clone(), 2_000, timeout).is_active());
+        assert!(!path.exists());
+
+        // 时钟往回调过，剩余时间不超过 timeout
+        maintenance.enable(1_000, timeout).unwrap();
+        let reloaded = Maintenance::load(path.clone(), 100, timeout);
+        assert_eq!(
+            reloaded.status_line(100),
+            "maintenance=on remaining=600s maintenance_suppressed=0"
+        );
+
+        assert!(maintenance.disable().unwrap());
+        assert!(!maintenance.disable().unwrap());
+        assert!(!path.exists());
+    }
+}
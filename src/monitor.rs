@@ -0,0 +1,1381 @@
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::score::{HealthScore, ScoreLevel, Signals};
+use crate::severity::Severity;
+use crate::{
+    LogLevel, HIGH_LATENCY_THRESHOLD, HIGH_LATENCY_THRESHOLD_MAX, HIGH_LATENCY_THRESHOLD_MIN,
+};
+
+/// 链路健康状态（每轮检查后根据连接结果、RTT 和丢包率计算）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthState {
+    Healthy,
+    /// 仍能连接，但延迟高或近期有丢包
+    Degraded,
+    Failed,
+}
+
+impl HealthState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthState::Healthy => "HEALTHY",
+            HealthState::Degraded => "DEGRADED",
+            HealthState::Failed => "FAILED",
+        }
+    }
+}
+
+/// 连接失败的原因。不同原因说明的问题不同：拒绝说明目标在线只是端口关闭，
+/// 网络不可达说明本地已经没有路由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureReason {
+    Timeout,
+    Refused,
+    NetworkUnreachable,
+    HostUnreachable,
+    /// gateway_probe 检查到默认网关不通，没有检查目标
+    GatewayDown,
+    Other,
+}
+
+impl FailureReason {
+    pub fn from_io_error(e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => FailureReason::Timeout,
+            io::ErrorKind::ConnectionRefused => FailureReason::Refused,
+            io::ErrorKind::NetworkUnreachable => FailureReason::NetworkUnreachable,
+            io::ErrorKind::HostUnreachable => FailureReason::HostUnreachable,
+            _ => FailureReason::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureReason::Timeout => "timeout",
+            FailureReason::Refused => "refused",
+            FailureReason::NetworkUnreachable => "net_unreachable",
+            FailureReason::HostUnreachable => "host_unreachable",
+            FailureReason::GatewayDown => "gateway_down",
+            FailureReason::Other => "error",
+        }
+    }
+
+    /// 计入连续失败次数的权重：网络不可达是明确的本地故障，升级得更快
+    fn weight(&self) -> u32 {
+        match self {
+            FailureReason::NetworkUnreachable => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// 一次连通性检查后需要执行的保护动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LatencyAction {
+    None,
+    /// 连续高延迟达到上限：结束 adbd/goahead 并收紧网络参数
+    Throttle,
+    /// 限流期间延迟恢复到很低：恢复网络参数并重新启动 goahead
+    Restore,
+}
+
+/// 最近 N 次检查结果，用于计算丢包率
+pub struct LossWindow {
+    results: VecDeque<bool>,
+    size: usize,
+}
+
+impl LossWindow {
+    pub fn new(size: usize) -> Self {
+        LossWindow {
+            results: VecDeque::with_capacity(size),
+            size,
+        }
+    }
+
+    pub fn record(&mut self, success: bool) {
+        if self.results.len() == self.size {
+            self.results.pop_front();
+        }
+        self.results.push_back(success);
+    }
+
+    /// 丢包率百分比（0-100），无样本时为 0
+    pub fn loss_percent(&self) -> u32 {
+        if self.results.is_empty() {
+            return 0;
+        }
+        let (failed, checks) = self.failures();
+        (failed * 100 / checks) as u32
+    }
+
+    /// 窗口内失败的次数和已记录的次数
+    pub fn failures(&self) -> (usize, usize) {
+        let failed = self.results.iter().filter(|ok| !**ok).count();
+        (failed, self.results.len())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.size > 0 && self.results.len() >= self.size
+    }
+
+    /// 修改窗口大小（配置重新加载后），变小时丢掉最早的结果
+    pub fn resize(&mut self, size: usize) {
+        self.size = size;
+        while self.results.len() > size {
+            self.results.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.results.clear();
+    }
+}
+
+/// 主循环的连通性监控状态
+pub struct MonitorState {
+    pub failure_count: u32,
+    /// 本次连续失败中第一次失败的时间，成功后清除
+    failing_since: Option<Instant>,
+    pub high_latency_count: u32,
+    pub health: HealthState,
+    pub loss: LossWindow,
+    pub last_rtt_ms: Option<u128>,
+    /// 当前连续成功的检查次数，失败时清零
+    pub success_streak: u32,
+    /// 当前连续成功区间的开始时间（第一次成功的检查）
+    streak_since: Option<Instant>,
+    /// 启动以来最长的连续成功时长
+    longest_streak: Duration,
+    /// 当前链路质量级别（对应已写入的网络参数）
+    pub severity: Severity,
+    /// 本次断网期间已因高负载卸载过负载，恢复连接时撤销
+    load_shed: bool,
+    /// 失败、高延迟、高负载综合的健康分数
+    pub score: HealthScore,
+    /// 最近一次失败的原因，成功后清除
+    pub last_failure: Option<FailureReason>,
+    /// 启动以来的软丢包次数（连接成功但耗时超过 rtt_outlier_ms）
+    pub soft_losses: u64,
+    /// 最近 failure_window 次检查的结果（不含软丢包），大小为 0 时不记录
+    pub failure_window: LossWindow,
+}
+
+impl MonitorState {
+    pub fn new() -> Self {
+        MonitorState {
+            failure_count: 0,
+            failing_since: None,
+            high_latency_count: 0,
+            health: HealthState::Healthy,
+            loss: LossWindow::new(10),
+            last_rtt_ms: None,
+            success_streak: 0,
+            streak_since: None,
+            longest_streak: Duration::ZERO,
+            severity: Severity::Normal,
+            load_shed: false,
+            score: HealthScore::new(),
+            last_failure: None,
+            soft_losses: 0,
+            failure_window: LossWindow::new(0),
+        }
+    }
+
+    /// 记录连续成功次数和时长，失败时结束当前区间（最长值保留）
+    pub fn record_streak(&mut self, connected: bool, now: Instant) {
+        if connected {
+            self.success_streak += 1;
+            let since = *self.streak_since.get_or_insert(now);
+            self.longest_streak = self.longest_streak.max(now.duration_since(since));
+        } else {
+            self.success_streak = 0;
+            self.streak_since = None;
+        }
+    }
+
+    /// 当前连续成功区间的时长
+    pub fn current_streak(&self, now: Instant) -> Duration {
+        self.streak_since
+            .map(|since| now.duration_since(since))
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// 本次断网已持续的时间（从第一次失败算起），未断网时为 0
+    pub fn outage(&self, now: Instant) -> Duration {
+        self.failing_since
+            .map(|since| now.duration_since(since))
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// 最长连续成功时长（包括仍在进行的区间）
+    pub fn longest_streak(&self, now: Instant) -> Duration {
+        self.longest_streak.max(self.current_streak(now))
+    }
+
+    /// HEARTBEAT 通知中的可用性字段：`STREAK=42 HEALTHY_FOR=1260s LONGEST_HEALTHY=86400s`
+    pub fn heartbeat_fields(&self, now: Instant) -> String {
+        format!(
+            "STREAK={} HEALTHY_FOR={}s LONGEST_HEALTHY={}s",
+            self.success_streak,
+            self.current_streak(now).as_secs(),
+            self.longest_streak(now).as_secs()
+        )
+    }
+
+    /// 记录一次检查结果并重新计算健康状态，状态变化时返回旧状态。
+    /// soft_loss 为连接成功但耗时异常，在丢包率中按失败计
+    pub fn update_health(
+        &mut self,
+        connected: bool,
+        rtt_ms: Option<u128>,
+        high_latency_threshold: u128,
+        soft_loss: bool,
+    ) -> Option<HealthState> {
+        self.loss.record(connected && !soft_loss);
+        if soft_loss {
+            self.soft_losses += 1;
+        }
+        if connected {
+            self.last_rtt_ms = rtt_ms;
+        }
+
+        let high_latency = rtt_ms.is_some_and(|rtt| rtt > high_latency_threshold);
+        let new_health = if !connected {
+            HealthState::Failed
+        } else if high_latency || self.loss.loss_percent() > 0 {
+            HealthState::Degraded
+        } else {
+            HealthState::Healthy
+        };
+
+        let old_health = self.health;
+        self.health = new_health;
+        if old_health != new_health {
+            Some(old_health)
+        } else {
+            None
+        }
+    }
+
+    /// 根据一次检查结果更新失败和高延迟计数，返回需要执行的保护动作
+    fn record_check(
+        &mut self,
+        connected: bool,
+        rtt: Option<Duration>,
+        failure: Option<FailureReason>,
+        config: &Config,
+    ) -> LatencyAction {
+        if !connected {
+            self.failure_count += failure.map_or(1, |reason| reason.weight());
+            self.last_failure = failure;
+            return LatencyAction::None;
+        }
+        self.failure_count = 0;
+        self.last_failure = None;
+        let Some(rtt) = rtt else {
+            // 连接成功但没有获取到时间（理论上不应该发生）
+            self.high_latency_count = 0;
+            return LatencyAction::None;
+        };
+
+        let rtt_ms = rtt.as_millis();
+        let max = config.max_high_latency;
+        if rtt_ms > HIGH_LATENCY_THRESHOLD {
+            self.high_latency_count += 1;
+            // 延迟极高时直接进入限流
+            if rtt_ms > HIGH_LATENCY_THRESHOLD_MAX && self.high_latency_count < max {
+                self.high_latency_count = max;
+            }
+            if self.high_latency_count == max {
+                return LatencyAction::Throttle;
+            }
+        } else if self.high_latency_count >= max {
+            // 限流中只有延迟足够低才恢复，否则保持
+            if rtt_ms < HIGH_LATENCY_THRESHOLD_MIN {
+                self.high_latency_count = 1;
+                return LatencyAction::Restore;
+            }
+            self.high_latency_count = max;
+        } else {
+            self.high_latency_count = self.high_latency_count.saturating_sub(1);
+        }
+        LatencyAction::None
+    }
+
+    /// PING2 命令的单行摘要，字段顺序固定（脚本可按空格切分）：
+    /// `OK up=<秒> fail=<连续失败> lat=<ms>ms cpu=<占用>% load=<normal|high|throttled> tgt=<目标> ver=<版本>`
+    /// 未知的值写作 `-`，整行不超过一个小 UDP 包
+    pub fn summary_line(
+        &self,
+        uptime_secs: u64,
+        cpu_usage: Option<f32>,
+        load: &str,
+        target: &str,
+        clients: Option<usize>,
+    ) -> String {
+        format!(
+            "OK up={} fail={} lat={} cpu={} load={} tgt={} ver={} clients={}",
+            uptime_secs,
+            self.failure_count,
+            self.last_rtt_ms
+                .map(|ms| format!("{}ms", ms))
+                .unwrap_or_else(|| "-".to_string()),
+            cpu_usage
+                .map(|cpu| format!("{:.0}%", cpu))
+                .unwrap_or_else(|| "-".to_string()),
+            load,
+            target,
+            env!("CARGO_PKG_VERSION"),
+            clients
+                .map(|count| count.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        )
+    }
+
+    /// STATUS 命令的返回内容（每行 key=value）
+    pub fn status_lines(&self) -> Vec<String> {
+        let now = Instant::now();
+        vec![
+            format!("health={}", self.health.as_str()),
+            format!("failure_count={}", self.failure_count),
+            format!(
+                "last_failure={}",
+                self.last_failure.map_or("-", |reason| reason.as_str())
+            ),
+            format!("outage={}s", self.outage(now).as_secs()),
+            format!("high_latency_count={}", self.high_latency_count),
+            format!("loss={}%", self.loss.loss_percent()),
+            format!("soft_loss={}", self.soft_losses),
+            if self.failure_window.size == 0 {
+                "failure_ratio=-".to_string()
+            } else {
+                let (failed, checks) = self.failure_window.failures();
+                format!(
+                    "failure_ratio={}% failure_window_failed={} failure_window_checks={}",
+                    self.failure_window.loss_percent(),
+                    failed,
+                    checks
+                )
+            },
+            format!("severity={}", self.severity.as_str()),
+            format!(
+                "last_rtt_ms={}",
+                self.last_rtt_ms
+                    .map(|ms| ms.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            ),
+            format!("success_streak={}", self.success_streak),
+            format!("healthy_for={}s", self.current_streak(now).as_secs()),
+            format!("longest_healthy={}s", self.longest_streak(now).as_secs()),
+            self.score.status_line(),
+        ]
+    }
+}
+
+/// 一轮网络检查的输入
+pub struct CycleInputs<'a> {
+    pub target: &'a str,
+    pub connected: bool,
+    pub rtt: Option<Duration>,
+    /// 连接失败的原因，未知时为 None（按超时计）
+    pub failure: Option<FailureReason>,
+    /// 不在重启退避期内
+    pub reboot_allowed: bool,
+    /// 已有路径探测在运行
+    pub probe_running: bool,
+    /// CPU 处于高负载状态
+    pub high_load: bool,
+    pub now: Instant,
+}
+
+/// 重启原因（写入启动记录）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebootReason {
+    /// CPU 正常时的连续失败，按链路问题处理
+    Link,
+    /// 高负载期间的连续失败超过了放大后的预算
+    HighLoad,
+    /// 限流后高延迟持续到最后一级升级
+    Latency,
+    /// 综合健康分数越过重启阈值
+    Score,
+    /// 最近 failure_window 次检查的失败比例超过 failure_window_percent
+    FailureRatio,
+}
+
+impl RebootReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RebootReason::Link => "link",
+            RebootReason::HighLoad => "high_load",
+            RebootReason::Latency => "latency",
+            RebootReason::Score => "score",
+            RebootReason::FailureRatio => "failure_ratio",
+        }
+    }
+}
+
+/// 连续失败时的升级方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escalation {
+    Wait,
+    /// 先限流、清理页缓存并报告占用 CPU 最多的进程
+    ShedLoad,
+    Reboot(RebootReason),
+}
+
+/// 限流后高延迟仍持续时的升级步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyStage {
+    ClearCache,
+    BounceInterface,
+    Reboot,
+}
+
+impl LatencyStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LatencyStage::ClearCache => "clear_cache",
+            LatencyStage::BounceInterface => "bounce_interface",
+            LatencyStage::Reboot => "reboot",
+        }
+    }
+}
+
+/// 限流（high_latency_count 达到 max_high_latency）之后，高延迟每再持续
+/// latency_escalate_after 次检查升级一级；每一级只在刚好达到时触发一次
+pub fn latency_escalation(high_latency_count: u32, config: &Config) -> Option<LatencyStage> {
+    let step = config.latency_escalate_after;
+    let over = high_latency_count.checked_sub(config.max_high_latency)?;
+    if step == 0 || over == 0 || over % step != 0 {
+        return None;
+    }
+    match over / step {
+        1 => Some(LatencyStage::ClearCache),
+        2 => Some(LatencyStage::BounceInterface),
+        3 => Some(LatencyStage::Reboot),
+        _ => None,
+    }
+}
+
+/// 重启前允许的连续失败次数：高负载时放大 high_load_failure_factor 倍
+pub fn failure_budget(high_load: bool, config: &Config) -> u32 {
+    if high_load {
+        config
+            .max_failures
+            .saturating_mul(config.high_load_failure_factor)
+    } else {
+        config.max_failures
+    }
+}
+
+/// 根据连续失败次数和 CPU 负载决定升级方式。高负载时的断网多半是负载的结果，
+/// 直接重启会丢掉现场，所以达到 max_failures 时先卸载负载（每次断网一次），到放大后的预算才重启
+pub fn escalation(
+    failure_count: u32,
+    high_load: bool,
+    load_shed: bool,
+    config: &Config,
+) -> Escalation {
+    if failure_count >= failure_budget(high_load, config) {
+        let reason = if high_load {
+            RebootReason::HighLoad
+        } else {
+            RebootReason::Link
+        };
+        Escalation::Reboot(reason)
+    } else if high_load && !load_shed && failure_count >= config.max_failures {
+        Escalation::ShedLoad
+    } else {
+        Escalation::Wait
+    }
+}
+
+/// step() 产生、由主循环执行的动作
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Log(LogLevel, String),
+    Notify(String),
+    /// 结束 adbd/goahead 并收紧网络参数
+    Throttle,
+    /// 恢复网络参数、重新启动 goahead 并清理页缓存
+    Restore,
+    RebootSystem(RebootReason),
+    /// 高负载期间断网：限流、清理页缓存并报告占用 CPU 最多的进程（恢复连接时撤销限流）
+    ShedLoad,
+    ClearPageCache,
+    /// 重启 WAN 接口（down/up）
+    BounceInterface,
+    StartPathProbe,
+    /// 写入该级别的网络参数
+    SetSeverity(Severity),
+    /// 检查厂商连接管理进程，不在则重新启动（断网后的第一步）
+    CheckConnManager,
+}
+
+impl Action {
+    /// 会改动系统（参数、进程、接口、重启）的动作名，维护模式下跳过；只记录/通知/探测的为 None
+    pub fn protective_name(&self) -> Option<&'static str> {
+        match self {
+            Action::Log(..) | Action::Notify(_) | Action::StartPathProbe => None,
+            Action::Throttle => Some("THROTTLE"),
+            Action::Restore => Some("RESTORE"),
+            Action::RebootSystem(_) => Some("REBOOT"),
+            Action::ShedLoad => Some("SHED_LOAD"),
+            Action::ClearPageCache => Some("CLEAR_PAGE_CACHE"),
+            Action::BounceInterface => Some("BOUNCE_INTERFACE"),
+            Action::SetSeverity(_) => Some("SET_SEVERITY"),
+            Action::CheckConnManager => Some("CHECK_CONN_MANAGER"),
+        }
+    }
+}
+
+/// 一轮网络检查的决策：更新计数和健康状态，返回需要执行的动作（不做任何 I/O）
+pub fn step(state: &mut MonitorState, config: &Config, inputs: CycleInputs) -> Vec<Action> {
+    let mut actions = Vec::new();
+    let previous_failures = state.failure_count;
+    let latency_action = state.record_check(inputs.connected, inputs.rtt, inputs.failure, config);
+    // 恢复时用于报告刚结束的断网时长
+    let outage = state.outage(inputs.now);
+    if inputs.connected {
+        state.failing_since = None;
+    } else {
+        state.failing_since.get_or_insert(inputs.now);
+    }
+    match (inputs.connected, inputs.rtt) {
+        (true, Some(rtt)) if rtt.as_millis() > HIGH_LATENCY_THRESHOLD => {
+            actions.push(Action::Log(
+                LogLevel::Info,
+                format!(
+                    "High latency detected: {}ms (> {}ms)",
+                    rtt.as_millis(),
+                    HIGH_LATENCY_THRESHOLD
+                ),
+            ));
+            actions.push(Action::Log(
+                LogLevel::Info,
+                format!(
+                    "High latency count: {}/{}",
+                    state.high_latency_count, config.max_high_latency
+                ),
+            ));
+            actions.push(Action::Notify(format!(
+                "HIGH_LATENCY: LATENCY={:.1}",
+                rtt.as_millis()
+            )));
+            if latency_action == LatencyAction::Throttle && config.health_score_throttle == 0 {
+                actions.push(Action::Log(
+                    LogLevel::Warn,
+                    format!(
+                        "WARN: {} consecutive high latency connections detected",
+                        config.max_high_latency
+                    ),
+                ));
+                actions.push(Action::Throttle);
+            }
+            if let Some(stage) = latency_escalation(state.high_latency_count, config) {
+                actions.push(Action::Log(
+                    LogLevel::Warn,
+                    format!(
+                        "High latency persists after throttling ({} checks), escalating: {}",
+                        state.high_latency_count,
+                        stage.as_str()
+                    ),
+                ));
+                actions.push(Action::Notify(format!(
+                    "LATENCY_ESCALATION: STAGE={} COUNT={}",
+                    stage.as_str(),
+                    state.high_latency_count
+                )));
+                match stage {
+                    LatencyStage::ClearCache => actions.push(Action::ClearPageCache),
+                    LatencyStage::BounceInterface => actions.push(Action::BounceInterface),
+                    LatencyStage::Reboot
+                        if config.auto_reboot
+                            && inputs.reboot_allowed
+                            && config.health_score_reboot == 0 =>
+                    {
+                        actions.push(Action::RebootSystem(RebootReason::Latency))
+                    }
+                    LatencyStage::Reboot => {}
+                }
+            }
+        }
+        (true, Some(rtt)) => {
+            if latency_action == LatencyAction::Restore && config.health_score_throttle == 0 {
+                actions.push(Action::Restore);
+            }
+            actions.push(Action::Notify(format!(
+                "NORMAL_LATENCY: LATENCY={:.1}",
+                rtt.as_millis()
+            )));
+        }
+        (true, None) => {
+            // 连接成功但没有测得时间（keepalive_check 的长连接仍在时没有新的 RTT）
+            actions.push(Action::Log(
+                LogLevel::Debug,
+                format!(
+                    "✓ Connection to {} successful, but duration not measured",
+                    inputs.target
+                ),
+            ));
+        }
+        (false, _) => {
+            let cause = state
+                .last_failure
+                .map_or("unknown", |reason| reason.as_str());
+            actions.push(Action::Log(
+                LogLevel::Warn,
+                format!("✗ Connection to {} failed ({})", inputs.target, cause),
+            ));
+            actions.push(Action::Log(
+                LogLevel::Info,
+                format!(
+                    "Failure count: {}/{} (outage {}s)",
+                    state.failure_count,
+                    failure_budget(inputs.high_load, config),
+                    outage.as_secs()
+                ),
+            ));
+            if previous_failures == 0 && !config.conn_manager_process.is_empty() {
+                actions.push(Action::CheckConnManager);
+            }
+            let escalation = escalation(
+                state.failure_count,
+                inputs.high_load,
+                state.load_shed,
+                config,
+            );
+            if escalation == Escalation::ShedLoad {
+                state.load_shed = true;
+                actions.push(Action::Log(
+                    LogLevel::Warn,
+                    format!(
+                        "{} consecutive failures under high CPU load, shedding load before rebooting (budget {})",
+                        state.failure_count,
+                        failure_budget(true, config)
+                    ),
+                ));
+                actions.push(Action::ShedLoad);
+            }
+            // 开启了健康分数重启时由分数决定是否重启
+            if let (Escalation::Reboot(reason), true, true, 0) = (
+                escalation,
+                config.auto_reboot,
+                inputs.reboot_allowed,
+                config.health_score_reboot,
+            ) {
+                // 间隔很短的连续重试不算持续断网
+                if outage >= config.reboot_min_outage {
+                    actions.push(Action::Log(
+                        LogLevel::Error,
+                        format!(
+                            "Critical: {} consecutive failures over {}s detected ({}, last {}), rebooting",
+                            state.failure_count,
+                            outage.as_secs(),
+                            reason.as_str(),
+                            cause
+                        ),
+                    ));
+                    actions.push(Action::RebootSystem(reason));
+                } else {
+                    actions.push(Action::Log(
+                        LogLevel::Info,
+                        format!(
+                            "Reboot deferred: outage {}s < {}s",
+                            outage.as_secs(),
+                            config.reboot_min_outage.as_secs()
+                        ),
+                    ));
+                }
+            }
+            if previous_failures < config.diag_failure_threshold
+                && state.failure_count >= config.diag_failure_threshold
+                && !inputs.probe_running
+            {
+                actions.push(Action::StartPathProbe);
+            }
+        }
+    }
+
+    if inputs.connected && state.load_shed {
+        // 撤销断网期间为卸载负载做的限流
+        state.load_shed = false;
+        if !actions.contains(&Action::Restore) {
+            actions.push(Action::Restore);
+        }
+    }
+
+    state.record_streak(inputs.connected, inputs.now);
+    let soft_loss = !config.rtt_outlier.is_zero()
+        && inputs.connected
+        && inputs.rtt.is_some_and(|rtt| rtt > config.rtt_outlier);
+    if soft_loss {
+        actions.push(Action::Log(
+            LogLevel::Info,
+            format!(
+                "RTT outlier {}ms (> {}ms) counted as soft loss",
+                inputs.rtt.unwrap_or_default().as_millis(),
+                config.rtt_outlier.as_millis()
+            ),
+        ));
+    }
+    if let Some(old_health) = state.update_health(
+        inputs.connected,
+        inputs.rtt.map(|d| d.as_millis()),
+        HIGH_LATENCY_THRESHOLD,
+        soft_loss,
+    ) {
+        actions.push(Action::Log(
+            LogLevel::Info,
+            format!(
+                "Health state changed: {} -> {}",
+                old_health.as_str(),
+                state.health.as_str()
+            ),
+        ));
+        let mut message = format!(
+            "HEALTH_STATE: {} (was {}, loss={}%)",
+            state.health.as_str(),
+            old_health.as_str(),
+            state.loss.loss_percent()
+        );
+        if old_health == HealthState::Failed {
+            message = format!("{} outage={}s", message, outage.as_secs());
+        }
+        if let Some(reason) = state.last_failure {
+            message = format!("{} cause={}", message, reason.as_str());
+        }
+        actions.push(Action::Notify(message));
+    }
+
+    ratio_actions(state, config, &inputs, &mut actions);
+    score_actions(state, config, &inputs, &mut actions);
+
+    let severity = Severity::assess(
+        state.high_latency_count,
+        config.max_high_latency,
+        state.loss.loss_percent(),
+    );
+    if severity != state.severity {
+        actions.push(Action::Log(
+            LogLevel::Info,
+            format!(
+                "Link severity changed: {} -> {}",
+                state.severity.as_str(),
+                severity.as_str()
+            ),
+        ));
+        actions.push(Action::Notify(format!(
+            "SEVERITY: {} (was {})",
+            severity.as_str(),
+            state.severity.as_str()
+        )));
+        actions.push(Action::SetSeverity(severity));
+        state.severity = severity;
+    }
+    actions
+}
+
+/// 记录到失败比例窗口；窗口已满且失败比例超过 failure_window_percent 时通知并重启，然后清空窗口。
+/// 时好时坏的链路连续失败总被成功打断，达不到 max_failures，但按比例能发现
+fn ratio_actions(
+    state: &mut MonitorState,
+    config: &Config,
+    inputs: &CycleInputs,
+    actions: &mut Vec<Action>,
+) {
+    let window = &mut state.failure_window;
+    window.resize(config.failure_window as usize);
+    if config.failure_window == 0 {
+        return;
+    }
+    window.record(inputs.connected);
+    let (failed, checks) = window.failures();
+    if inputs.connected
+        || !window.is_full()
+        || failed * 100 <= checks * config.failure_window_percent as usize
+    {
+        return;
+    }
+    window.clear();
+    actions.push(Action::Log(
+        LogLevel::Warn,
+        format!(
+            "{} of the last {} checks failed (> {}%)",
+            failed, checks, config.failure_window_percent
+        ),
+    ));
+    actions.push(Action::Notify(format!(
+        "FAILURE_RATIO: FAILED={} WINDOW={} THRESHOLD={}%",
+        failed, checks, config.failure_window_percent
+    )));
+    if config.auto_reboot
+        && inputs.reboot_allowed
+        && config.health_score_reboot == 0
+        && !actions.iter().any(|a| matches!(a, Action::RebootSystem(_)))
+    {
+        actions.push(Action::RebootSystem(RebootReason::FailureRatio));
+    }
+}
+
+/// 更新健康分数；越过阈值时通知并按级别限流或重启，从限流级别退下时恢复。
+/// 限流、重启阈值开启后分别代替各计数各自触发的限流和重启，失败、高延迟、高负载只作为扣分
+fn score_actions(
+    state: &mut MonitorState,
+    config: &Config,
+    inputs: &CycleInputs,
+    actions: &mut Vec<Action>,
+) {
+    let signals = Signals {
+        failed: !inputs.connected,
+        high_latency: inputs
+            .rtt
+            .is_some_and(|rtt| rtt.as_millis() > HIGH_LATENCY_THRESHOLD),
+        high_load: inputs.high_load,
+    };
+    let Some(previous) = state.score.update(&signals, inputs.now, config) else {
+        return;
+    };
+    let level = state.score.level;
+    actions.push(Action::Log(
+        if level > previous {
+            LogLevel::Warn
+        } else {
+            LogLevel::Info
+        },
+        format!(
+            "Health score {:.1}: {} -> {}",
+            state.score.value(),
+            previous.as_str(),
+            level.as_str()
+        ),
+    ));
+    actions.push(Action::Notify(format!(
+        "HEALTH_SCORE: SCORE={:.1} LEVEL={} (was {})",
+        state.score.value(),
+        level.as_str(),
+        previous.as_str()
+    )));
+    if level >= ScoreLevel::Throttle && previous < ScoreLevel::Throttle {
+        if !actions.contains(&Action::Throttle) {
+            actions.push(Action::Throttle);
+        }
+    } else if level < ScoreLevel::Throttle
+        && previous >= ScoreLevel::Throttle
+        && !actions.contains(&Action::Restore)
+    {
+        actions.push(Action::Restore);
+    }
+    if level == ScoreLevel::Reboot
+        && config.auto_reboot
+        && inputs.reboot_allowed
+        && !actions.iter().any(|a| matches!(a, Action::RebootSystem(_)))
+    {
+        actions.push(Action::RebootSystem(RebootReason::Score));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loss_window() {
+        let mut window = LossWindow::new(4);
+        assert_eq!(window.loss_percent(), 0);
+        window.record(true);
+        window.record(false);
+        assert_eq!(window.loss_percent(), 50);
+        for _ in 0..4 {
+            window.record(true);
+        }
+        // 最早的失败已被挤出窗口
+        assert_eq!(window.loss_percent(), 0);
+    }
+
+    #[test]
+    fn test_update_health() {
+        let mut state = MonitorState::new();
+        assert_eq!(state.update_health(true, Some(50), 300, false), None);
+        assert_eq!(state.health, HealthState::Healthy);
+
+        assert_eq!(
+            state.update_health(true, Some(500), 300, false),
+            Some(HealthState::Healthy)
+        );
+        assert_eq!(state.health, HealthState::Degraded);
+
+        state.update_health(false, None, 300, false);
+        assert_eq!(state.health, HealthState::Failed);
+        assert_eq!(state.last_rtt_ms, Some(500));
+
+        // 恢复连接后，窗口内仍有失败记录，保持 DEGRADED
+        state.update_health(true, Some(50), 300, false);
+        assert_eq!(state.health, HealthState::Degraded);
+    }
+
+    #[test]
+    fn test_soft_loss() {
+        let config = Config {
+            rtt_outlier: Duration::from_millis(1000),
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        step(&mut state, &config, inputs(Some(50)));
+        step(&mut state, &config, inputs(Some(1500)));
+        // 连接成功但算作一次丢包
+        assert_eq!(state.failure_count, 0);
+        assert_eq!(state.soft_losses, 1);
+        assert_eq!(state.loss.loss_percent(), 50);
+        assert!(state.status_lines().contains(&"soft_loss=1".to_string()));
+
+        // 关闭时不计
+        let mut state = MonitorState::new();
+        step(&mut state, &Config::default(), inputs(Some(1500)));
+        assert_eq!(state.soft_losses, 0);
+        assert_eq!(state.loss.loss_percent(), 0);
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let mut state = MonitorState::new();
+        assert_eq!(
+            state.summary_line(5, None, "normal", "1.2.3.4:80", None),
+            concat!(
+                "OK up=5 fail=0 lat=- cpu=- load=normal tgt=1.2.3.4:80 ver=",
+                env!("CARGO_PKG_VERSION"),
+                " clients=-"
+            )
+        );
+        state.update_health(true, Some(23), 300, false);
+        state.failure_count = 2;
+        assert_eq!(
+            state.summary_line(86400, Some(41.4), "throttled", "1.2.3.4:80", Some(7)),
+            concat!(
+                "OK up=86400 fail=2 lat=23ms cpu=41% load=throttled tgt=1.2.3.4:80 ver=",
+                env!("CARGO_PKG_VERSION"),
+                " clients=7"
+            )
+        );
+    }
+
+    fn inputs(rtt_ms: Option<u64>) -> CycleInputs<'static> {
+        CycleInputs {
+            target: "1.2.3.4:80",
+            connected: rtt_ms.is_some(),
+            rtt: rtt_ms.map(Duration::from_millis),
+            failure: None,
+            reboot_allowed: true,
+            probe_running: false,
+            high_load: false,
+            now: Instant::now(),
+        }
+    }
+
+    fn reboots(actions: &[Action]) -> bool {
+        actions
+            .iter()
+            .any(|action| matches!(action, Action::RebootSystem(_)))
+    }
+
+    fn notifications(actions: &[Action]) -> Vec<&str> {
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Notify(msg) => Some(msg.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_step_notifications() {
+        let config = Config::default();
+        let mut state = MonitorState::new();
+        let actions = step(&mut state, &config, inputs(Some(50)));
+        assert_eq!(notifications(&actions), ["NORMAL_LATENCY: LATENCY=50"]);
+
+        let actions = step(&mut state, &config, inputs(Some(500)));
+        assert_eq!(
+            notifications(&actions),
+            [
+                "HIGH_LATENCY: LATENCY=500",
+                "HEALTH_STATE: DEGRADED (was HEALTHY, loss=0%)",
+                "SEVERITY: MILD (was NORMAL)"
+            ]
+        );
+        assert!(!actions.contains(&Action::Throttle));
+        assert!(actions.contains(&Action::SetSeverity(Severity::Mild)));
+
+        // 第二次高延迟升到 Moderate，第三次整体限流
+        let actions = step(&mut state, &config, inputs(Some(500)));
+        assert!(actions.contains(&Action::SetSeverity(Severity::Moderate)));
+        let actions = step(&mut state, &config, inputs(Some(500)));
+        assert!(actions.contains(&Action::Throttle));
+        assert!(actions.contains(&Action::SetSeverity(Severity::Severe)));
+    }
+
+    #[test]
+    fn test_step_failures() {
+        let config = Config {
+            auto_reboot: true,
+            max_failures: 3,
+            diag_failure_threshold: 2,
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        let actions = step(&mut state, &config, inputs(None));
+        assert!(!actions.contains(&Action::StartPathProbe));
+        // 未配置连接管理进程时不检查
+        assert!(!actions.contains(&Action::CheckConnManager));
+        let actions = step(&mut state, &config, inputs(None));
+        assert!(actions.contains(&Action::StartPathProbe));
+        assert!(!reboots(&actions));
+
+        // 重启退避期内不重启
+        let mut backoff = inputs(None);
+        backoff.reboot_allowed = false;
+        assert!(!reboots(&step(&mut state, &config, backoff)));
+        assert!(step(&mut state, &config, inputs(None))
+            .contains(&Action::RebootSystem(RebootReason::Link)));
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        let io_error = |kind| io::Error::new(kind, "test");
+        assert_eq!(
+            FailureReason::from_io_error(&io_error(io::ErrorKind::ConnectionRefused)),
+            FailureReason::Refused
+        );
+        assert_eq!(
+            FailureReason::from_io_error(&io::Error::from_raw_os_error(libc::ENETUNREACH)),
+            FailureReason::NetworkUnreachable
+        );
+
+        let config = Config {
+            auto_reboot: true,
+            max_failures: 4,
+            diag_failure_threshold: 3,
+            ..Config::default()
+        };
+        let failed = |reason| CycleInputs {
+            failure: Some(reason),
+            ..inputs(None)
+        };
+        // 拒绝连接按一次失败计
+        let mut state = MonitorState::new();
+        step(&mut state, &config, failed(FailureReason::Refused));
+        assert_eq!(state.failure_count, 1);
+        assert_eq!(state.last_failure, Some(FailureReason::Refused));
+
+        // 网络不可达计两次，升级更快；跨过诊断阈值时仍会启动路径探测
+        let mut state = MonitorState::new();
+        let actions = step(
+            &mut state,
+            &config,
+            failed(FailureReason::NetworkUnreachable),
+        );
+        assert!(actions.contains(&Action::Log(
+            LogLevel::Warn,
+            "✗ Connection to 1.2.3.4:80 failed (net_unreachable)".to_string()
+        )));
+        assert!(notifications(&actions)
+            .iter()
+            .any(|n| n.starts_with("HEALTH_STATE") && n.ends_with("cause=net_unreachable")));
+        let actions = step(
+            &mut state,
+            &config,
+            failed(FailureReason::NetworkUnreachable),
+        );
+        assert!(actions.contains(&Action::StartPathProbe));
+        assert!(reboots(&actions));
+
+        step(&mut state, &config, inputs(Some(20)));
+        assert_eq!(state.last_failure, None);
+    }
+
+    #[test]
+    fn test_escalation() {
+        let config = Config {
+            max_failures: 3,
+            high_load_failure_factor: 2,
+            ..Config::default()
+        };
+        // CPU 正常：原来的预算
+        assert_eq!(escalation(2, false, false, &config), Escalation::Wait);
+        assert_eq!(
+            escalation(3, false, false, &config),
+            Escalation::Reboot(RebootReason::Link)
+        );
+        // 高负载：先卸载负载，预算翻倍
+        assert_eq!(escalation(3, true, false, &config), Escalation::ShedLoad);
+        assert_eq!(escalation(4, true, true, &config), Escalation::Wait);
+        // 达到 max_failures 之后才进入高负载的也卸载一次
+        assert_eq!(escalation(5, true, false, &config), Escalation::ShedLoad);
+        assert_eq!(
+            escalation(6, true, true, &config),
+            Escalation::Reboot(RebootReason::HighLoad)
+        );
+        // 负载降下来后按原预算
+        assert_eq!(
+            escalation(5, false, true, &config),
+            Escalation::Reboot(RebootReason::Link)
+        );
+        assert_eq!(failure_budget(true, &config), 6);
+    }
+
+    #[test]
+    fn test_step_high_load() {
+        let config = Config {
+            auto_reboot: true,
+            max_failures: 2,
+            high_load_failure_factor: 3,
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        let busy = |rtt_ms: Option<u64>| CycleInputs {
+            high_load: true,
+            ..inputs(rtt_ms)
+        };
+        assert!(!step(&mut state, &config, busy(None)).contains(&Action::ShedLoad));
+        let actions = step(&mut state, &config, busy(None));
+        assert!(actions.contains(&Action::ShedLoad));
+        assert!(!reboots(&actions));
+        for _ in 0..3 {
+            let actions = step(&mut state, &config, busy(None));
+            assert!(!actions.contains(&Action::ShedLoad));
+            assert!(!reboots(&actions));
+        }
+        assert!(step(&mut state, &config, busy(None))
+            .contains(&Action::RebootSystem(RebootReason::HighLoad)));
+
+        // 恢复连接时撤销限流，只撤销一次
+        assert!(step(&mut state, &config, busy(Some(50))).contains(&Action::Restore));
+        assert!(!step(&mut state, &config, busy(Some(50))).contains(&Action::Restore));
+    }
+
+    #[test]
+    fn test_latency_escalation() {
+        let config = Config {
+            max_high_latency: 3,
+            latency_escalate_after: 10,
+            ..Config::default()
+        };
+        assert_eq!(latency_escalation(3, &config), None);
+        assert_eq!(latency_escalation(12, &config), None);
+        assert_eq!(
+            latency_escalation(13, &config),
+            Some(LatencyStage::ClearCache)
+        );
+        assert_eq!(latency_escalation(14, &config), None);
+        assert_eq!(
+            latency_escalation(23, &config),
+            Some(LatencyStage::BounceInterface)
+        );
+        assert_eq!(latency_escalation(33, &config), Some(LatencyStage::Reboot));
+        assert_eq!(latency_escalation(43, &config), None);
+
+        let off = Config {
+            latency_escalate_after: 0,
+            ..config
+        };
+        assert_eq!(latency_escalation(13, &off), None);
+    }
+
+    #[test]
+    fn test_step_latency_escalation() {
+        let config = Config {
+            auto_reboot: true,
+            max_high_latency: 2,
+            latency_escalate_after: 2,
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        let mut escalations = Vec::new();
+        for _ in 0..9 {
+            for action in step(&mut state, &config, inputs(Some(500))) {
+                if matches!(
+                    action,
+                    Action::ClearPageCache | Action::BounceInterface | Action::RebootSystem(_)
+                ) {
+                    escalations.push((state.high_latency_count, action));
+                }
+            }
+        }
+        assert_eq!(
+            escalations,
+            [
+                (4, Action::ClearPageCache),
+                (6, Action::BounceInterface),
+                (8, Action::RebootSystem(RebootReason::Latency))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step_min_outage() {
+        let config = Config {
+            auto_reboot: true,
+            max_failures: 3,
+            reboot_min_outage: Duration::from_secs(120),
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        let start = Instant::now();
+        let at = |rtt_ms: Option<u64>, secs: u64| CycleInputs {
+            now: start + Duration::from_secs(secs),
+            ..inputs(rtt_ms)
+        };
+
+        let config = Config {
+            conn_manager_process: "zte_cm".to_string(),
+            ..config
+        };
+        // 只在第一次失败时检查连接管理进程
+        assert!(step(&mut state, &config, at(None, 0)).contains(&Action::CheckConnManager));
+        // 次数够了但断网时间不够：不重启
+        for secs in [10, 20, 30] {
+            let actions = step(&mut state, &config, at(None, secs));
+            assert!(!actions.contains(&Action::CheckConnManager));
+            assert!(!reboots(&actions));
+        }
+        assert_eq!(
+            state.outage(start + Duration::from_secs(30)),
+            Duration::from_secs(30)
+        );
+        assert!(step(&mut state, &config, at(None, 120))
+            .contains(&Action::RebootSystem(RebootReason::Link)));
+
+        // 恢复时报告断网时长并清除
+        let actions = step(&mut state, &config, at(Some(50), 150));
+        assert_eq!(
+            notifications(&actions)[1],
+            "HEALTH_STATE: DEGRADED (was FAILED, loss=83%) outage=150s"
+        );
+        assert_eq!(
+            state.outage(start + Duration::from_secs(150)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_step_health_score() {
+        let config = Config {
+            auto_reboot: true,
+            // 开启分数重启后连续失败计数不再单独触发重启
+            max_failures: 2,
+            health_score_notify: 15,
+            health_score_throttle: 25,
+            health_score_reboot: 45,
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        let score_notifications = |actions: &[Action]| {
+            notifications(actions)
+                .into_iter()
+                .filter(|msg| msg.starts_with("HEALTH_SCORE"))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert!(score_notifications(&step(&mut state, &config, inputs(None))).is_empty());
+        let actions = step(&mut state, &config, inputs(None));
+        assert_eq!(
+            score_notifications(&actions),
+            ["HEALTH_SCORE: SCORE=20.0 LEVEL=notify (was ok)"]
+        );
+        assert!(!reboots(&actions));
+        let actions = step(&mut state, &config, inputs(None));
+        assert!(actions.contains(&Action::Throttle));
+        assert!(!reboots(&actions));
+        assert!(!reboots(&step(&mut state, &config, inputs(None))));
+        let actions = step(&mut state, &config, inputs(None));
+        assert!(actions.contains(&Action::RebootSystem(RebootReason::Score)));
+        assert!(state
+            .status_lines()
+            .contains(&"health_score=50.0 (reboot)".to_string()));
+
+        // 分数衰减到限流阈值一半以下时恢复
+        let mut later = inputs(Some(50));
+        later.now += config.health_score_half_life * 3;
+        let actions = step(&mut state, &config, later);
+        assert!(actions.contains(&Action::Restore));
+        assert_eq!(state.score.level, ScoreLevel::Ok);
+    }
+
+    #[test]
+    fn test_step_failure_ratio() {
+        let config = Config {
+            auto_reboot: true,
+            max_failures: 3,
+            failure_window: 10,
+            failure_window_percent: 50,
+            ..Config::default()
+        };
+        let mut state = MonitorState::new();
+        assert!(state
+            .status_lines()
+            .contains(&"failure_ratio=-".to_string()));
+        // 隔一次失败一次：连续失败从未达到 max_failures，窗口未满时不触发
+        for _ in 0..4 {
+            assert!(!reboots(&step(&mut state, &config, inputs(None))));
+            assert!(!reboots(&step(&mut state, &config, inputs(Some(50)))));
+        }
+        assert!(!reboots(&step(&mut state, &config, inputs(None))));
+        assert!(state.status_lines().contains(
+            &"failure_ratio=55% failure_window_failed=5 failure_window_checks=9".to_string()
+        ));
+        // 第 10 次失败：6/10 超过 50%，连续失败只有 2 次
+        let actions = step(&mut state, &config, inputs(None));
+        assert_eq!(state.failure_count, 2);
+        assert!(actions.contains(&Action::RebootSystem(RebootReason::FailureRatio)));
+        assert!(
+            notifications(&actions).contains(&"FAILURE_RATIO: FAILED=6 WINDOW=10 THRESHOLD=50%")
+        );
+        // 触发后重新计数
+        assert!(state.status_lines().contains(
+            &"failure_ratio=0% failure_window_failed=0 failure_window_checks=0".to_string()
+        ));
+    }
+
+    #[test]
+    fn test_record_streak() {
+        let mut state = MonitorState::new();
+        let start = Instant::now();
+        state.record_streak(true, start);
+        state.record_streak(true, start + Duration::from_secs(60));
+        state.record_streak(true, start + Duration::from_secs(120));
+        assert_eq!(state.success_streak, 3);
+        assert_eq!(
+            state.current_streak(start + Duration::from_secs(120)),
+            Duration::from_secs(120)
+        );
+
+        state.record_streak(false, start + Duration::from_secs(180));
+        assert_eq!(state.success_streak, 0);
+        state.record_streak(true, start + Duration::from_secs(240));
+        let later = start + Duration::from_secs(300);
+        assert_eq!(state.current_streak(later), Duration::from_secs(60));
+        // 最长值保留之前的 120 秒
+        assert_eq!(state.longest_streak(later), Duration::from_secs(120));
+        assert_eq!(
+            state.heartbeat_fields(later),
+            "STREAK=1 HEALTHY_FOR=60s LONGEST_HEALTHY=120s"
+        );
+    }
+
+    #[test]
+    fn test_protective_name() {
+        assert_eq!(Action::Throttle.protective_name(), Some("THROTTLE"));
+        assert_eq!(
+            Action::RebootSystem(RebootReason::Link).protective_name(),
+            Some("REBOOT")
+        );
+        assert_eq!(Action::Notify("X".to_string()).protective_name(), None);
+        assert_eq!(Action::StartPathProbe.protective_name(), None);
+    }
+}
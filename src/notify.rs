@@ -0,0 +1,642 @@
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::Read;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::crc32::crc32;
+use crate::sockmark::Marking;
+use crate::{log_message, log_warn};
+
+/// 接收端要求的退避时间上限，超过的按上限计
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// 单个通知接收端，发送失败次数各自独立统计
+struct Receiver {
+    addr: String,
+    failures: Cell<u64>,
+}
+
+/// UDP 通知发送器，每条通知发往所有接收端
+pub struct Notifier {
+    receivers: Vec<Receiver>,
+    local_bind: String,
+    timeout: Duration,
+    /// 是否给通知加上 sid/seq/crc 信封
+    envelope: bool,
+    /// 每次启动随机生成，用于区分不同启动的序号
+    session_id: u32,
+    /// 已发出的通知数量（含发送失败的），接收端据此检测丢包/重复
+    sequence: Cell<u32>,
+    /// 关键通知等待回显确认的时间，零为不等待
+    ack_timeout: Duration,
+    ack_retries: u32,
+    /// 只发送这些类型，为空时发送全部
+    only: Vec<String>,
+    /// 不发送这些类型
+    disabled: Vec<String>,
+    /// 按配置没有发送的通知数
+    suppressed: Cell<u64>,
+    /// 最近一次发送用的 socket（非阻塞），poll 从中读取接收端的回复
+    reply_socket: RefCell<Option<UdpSocket>>,
+    /// 接收端要求退避（`ACK:<seq>:BACKOFF:<秒>`）到这个时间，期间只发送关键通知
+    backoff_until: Cell<Option<Instant>>,
+    /// 本次退避期间没有发送的通知数，退避结束后的第一条通知带上
+    backoff_suppressed: Cell<u32>,
+    /// notify_mark 开启时通知 socket 使用探测的 DSCP/SO_PRIORITY 标记
+    marking: Marking,
+}
+
+/// 接收端的确认回复：`ACK:<seq>` 或 `ACK:<seq>:BACKOFF:<秒>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ack {
+    seq: u32,
+    /// 已限制在 MAX_BACKOFF 以内；0 为取消退避，格式不对时为 None
+    backoff: Option<Duration>,
+}
+
+impl Notifier {
+    /// 接收端取 config.notify_addrs，未配置时发往 default_addr
+    pub fn new(default_addr: &str, config: &Config) -> Self {
+        let addrs = if config.notify_addrs.is_empty() {
+            vec![default_addr.to_string()]
+        } else {
+            config.notify_addrs.clone()
+        };
+        Notifier {
+            receivers: addrs
+                .into_iter()
+                .map(|addr| Receiver {
+                    addr,
+                    failures: Cell::new(0),
+                })
+                .collect(),
+            local_bind: config.udp_local_bind.clone(),
+            timeout: config.udp_timeout,
+            envelope: config.notify_envelope,
+            session_id: random_session_id(),
+            sequence: Cell::new(0),
+            ack_timeout: config.notify_ack_timeout,
+            ack_retries: config.notify_ack_retries,
+            only: config.notify_only.clone(),
+            disabled: config.notify_disable.clone(),
+            suppressed: Cell::new(0),
+            reply_socket: RefCell::new(None),
+            backoff_until: Cell::new(None),
+            backoff_suppressed: Cell::new(0),
+            marking: if config.notify_mark {
+                Marking::from_config(config)
+            } else {
+                Marking::default()
+            },
+        }
+    }
+
+    /// 最近一条通知的序号
+    pub fn sequence(&self) -> u32 {
+        self.sequence.get()
+    }
+
+    /// 从上次进程保存的序号继续编号
+    pub fn resume_sequence(&self, sequence: u32) {
+        self.sequence.set(sequence);
+    }
+
+    /// STATUS 中的通知序号、退避状态和各接收端的失败次数
+    pub fn status_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "notify_seq={} sid={:08x} suppressed={}",
+            self.sequence(),
+            self.session_id,
+            self.suppressed.get()
+        )];
+        lines.push(format!(
+            "notify_backoff={}s suppressed={}",
+            self.backoff_remaining(Instant::now()).as_secs(),
+            self.backoff_suppressed.get()
+        ));
+        for receiver in &self.receivers {
+            lines.push(format!(
+                "notify_receiver={} failures={}",
+                receiver.addr,
+                receiver.failures.get()
+            ));
+        }
+        lines
+    }
+
+    pub fn send(&self, message: &str, is_prod: bool) {
+        // 获取设备标识（可以使用主机名或自定义标识）
+        // let hostname = get_hostname().unwrap_or_else(|_| "unknown".to_string());
+        // let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+        if !self.is_enabled(message) {
+            return;
+        }
+        // 退避期间不发送；结束后的第一条通知带上期间没有发送的数量
+        let mut message = message.to_string();
+        if self.backoff_until.get().is_some() {
+            if !self.backoff_remaining(Instant::now()).is_zero() {
+                self.backoff_suppressed
+                    .set(self.backoff_suppressed.get() + 1);
+                return;
+            }
+            self.backoff_until.set(None);
+            let suppressed = self.backoff_suppressed.replace(0);
+            if suppressed > 0 {
+                message = format!("{} BACKOFF_SUPPRESSED={}", message, suppressed);
+            }
+        }
+        self.deliver(&message, is_prod);
+    }
+
+    /// 发往所有接收端，不等待确认
+    fn deliver(&self, message: &str, is_prod: bool) {
+        let datagram = self.next_datagram(message);
+        let Some(socket) = self.bind(is_prod) else {
+            return;
+        };
+        // 某个接收端失败不影响其他接收端
+        for receiver in &self.receivers {
+            self.send_one(&socket, receiver, &datagram, is_prod);
+        }
+        self.keep_for_replies(socket);
+    }
+
+    /// 读取接收端发回最近一次发送的 socket 的回复，处理其中的退避请求（主循环每轮调用）
+    pub fn poll(&self, is_prod: bool) {
+        let mut buf = [0u8; 128];
+        loop {
+            let received = match self.reply_socket.borrow().as_ref() {
+                Some(socket) => socket.recv_from(&mut buf),
+                None => return,
+            };
+            let Ok((len, from)) = received else {
+                return;
+            };
+            if !self.is_receiver(from) {
+                continue;
+            }
+            if let Some(ack) = parse_ack(&buf[..len]) {
+                self.apply_backoff(ack, from, is_prod);
+            }
+        }
+    }
+
+    /// 退避的剩余时间，不在退避中时为零
+    fn backoff_remaining(&self, now: Instant) -> Duration {
+        self.backoff_until
+            .get()
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now))
+    }
+
+    fn apply_backoff(&self, ack: Ack, from: SocketAddr, is_prod: bool) {
+        let Some(backoff) = ack.backoff else {
+            return;
+        };
+        if backoff.is_zero() {
+            if self.backoff_until.get().is_some() {
+                log_message(&format!("Notification backoff lifted by {}", from), is_prod);
+                // 下一条通知照常带上退避期间没有发送的数量
+                self.backoff_until.set(Some(Instant::now()));
+            }
+            return;
+        }
+        log_message(
+            &format!(
+                "Collector {} requested notification backoff for {}s",
+                from,
+                backoff.as_secs()
+            ),
+            is_prod,
+        );
+        self.backoff_until.set(Some(Instant::now() + backoff));
+    }
+
+    fn is_receiver(&self, from: SocketAddr) -> bool {
+        self.receivers.iter().any(|receiver| {
+            receiver
+                .addr
+                .to_socket_addrs()
+                .is_ok_and(|mut addrs| addrs.any(|addr| addr == from))
+        })
+    }
+
+    /// 保留发送用的 socket，接收端对这次发送的回复由 poll 读取
+    fn keep_for_replies(&self, socket: UdpSocket) {
+        if socket.set_nonblocking(true).is_ok() {
+            *self.reply_socket.borrow_mut() = Some(socket);
+        }
+    }
+
+    /// 发送关键通知（重启前），等待接收端把收到的内容原样发回作为确认，
+    /// 没有确认的接收端按 notify_ack_retries 重发（序号不变）。返回确认的接收端数；
+    /// 没有配置 notify_ack_timeout_ms 时与 send 相同，返回 0；按配置不发送时也返回 0
+    pub fn send_critical(&self, message: &str, is_prod: bool) -> usize {
+        if !self.is_enabled(message) {
+            return 0;
+        }
+        // 关键通知不受退避限制
+        if self.ack_timeout.is_zero() {
+            self.deliver(message, is_prod);
+            return 0;
+        }
+        let datagram = self.next_datagram(message);
+        let seq = self.sequence.get();
+        let Some(socket) = self.bind(is_prod) else {
+            return 0;
+        };
+        let mut pending: Vec<(&Receiver, Option<SocketAddr>)> = self
+            .receivers
+            .iter()
+            .map(|receiver| {
+                let addr = receiver
+                    .addr
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next());
+                (receiver, addr)
+            })
+            .collect();
+        let mut attempts = 0;
+        let mut buf = [0u8; 2048];
+        while !pending.is_empty() && attempts <= self.ack_retries {
+            attempts += 1;
+            for (receiver, _) in &pending {
+                self.send_one(&socket, receiver, &datagram, is_prod);
+            }
+            let deadline = Instant::now() + self.ack_timeout;
+            while !pending.is_empty() {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() || socket.set_read_timeout(Some(remaining)).is_err() {
+                    break;
+                }
+                // 原样回显或 `ACK:<seq>[:BACKOFF:<秒>]` 都算确认
+                match socket.recv_from(&mut buf) {
+                    Ok((len, from)) if &buf[..len] == datagram.as_bytes() => {
+                        pending.retain(|(_, addr)| *addr != Some(from));
+                    }
+                    Ok((len, from)) => {
+                        let Some(ack) = parse_ack(&buf[..len]).filter(|ack| ack.seq == seq) else {
+                            continue;
+                        };
+                        if pending.iter().any(|(_, addr)| *addr == Some(from)) {
+                            pending.retain(|(_, addr)| *addr != Some(from));
+                            self.apply_backoff(ack, from, is_prod);
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        }
+        self.keep_for_replies(socket);
+
+        let name = message.split(':').next().unwrap_or(message);
+        let confirmed = self.receivers.len() - pending.len();
+        if pending.is_empty() {
+            log_message(
+                &format!(
+                    "Notification {} confirmed by {} receiver(s) after {} attempt(s)",
+                    name, confirmed, attempts
+                ),
+                is_prod,
+            );
+        } else {
+            let missing: Vec<&str> = pending.iter().map(|(r, _)| r.addr.as_str()).collect();
+            log_warn(
+                &format!(
+                    "Notification {} not confirmed by {} after {} attempt(s)",
+                    name,
+                    missing.join(","),
+                    attempts
+                ),
+                is_prod,
+            );
+        }
+        confirmed
+    }
+
+    /// 按 notify_only/notify_disable 检查这类通知是否发送；不发送的不占用序号
+    fn is_enabled(&self, message: &str) -> bool {
+        let name = event_name(message);
+        let enabled = !self.disabled.iter().any(|d| d == name)
+            && (self.only.is_empty() || self.only.iter().any(|o| o == name));
+        if !enabled {
+            self.suppressed.set(self.suppressed.get() + 1);
+        }
+        enabled
+    }
+
+    /// 分配序号并按是否使用信封生成要发送的内容
+    fn next_datagram(&self, message: &str) -> String {
+        let seq = self.sequence.get().wrapping_add(1);
+        self.sequence.set(seq);
+
+        if self.envelope {
+            format_envelope(self.session_id, seq, message)
+        } else {
+            format!("[{}] {}", "zxic", message)
+        }
+    }
+
+    /// 发送用的 socket：沿用上次保留的（udp_local_bind 为固定端口时不能重复绑定），没有时新建
+    fn bind(&self, is_prod: bool) -> Option<UdpSocket> {
+        if let Some(socket) = self.reply_socket.borrow_mut().take() {
+            if socket.set_nonblocking(false).is_ok() {
+                return Some(socket);
+            }
+        }
+        let socket = match UdpSocket::bind(&self.local_bind) {
+            Ok(socket) => socket,
+            Err(e) => {
+                for receiver in &self.receivers {
+                    receiver.failures.set(receiver.failures.get() + 1);
+                }
+                if !is_prod {
+                    log_message(
+                        &format!("Failed to create UDP socket on {}: {}", self.local_bind, e),
+                        is_prod,
+                    );
+                }
+                return None;
+            }
+        };
+        // 设置超时时间
+        let _ = socket.set_write_timeout(Some(self.timeout));
+        if !self.marking.is_empty() {
+            let ipv6 = socket.local_addr().is_ok_and(|addr| addr.is_ipv6());
+            let _ = self.marking.apply(socket.as_raw_fd(), ipv6);
+        }
+        Some(socket)
+    }
+
+    fn send_one(&self, socket: &UdpSocket, receiver: &Receiver, datagram: &str, is_prod: bool) {
+        if let Err(e) = socket.send_to(datagram.as_bytes(), &receiver.addr) {
+            receiver.failures.set(receiver.failures.get() + 1);
+            if !is_prod {
+                log_message(
+                    &format!(
+                        "Failed to send UDP notification to {}: {}",
+                        receiver.addr, e
+                    ),
+                    is_prod,
+                );
+            }
+        }
+    }
+}
+
+/// 解析 `ACK:<seq>` 和 `ACK:<seq>:BACKOFF:<秒>`，退避时间超过 MAX_BACKOFF 的按上限计
+fn parse_ack(reply: &[u8]) -> Option<Ack> {
+    let reply = std::str::from_utf8(reply).ok()?.trim();
+    let mut fields = reply.strip_prefix("ACK:")?.split(':');
+    let seq = fields.next()?.parse().ok()?;
+    let backoff = match (fields.next(), fields.next()) {
+        (Some("BACKOFF"), Some(secs)) => secs
+            .parse::<u64>()
+            .ok()
+            .map(|secs| Duration::from_secs(secs).min(MAX_BACKOFF)),
+        _ => None,
+    };
+    Some(Ack { seq, backoff })
+}
+
+/// 通知类型：开头的大写名称，如 `HIGH_LOAD: CPU=95.0` 中的 `HIGH_LOAD`
+fn event_name(message: &str) -> &str {
+    let end = message
+        .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+        .unwrap_or(message.len());
+    &message[..end]
+}
+
+/// 信封格式：`[zxic sid=<8位hex> seq=<序号> crc=<8位hex>] <消息>`，crc 只覆盖消息部分
+fn format_envelope(session_id: u32, seq: u32, message: &str) -> String {
+    format!(
+        "[zxic sid={:08x} seq={} crc={:08x}] {}",
+        session_id,
+        seq,
+        crc32(message.as_bytes()),
+        message
+    )
+}
+
+/// 优先从 /dev/urandom 取随机数，失败时用时间和 pid 混合
+fn random_session_id() -> u32 {
+    let mut buf = [0u8; 4];
+    if File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut buf))
+        .is_ok()
+    {
+        return u32::from_ne_bytes(buf);
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u32;
+    nanos ^ std::process::id().rotate_left(16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_envelope() {
+        assert_eq!(
+            format_envelope(0x1a2b3c4d, 7, "123456789"),
+            "[zxic sid=1a2b3c4d seq=7 crc=cbf43926] 123456789"
+        );
+    }
+
+    #[test]
+    fn test_event_filter() {
+        assert_eq!(event_name("HIGH_LOAD: CPU=95.0"), "HIGH_LOAD");
+        assert_eq!(
+            event_name("RUNAWAY_PROCESS:goahead CPU=90"),
+            "RUNAWAY_PROCESS"
+        );
+        assert_eq!(event_name("STORAGE_RESTORED"), "STORAGE_RESTORED");
+
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = Config {
+            udp_local_bind: "127.0.0.1:0".to_string(),
+            notify_addrs: vec![collector.local_addr().unwrap().to_string()],
+            notify_disable: vec!["HIGH_LOAD".to_string()],
+            ..Config::default()
+        };
+        let notifier = Notifier::new("127.0.0.1:9", &config);
+        notifier.send("HIGH_LOAD: CPU=95.0", true);
+        notifier.send("HIGH_LOAD_ENTER: CPU=95.0", true);
+        // 被过滤的通知不占用序号
+        assert_eq!(notifier.sequence(), 1);
+        assert_eq!(notifier.suppressed.get(), 1);
+
+        let notifier = Notifier {
+            only: vec!["REBOOTING".to_string()],
+            ..notifier
+        };
+        notifier.send("HIGH_LOAD_ENTER: CPU=95.0", true);
+        assert_eq!(notifier.send_critical("REBOOTING: REASON=link", true), 0);
+        assert_eq!(notifier.sequence(), 2);
+
+        let mut buf = [0u8; 2048];
+        let (len, _) = collector.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"[zxic] HIGH_LOAD_ENTER: CPU=95.0");
+        let (len, _) = collector.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"[zxic] REBOOTING: REASON=link");
+    }
+
+    #[test]
+    fn test_parse_ack() {
+        assert_eq!(
+            parse_ack(b"ACK:7"),
+            Some(Ack {
+                seq: 7,
+                backoff: None
+            })
+        );
+        assert_eq!(
+            parse_ack(b"ACK:7:BACKOFF:120\n"),
+            Some(Ack {
+                seq: 7,
+                backoff: Some(Duration::from_secs(120))
+            })
+        );
+        // 过大的退避按上限计，格式不对的忽略退避
+        assert_eq!(
+            parse_ack(b"ACK:7:BACKOFF:99999999").and_then(|ack| ack.backoff),
+            Some(MAX_BACKOFF)
+        );
+        assert_eq!(
+            parse_ack(b"ACK:7:BACKOFF:-5").and_then(|ack| ack.backoff),
+            None
+        );
+        assert_eq!(parse_ack(b"ACK:x"), None);
+        assert_eq!(parse_ack(b"[zxic] HIGH_LOAD"), None);
+    }
+
+    /// 等接收端的回复到达后调用 poll，直到 done 成立
+    fn poll_until(notifier: &Notifier, done: impl Fn(&Notifier) -> bool) {
+        for _ in 0..100 {
+            notifier.poll(true);
+            if done(notifier) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("no reply processed");
+    }
+
+    #[test]
+    fn test_backoff() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let notifier = notifier_for(&collector, 0);
+        let mut buf = [0u8; 2048];
+        notifier.send("HIGH_LOAD: CPU=95.0", true);
+        let (_, from) = collector.recv_from(&mut buf).unwrap();
+        collector.send_to(b"ACK:1:BACKOFF:60", from).unwrap();
+        poll_until(&notifier, |n| n.backoff_until.get().is_some());
+        assert!(notifier.backoff_remaining(Instant::now()) > Duration::from_secs(50));
+
+        // 退避期间普通通知不发送、不占用序号，关键通知照常发送
+        notifier.send("HIGH_LOAD: CPU=96.0", true);
+        notifier.send("HIGH_LOAD: CPU=97.0", true);
+        assert_eq!(notifier.sequence(), 1);
+        assert_eq!(notifier.backoff_suppressed.get(), 2);
+        let echo = std::thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            let (len, from) = collector.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..len], b"[zxic] REBOOTING: REASON=link");
+            // 用 ACK 确认，同时取消退避
+            collector.send_to(b"ACK:2:BACKOFF:0", from).unwrap();
+            collector
+        });
+        assert_eq!(notifier.send_critical("REBOOTING: REASON=link", true), 1);
+        let collector = echo.join().unwrap();
+
+        // 退避结束后的第一条通知带上期间没有发送的数量
+        notifier.send("HIGH_LOAD: CPU=50.0", true);
+        let (len, _) = collector.recv_from(&mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            b"[zxic] HIGH_LOAD: CPU=50.0 BACKOFF_SUPPRESSED=2"
+        );
+        assert_eq!(notifier.backoff_until.get(), None);
+    }
+
+    #[test]
+    fn test_fixed_local_port() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        // 先占一个空闲端口再释放，作为固定的 udp_local_bind
+        let local = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = Config {
+            udp_local_bind: local.to_string(),
+            notify_addrs: vec![collector.local_addr().unwrap().to_string()],
+            ..Config::default()
+        };
+        let notifier = Notifier::new("127.0.0.1:9", &config);
+        notifier.send("HIGH_LOAD: CPU=95.0", true);
+        notifier.send("HIGH_LOAD: CPU=96.0", true);
+        assert_eq!(notifier.send_critical("REBOOTING: REASON=link", true), 0);
+        let mut buf = [0u8; 2048];
+        for expected in [
+            &b"[zxic] HIGH_LOAD: CPU=95.0"[..],
+            b"[zxic] HIGH_LOAD: CPU=96.0",
+            b"[zxic] REBOOTING: REASON=link",
+        ] {
+            let (len, from) = collector.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..len], expected);
+            assert_eq!(from, local);
+        }
+        assert_eq!(notifier.receivers[0].failures.get(), 0);
+    }
+
+    fn notifier_for(collector: &UdpSocket, retries: u32) -> Notifier {
+        let config = Config {
+            udp_local_bind: "127.0.0.1:0".to_string(),
+            notify_addrs: vec![collector.local_addr().unwrap().to_string()],
+            notify_ack_timeout: Duration::from_millis(200),
+            notify_ack_retries: retries,
+            ..Config::default()
+        };
+        Notifier::new("127.0.0.1:9", &config)
+    }
+
+    #[test]
+    fn test_send_critical_retries_until_echoed() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let notifier = notifier_for(&collector, 2);
+        let echo = std::thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            // 第一次的丢掉，模拟丢包；重发的原样发回
+            let _ = collector.recv_from(&mut buf).unwrap();
+            let (len, from) = collector.recv_from(&mut buf).unwrap();
+            collector.send_to(&buf[..len], from).unwrap();
+            String::from_utf8_lossy(&buf[..len]).to_string()
+        });
+        assert_eq!(notifier.send_critical("REBOOTING: REASON=link", true), 1);
+        assert_eq!(echo.join().unwrap(), "[zxic] REBOOTING: REASON=link");
+        // 重发不增加序号
+        assert_eq!(notifier.sequence(), 1);
+    }
+
+    #[test]
+    fn test_send_critical_unconfirmed() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let notifier = notifier_for(&collector, 1);
+        assert_eq!(notifier.send_critical("REBOOTING: REASON=link", true), 0);
+        // 一次发送加一次重发
+        collector
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut buf = [0u8; 2048];
+        assert!(collector.recv_from(&mut buf).is_ok());
+        assert!(collector.recv_from(&mut buf).is_ok());
+        assert!(collector.recv_from(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::storage;
+
+/// 保留最近这么多次断网
+const MAX_OUTAGES: usize = 10;
+
+/// 断网是怎么结束的。断网期间记录已采取的最高一级动作，恢复时即为恢复方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Recovery {
+    /// 没有采取任何动作，自行恢复
+    SelfRecovered,
+    /// 已开始升级处理（卸载负载、重启连接管理进程等）后恢复
+    Escalation,
+    /// 重启 WAN 接口后恢复
+    InterfaceRestart,
+    /// 为此重启了设备
+    Reboot,
+    /// 断网期间本程序退出（非自己重启设备），下次启动时结束记录
+    Interrupted,
+}
+
+impl Recovery {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Recovery::SelfRecovered => "self",
+            Recovery::Escalation => "escalation",
+            Recovery::InterfaceRestart => "interface_restart",
+            Recovery::Reboot => "reboot",
+            Recovery::Interrupted => "interrupted",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [
+            Recovery::SelfRecovered,
+            Recovery::Escalation,
+            Recovery::InterfaceRestart,
+            Recovery::Reboot,
+            Recovery::Interrupted,
+        ]
+        .into_iter()
+        .find(|r| r.name() == name)
+    }
+}
+
+/// 一次断网
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outage {
+    /// 第一次失败的 Unix 时间
+    pub start: u64,
+    /// 恢复的 Unix 时间；仍在断网时为最近一次写入的时间
+    pub end: u64,
+    /// 持续秒数（进程内按单调时钟计算，不受校时影响）
+    pub duration: u64,
+    /// 期间达到的最大失败计数
+    pub max_failures: u32,
+    pub recovery: Recovery,
+    pub ongoing: bool,
+}
+
+impl Outage {
+    /// OUTAGES 的一行：`START=1760600000 END=1760600312 DURATION=312s MAX_FAILURES=15 RECOVERY=reboot`
+    pub fn describe(&self) -> String {
+        if self.ongoing {
+            format!(
+                "START={} END=ongoing DURATION={}s MAX_FAILURES={} RECOVERY=-",
+                self.start, self.duration, self.max_failures
+            )
+        } else {
+            format!(
+                "START={} END={} DURATION={}s MAX_FAILURES={} RECOVERY={}",
+                self.start,
+                self.end,
+                self.duration,
+                self.max_failures,
+                self.recovery.name()
+            )
+        }
+    }
+
+    /// `<start>,<end>,<duration>,<max_failures>,<recovery>,<ongoing|closed>`
+    fn encode(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}\n",
+            self.start,
+            self.end,
+            self.duration,
+            self.max_failures,
+            self.recovery.name(),
+            if self.ongoing { "ongoing" } else { "closed" }
+        )
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        let [start, end, duration, max_failures, recovery, state] = fields[..] else {
+            return None;
+        };
+        Some(Outage {
+            start: start.parse().ok()?,
+            end: end.parse().ok()?,
+            duration: duration.parse().ok()?,
+            max_failures: max_failures.parse().ok()?,
+            recovery: Recovery::parse(recovery)?,
+            ongoing: match state {
+                "ongoing" => true,
+                "closed" => false,
+                _ => return None,
+            },
+        })
+    }
+}
+
+/// 最近 MAX_OUTAGES 次断网，在断网开始、升级处理和恢复时写入存储目录，进程重启和设备重启后仍在
+pub struct OutageLog {
+    path: PathBuf,
+    entries: VecDeque<Outage>,
+    /// 进行中的断网在本进程内开始的时间
+    since: Option<Instant>,
+}
+
+impl OutageLog {
+    /// 读取记录；上次退出时仍在进行的断网在这里结束：为它重启过设备的记为 reboot，
+    /// 否则记为 interrupted，结束时间为最后一次写入的时间。返回这样结束的记录
+    pub fn load(path: PathBuf) -> (Self, Option<Outage>) {
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        let mut entries: VecDeque<Outage> = content.lines().filter_map(Outage::parse).collect();
+        while entries.len() > MAX_OUTAGES {
+            entries.pop_front();
+        }
+        let mut closed = None;
+        for entry in entries.iter_mut().filter(|entry| entry.ongoing) {
+            entry.ongoing = false;
+            if entry.recovery != Recovery::Reboot {
+                entry.recovery = Recovery::Interrupted;
+            }
+            closed = Some(entry.clone());
+        }
+        let log = OutageLog {
+            path,
+            entries,
+            since: None,
+        };
+        if closed.is_some() {
+            log.write();
+        }
+        (log, closed)
+    }
+
+    pub fn is_ongoing(&self) -> bool {
+        self.entries.back().is_some_and(|entry| entry.ongoing)
+    }
+
+    /// 第一次失败
+    pub fn begin(&mut self, unix: u64, failure_count: u32, now: Instant) {
+        if self.entries.len() == MAX_OUTAGES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Outage {
+            start: unix,
+            end: unix,
+            duration: 0,
+            max_failures: failure_count,
+            recovery: Recovery::SelfRecovered,
+            ongoing: true,
+        });
+        self.since = Some(now);
+        self.write();
+    }
+
+    /// 断网期间的每次失败（只在内存中更新，下次写入时保存）
+    pub fn update(&mut self, failure_count: u32) {
+        if let Some(entry) = self.entries.back_mut().filter(|entry| entry.ongoing) {
+            entry.max_failures = entry.max_failures.max(failure_count);
+        }
+    }
+
+    /// 断网期间采取了保护动作，级别更高时记下并立即写入（重启设备前也在这里写入）；
+    /// 提升了级别时返回原来的级别
+    pub fn escalate(&mut self, recovery: Recovery, unix: u64, now: Instant) -> Option<Recovery> {
+        let entry = self.entries.back_mut().filter(|entry| entry.ongoing)?;
+        if recovery <= entry.recovery {
+            return None;
+        }
+        let previous = std::mem::replace(&mut entry.recovery, recovery);
+        self.save(unix, now);
+        Some(previous)
+    }
+
+    /// 记下的动作最终没有执行（重启失败），退回 escalate 返回的原级别
+    pub fn withdraw(&mut self, previous: Recovery, unix: u64, now: Instant) {
+        if let Some(entry) = self.entries.back_mut().filter(|entry| entry.ongoing) {
+            entry.recovery = previous;
+            self.save(unix, now);
+        }
+    }
+
+    /// 恢复连接，返回结束的记录
+    pub fn end(&mut self, unix: u64, now: Instant) -> Option<Outage> {
+        if !self.is_ongoing() {
+            return None;
+        }
+        self.refresh(unix, now);
+        let entry = self.entries.back_mut()?;
+        entry.ongoing = false;
+        let entry = entry.clone();
+        self.since = None;
+        self.write();
+        Some(entry)
+    }
+
+    /// 更新进行中断网的结束时间和时长后写入（退出前调用）
+    pub fn save(&mut self, unix: u64, now: Instant) {
+        self.refresh(unix, now);
+        self.write();
+    }
+
+    fn refresh(&mut self, unix: u64, now: Instant) {
+        let since = self.since;
+        if let Some(entry) = self.entries.back_mut().filter(|entry| entry.ongoing) {
+            entry.end = unix.max(entry.start);
+            entry.duration = match since {
+                Some(since) => now.duration_since(since).as_secs(),
+                None => entry.end - entry.start,
+            };
+        }
+    }
+
+    /// 写入统计文件（每行一次断网，最早的在前；其他统计的行保留），写失败时忽略（下次转换时再写）
+    fn write(&self) {
+        let content: String = self.entries.iter().map(Outage::encode).collect();
+        let _ = storage::write_shared(&self.path, |line| Outage::parse(line).is_some(), &content);
+    }
+
+    /// OUTAGES 命令的回复，最近的在前
+    pub fn reply_lines(&mut self, unix: u64, now: Instant) -> Vec<String> {
+        self.refresh(unix, now);
+        if self.entries.is_empty() {
+            return vec!["OUTAGES none".to_string()];
+        }
+        self.entries.iter().rev().map(Outage::describe).collect()
+    }
+
+    /// 每日汇总中的最近一次断网：`LAST_OUTAGE=1760600000,312s,reboot`
+    pub fn last_summary(&self) -> String {
+        match self.entries.back() {
+            None => "LAST_OUTAGE=-".to_string(),
+            Some(entry) if entry.ongoing => format!("LAST_OUTAGE={},ongoing", entry.start),
+            Some(entry) => format!(
+                "LAST_OUTAGE={},{}s,{}",
+                entry.start,
+                entry.duration,
+                entry.recovery.name()
+            ),
+        }
+    }
+
+    /// 存储目录切换后改写到新路径
+    pub fn relocate(&mut self, path: PathBuf) {
+        self.path = path;
+        self.write();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("zxping-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_outage_log() {
+        let path = temp_path("outages");
+        let (mut log, closed) = OutageLog::load(path.clone());
+        assert!(closed.is_none());
+        assert_eq!(log.reply_lines(0, Instant::now()), ["OUTAGES none"]);
+        assert_eq!(log.last_summary(), "LAST_OUTAGE=-");
+
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        log.begin(1000, 1, at(0));
+        log.update(3);
+        log.update(2);
+        log.escalate(Recovery::Escalation, 1010, at(10));
+        assert_eq!(
+            log.reply_lines(1020, at(20)),
+            ["START=1000 END=ongoing DURATION=20s MAX_FAILURES=3 RECOVERY=-"]
+        );
+        assert_eq!(log.last_summary(), "LAST_OUTAGE=1000,ongoing");
+        // 低一级的动作不改变记录
+        assert_eq!(log.escalate(Recovery::SelfRecovered, 1030, at(30)), None);
+        // 重启失败时退回
+        let previous = log.escalate(Recovery::Reboot, 1030, at(30));
+        assert_eq!(previous, Some(Recovery::Escalation));
+        log.withdraw(previous.unwrap(), 1030, at(30));
+        // 期间校时：时长按单调时钟计算
+        let ended = log.end(5000, at(40)).unwrap();
+        assert_eq!(ended.duration, 40);
+        assert_eq!(ended.recovery, Recovery::Escalation);
+        assert!(log.end(5001, at(41)).is_none());
+        assert_eq!(log.last_summary(), "LAST_OUTAGE=1000,40s,escalation");
+
+        // 超出上限时丢掉最早的
+        for i in 0..MAX_OUTAGES as u64 {
+            log.begin(6000 + i * 100, 1, at(100));
+            log.end(6010 + i * 100, at(110));
+        }
+        let lines = log.reply_lines(0, at(200));
+        assert_eq!(lines.len(), MAX_OUTAGES);
+        assert_eq!(
+            lines[0],
+            "START=6900 END=6910 DURATION=10s MAX_FAILURES=1 RECOVERY=self"
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_ongoing_closed_on_load() {
+        let path = temp_path("outages-ongoing");
+        let start = Instant::now();
+        let (mut log, _) = OutageLog::load(path.clone());
+        log.begin(1000, 1, start);
+        log.update(15);
+        log.save(1060, start + Duration::from_secs(60));
+
+        // 退出时仍在断网：下次启动时记为 interrupted
+        let (mut log, closed) = OutageLog::load(path.clone());
+        let closed = closed.unwrap();
+        assert_eq!(
+            closed.describe(),
+            "START=1000 END=1060 DURATION=60s MAX_FAILURES=15 RECOVERY=interrupted"
+        );
+        assert!(!log.is_ongoing());
+
+        // 为断网重启了设备：记为 reboot
+        log.begin(2000, 1, start);
+        log.escalate(Recovery::Reboot, 2300, start + Duration::from_secs(300));
+        let (log, closed) = OutageLog::load(path.clone());
+        assert_eq!(closed.unwrap().recovery, Recovery::Reboot);
+        assert_eq!(log.last_summary(), "LAST_OUTAGE=2000,300s,reboot");
+        // 再次读取时已经结束
+        let (_, closed) = OutageLog::load(path.clone());
+        assert!(closed.is_none());
+        let _ = fs::remove_file(&path);
+
+        assert!(Outage::parse("1,2,3,4,self").is_none());
+        assert!(Outage::parse("1,2,3,4,unknown,closed").is_none());
+    }
+}
@@ -0,0 +1,174 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::sockmark;
+
+/// 最多探测的跳数
+const MAX_HOPS: u8 = 8;
+/// 每跳等待 ICMP 应答的时间
+const HOP_TIMEOUT: Duration = Duration::from_millis(400);
+/// 整个探测的硬性截止时间，超过后主循环不再等待结果
+const PROBE_DEADLINE: Duration = Duration::from_secs(5);
+/// traceroute 惯用的 UDP 目的端口起点
+const BASE_PORT: u16 = 33434;
+
+/// 单跳的探测结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hop {
+    /// 中间路由返回 ICMP 超时/不可达
+    Router,
+    /// 到达目标（端口不可达或有应答）
+    Target,
+    /// 本地无路由
+    NoRoute,
+    Timeout,
+}
+
+/// 在后台线程中进行的路径探测（UDP + 递增 TTL，通过 IP_RECVERR 获取 ICMP 错误）
+pub struct PathProbe {
+    rx: Receiver<String>,
+    started: Instant,
+}
+
+impl PathProbe {
+    pub fn start(target: IpAddr) -> PathProbe {
+        let (tx, rx) = mpsc::channel();
+        let started = Instant::now();
+        thread::spawn(move || {
+            // 主循环超时后接收端已被丢弃，发送失败可以忽略
+            let _ = tx.send(run_probe(target, started + PROBE_DEADLINE));
+        });
+        PathProbe { rx, started }
+    }
+
+    /// 非阻塞地取结果，探测完成或超过截止时间时返回 Some
+    pub fn poll(&self, now: Instant) -> Option<String> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Disconnected) => Some("hops: failed".to_string()),
+            Err(TryRecvError::Empty) if now.duration_since(self.started) >= PROBE_DEADLINE => {
+                Some("hops: timeout".to_string())
+            }
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+}
+
+fn run_probe(target: IpAddr, deadline: Instant) -> String {
+    let mut hops = Vec::new();
+    for ttl in 1..=MAX_HOPS {
+        if Instant::now() + HOP_TIMEOUT > deadline {
+            break;
+        }
+        let socket = match open_hop_socket(target, ttl) {
+            Ok(socket) => socket,
+            // 内核不支持所需的 socket 选项
+            Err(_) => return "hops: unsupported".to_string(),
+        };
+        let hop = probe_hop(&socket);
+        hops.push(hop);
+        if hop == Hop::Target || hop == Hop::NoRoute {
+            break;
+        }
+    }
+    format_hops(&hops)
+}
+
+fn open_hop_socket(target: IpAddr, ttl: u8) -> io::Result<Socket> {
+    let addr = SocketAddr::new(target, BASE_PORT + ttl as u16);
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    // 与连通性检查走同一条路由（探测标记、bind_interface）
+    sockmark::prepare_probe(socket.as_raw_fd(), target.is_ipv6());
+    let (level, option) = match target {
+        IpAddr::V4(_) => {
+            socket.set_ttl(ttl as u32)?;
+            (libc::IPPROTO_IP, libc::IP_RECVERR)
+        }
+        IpAddr::V6(_) => {
+            socket.set_unicast_hops_v6(ttl as u32)?;
+            (libc::IPPROTO_IPV6, libc::IPV6_RECVERR)
+        }
+    };
+    // 开启 RECVERR 后，ICMP 超时也会以 EHOSTUNREACH 的形式从 recv 返回
+    let enable: libc::c_int = 1;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            option,
+            &enable as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    socket.set_read_timeout(Some(HOP_TIMEOUT))?;
+    socket.connect(&addr.into())?;
+    Ok(socket)
+}
+
+fn probe_hop(socket: &Socket) -> Hop {
+    if let Err(e) = socket.send(b"zxping") {
+        return hop_from_error(&e);
+    }
+    let mut buf = [MaybeUninit::<u8>::uninit(); 64];
+    match socket.recv(&mut buf) {
+        Ok(_) => Hop::Target,
+        Err(e) => hop_from_error(&e),
+    }
+}
+
+fn hop_from_error(e: &io::Error) -> Hop {
+    match e.raw_os_error() {
+        Some(libc::ECONNREFUSED) => Hop::Target,
+        Some(libc::EHOSTUNREACH) => Hop::Router,
+        Some(libc::ENETUNREACH) => Hop::NoRoute,
+        _ => Hop::Timeout,
+    }
+}
+
+/// 压缩为一行，如 "hops: 1 ok 2 ok 3 * 4 dst"
+fn format_hops(hops: &[Hop]) -> String {
+    let mut line = "hops:".to_string();
+    for (i, hop) in hops.iter().enumerate() {
+        let mark = match hop {
+            Hop::Router => "ok",
+            Hop::Target => "dst",
+            Hop::NoRoute => "!",
+            Hop::Timeout => "*",
+        };
+        line.push_str(&format!(" {} {}", i + 1, mark));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_hops() {
+        assert_eq!(
+            format_hops(&[Hop::Router, Hop::Router, Hop::Timeout, Hop::Target]),
+            "hops: 1 ok 2 ok 3 * 4 dst"
+        );
+        assert_eq!(format_hops(&[Hop::NoRoute]), "hops: 1 !");
+        assert_eq!(format_hops(&[]), "hops:");
+    }
+
+    #[test]
+    fn test_hop_from_error() {
+        let err = |code| io::Error::from_raw_os_error(code);
+        assert_eq!(hop_from_error(&err(libc::ECONNREFUSED)), Hop::Target);
+        assert_eq!(hop_from_error(&err(libc::EHOSTUNREACH)), Hop::Router);
+        assert_eq!(hop_from_error(&err(libc::EAGAIN)), Hop::Timeout);
+    }
+}
@@ -0,0 +1,238 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::monitor::FailureReason;
+use crate::sockmark;
+
+/// 拆分 `1.2.3.4:80,443` 形式的目标：返回第一个端口的 `IP:PORT` 和全部端口
+pub fn split_target_ports(target: &str) -> Result<(String, Vec<u16>), String> {
+    let mut parts = target.split(',');
+    let first = parts.next().unwrap_or_default().trim();
+    let addr: SocketAddr = first
+        .parse()
+        .map_err(|_| format!("invalid target_ip:PORT: {}", target))?;
+    let mut ports = vec![addr.port()];
+    for part in parts {
+        let port = part
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|port| *port > 0)
+            .ok_or_else(|| format!("invalid port '{}' in target {}", part.trim(), target))?;
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+    Ok((first.to_string(), ports))
+}
+
+/// 单个端口的检查统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PortStats {
+    port: u16,
+    attempts: u32,
+    failures: u32,
+    /// 最近一次检查的结果，None 为成功
+    last: Option<FailureReason>,
+}
+
+impl PortStats {
+    fn record(&mut self, result: Result<(), FailureReason>) {
+        self.attempts += 1;
+        self.last = result.err();
+        if self.last.is_some() {
+            self.failures += 1;
+        }
+    }
+}
+
+fn connect(addr: SocketAddr, timeout: Duration) -> Result<Duration, FailureReason> {
+    let start = Instant::now();
+    sockmark::connect_tcp(addr, timeout)
+        .map(|_| start.elapsed())
+        .map_err(|e| FailureReason::from_io_error(&e))
+}
+
+/// 同一主机的多个端口：按顺序连接，第一个成功的端口即算本轮成功。
+/// 没有轮到的端口在后台线程中检查，只用于统计，持续不通的端口在 STATUS 中仍然可见
+pub struct MultiPort {
+    host: IpAddr,
+    stats: Vec<PortStats>,
+    /// 上一次满足检查的端口
+    pub satisfied_by: Option<u16>,
+    results_tx: Sender<Vec<(u16, Result<(), FailureReason>)>>,
+    results_rx: Receiver<Vec<(u16, Result<(), FailureReason>)>>,
+    background_running: bool,
+}
+
+impl MultiPort {
+    pub fn new(host: IpAddr, ports: &[u16]) -> Self {
+        let (results_tx, results_rx) = mpsc::channel();
+        MultiPort {
+            host,
+            stats: ports
+                .iter()
+                .map(|port| PortStats {
+                    port: *port,
+                    attempts: 0,
+                    failures: 0,
+                    last: None,
+                })
+                .collect(),
+            satisfied_by: None,
+            results_tx,
+            results_rx,
+            background_running: false,
+        }
+    }
+
+    fn record(&mut self, port: u16, result: Result<(), FailureReason>) {
+        if let Some(stats) = self.stats.iter_mut().find(|stats| stats.port == port) {
+            stats.record(result);
+        }
+    }
+
+    /// 收集后台检查的结果
+    fn collect(&mut self) {
+        while let Ok(results) = self.results_rx.try_recv() {
+            self.background_running = false;
+            for (port, result) in results {
+                self.record(port, result);
+            }
+        }
+    }
+
+    /// 按顺序连接各端口，返回第一个成功的端口和它的连接耗时；全部失败时返回最后一个端口的原因
+    pub fn check(&mut self, timeout: Duration) -> Result<(u16, Duration), FailureReason> {
+        self.collect();
+        let mut reason = FailureReason::Other;
+        for i in 0..self.stats.len() {
+            let port = self.stats[i].port;
+            match connect(SocketAddr::new(self.host, port), timeout) {
+                Ok(rtt) => {
+                    self.stats[i].record(Ok(()));
+                    self.check_rest_in_background(i + 1, timeout);
+                    self.satisfied_by = Some(port);
+                    return Ok((port, rtt));
+                }
+                Err(e) => {
+                    self.stats[i].record(Err(e));
+                    reason = e;
+                }
+            }
+        }
+        self.satisfied_by = None;
+        Err(reason)
+    }
+
+    fn check_rest_in_background(&mut self, from: usize, timeout: Duration) {
+        if from >= self.stats.len() || self.background_running {
+            return;
+        }
+        self.background_running = true;
+        let host = self.host;
+        let ports: Vec<u16> = self.stats[from..].iter().map(|stats| stats.port).collect();
+        let tx = self.results_tx.clone();
+        thread::spawn(move || {
+            let results = ports
+                .into_iter()
+                .map(|port| {
+                    (
+                        port,
+                        connect(SocketAddr::new(host, port), timeout).map(|_| ()),
+                    )
+                })
+                .collect();
+            let _ = tx.send(results);
+        });
+    }
+
+    /// STATUS 中的一行：`ports=80:ok(0/120) 443:refused(120/120)`（失败次数/检查次数）
+    pub fn status_line(&mut self) -> String {
+        self.collect();
+        let ports: Vec<String> = self
+            .stats
+            .iter()
+            .map(|stats| {
+                let last = match (stats.attempts, stats.last) {
+                    (0, _) => "-",
+                    (_, None) => "ok",
+                    (_, Some(reason)) => reason.as_str(),
+                };
+                format!(
+                    "{}:{}({}/{})",
+                    stats.port, last, stats.failures, stats.attempts
+                )
+            })
+            .collect();
+        format!("ports={}", ports.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_split_target_ports() {
+        assert_eq!(
+            split_target_ports("1.2.3.4:80,443").unwrap(),
+            ("1.2.3.4:80".to_string(), vec![80, 443])
+        );
+        assert_eq!(
+            split_target_ports("1.2.3.4:80").unwrap(),
+            ("1.2.3.4:80".to_string(), vec![80])
+        );
+        assert_eq!(
+            split_target_ports("[::1]:80, 443,80").unwrap(),
+            ("[::1]:80".to_string(), vec![80, 443])
+        );
+        assert!(split_target_ports("1.2.3.4:80,https").is_err());
+        assert!(split_target_ports("1.2.3.4,443").is_err());
+    }
+
+    #[test]
+    fn test_multi_port() {
+        let open = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = open.local_addr().unwrap().port();
+        // 绑定后立即关闭，得到一个大概率没有监听的端口
+        let closed_port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let host: IpAddr = "127.0.0.1".parse().unwrap();
+
+        // 第一个端口不通时用第二个
+        let mut multi = MultiPort::new(host, &[closed_port, open_port]);
+        let (port, _) = multi.check(Duration::from_secs(2)).unwrap();
+        assert_eq!(port, open_port);
+        assert_eq!(multi.satisfied_by, Some(open_port));
+        assert_eq!(
+            multi.status_line(),
+            format!("ports={}:refused(1/1) {}:ok(0/1)", closed_port, open_port)
+        );
+
+        // 第一个端口成功时，后面的端口在后台检查，失败仍计入统计
+        let mut multi = MultiPort::new(host, &[open_port, closed_port]);
+        assert!(multi.check(Duration::from_secs(2)).is_ok());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while multi.background_running && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            multi.collect();
+        }
+        assert_eq!(
+            multi.status_line(),
+            format!("ports={}:ok(0/1) {}:refused(1/1)", open_port, closed_port)
+        );
+
+        let mut multi = MultiPort::new(host, &[closed_port]);
+        assert_eq!(
+            multi.check(Duration::from_secs(2)),
+            Err(FailureReason::Refused)
+        );
+    }
+}
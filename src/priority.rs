@@ -0,0 +1,158 @@
+/// 可能同时出现的保护条件，各自的处理可能相互抵消
+/// （如低内存时结束了 goahead，延迟恢复时又把它启动起来）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// 空闲内存低于 MEMORY_LOW_THRESHOLD_KB
+    Memory,
+    /// CPU 高负载
+    Load,
+    /// socket 数量超过阈值
+    Socket,
+    /// 连续高延迟，已限流
+    Latency,
+}
+
+/// 默认优先级，从高到低
+pub const DEFAULT_PRIORITY: &[Condition] = &[
+    Condition::Memory,
+    Condition::Load,
+    Condition::Socket,
+    Condition::Latency,
+];
+
+impl Condition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Condition::Memory => "memory",
+            Condition::Load => "load",
+            Condition::Socket => "socket",
+            Condition::Latency => "latency",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Condition> {
+        DEFAULT_PRIORITY.iter().copied().find(|c| c.name() == name)
+    }
+}
+
+/// 解析 `memory,load,socket,latency` 形式的优先级，每个条件必须恰好出现一次
+pub fn parse_priority(value: &str) -> Result<Vec<Condition>, String> {
+    let mut order = Vec::new();
+    for name in value.split(',').map(str::trim) {
+        let condition = Condition::from_name(name)
+            .ok_or_else(|| format!("condition_priority: unknown condition '{}'", name))?;
+        if order.contains(&condition) {
+            return Err(format!(
+                "condition_priority: duplicate condition '{}'",
+                name
+            ));
+        }
+        order.push(condition);
+    }
+    if order.len() != DEFAULT_PRIORITY.len() {
+        return Err(format!(
+            "condition_priority: expected all of memory,load,socket,latency, got '{}'",
+            value
+        ));
+    }
+    Ok(order)
+}
+
+/// 每轮根据当前活动的条件选出优先级最高的一个，低优先级条件的恢复动作在它之下推迟执行
+pub struct Arbiter {
+    active: Vec<Condition>,
+    dominant: Option<Condition>,
+    /// 被推迟的延迟限流恢复
+    pub pending_restore: bool,
+}
+
+impl Arbiter {
+    pub fn new() -> Self {
+        Arbiter {
+            active: Vec::new(),
+            dominant: None,
+            pending_restore: false,
+        }
+    }
+
+    /// 更新活动条件，返回主导条件是否变化
+    pub fn update(&mut self, active: Vec<Condition>, order: &[Condition]) -> bool {
+        let dominant = order.iter().copied().find(|c| active.contains(c));
+        self.active = active;
+        let changed = self.dominant != dominant;
+        self.dominant = dominant;
+        changed
+    }
+
+    /// 是否有比 condition 优先级更高的条件处于活动状态
+    pub fn outranked(&self, condition: Condition, order: &[Condition]) -> bool {
+        let rank = |c: Condition| order.iter().position(|o| *o == c);
+        self.dominant
+            .is_some_and(|dominant| rank(dominant) < rank(condition))
+    }
+
+    /// STATUS 中的一行：`conditions=load,latency dominant=load`
+    pub fn status_line(&self) -> String {
+        let names: Vec<&str> = self.active.iter().map(Condition::name).collect();
+        format!(
+            "conditions={} dominant={}{}",
+            if names.is_empty() {
+                "-".to_string()
+            } else {
+                names.join(",")
+            },
+            self.dominant.map(|c| c.name()).unwrap_or("-"),
+            if self.pending_restore {
+                " restore=deferred"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_priority() {
+        assert_eq!(
+            parse_priority("latency, socket,load,memory").unwrap(),
+            [
+                Condition::Latency,
+                Condition::Socket,
+                Condition::Load,
+                Condition::Memory
+            ]
+        );
+        assert!(parse_priority("memory,load,socket").is_err());
+        assert!(parse_priority("memory,load,socket,socket").is_err());
+        assert!(parse_priority("memory,load,socket,cpu").is_err());
+    }
+
+    #[test]
+    fn test_arbiter() {
+        let order = DEFAULT_PRIORITY;
+        let mut arbiter = Arbiter::new();
+        assert!(arbiter.update(vec![Condition::Latency], order));
+        assert!(!arbiter.outranked(Condition::Latency, order));
+
+        // 高负载优先于延迟：延迟的恢复被压住
+        assert!(arbiter.update(vec![Condition::Latency, Condition::Load], order));
+        assert_eq!(arbiter.dominant, Some(Condition::Load));
+        assert!(arbiter.outranked(Condition::Latency, order));
+        assert!(!arbiter.outranked(Condition::Memory, order));
+        assert_eq!(
+            arbiter.status_line(),
+            "conditions=latency,load dominant=load"
+        );
+        // 主导条件不变时不报告
+        assert!(!arbiter.update(vec![Condition::Load], order));
+
+        // 配置中把延迟排在最前时不再被压住
+        let order = parse_priority("latency,memory,load,socket").unwrap();
+        arbiter.update(vec![Condition::Latency, Condition::Load], &order);
+        assert!(!arbiter.outranked(Condition::Latency, &order));
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 等待进程退出时的轮询间隔
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 所有进程的 PID
+pub fn list_pids() -> Vec<u32> {
+    fs::read_dir("/proc")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_string_lossy().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 程序名为 name 的所有进程，不含本进程（本进程的参数中可能带着 `--adbd-...` 之类的名字）
+pub fn find_by_name(name: &str) -> Vec<u32> {
+    let own = std::process::id();
+    list_pids()
+        .into_iter()
+        .filter(|pid| {
+            *pid != own
+                && fs::read(format!("/proc/{}/cmdline", pid))
+                    .is_ok_and(|cmdline| argv0_matches(&cmdline, name))
+        })
+        .collect()
+}
+
+/// cmdline（NUL 分隔）的 argv[0] 取文件名后与 name 比较；name 带路径时比较完整路径。
+/// 改写过 cmdline 的进程 argv[0] 可能带空格和参数，只取第一段
+fn argv0_matches(cmdline: &[u8], name: &str) -> bool {
+    let argv0 = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+    let argv0 = String::from_utf8_lossy(argv0);
+    let program = argv0.split(' ').next().unwrap_or_default();
+    if name.contains('/') {
+        program == name
+    } else {
+        program.rsplit('/').next() == Some(name)
+    }
+}
+
+/// 每 200ms 重新查找名为 name 的进程，直到全部退出（返回等待时长）
+/// 或超过 timeout（返回仍存在的 PID）
+pub fn wait_for_exit(name: &str, timeout: Duration) -> Result<Duration, Vec<u32>> {
+    wait_until_gone(|| find_by_name(name), timeout)
+}
+
+/// 每 200ms 检查一次 /proc/<pid>，直到这些进程全部退出（僵尸进程算已退出）
+/// 或超过 timeout（返回仍存在的 PID）
+pub fn wait_for_pids_exit(pids: &[u32], timeout: Duration) -> Result<Duration, Vec<u32>> {
+    wait_until_gone(
+        || {
+            pids.iter()
+                .copied()
+                .filter(|pid| is_running(*pid))
+                .collect()
+        },
+        timeout,
+    )
+}
+
+fn wait_until_gone(
+    mut find: impl FnMut() -> Vec<u32>,
+    timeout: Duration,
+) -> Result<Duration, Vec<u32>> {
+    let start = Instant::now();
+    loop {
+        let pids = find();
+        if pids.is_empty() {
+            return Ok(start.elapsed());
+        }
+        if start.elapsed() >= timeout {
+            return Err(pids);
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+}
+
+/// 进程已运行的时长：/proc/<pid>/stat 第 22 个字段（开机后多少 tick 启动）
+/// 加上 /proc/stat 的 btime 得到启动时间
+pub fn process_age(pid: u32) -> Option<Duration> {
+    let start_ticks = parse_start_ticks(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)?;
+    let btime = parse_btime(&fs::read_to_string("/proc/stat").ok()?)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(age_since_start(
+        start_ticks,
+        btime,
+        clock_ticks_per_sec() as u64,
+        now,
+    ))
+}
+
+/// 同名进程中最近启动的一个（PID 和已运行时长）
+pub fn youngest_by_name(name: &str) -> Option<(u32, Duration)> {
+    find_by_name(name)
+        .into_iter()
+        .filter_map(|pid| Some((pid, process_age(pid)?)))
+        .min_by_key(|(_, age)| *age)
+}
+
+/// 运行时长的简短表示，只保留最大的两个单位，如 "3d14h"、"5m12s"
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, mins)
+    } else if mins > 0 {
+        format!("{}m{}s", mins, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+pub fn read_comm(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/comm", pid))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+pub fn is_alive(pid: u32) -> bool {
+    fs::metadata(format!("/proc/{}", pid)).is_ok()
+}
+
+/// 进程存在且不是僵尸（被 kill 的子进程在被回收前仍留在 /proc 中）
+fn is_running(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .is_ok_and(|stat| parse_state(&stat).is_some_and(|state| state != 'Z'))
+}
+
+/// 占用 CPU 最多的进程
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopProcess {
+    pub pid: u32,
+    pub name: String,
+    /// 采样间隔内占全部 CPU 时间的百分比
+    pub cpu_percent: f32,
+}
+
+/// 通过两次读取 /proc/<pid>/stat 的 utime+stime 找出 CPU 占用最高的进程
+pub struct TopTracker {
+    prev: HashMap<u32, u64>,
+    prev_at: Option<Instant>,
+}
+
+impl TopTracker {
+    pub fn new() -> Self {
+        TopTracker {
+            prev: HashMap::new(),
+            prev_at: None,
+        }
+    }
+
+    /// 扫描所有进程，返回上次采样以来 CPU 占用最高的进程（第一次采样返回 None）
+    pub fn sample(&mut self, now: Instant) -> Option<TopProcess> {
+        let mut current = HashMap::new();
+        for pid in list_pids() {
+            if let Some(ticks) = fs::read_to_string(format!("/proc/{}/stat", pid))
+                .ok()
+                .and_then(|content| parse_cpu_ticks(&content))
+            {
+                current.insert(pid, ticks);
+            }
+        }
+
+        let elapsed = self.prev_at.map(|at| now.duration_since(at));
+        let prev = std::mem::replace(&mut self.prev, current);
+        self.prev_at = Some(now);
+
+        // 可用的 CPU 时间（ticks）= 经过时间 × 每秒 ticks × CPU 数
+        let capacity = elapsed?.as_secs_f32() * clock_ticks_per_sec() * cpu_count();
+        if capacity <= 0.0 {
+            return None;
+        }
+        let (pid, delta) = self
+            .prev
+            .iter()
+            .filter_map(|(pid, ticks)| Some((*pid, ticks.checked_sub(*prev.get(pid)?)?)))
+            .max_by_key(|(_, delta)| *delta)?;
+        Some(TopProcess {
+            pid,
+            name: read_comm(pid).unwrap_or_else(|| "?".to_string()),
+            cpu_percent: delta as f32 * 100.0 / capacity,
+        })
+    }
+}
+
+/// 打开文件句柄最多的进程
+#[derive(Debug, Clone, PartialEq)]
+pub struct FdHolder {
+    pub pid: u32,
+    pub name: String,
+    /// 达到 cap 时为 cap
+    pub fds: usize,
+}
+
+/// /proc/<pid>/fd 的条目数，最多数到 cap（限制单个进程的遍历开销）
+pub fn count_fds(pid: u32, cap: usize) -> Option<usize> {
+    let entries = fs::read_dir(format!("/proc/{}/fd", pid)).ok()?;
+    Some(entries.take(cap).count())
+}
+
+/// 遍历所有进程，返回打开文件句柄最多的 n 个（无权读取的进程跳过）
+pub fn top_fd_holders(n: usize, cap: usize) -> Vec<FdHolder> {
+    let counts = list_pids()
+        .into_iter()
+        .filter_map(|pid| Some((pid, count_fds(pid, cap)?)))
+        .collect();
+    top_by_count(counts, n)
+        .into_iter()
+        .map(|(pid, fds)| FdHolder {
+            pid,
+            name: read_comm(pid).unwrap_or_else(|| "?".to_string()),
+            fds,
+        })
+        .collect()
+}
+
+/// 按数量从大到小取前 n 个，数量相同时 PID 小的在前
+fn top_by_count(mut counts: Vec<(u32, usize)>, n: usize) -> Vec<(u32, usize)> {
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(n);
+    counts
+}
+
+/// 先 SIGTERM，宽限期内未退出再 SIGKILL，返回是否用到了 SIGKILL
+pub fn graceful_kill(pid: u32, grace: Duration) -> bool {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        if !is_alive(pid) {
+            return false;
+        }
+        thread::sleep(EXIT_POLL_INTERVAL);
+    }
+    if !is_alive(pid) {
+        return false;
+    }
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+    true
+}
+
+/// /proc/<pid>/stat 的 utime + stime（第 14、15 个字段）
+/// comm 可能包含空格和括号，所以从最后一个 ')' 之后开始数
+fn parse_cpu_ticks(content: &str) -> Option<u64> {
+    let rest = &content[content.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // rest 从第 3 个字段（state）开始
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// /proc/<pid>/stat 的进程状态（第 3 个字段，R/S/D/Z 等）
+fn parse_state(content: &str) -> Option<char> {
+    let rest = &content[content.rfind(')')? + 1..];
+    rest.split_whitespace().next()?.chars().next()
+}
+
+/// /proc/<pid>/stat 的 starttime（第 22 个字段）
+fn parse_start_ticks(content: &str) -> Option<u64> {
+    let rest = &content[content.rfind(')')? + 1..];
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// /proc/stat 中 "btime <开机时间戳>" 行
+fn parse_btime(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn age_since_start(start_ticks: u64, btime: u64, ticks_per_sec: u64, now: u64) -> Duration {
+    let started = btime + start_ticks / ticks_per_sec.max(1);
+    Duration::from_secs(now.saturating_sub(started))
+}
+
+fn clock_ticks_per_sec() -> f32 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f32
+    } else {
+        100.0
+    }
+}
+
+fn cpu_count() -> f32 {
+    let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if count > 0 {
+        count as f32
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argv0_matches() {
+        assert!(argv0_matches(b"/sbin/adbd\0", "adbd"));
+        assert!(argv0_matches(b"adbd\0--root\0", "adbd"));
+        assert!(argv0_matches(b"zte_cm -d\0", "zte_cm"));
+        assert!(argv0_matches(b"/sbin/adbd\0", "/sbin/adbd"));
+        // 参数或程序名中包含 name 的不算
+        assert!(!argv0_matches(
+            b"/usr/bin/zxic_ping\0--adbd-ready-settle-ms=500\0",
+            "adbd"
+        ));
+        assert!(!argv0_matches(b"/bin/adbd_helper\0", "adbd"));
+        assert!(!argv0_matches(b"/bin/adbd\0", "/sbin/adbd"));
+        assert!(!argv0_matches(b"", "adbd"));
+        // 本进程不会被找到
+        let own = std::fs::read_link("/proc/self/exe").unwrap();
+        let own_name = own.file_name().unwrap().to_string_lossy().to_string();
+        assert!(!find_by_name(&own_name).contains(&std::process::id()));
+    }
+
+    #[test]
+    fn test_wait_until_gone() {
+        let mut scans = vec![vec![], vec![12], vec![12, 13]];
+        let waited = wait_until_gone(|| scans.pop().unwrap(), Duration::from_secs(2));
+        assert!(waited.is_ok_and(|d| d >= EXIT_POLL_INTERVAL * 2));
+
+        assert_eq!(
+            wait_until_gone(|| vec![12], Duration::from_millis(300)),
+            Err(vec![12])
+        );
+    }
+
+    #[test]
+    fn test_process_age() {
+        let stat =
+            "1234 (adbd) S 1 1234 1234 0 -1 4194560 500 0 0 0 150 50 0 0 20 0 1 0 30000 1000 50";
+        assert_eq!(parse_start_ticks(stat), Some(30000));
+        assert_eq!(parse_start_ticks("1234 (adbd) S 1"), None);
+        assert_eq!(
+            parse_btime("cpu  1 2 3 4\nintr 5\nbtime 1700000000\nprocesses 99\n"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(parse_btime("cpu  1 2 3 4\n"), None);
+
+        // 开机 300 秒后启动，现在是开机后 3 天 14 小时
+        let now = 1_700_000_000 + 300 + (3 * 24 + 14) * 3600;
+        let age = age_since_start(30000, 1_700_000_000, 100, now);
+        assert_eq!(age, Duration::from_secs((3 * 24 + 14) * 3600));
+        assert_eq!(format_age(age), "3d14h");
+        // 时钟回拨时不会得到负数
+        assert_eq!(
+            age_since_start(30000, 1_700_000_000, 100, 1_600_000_000),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(42)), "42s");
+        assert_eq!(format_age(Duration::from_secs(312)), "5m12s");
+        assert_eq!(format_age(Duration::from_secs(7500)), "2h5m");
+    }
+
+    #[test]
+    fn test_parse_state() {
+        assert_eq!(parse_state("1234 (adbd) Z 1 1234 1234 0"), Some('Z'));
+        assert_eq!(parse_state("42 (zte (mgr) d) S 1 42"), Some('S'));
+        assert_eq!(parse_state("42 (x)"), None);
+        // 本进程一定在运行
+        assert!(is_running(std::process::id()));
+        assert!(wait_for_pids_exit(&[std::process::id()], Duration::from_millis(1)).is_err());
+    }
+
+    #[test]
+    fn test_fd_counts() {
+        // 本进程至少有 stdin/stdout/stderr
+        let own = count_fds(std::process::id(), 1000).unwrap();
+        assert!(own >= 3);
+        assert_eq!(count_fds(std::process::id(), 2), Some(2));
+        assert_eq!(
+            top_by_count(vec![(10, 5), (11, 50), (12, 7), (9, 50)], 3),
+            vec![(9, 50), (11, 50), (12, 7)]
+        );
+        assert!(top_by_count(vec![], 3).is_empty());
+    }
+
+    #[test]
+    fn test_parse_cpu_ticks() {
+        let stat =
+            "1234 (goahead) S 1 1234 1234 0 -1 4194560 500 0 0 0 150 50 0 0 20 0 1 0 300 1000 50";
+        assert_eq!(parse_cpu_ticks(stat), Some(200));
+        // comm 中带空格和括号
+        let stat = "42 (zte (mgr) d) R 1 42 42 0 -1 0 0 0 0 0 7 3 0 0 20 0 1 0 1 1 1";
+        assert_eq!(parse_cpu_ticks(stat), Some(10));
+        assert_eq!(parse_cpu_ticks("42 (x) R 1"), None);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 运行时切换的调优档位文件名（位于存储目录下，内容为档位名）
+pub const PROFILE_FILE: &str = "zxping.profile";
+
+/// 调优档位，决定 nf_conntrack 相关参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// 低内存/弱设备：更小的连接跟踪表
+    Conservative,
+    Balanced,
+    /// 多终端共享：更大的连接跟踪表
+    Performance,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 3] = [
+        Profile::Conservative,
+        Profile::Balanced,
+        Profile::Performance,
+    ];
+
+    pub fn parse(name: &str) -> Option<Profile> {
+        Profile::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Conservative => "conservative",
+            Profile::Balanced => "balanced",
+            Profile::Performance => "performance",
+        }
+    }
+
+    /// (conntrack_max, conntrack_max_throttled, conntrack_hashsize)
+    pub fn conntrack(&self) -> (u32, u32, u32) {
+        match self {
+            Profile::Conservative => (4096, 2048, 1024),
+            Profile::Balanced => (8192, 4096, 2048),
+            Profile::Performance => (16384, 8192, 4096),
+        }
+    }
+
+    /// 所有档位名，用于错误提示
+    pub fn names() -> String {
+        Profile::ALL.map(|p| p.name()).join("|")
+    }
+}
+
+/// 读取持久化的档位，文件不存在或内容无效时返回 None
+pub fn load_persisted(path: &Path) -> Option<Profile> {
+    let content = fs::read_to_string(path).ok()?;
+    Profile::parse(content.trim())
+}
+
+pub fn persist(path: &Path, profile: Profile) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, format!("{}\n", profile.name()))?;
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        for profile in Profile::ALL {
+            assert_eq!(Profile::parse(profile.name()), Some(profile));
+        }
+        assert_eq!(Profile::parse("turbo"), None);
+        assert_eq!(Profile::names(), "conservative|balanced|performance");
+    }
+}
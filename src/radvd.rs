@@ -47,15 +47,16 @@ pub fn update_radvd_prefix(conf: &mut Config, new_prefix: &str) -> Result<(), St
 }
 
 pub fn get_radvd_prefix() -> String {
-    use std::process::Command;
+    use crate::command;
     use std::str;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(3);
 
     // 尝试 nv 命令
-    if let Ok(output) = Command::new("nv")
-        .args(["get", "wan1_ipv6_prefix_info"])
-        .output()
+    if let Ok(output) = command::run_with_timeout("nv", &["get", "wan1_ipv6_prefix_info"], TIMEOUT)
     {
-        if output.status.success() {
+        if output.success() {
             if let Ok(prefix) = str::from_utf8(&output.stdout) {
                 let prefix = prefix.trim();
                 if !prefix.is_empty() {
@@ -66,11 +67,8 @@ pub fn get_radvd_prefix() -> String {
     }
 
     // 从 ip 命令输出中提取前缀
-    if let Ok(output) = Command::new("ip")
-        .args(["-6", "addr", "show", "wan1"])
-        .output()
-    {
-        if output.status.success() {
+    if let Ok(output) = command::run_with_timeout("ip", &["-6", "addr", "show", "wan1"], TIMEOUT) {
+        if output.success() {
             if let Ok(output_str) = str::from_utf8(&output.stdout) {
                 // 查找 inet6 地址行
                 for line in output_str.lines() {
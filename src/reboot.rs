@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+/// 执行 reboot 后等待多久仍未重启即视为失败
+pub const REBOOT_CONFIRM_WAIT: Duration = Duration::from_secs(20);
+/// 第一次重启失败后的重试间隔，之后每次翻倍
+const REBOOT_RETRY_BASE: Duration = Duration::from_secs(600);
+const REBOOT_RETRY_MAX: Duration = Duration::from_secs(6 * 3600);
+/// 重启前本地检查正常、跳过重启后，多久内不再尝试重启
+const REBOOT_SKIP_BACKOFF: Duration = Duration::from_secs(600);
+
+/// 重启失败（或本地检查正常而跳过）后的退避状态，避免重启机制本身损坏时每轮都重试
+pub struct RebootGuard {
+    failed_attempts: u32,
+    retry_at: Option<Instant>,
+}
+
+impl RebootGuard {
+    pub fn new() -> Self {
+        RebootGuard {
+            failed_attempts: 0,
+            retry_at: None,
+        }
+    }
+
+    pub fn failed_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    /// 是否允许自动重启（退避期间不允许）
+    pub fn allowed(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    /// 记录一次失败的重启，返回下次允许重试前的等待时间
+    pub fn record_failure(&mut self, now: Instant) -> Duration {
+        self.failed_attempts += 1;
+        let backoff = REBOOT_RETRY_BASE
+            .saturating_mul(1 << (self.failed_attempts - 1).min(10))
+            .min(REBOOT_RETRY_MAX);
+        self.retry_at = Some(now + backoff);
+        backoff
+    }
+
+    /// 记录一次因本地检查正常而跳过的重启（不计入失败次数），返回下次允许重启前的等待时间
+    pub fn record_skip(&mut self, now: Instant) -> Duration {
+        let until = now + REBOOT_SKIP_BACKOFF;
+        self.retry_at = Some(self.retry_at.map_or(until, |at| at.max(until)));
+        REBOOT_SKIP_BACKOFF
+    }
+
+    /// STATUS 中的重启失败状态
+    pub fn status_line(&self, now: Instant) -> String {
+        match self.retry_at {
+            Some(at) if self.failed_attempts > 0 => format!(
+                "reboot_failed={} retry_in={}s",
+                self.failed_attempts,
+                at.saturating_duration_since(now).as_secs()
+            ),
+            Some(at) if at > now => format!(
+                "reboot_failed=0 retry_in={}s",
+                at.saturating_duration_since(now).as_secs()
+            ),
+            _ => "reboot_failed=0".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reboot_backoff() {
+        let mut guard = RebootGuard::new();
+        let now = Instant::now();
+        assert!(guard.allowed(now));
+
+        assert_eq!(guard.record_failure(now), Duration::from_secs(600));
+        assert!(!guard.allowed(now + Duration::from_secs(599)));
+        assert!(guard.allowed(now + Duration::from_secs(600)));
+
+        assert_eq!(guard.record_failure(now), Duration::from_secs(1200));
+        for _ in 0..10 {
+            guard.record_failure(now);
+        }
+        assert_eq!(guard.record_failure(now), REBOOT_RETRY_MAX);
+        assert_eq!(guard.failed_attempts(), 13);
+    }
+
+    #[test]
+    fn test_reboot_skip_backoff() {
+        let mut guard = RebootGuard::new();
+        let now = Instant::now();
+        assert_eq!(guard.record_skip(now), REBOOT_SKIP_BACKOFF);
+        assert!(!guard.allowed(now + Duration::from_secs(599)));
+        assert!(guard.allowed(now + REBOOT_SKIP_BACKOFF));
+        assert_eq!(guard.failed_attempts(), 0);
+        assert_eq!(guard.status_line(now), "reboot_failed=0 retry_in=600s");
+
+        // 不缩短重启失败的退避
+        guard.record_failure(now);
+        guard.record_failure(now);
+        guard.record_skip(now);
+        assert!(!guard.allowed(now + REBOOT_SKIP_BACKOFF));
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::crc32::crc32;
+use crate::monitor::MonitorState;
+
+/// 运行状态文件（tmpfs，设备重启后自然丢失，只在进程重启之间传递）
+pub const STATE_FILE: &str = "/tmp/zxping.state";
+/// 超过这个时间的状态不再恢复（计数已经不能代表当前的链路）
+pub const STATE_MAX_AGE: Duration = Duration::from_secs(300);
+
+const MAGIC: &[u8; 4] = b"ZXPS";
+/// 格式版本，字段变化时递增；版本不同的状态直接丢弃
+const VERSION: u8 = 1;
+/// magic + 版本 + 负载长度
+const HEADER_LEN: usize = 4 + 1 + 2;
+/// saved_at(8) failure_count(4) high_latency_count(4) success_streak(4)
+/// last_rtt_ms(4) health_score(8) notify_seq(4)
+const PAYLOAD_LEN: usize = 36;
+/// last_rtt_ms 为空时写入的值
+const NO_RTT: u32 = u32::MAX;
+
+/// 进程重启之间保留的运行状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeState {
+    pub failure_count: u32,
+    pub high_latency_count: u32,
+    pub success_streak: u32,
+    pub last_rtt_ms: Option<u32>,
+    pub health_score: f64,
+    pub notify_seq: u32,
+}
+
+impl RuntimeState {
+    pub fn capture(state: &MonitorState, notify_seq: u32) -> Self {
+        RuntimeState {
+            failure_count: state.failure_count,
+            high_latency_count: state.high_latency_count,
+            success_streak: state.success_streak,
+            last_rtt_ms: state
+                .last_rtt_ms
+                .map(|ms| ms.min(NO_RTT as u128 - 1) as u32),
+            health_score: state.score.value(),
+            notify_seq,
+        }
+    }
+
+    /// 恢复到新的 MonitorState。退出时限流参数已经恢复，高延迟计数停在限流阈值之下，
+    /// 下一次高延迟时重新进入限流
+    pub fn apply(&self, state: &mut MonitorState, max_high_latency: u32) {
+        state.failure_count = self.failure_count;
+        state.high_latency_count = self
+            .high_latency_count
+            .min(max_high_latency.saturating_sub(1));
+        state.success_streak = self.success_streak;
+        state.last_rtt_ms = self.last_rtt_ms.map(u128::from);
+        state.score.restore(self.health_score);
+    }
+
+    /// 编码为 `magic | 版本 | 负载长度 | 负载 | crc32`，整数均为小端
+    pub fn encode(&self, saved_at: u64) -> Vec<u8> {
+        let mut blob = Vec::with_capacity(HEADER_LEN + PAYLOAD_LEN + 4);
+        blob.extend_from_slice(MAGIC);
+        blob.push(VERSION);
+        blob.extend_from_slice(&(PAYLOAD_LEN as u16).to_le_bytes());
+        blob.extend_from_slice(&saved_at.to_le_bytes());
+        blob.extend_from_slice(&self.failure_count.to_le_bytes());
+        blob.extend_from_slice(&self.high_latency_count.to_le_bytes());
+        blob.extend_from_slice(&self.success_streak.to_le_bytes());
+        blob.extend_from_slice(&self.last_rtt_ms.unwrap_or(NO_RTT).to_le_bytes());
+        blob.extend_from_slice(&self.health_score.to_le_bytes());
+        blob.extend_from_slice(&self.notify_seq.to_le_bytes());
+        let crc = crc32(&blob);
+        blob.extend_from_slice(&crc.to_le_bytes());
+        blob
+    }
+
+    /// 解码并检查版本、长度、校验和和保存时间（now 为当前 UNIX 秒数）
+    pub fn decode(blob: &[u8], now: u64, max_age: Duration) -> Result<Self, String> {
+        if blob.len() < HEADER_LEN || &blob[..4] != MAGIC {
+            return Err("not a state file".to_string());
+        }
+        if blob[4] != VERSION {
+            return Err(format!(
+                "unsupported version {} (expected {})",
+                blob[4], VERSION
+            ));
+        }
+        let payload_len = u16::from_le_bytes([blob[5], blob[6]]) as usize;
+        if payload_len != PAYLOAD_LEN || blob.len() != HEADER_LEN + payload_len + 4 {
+            return Err(format!("bad length {}", blob.len()));
+        }
+        let (data, crc) = blob.split_at(HEADER_LEN + payload_len);
+        if crc32(data).to_le_bytes() != crc {
+            return Err("checksum mismatch".to_string());
+        }
+
+        let mut payload = Reader(&data[HEADER_LEN..]);
+        let saved_at = u64::from_le_bytes(payload.take());
+        let age = now.checked_sub(saved_at).map(Duration::from_secs);
+        if age.is_none_or(|age| age > max_age) {
+            return Err(format!("stale (saved at {}, now {})", saved_at, now));
+        }
+        let state = RuntimeState {
+            failure_count: u32::from_le_bytes(payload.take()),
+            high_latency_count: u32::from_le_bytes(payload.take()),
+            success_streak: u32::from_le_bytes(payload.take()),
+            last_rtt_ms: Some(u32::from_le_bytes(payload.take())).filter(|ms| *ms != NO_RTT),
+            health_score: f64::from_le_bytes(payload.take()),
+            notify_seq: u32::from_le_bytes(payload.take()),
+        };
+        if !state.health_score.is_finite() || state.health_score < 0.0 {
+            return Err("bad health score".to_string());
+        }
+        Ok(state)
+    }
+
+    /// 写入状态文件（先写临时文件再改名，避免留下写了一半的文件）
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.encode(unix_now()))?;
+        fs::rename(&tmp, path)
+    }
+
+    /// 读取并删除状态文件；文件不存在时返回 Ok(None)，无效时返回原因
+    pub fn take(path: &Path, max_age: Duration) -> Result<Option<Self>, String> {
+        let blob = match fs::read(path) {
+            Ok(blob) => blob,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let _ = fs::remove_file(path);
+        Self::decode(&blob, unix_now(), max_age).map(Some)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 按顺序读取定长字段（调用前已检查过总长度）
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (field, rest) = self.0.split_at(N);
+        self.0 = rest;
+        field.try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> RuntimeState {
+        RuntimeState {
+            failure_count: 3,
+            high_latency_count: 7,
+            success_streak: 0,
+            last_rtt_ms: Some(420),
+            health_score: 12.5,
+            notify_seq: 99,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let age = Duration::from_secs(300);
+        let blob = sample().encode(1_000);
+        assert_eq!(blob.len(), HEADER_LEN + PAYLOAD_LEN + 4);
+        assert_eq!(RuntimeState::decode(&blob, 1_010, age), Ok(sample()));
+
+        let empty = RuntimeState {
+            last_rtt_ms: None,
+            ..sample()
+        };
+        assert_eq!(
+            RuntimeState::decode(&empty.encode(1_000), 1_000, age),
+            Ok(empty)
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_blobs() {
+        let age = Duration::from_secs(300);
+        let blob = sample().encode(1_000);
+
+        // 旧版本：即使校验和正确也不恢复
+        let mut old = blob.clone();
+        old[4] = VERSION - 1;
+        let len = old.len();
+        let crc = crc32(&old[..len - 4]).to_le_bytes();
+        old[len - 4..].copy_from_slice(&crc);
+        assert!(RuntimeState::decode(&old, 1_000, age)
+            .unwrap_err()
+            .starts_with("unsupported version"));
+
+        let mut corrupt = blob.clone();
+        corrupt[HEADER_LEN + 9] ^= 0x01;
+        assert_eq!(
+            RuntimeState::decode(&corrupt, 1_000, age),
+            Err("checksum mismatch".to_string())
+        );
+        assert!(RuntimeState::decode(&blob[..blob.len() - 1], 1_000, age).is_err());
+        assert!(RuntimeState::decode(b"", 1_000, age).is_err());
+        assert!(RuntimeState::decode(b"garbage", 1_000, age).is_err());
+
+        // 太旧，或保存时间在未来（时钟跳变）
+        assert!(RuntimeState::decode(&blob, 1_301, age)
+            .unwrap_err()
+            .starts_with("stale"));
+        assert!(RuntimeState::decode(&blob, 999, age).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut state = MonitorState::new();
+        sample().apply(&mut state, 5);
+        assert_eq!(state.failure_count, 3);
+        // 限流参数退出时已恢复，计数停在阈值之下
+        assert_eq!(state.high_latency_count, 4);
+        assert_eq!(state.last_rtt_ms, Some(420));
+        assert_eq!(state.score.value(), 12.5);
+        assert_eq!(RuntimeState::capture(&state, 99).high_latency_count, 4);
+    }
+
+    #[test]
+    fn test_save_and_take() {
+        let path = std::env::temp_dir().join(format!("zxping-state-{}", std::process::id()));
+        sample().save(&path).unwrap();
+        assert_eq!(RuntimeState::take(&path, STATE_MAX_AGE), Ok(Some(sample())));
+        // 读取后删除，只恢复一次
+        assert_eq!(RuntimeState::take(&path, STATE_MAX_AGE), Ok(None));
+    }
+}
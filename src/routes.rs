@@ -0,0 +1,274 @@
+use std::fs;
+use std::net::Ipv4Addr;
+
+/// /proc/net/route 中的一条默认路由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultRoute {
+    pub iface: String,
+    pub gateway: Ipv4Addr,
+}
+
+impl DefaultRoute {
+    /// `192.168.0.1 dev wan1`
+    pub fn describe(&self) -> String {
+        format!("{} dev {}", self.gateway, self.iface)
+    }
+}
+
+/// 默认路由异常
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteAnomaly {
+    /// 没有默认路由（附带最近一次正常的路由）
+    Missing(Option<DefaultRoute>),
+    /// 有多条网关不同的默认路由
+    Multiple(Vec<DefaultRoute>),
+    /// 网关与上次检查时不同
+    Changed(DefaultRoute, DefaultRoute),
+}
+
+impl RouteAnomaly {
+    /// ROUTE_ANOMALY 通知的内容
+    pub fn describe(&self) -> String {
+        match self {
+            RouteAnomaly::Missing(Some(last)) => format!("MISSING (last {})", last.describe()),
+            RouteAnomaly::Missing(None) => "MISSING".to_string(),
+            RouteAnomaly::Multiple(routes) => format!("MULTIPLE {}", describe_all(routes)),
+            RouteAnomaly::Changed(from, to) => {
+                format!("CHANGED {} -> {}", from.describe(), to.describe())
+            }
+        }
+    }
+}
+
+fn describe_all(routes: &[DefaultRoute]) -> String {
+    routes
+        .iter()
+        .map(DefaultRoute::describe)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 解析 /proc/net/route，返回所有默认路由（目标和掩码都为 0）；地址为小端十六进制
+pub fn parse_proc_route(content: &str) -> Vec<DefaultRoute> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || fields[1] != "00000000" || fields[7] != "00000000" {
+                return None;
+            }
+            Some(DefaultRoute {
+                iface: fields[0].to_string(),
+                gateway: decode_hex_addr(fields[2])?,
+            })
+        })
+        .collect()
+}
+
+/// /proc/net/route 中的地址是按内存顺序（小端）打印的十六进制：
+/// `0100A8C0` 是 192.168.0.1，不是 1.0.168.192
+fn decode_hex_addr(hex: &str) -> Option<Ipv4Addr> {
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .map(|value| Ipv4Addr::from(value.swap_bytes()))
+}
+
+/// 解析 /proc/net/route 中 iface 的网段（如 `192.168.0.0/24`），跳过默认路由和 169.254.0.0/16
+pub fn parse_iface_networks(content: &str, iface: &str) -> Vec<String> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || fields[0] != iface {
+                return None;
+            }
+            let destination = decode_hex_addr(fields[1])?;
+            let mask = decode_hex_addr(fields[7])?;
+            if mask.is_unspecified() || destination.is_link_local() {
+                return None;
+            }
+            Some(format!("{}/{}", destination, u32::from(mask).count_ones()))
+        })
+        .collect()
+}
+
+/// 解析 `192.168.0.0/24` 形式的 IPv4 网段，返回 (地址, 前缀长度)
+pub fn parse_network(value: &str) -> Option<(Ipv4Addr, u32)> {
+    let (addr, prefix) = value.split_once('/')?;
+    let addr = addr.trim().parse().ok()?;
+    let prefix = prefix.trim().parse().ok().filter(|p| *p <= 32)?;
+    Some((addr, prefix))
+}
+
+/// `ip route show dev br0` 的输出中第一条网段路由（/proc/net/route 读不到时使用）
+pub fn parse_ip_route_network(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let network = line.split_whitespace().next()?;
+        (network.contains('/') && !network.starts_with("169.254")).then(|| network.to_string())
+    })
+}
+
+pub fn read_default_routes() -> Option<Vec<DefaultRoute>> {
+    fs::read_to_string("/proc/net/route")
+        .ok()
+        .map(|content| parse_proc_route(&content))
+}
+
+/// 定期检查默认路由，异常只在出现时报告一次
+pub struct RouteWatch {
+    /// 最近一次只有一条（或网关都相同）的默认路由
+    last_good: Option<DefaultRoute>,
+    snapshot: Vec<DefaultRoute>,
+    /// 当前异常的种类（同一种异常持续时不重复报告）
+    anomaly: Option<&'static str>,
+}
+
+impl RouteWatch {
+    pub fn new() -> Self {
+        RouteWatch {
+            last_good: None,
+            snapshot: Vec::new(),
+            anomaly: None,
+        }
+    }
+
+    pub fn check(&mut self, routes: Vec<DefaultRoute>) -> Option<RouteAnomaly> {
+        self.snapshot = routes.clone();
+        let Some(first) = routes.first() else {
+            return self.report("missing", RouteAnomaly::Missing(self.last_good.clone()));
+        };
+        if routes.iter().any(|route| route.gateway != first.gateway) {
+            return self.report("multiple", RouteAnomaly::Multiple(routes));
+        }
+        self.anomaly = None;
+        let previous = self.last_good.replace(first.clone());
+        match previous {
+            Some(previous) if previous.gateway != first.gateway => {
+                Some(RouteAnomaly::Changed(previous, first.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn report(&mut self, kind: &'static str, anomaly: RouteAnomaly) -> Option<RouteAnomaly> {
+        let changed = self.anomaly != Some(kind);
+        self.anomaly = Some(kind);
+        changed.then_some(anomaly)
+    }
+
+    /// STATUS 中的最近一次路由快照
+    pub fn status_line(&self) -> String {
+        if self.snapshot.is_empty() {
+            "default_route=none".to_string()
+        } else {
+            format!("default_route={}", describe_all(&self.snapshot))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTE_HEADER: &str =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n";
+
+    fn route(iface: &str, gateway: &str) -> DefaultRoute {
+        DefaultRoute {
+            iface: iface.to_string(),
+            gateway: gateway.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_proc_route() {
+        let content = format!(
+            "{}{}{}",
+            ROUTE_HEADER,
+            "wan1\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n",
+            "br0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n"
+        );
+        assert_eq!(parse_proc_route(&content), [route("wan1", "192.168.0.1")]);
+        assert!(parse_proc_route(ROUTE_HEADER).is_empty());
+    }
+
+    #[test]
+    fn test_parse_iface_networks() {
+        let content = format!(
+            "{}{}{}{}{}",
+            ROUTE_HEADER,
+            "wan1\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n",
+            "br0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n",
+            "br0\t0000FEA9\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0\n",
+            "br0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n"
+        );
+        // 按小端解码：0001A8C0 是 192.168.1.0，00FFFFFF 是 /24
+        assert_eq!(parse_iface_networks(&content, "br0"), ["192.168.1.0/24"]);
+        assert!(parse_iface_networks(&content, "usblan0").is_empty());
+        let wide = format!(
+            "{}{}",
+            ROUTE_HEADER, "br0\t0000000A\t00000000\t0001\t0\t0\t0\t00F0FFFF\t0\t0\t0\n"
+        );
+        assert_eq!(parse_iface_networks(&wide, "br0"), ["10.0.0.0/20"]);
+
+        assert_eq!(
+            parse_network("10.0.0.0/24"),
+            Some(("10.0.0.0".parse().unwrap(), 24))
+        );
+        assert_eq!(parse_network("10.0.0.0"), None);
+        assert_eq!(parse_network("10.0.0.0/33"), None);
+        assert_eq!(parse_network("host.lan/24"), None);
+
+        assert_eq!(
+            parse_ip_route_network(
+                "169.254.0.0/16 scope link\n192.168.0.0/24 proto kernel scope link src 192.168.0.1\n"
+            )
+            .as_deref(),
+            Some("192.168.0.0/24")
+        );
+        assert_eq!(parse_ip_route_network("default via 192.168.0.1\n"), None);
+    }
+
+    #[test]
+    fn test_route_watch() {
+        let mut watch = RouteWatch::new();
+        assert_eq!(watch.check(vec![route("wan1", "10.0.0.1")]), None);
+
+        // 丢失只报告一次
+        assert_eq!(
+            watch.check(Vec::new()),
+            Some(RouteAnomaly::Missing(Some(route("wan1", "10.0.0.1"))))
+        );
+        assert_eq!(watch.check(Vec::new()), None);
+        assert_eq!(watch.status_line(), "default_route=none");
+
+        // 网关相同的多条不算异常；网关变化报告一次
+        assert_eq!(
+            watch.check(vec![route("wan1", "10.0.0.1"), route("wan1", "10.0.0.1")]),
+            None
+        );
+        let changed = watch.check(vec![route("wan1", "10.0.0.9")]).unwrap();
+        assert_eq!(
+            changed.describe(),
+            "CHANGED 10.0.0.1 dev wan1 -> 10.0.0.9 dev wan1"
+        );
+
+        let multiple = vec![route("wan1", "10.0.0.9"), route("ppp0", "10.64.0.1")];
+        assert_eq!(
+            watch.check(multiple.clone()),
+            Some(RouteAnomaly::Multiple(multiple.clone()))
+        );
+        assert_eq!(watch.check(multiple), None);
+        assert_eq!(
+            watch.status_line(),
+            "default_route=10.0.0.9 dev wan1, 10.64.0.1 dev ppp0"
+        );
+        // 从一种异常变成另一种时再次报告
+        assert!(matches!(
+            watch.check(Vec::new()),
+            Some(RouteAnomaly::Missing(_))
+        ));
+    }
+}
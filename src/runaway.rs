@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::procs::TopProcess;
+
+/// 同一进程被杀后再次允许杀的基础间隔，之后每次翻倍
+const KILL_BACKOFF_BASE: Duration = Duration::from_secs(600);
+const KILL_BACKOFF_MAX: Duration = Duration::from_secs(24 * 3600);
+
+/// 对持续占用 CPU 的进程采取的动作
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunawayAction {
+    /// 只报告（不在允许列表中、未开启强制或处于退避期）
+    Report(TopProcess, u32),
+    /// 在允许列表中且已开启强制，应结束该进程
+    Kill(TopProcess),
+}
+
+struct KillRecord {
+    count: u32,
+    next_allowed: Instant,
+}
+
+/// 高负载期间跟踪连续成为最高占用的进程
+pub struct RunawayGuard {
+    /// 当前连续成为最高占用（且超过阈值）的进程名和次数
+    streak: Option<(String, u32)>,
+    kills: HashMap<String, KillRecord>,
+}
+
+impl RunawayGuard {
+    pub fn new() -> Self {
+        RunawayGuard {
+            streak: None,
+            kills: HashMap::new(),
+        }
+    }
+
+    /// 高负载检查时调用；连续次数刚超过 runaway_checks 时返回一次动作
+    pub fn update(
+        &mut self,
+        top: Option<TopProcess>,
+        config: &Config,
+        now: Instant,
+    ) -> Option<RunawayAction> {
+        let top = match top {
+            Some(top) if top.cpu_percent >= config.runaway_cpu_threshold => top,
+            _ => {
+                self.streak = None;
+                return None;
+            }
+        };
+
+        let count = match &mut self.streak {
+            Some((name, count)) if *name == top.name => {
+                *count += 1;
+                *count
+            }
+            _ => {
+                self.streak = Some((top.name.clone(), 1));
+                1
+            }
+        };
+        if count != config.runaway_checks + 1 {
+            return None;
+        }
+
+        let killable = config.runaway_kill
+            && config.runaway_kill_list.contains(&top.name)
+            && self
+                .kills
+                .get(&top.name)
+                .is_none_or(|record| now >= record.next_allowed);
+        if killable {
+            Some(RunawayAction::Kill(top))
+        } else {
+            Some(RunawayAction::Report(top, count))
+        }
+    }
+
+    /// 记录一次结束进程，返回该进程累计被结束的次数
+    pub fn record_kill(&mut self, name: &str, now: Instant) -> u32 {
+        let record = self.kills.entry(name.to_string()).or_insert(KillRecord {
+            count: 0,
+            next_allowed: now,
+        });
+        record.count += 1;
+        let backoff = KILL_BACKOFF_BASE
+            .saturating_mul(1 << (record.count - 1).min(10))
+            .min(KILL_BACKOFF_MAX);
+        record.next_allowed = now + backoff;
+        self.streak = None;
+        record.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top(name: &str, cpu_percent: f32) -> Option<TopProcess> {
+        Some(TopProcess {
+            pid: 100,
+            name: name.to_string(),
+            cpu_percent,
+        })
+    }
+
+    fn config(kill: bool) -> Config {
+        Config {
+            runaway_kill: kill,
+            runaway_kill_list: vec!["zte_mainctrl".to_string()],
+            runaway_cpu_threshold: 50.0,
+            runaway_checks: 2,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_runaway_kill_after_streak() {
+        let config = config(true);
+        let mut guard = RunawayGuard::new();
+        let now = Instant::now();
+        assert_eq!(guard.update(top("zte_mainctrl", 70.0), &config, now), None);
+        assert_eq!(guard.update(top("zte_mainctrl", 70.0), &config, now), None);
+        assert!(matches!(
+            guard.update(top("zte_mainctrl", 70.0), &config, now),
+            Some(RunawayAction::Kill(_))
+        ));
+        // 同一次连续期间只触发一次
+        assert_eq!(guard.update(top("zte_mainctrl", 70.0), &config, now), None);
+
+        assert_eq!(guard.record_kill("zte_mainctrl", now), 1);
+        // 退避期内只报告
+        for _ in 0..2 {
+            guard.update(top("zte_mainctrl", 70.0), &config, now);
+        }
+        assert!(matches!(
+            guard.update(top("zte_mainctrl", 70.0), &config, now),
+            Some(RunawayAction::Report(_, 3))
+        ));
+    }
+
+    #[test]
+    fn test_runaway_never_kills_unlisted() {
+        let config = config(true);
+        let mut guard = RunawayGuard::new();
+        let now = Instant::now();
+        for _ in 0..2 {
+            guard.update(top("goahead", 90.0), &config, now);
+        }
+        assert!(matches!(
+            guard.update(top("goahead", 90.0), &config, now),
+            Some(RunawayAction::Report(..))
+        ));
+
+        // 低于阈值或换了进程会重新计数
+        guard.update(top("zte_mainctrl", 90.0), &config, now);
+        guard.update(top("zte_mainctrl", 10.0), &config, now);
+        guard.update(top("zte_mainctrl", 90.0), &config, now);
+        assert_eq!(guard.update(top("zte_mainctrl", 90.0), &config, now), None);
+    }
+}
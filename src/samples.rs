@@ -0,0 +1,136 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// 采样记录文件名（位于存储目录下），超过上限时改名为 `.1` 后重新开始
+pub const SAMPLES_FILE: &str = "zxping.samples.csv";
+
+/// 文件开头的注释，列的含义变化时版本号加 1
+const HEADER: &str = "# zxping samples v1\n\
+# timestamp: Unix seconds; cpu_percent: CPU sample rows only; rtt_ms/result: check rows only\n\
+# result: ok or the failure reason (timeout, refused, net_unreachable, ...)\n\
+timestamp,cpu_percent,rtt_ms,result\n";
+/// 攒够这么多字节或距上次写入满 FLUSH_INTERVAL 时写一次文件，减少闪存写入次数
+const FLUSH_BYTES: usize = 4096;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// sample_log：把每次 CPU 采样和连通性检查追加到 CSV 文件，供导出后离线分析趋势
+pub struct SampleLog {
+    path: PathBuf,
+    max_bytes: u64,
+    pending: String,
+    last_flush: Instant,
+}
+
+impl SampleLog {
+    pub fn new(path: PathBuf, max_bytes: u64, now: Instant) -> Self {
+        SampleLog {
+            path,
+            max_bytes,
+            pending: String::new(),
+            last_flush: now,
+        }
+    }
+
+    /// 一次 CPU 采样
+    pub fn cpu(&mut self, unix: u64, usage: f32, now: Instant) -> io::Result<()> {
+        self.push(format!("{},{:.1},,\n", unix, usage), now)
+    }
+
+    /// 一次连通性检查，失败时 rtt 为 None，result 为失败原因
+    pub fn check(
+        &mut self,
+        unix: u64,
+        rtt: Option<Duration>,
+        result: &str,
+        now: Instant,
+    ) -> io::Result<()> {
+        let rtt = rtt.map(|d| d.as_millis().to_string()).unwrap_or_default();
+        self.push(format!("{},,{},{}\n", unix, rtt, result), now)
+    }
+
+    fn push(&mut self, row: String, now: Instant) -> io::Result<()> {
+        self.pending.push_str(&row);
+        if self.pending.len() >= FLUSH_BYTES
+            || now.duration_since(self.last_flush) >= FLUSH_INTERVAL
+        {
+            self.last_flush = now;
+            return self.flush();
+        }
+        Ok(())
+    }
+
+    /// 写入缓存的记录。文件超过 max_bytes 时先改名为 `.1`（覆盖更早的一份），新文件重新写表头
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        // 写失败时丢弃这批记录，避免存储有问题时内存无限增长
+        let pending = std::mem::take(&mut self.pending);
+        let size = fs::metadata(&self.path).map(|meta| meta.len()).unwrap_or(0);
+        if size > 0 && size + pending.len() as u64 > self.max_bytes {
+            fs::rename(&self.path, self.rotated_path())?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(HEADER.as_bytes())?;
+        }
+        file.write_all(pending.as_bytes())
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+
+    /// 存储目录切换后写到新路径
+    pub fn relocate(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_log() {
+        let dir = std::env::temp_dir().join(format!("zxping-samples-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SAMPLES_FILE);
+        let start = Instant::now();
+        let mut log = SampleLog::new(path.clone(), 64 * 1024, start);
+
+        log.cpu(1700000000, 12.34, start).unwrap();
+        log.check(1700000005, Some(Duration::from_millis(42)), "ok", start)
+            .unwrap();
+        // 间隔未到，还没有写文件
+        assert!(!path.exists());
+        log.check(1700000010, None, "timeout", start + FLUSH_INTERVAL)
+            .unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# zxping samples v1\n"));
+        assert!(content.ends_with(
+            "timestamp,cpu_percent,rtt_ms,result\n\
+             1700000000,12.3,,\n\
+             1700000005,,42,ok\n\
+             1700000010,,,timeout\n"
+        ));
+
+        // 超过上限：旧文件改名为 .1，新文件重新写表头
+        log.max_bytes = content.len() as u64 + 10;
+        log.cpu(1700000015, 50.0, start).unwrap();
+        log.flush().unwrap();
+        let rotated = dir.join(format!("{}.1", SAMPLES_FILE));
+        assert_eq!(fs::read_to_string(&rotated).unwrap(), content);
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# zxping samples v1\n"));
+        assert!(content.ends_with("result\n1700000015,50.0,,\n"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,227 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::routes;
+
+/// 同时查询的线程数
+const SCAN_WORKERS: usize = 8;
+/// 待查询地址队列的容量
+const SCAN_QUEUE: usize = 32;
+const SCAN_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const SCAN_READ_TIMEOUT: Duration = Duration::from_secs(1);
+/// 一次最多扫描的地址数（/20）
+const MAX_SCAN_HOSTS: usize = 4096;
+
+/// 解析 `192.168.0.0/24` 或单个地址；/31 以下不含网络地址和广播地址
+pub fn parse_cidr(value: &str) -> Result<Vec<IpAddr>, String> {
+    let (addr, prefix) = if value.contains('/') {
+        routes::parse_network(value).ok_or_else(|| format!("invalid network '{}'", value))?
+    } else {
+        let addr: Ipv4Addr = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid address '{}'", value))?;
+        (addr, 32)
+    };
+    let size = 1u64 << (32 - prefix);
+    if size as usize > MAX_SCAN_HOSTS {
+        return Err(format!(
+            "'{}' has {} addresses, at most {} allowed",
+            value, size, MAX_SCAN_HOSTS
+        ));
+    }
+    let network = u32::from(addr) & !((size - 1) as u32);
+    let hosts = if size > 2 { 1..size - 1 } else { 0..size };
+    Ok(hosts
+        .map(|offset| IpAddr::V4(Ipv4Addr::from(network + offset as u32)))
+        .collect())
+}
+
+/// 每行一个地址或网段的文件，`#` 开头的行和空行忽略
+pub fn parse_host_list(content: &str) -> Result<Vec<IpAddr>, String> {
+    let mut hosts = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        hosts.extend(parse_cidr(line)?);
+    }
+    Ok(hosts)
+}
+
+/// PING2 回复中的字段（`OK up=.. fail=.. lat=.. ... ver=..`）整理为一行：
+/// `<地址> ver=<版本> up=<秒> fail=<连续失败> lat=<延迟>`，缺少的字段为 `-`
+pub fn format_reply(addr: IpAddr, reply: &str) -> Option<String> {
+    let mut words = reply.split_whitespace();
+    if words.next() != Some("OK") {
+        return None;
+    }
+    let fields: Vec<(&str, &str)> = words.filter_map(|word| word.split_once('=')).collect();
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+            .unwrap_or("-")
+    };
+    Some(format!(
+        "{} ver={} up={} fail={} lat={}",
+        addr,
+        field("ver"),
+        field("up"),
+        field("fail"),
+        field("lat")
+    ))
+}
+
+/// 扫描的是 LAN 上的设备，不用探测标记和 bind_interface（它们针对 WAN 方向的检查）
+fn query(addr: IpAddr, port: u16) -> Result<String, String> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::new(addr, port), SCAN_CONNECT_TIMEOUT)
+        .map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(SCAN_READ_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    stream.write_all(b"PING2").map_err(|e| e.to_string())?;
+    let mut buf = [0u8; 256];
+    let size = stream.read(&mut buf).map_err(|e| e.to_string())?;
+    let reply = String::from_utf8_lossy(&buf[..size]).to_string();
+    format_reply(addr, &reply).ok_or_else(|| format!("unexpected reply '{}'", reply.trim()))
+}
+
+/// 并发向每个地址的控制端口发送 PING2，每台回复的设备输出一行；
+/// 返回 (回复的设备数, 失败的地址数)
+pub fn scan(hosts: Vec<IpAddr>, port: u16, verbose: bool) -> (usize, usize) {
+    let (job_tx, job_rx) = mpsc::sync_channel::<IpAddr>(SCAN_QUEUE);
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let (result_tx, result_rx) = mpsc::channel();
+    for _ in 0..SCAN_WORKERS {
+        let job_rx = Arc::clone(&job_rx);
+        let result_tx = result_tx.clone();
+        thread::spawn(move || loop {
+            let next = job_rx.lock().unwrap().recv();
+            let Ok(addr) = next else {
+                break;
+            };
+            let _ = result_tx.send((addr, query(addr, port)));
+        });
+    }
+    drop(result_tx);
+    // 队列满时阻塞在这里，不会一次把所有地址放进内存
+    thread::spawn(move || {
+        for addr in hosts {
+            if job_tx.send(addr).is_err() {
+                break;
+            }
+        }
+    });
+
+    let (mut responded, mut failed) = (0, 0);
+    for (addr, result) in result_rx {
+        match result {
+            Ok(line) => {
+                responded += 1;
+                println!("{}", line);
+            }
+            Err(e) => {
+                failed += 1;
+                if verbose {
+                    eprintln!("{} unreachable: {}", addr, e);
+                }
+            }
+        }
+    }
+    (responded, failed)
+}
+
+/// `ctl-scan <网段|地址|文件>... [-v]`：返回进程退出码（至少一台设备回复时为 0）
+pub fn run(args: &[String], port: u16) -> i32 {
+    let verbose = args.iter().any(|arg| arg == "-v");
+    let targets: Vec<&String> = args.iter().filter(|arg| *arg != "-v").collect();
+    if targets.is_empty() {
+        eprintln!("Usage: zxic_ping ctl-scan <CIDR|IP|FILE>... [-v]");
+        return 2;
+    }
+    let mut hosts = Vec::new();
+    for target in targets {
+        let parsed = match fs::read_to_string(target) {
+            Ok(content) => parse_host_list(&content),
+            Err(_) => parse_cidr(target),
+        };
+        match parsed {
+            Ok(parsed) => hosts.extend(parsed),
+            Err(e) => {
+                eprintln!("ctl-scan: {}", e);
+                return 2;
+            }
+        }
+    }
+    let (responded, failed) = scan(hosts, port, verbose);
+    eprintln!("{} responded, {} unreachable", responded, failed);
+    if responded > 0 {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_cidr() {
+        let hosts = parse_cidr("192.168.0.77/30").unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0], "192.168.0.77".parse::<IpAddr>().unwrap());
+        assert_eq!(parse_cidr("10.0.0.0/24").unwrap().len(), 254);
+        assert_eq!(parse_cidr("10.0.0.5").unwrap().len(), 1);
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("10.0.0.0/8").is_err());
+        assert!(parse_cidr("host.lan").is_err());
+
+        let list = parse_host_list("# lab\n10.0.0.5\n\n10.0.1.0/31\n").unwrap();
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn test_format_reply() {
+        let addr: IpAddr = "10.0.0.5".parse().unwrap();
+        assert_eq!(
+            format_reply(
+                addr,
+                "OK up=3600 fail=0 lat=42ms cpu=12% load=normal tgt=1.2.3.4:80 ver=0.1.0"
+            )
+            .unwrap(),
+            "10.0.0.5 ver=0.1.0 up=3600 fail=0 lat=42ms"
+        );
+        // 旧版本没有 ver 字段
+        assert_eq!(
+            format_reply(addr, "OK up=5 fail=2 lat=- cpu=- load=high tgt=x").unwrap(),
+            "10.0.0.5 ver=- up=5 fail=2 lat=-"
+        );
+        assert_eq!(format_reply(addr, "ERR:UNKNOWN_CMD"), None);
+    }
+
+    #[test]
+    fn test_scan() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut stream = stream;
+                let mut buf = [0u8; 16];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(b"OK up=1 fail=0 lat=3ms ver=9.9");
+            }
+        });
+        let hosts = vec!["127.0.0.1".parse().unwrap(), "127.0.0.2".parse().unwrap()];
+        // 127.0.0.2 上没有监听（连接被拒绝）
+        assert_eq!(scan(hosts, port, false), (1, 1));
+    }
+}
@@ -0,0 +1,211 @@
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// 每个坏信号的基础扣分（乘以 health_score_scale_percent / 100）
+const FAILURE_PENALTY: f64 = 10.0;
+const HIGH_LATENCY_PENALTY: f64 = 3.0;
+const HIGH_LOAD_PENALTY: f64 = 2.0;
+/// 分数降到阈值的这个比例以下才退出该级别，避免在阈值附近来回切换
+const EXIT_RATIO: f64 = 0.5;
+
+/// 健康分数所处的级别（越过的最高阈值）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScoreLevel {
+    Ok,
+    Notify,
+    Throttle,
+    Reboot,
+}
+
+impl ScoreLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreLevel::Ok => "ok",
+            ScoreLevel::Notify => "notify",
+            ScoreLevel::Throttle => "throttle",
+            ScoreLevel::Reboot => "reboot",
+        }
+    }
+
+    /// 该级别的阈值，0 为关闭
+    fn threshold(&self, config: &Config) -> u32 {
+        match self {
+            ScoreLevel::Ok => 0,
+            ScoreLevel::Notify => config.health_score_notify,
+            ScoreLevel::Throttle => config.health_score_throttle,
+            ScoreLevel::Reboot => config.health_score_reboot,
+        }
+    }
+}
+
+/// 一轮检查中的坏信号
+pub struct Signals {
+    pub failed: bool,
+    pub high_latency: bool,
+    pub high_load: bool,
+}
+
+/// 综合健康分数：失败、高延迟、高负载按权重累加，随时间按半衰期指数衰减。
+/// 分数越高越不健康，越过阈值时升级（通知、限流、重启）
+pub struct HealthScore {
+    value: f64,
+    last_update: Option<Instant>,
+    pub level: ScoreLevel,
+}
+
+impl HealthScore {
+    pub fn new() -> Self {
+        HealthScore {
+            value: 0.0,
+            last_update: None,
+            level: ScoreLevel::Ok,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// 恢复上次进程保存的分数；级别在下一次 update 时重新计算
+    pub fn restore(&mut self, value: f64) {
+        self.value = value;
+    }
+
+    /// 衰减到 now 后加上本轮的扣分；级别变化时返回之前的级别
+    pub fn update(
+        &mut self,
+        signals: &Signals,
+        now: Instant,
+        config: &Config,
+    ) -> Option<ScoreLevel> {
+        if let Some(last) = self.last_update {
+            self.value *= decay(
+                now.saturating_duration_since(last),
+                config.health_score_half_life,
+            );
+        }
+        self.last_update = Some(now);
+        let scale = config.health_score_scale_percent as f64 / 100.0;
+        let penalty = [
+            (signals.failed, FAILURE_PENALTY),
+            (signals.high_latency, HIGH_LATENCY_PENALTY),
+            (signals.high_load, HIGH_LOAD_PENALTY),
+        ]
+        .iter()
+        .filter(|(bad, _)| *bad)
+        .map(|(_, penalty)| penalty)
+        .sum::<f64>();
+        self.value += penalty * scale;
+
+        let level = self.level_for(config);
+        if level == self.level {
+            return None;
+        }
+        Some(std::mem::replace(&mut self.level, level))
+    }
+
+    /// 升级：越过的最高阈值；降级：只退到分数仍不低于其阈值 EXIT_RATIO 倍的最高级别
+    fn level_for(&self, config: &Config) -> ScoreLevel {
+        let highest = |ratio: f64| {
+            [ScoreLevel::Reboot, ScoreLevel::Throttle, ScoreLevel::Notify]
+                .into_iter()
+                .filter(|level| *level <= self.level || ratio == 1.0)
+                .find(|level| {
+                    let threshold = level.threshold(config);
+                    threshold > 0 && self.value >= threshold as f64 * ratio
+                })
+                .unwrap_or(ScoreLevel::Ok)
+        };
+        let crossed = highest(1.0);
+        if crossed >= self.level {
+            crossed
+        } else {
+            highest(EXIT_RATIO)
+        }
+    }
+
+    /// STATUS 中的一行：`health_score=12.5 (ok)`
+    pub fn status_line(&self) -> String {
+        format!("health_score={:.1} ({})", self.value, self.level.as_str())
+    }
+}
+
+/// 经过 elapsed 后剩余的比例
+fn decay(elapsed: Duration, half_life: Duration) -> f64 {
+    0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signals(failed: bool, high_latency: bool, high_load: bool) -> Signals {
+        Signals {
+            failed,
+            high_latency,
+            high_load,
+        }
+    }
+
+    #[test]
+    fn test_decay() {
+        let config = Config::default();
+        let mut score = HealthScore::new();
+        let start = Instant::now();
+        score.update(&signals(true, false, true), start, &config);
+        assert_eq!(score.value(), 12.0);
+        // 一个半衰期后剩一半
+        score.update(
+            &signals(false, false, false),
+            start + config.health_score_half_life,
+            &config,
+        );
+        assert!((score.value() - 6.0).abs() < 1e-9);
+        assert_eq!(score.status_line(), "health_score=6.0 (ok)");
+    }
+
+    #[test]
+    fn test_levels() {
+        let config = Config {
+            health_score_notify: 20,
+            health_score_throttle: 40,
+            health_score_reboot: 0,
+            ..Config::default()
+        };
+        let mut score = HealthScore::new();
+        let now = Instant::now();
+        let fail = signals(true, false, false);
+        assert_eq!(score.update(&fail, now, &config), None);
+        assert_eq!(score.update(&fail, now, &config), Some(ScoreLevel::Ok));
+        assert_eq!(score.level, ScoreLevel::Notify);
+        score.update(&fail, now, &config);
+        assert_eq!(score.update(&fail, now, &config), Some(ScoreLevel::Notify));
+        assert_eq!(score.level, ScoreLevel::Throttle);
+        // 重启阈值关闭：分数再高也停在限流
+        for _ in 0..10 {
+            score.update(&fail, now, &config);
+        }
+        assert_eq!(score.level, ScoreLevel::Throttle);
+
+        // 降到 40 以下但还在 20 以上时保持限流，降到 20 以下退到通知，降到 10 以下才恢复
+        let idle = signals(false, false, false);
+        score.update(&idle, now + config.health_score_half_life * 2, &config);
+        assert!((score.value() - 35.0).abs() < 1e-9);
+        assert_eq!(score.level, ScoreLevel::Throttle);
+        let changed = score.update(&idle, now + config.health_score_half_life * 3, &config);
+        assert_eq!(changed, Some(ScoreLevel::Throttle));
+        assert_eq!(score.level, ScoreLevel::Notify);
+        score.update(&idle, now + config.health_score_half_life * 5, &config);
+        assert_eq!(score.level, ScoreLevel::Ok);
+
+        // 倍率放大后更快越过阈值
+        let aggressive = Config {
+            health_score_scale_percent: 200,
+            ..config
+        };
+        let mut score = HealthScore::new();
+        score.update(&fail, now, &aggressive);
+        assert_eq!(score.level, ScoreLevel::Notify);
+    }
+}
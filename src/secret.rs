@@ -0,0 +1,51 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// 从单独的文件读取共享密钥（去掉末尾换行），返回密钥和文件是否其他用户可读
+pub fn load_key_file(path: &Path) -> Result<(Vec<u8>, bool), String> {
+    let metadata =
+        fs::metadata(path).map_err(|e| format!("cannot stat {}: {}", path.display(), e))?;
+    let content = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let key = trim_key(&content);
+    if key.is_empty() {
+        return Err(format!("{} is empty", path.display()));
+    }
+    Ok((
+        key.to_vec(),
+        is_world_readable(metadata.permissions().mode()),
+    ))
+}
+
+fn trim_key(content: &[u8]) -> &[u8] {
+    let end = content
+        .iter()
+        .rposition(|b| !matches!(b, b'\n' | b'\r'))
+        .map_or(0, |i| i + 1);
+    &content[..end]
+}
+
+fn is_world_readable(mode: u32) -> bool {
+    mode & 0o004 != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_key() {
+        assert_eq!(trim_key(b"s3cret\n"), b"s3cret");
+        assert_eq!(trim_key(b"s3cret\r\n\n"), b"s3cret");
+        // 密钥中间和开头的空白保留
+        assert_eq!(trim_key(b" a b\n"), b" a b");
+        assert_eq!(trim_key(b"\n"), b"");
+    }
+
+    #[test]
+    fn test_world_readable() {
+        assert!(!is_world_readable(0o100600));
+        assert!(!is_world_readable(0o100640));
+        assert!(is_world_readable(0o100644));
+    }
+}
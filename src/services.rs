@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+/// 内置的 adbd 服务（配置中没有同名服务时使用）
+const ADBD_EXEC: &str = "/etc_rw/adbd";
+const ADBD_NICE: i32 = 15;
+/// 未指定时结束服务用的信号和等待退出的时间
+const DEFAULT_SIGNAL: i32 = 9;
+const DEFAULT_GRACE: Duration = Duration::from_secs(3);
+
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", 1),
+    ("INT", 2),
+    ("QUIT", 3),
+    ("KILL", 9),
+    ("USR1", 10),
+    ("USR2", 12),
+    ("TERM", 15),
+];
+
+/// 信号名（`TERM`、`SIGTERM`）或编号
+fn parse_signal(value: &str) -> Option<i32> {
+    let name = value.strip_prefix("SIG").unwrap_or(value);
+    SIGNALS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, signal)| *signal)
+        .or_else(|| value.parse().ok().filter(|n| (1..=64).contains(n)))
+}
+
+fn signal_name(signal: i32) -> String {
+    SIGNALS
+        .iter()
+        .find(|(_, s)| *s == signal)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| signal.to_string())
+}
+
+/// 由 zxic-ping 管理（重启、结束）的服务
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    pub name: String,
+    /// 启动服务的程序（绝对路径），进程按它的文件名查找
+    pub exec: String,
+    /// 结束服务时发送的信号
+    pub signal: i32,
+    /// 重启时等待旧进程退出的最长时间
+    pub grace: Duration,
+    /// 重启后设置的 nice 值
+    pub nice: Option<i32>,
+}
+
+impl Service {
+    /// 解析 `名字:/程序路径[:信号[:等待毫秒[:nice]]]`，如 `dropbear:/usr/sbin/dropbear:TERM:2000`
+    pub fn parse(spec: &str) -> Result<Service, String> {
+        let invalid = |what: &str| format!("invalid service '{}': {}", spec, what);
+        let mut fields = spec.split(':').map(str::trim);
+        let name = fields.next().unwrap_or_default();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(invalid("name must be letters, digits, '_' or '-'"));
+        }
+        let exec = fields.next().unwrap_or_default();
+        if !exec.starts_with('/') {
+            return Err(invalid("program must be an absolute path"));
+        }
+        let signal = match fields.next().filter(|s| !s.is_empty()) {
+            Some(value) => parse_signal(value).ok_or_else(|| invalid("unknown signal"))?,
+            None => DEFAULT_SIGNAL,
+        };
+        let grace = match fields.next().filter(|s| !s.is_empty()) {
+            Some(value) => Duration::from_millis(
+                value
+                    .parse()
+                    .map_err(|_| invalid("grace must be milliseconds"))?,
+            ),
+            None => DEFAULT_GRACE,
+        };
+        let nice = match fields.next().filter(|s| !s.is_empty()) {
+            Some(value) => Some(
+                value
+                    .parse()
+                    .ok()
+                    .filter(|n| (-20..=19).contains(n))
+                    .ok_or_else(|| invalid("nice must be -20..19"))?,
+            ),
+            None => None,
+        };
+        if fields.next().is_some() {
+            return Err(invalid("too many fields"));
+        }
+        Ok(Service {
+            name: name.to_string(),
+            exec: exec.to_string(),
+            signal,
+            grace,
+            nice,
+        })
+    }
+
+    /// 配置中的写法（CONFIG 输出）
+    pub fn spec(&self) -> String {
+        let mut spec = format!(
+            "{}:{}:{}:{}",
+            self.name,
+            self.exec,
+            signal_name(self.signal),
+            self.grace.as_millis()
+        );
+        if let Some(nice) = self.nice {
+            spec.push_str(&format!(":{}", nice));
+        }
+        spec
+    }
+
+    /// 查找进程用的名字：程序的文件名
+    pub fn process_name(&self) -> &str {
+        self.exec.rsplit('/').next().unwrap_or(&self.exec)
+    }
+
+    /// 通知名前缀：`adbd` → `ADBD`（ADBD_FORCE_RESTARTED）
+    pub fn event_prefix(&self) -> String {
+        self.name.to_ascii_uppercase().replace('-', "_")
+    }
+}
+
+/// 配置的服务加上内置的 adbd（配置中有同名服务时以配置为准）
+pub fn effective(configured: &[Service], adbd_settle: Duration) -> Vec<Service> {
+    let mut services = configured.to_vec();
+    if !services.iter().any(|service| service.name == "adbd") {
+        services.insert(
+            0,
+            Service {
+                name: "adbd".to_string(),
+                exec: ADBD_EXEC.to_string(),
+                signal: DEFAULT_SIGNAL,
+                grace: adbd_settle,
+                nice: Some(ADBD_NICE),
+            },
+        );
+    }
+    services
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verb {
+    Restart,
+    Kill,
+}
+
+/// 解析服务命令 `RESTART:<名字>`、`KILL:<名字>`；RESTART_ADBD、KILL_ADBD 是 adbd 的别名
+pub fn parse_command(received: &[u8]) -> Option<(Verb, &str)> {
+    let command = std::str::from_utf8(received).ok()?.trim();
+    match command {
+        "RESTART_ADBD" => return Some((Verb::Restart, "adbd")),
+        "KILL_ADBD" => return Some((Verb::Kill, "adbd")),
+        _ => {}
+    }
+    let (verb, name) = command.split_once(':')?;
+    let verb = match verb {
+        "RESTART" => Verb::Restart,
+        "KILL" => Verb::Kill,
+        _ => return None,
+    };
+    Some((verb, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_service() {
+        let service = Service::parse("dropbear:/usr/sbin/dropbear:TERM:2000").unwrap();
+        assert_eq!(service.name, "dropbear");
+        assert_eq!(service.signal, 15);
+        assert_eq!(service.grace, Duration::from_secs(2));
+        assert_eq!(service.nice, None);
+        assert_eq!(service.process_name(), "dropbear");
+        assert_eq!(service.spec(), "dropbear:/usr/sbin/dropbear:TERM:2000");
+
+        let service = Service::parse("my-agent:/etc_rw/agent").unwrap();
+        assert_eq!(service.signal, 9);
+        assert_eq!(service.grace, DEFAULT_GRACE);
+        assert_eq!(service.event_prefix(), "MY_AGENT");
+        assert_eq!(
+            Service::parse("x:/bin/x:SIGHUP::5").unwrap().spec(),
+            "x:/bin/x:HUP:3000:5"
+        );
+        assert_eq!(Service::parse("x:/bin/x:31").unwrap().signal, 31);
+
+        assert!(Service::parse("x:bin/x").is_err());
+        assert!(Service::parse("a b:/bin/x").is_err());
+        assert!(Service::parse("x:/bin/x:NOPE").is_err());
+        assert!(Service::parse("x:/bin/x:KILL:soon").is_err());
+        assert!(Service::parse("x:/bin/x:KILL:1000:40").is_err());
+        assert!(Service::parse("x:/bin/x:KILL:1000:0:extra").is_err());
+    }
+
+    #[test]
+    fn test_effective() {
+        let settle = Duration::from_millis(1500);
+        let services = effective(&[], settle);
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].exec, ADBD_EXEC);
+        assert_eq!(services[0].grace, settle);
+        assert_eq!(services[0].nice, Some(ADBD_NICE));
+
+        let configured = vec![
+            Service::parse("dropbear:/usr/sbin/dropbear").unwrap(),
+            Service::parse("adbd:/usr/bin/adbd:TERM").unwrap(),
+        ];
+        let services = effective(&configured, settle);
+        assert_eq!(services, configured);
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command(b"RESTART_ADBD"),
+            Some((Verb::Restart, "adbd"))
+        );
+        assert_eq!(parse_command(b"KILL_ADBD"), Some((Verb::Kill, "adbd")));
+        assert_eq!(
+            parse_command(b"RESTART:dropbear\n"),
+            Some((Verb::Restart, "dropbear"))
+        );
+        assert_eq!(parse_command(b"KILL:x"), Some((Verb::Kill, "x")));
+        assert_eq!(parse_command(b"PROFILE:lowmem"), None);
+        assert_eq!(parse_command(b"RESTART_GOAHEAD"), None);
+    }
+}
@@ -0,0 +1,98 @@
+/// 链路质量的分级，每级对应一组网络参数；Severe 时还会执行原来的整体限流
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Normal,
+    Mild,
+    Moderate,
+    Severe,
+}
+
+/// 各级别使用的参数：(路径, [Normal, Mild, Moderate, Severe])
+/// Normal 的值与 optimize_network_parameters 中的设置一致
+const SEVERITY_PARAMS: &[(&str, [&str; 4])] = &[
+    ("/proc/sys/net/ipv4/tcp_retries2", ["5", "4", "3", "3"]),
+    (
+        "/proc/sys/net/ipv4/tcp_max_syn_backlog",
+        ["128", "96", "64", "32"],
+    ),
+    (
+        "/proc/sys/net/core/netdev_max_backlog",
+        ["1000", "750", "500", "300"],
+    ),
+];
+
+/// 丢包率达到这些值时至少为 Mild / Moderate
+const LOSS_MILD: u32 = 20;
+const LOSS_MODERATE: u32 = 50;
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Normal => "NORMAL",
+            Severity::Mild => "MILD",
+            Severity::Moderate => "MODERATE",
+            Severity::Severe => "SEVERE",
+        }
+    }
+
+    /// 根据连续高延迟次数和丢包率计算级别；达到 max_high_latency（已整体限流）为 Severe
+    pub fn assess(high_latency_count: u32, max_high_latency: u32, loss_percent: u32) -> Severity {
+        let by_latency = if high_latency_count >= max_high_latency {
+            Severity::Severe
+        } else if high_latency_count == 0 {
+            Severity::Normal
+        } else if high_latency_count * 2 < max_high_latency {
+            Severity::Mild
+        } else {
+            Severity::Moderate
+        };
+        let by_loss = if loss_percent >= LOSS_MODERATE {
+            Severity::Moderate
+        } else if loss_percent >= LOSS_MILD {
+            Severity::Mild
+        } else {
+            Severity::Normal
+        };
+        by_latency.max(by_loss)
+    }
+
+    /// 各级别会写入的所有路径
+    pub fn managed_paths() -> impl Iterator<Item = &'static str> {
+        SEVERITY_PARAMS.iter().map(|(path, _)| *path)
+    }
+
+    /// 该级别需要写入的参数
+    pub fn params(&self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        let index = *self as usize;
+        SEVERITY_PARAMS
+            .iter()
+            .map(move |(path, values)| (*path, values[index]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess() {
+        assert_eq!(Severity::assess(0, 3, 0), Severity::Normal);
+        assert_eq!(Severity::assess(1, 3, 0), Severity::Mild);
+        assert_eq!(Severity::assess(2, 3, 0), Severity::Moderate);
+        assert_eq!(Severity::assess(3, 3, 0), Severity::Severe);
+        // 丢包单独也能升级，但不会到 Severe
+        assert_eq!(Severity::assess(0, 3, 20), Severity::Mild);
+        assert_eq!(Severity::assess(1, 3, 60), Severity::Moderate);
+        assert_eq!(Severity::assess(0, 3, 100), Severity::Moderate);
+        // max_high_latency 较大时有更多 Mild 级别
+        assert_eq!(Severity::assess(2, 6, 0), Severity::Mild);
+        assert_eq!(Severity::assess(3, 6, 0), Severity::Moderate);
+    }
+
+    #[test]
+    fn test_params() {
+        let mild: Vec<_> = Severity::Mild.params().collect();
+        assert_eq!(mild[0], ("/proc/sys/net/ipv4/tcp_retries2", "4"));
+        assert_eq!(mild.len(), SEVERITY_PARAMS.len());
+    }
+}
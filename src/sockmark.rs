@@ -0,0 +1,231 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::config::Config;
+
+/// 探测 socket 的 DSCP 值和 SO_PRIORITY，-1 为不设置（启动时由配置写入，各探测方式共用）
+static PROBE_DSCP: AtomicI32 = AtomicI32::new(-1);
+static PROBE_PRIORITY: AtomicI32 = AtomicI32::new(-1);
+/// bind_interface：探测 socket 绑定的网卡，空为按路由表选择出口
+static PROBE_INTERFACE: Mutex<String> = Mutex::new(String::new());
+
+/// 探测（和可选的通知）socket 的 QoS 标记
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Marking {
+    /// DSCP（0-63），写入 IP_TOS / IPV6_TCLASS 的高 6 位
+    pub dscp: Option<u8>,
+    /// SO_PRIORITY，决定本机出口队列
+    pub priority: Option<u8>,
+}
+
+/// 各项标记的设置结果，None 为未配置
+#[derive(Debug)]
+pub struct MarkResult {
+    pub dscp: Option<io::Result<()>>,
+    pub priority: Option<io::Result<()>>,
+}
+
+impl MarkResult {
+    /// 启动日志中的说明：`dscp=46 ok, priority=6 failed (Operation not permitted)`
+    pub fn describe(&self, marking: &Marking) -> String {
+        [
+            describe_option("dscp", marking.dscp, &self.dscp),
+            describe_option("priority", marking.priority, &self.priority),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl Marking {
+    /// probe_dscp 和 probe_priority
+    pub fn from_config(config: &Config) -> Self {
+        Marking {
+            dscp: config.probe_dscp,
+            priority: config.probe_priority,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dscp.is_none() && self.priority.is_none()
+    }
+
+    /// 设置到 socket 上；内核或权限不允许时返回错误，调用方一般直接忽略
+    pub fn apply(&self, fd: RawFd, ipv6: bool) -> MarkResult {
+        MarkResult {
+            dscp: self.dscp.map(|dscp| {
+                let tos = (dscp as libc::c_int) << 2;
+                if ipv6 {
+                    set_int_option(fd, libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos)
+                } else {
+                    set_int_option(fd, libc::IPPROTO_IP, libc::IP_TOS, tos)
+                }
+            }),
+            priority: self.priority.map(|priority| {
+                set_int_option(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_PRIORITY,
+                    priority as libc::c_int,
+                )
+            }),
+        }
+    }
+}
+
+/// 启动时写入配置的探测标记
+pub fn set_probe_marking(marking: Marking) {
+    let store = |slot: &AtomicI32, value: Option<u8>| {
+        slot.store(value.map_or(-1, i32::from), Ordering::Relaxed)
+    };
+    store(&PROBE_DSCP, marking.dscp);
+    store(&PROBE_PRIORITY, marking.priority);
+}
+
+pub fn probe_marking() -> Marking {
+    let load = |slot: &AtomicI32| u8::try_from(slot.load(Ordering::Relaxed)).ok();
+    Marking {
+        dscp: load(&PROBE_DSCP),
+        priority: load(&PROBE_PRIORITY),
+    }
+}
+
+/// 启动时写入配置的 bind_interface
+pub fn set_probe_interface(name: &str) {
+    if let Ok(mut interface) = PROBE_INTERFACE.lock() {
+        *interface = name.to_string();
+    }
+}
+
+fn probe_interface() -> String {
+    PROBE_INTERFACE
+        .lock()
+        .map(|interface| interface.clone())
+        .unwrap_or_default()
+}
+
+/// SO_BINDTODEVICE：只经由该网卡收发，不管默认路由是哪个（需要 CAP_NET_RAW，否则为 EPERM）
+pub fn bind_interface(fd: RawFd, name: &str) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            name.as_ptr() as *const libc::c_void,
+            name.len() as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 探测 socket 连接前的设置：加上配置的标记，绑定到 bind_interface。
+/// 失败时静默按默认方式探测（启动时已记录过是否可用）
+pub fn prepare_probe(fd: RawFd, ipv6: bool) {
+    let marking = probe_marking();
+    if !marking.is_empty() {
+        let _ = marking.apply(fd, ipv6);
+    }
+    let interface = probe_interface();
+    if !interface.is_empty() {
+        let _ = bind_interface(fd, &interface);
+    }
+}
+
+/// 带探测标记（和绑定网卡）的 TCP 连接，代替 TcpStream::connect_timeout
+pub fn connect_tcp(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    prepare_probe(socket.as_raw_fd(), addr.is_ipv6());
+    socket.connect_timeout(&addr.into(), timeout)?;
+    Ok(socket.into())
+}
+
+/// 在一个临时 socket 上试一次，供启动日志说明标记是否生效
+pub fn probe_marking_check(ipv6: bool) -> io::Result<MarkResult> {
+    let domain = if ipv6 { Domain::IPV6 } else { Domain::IPV4 };
+    let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+    Ok(probe_marking().apply(socket.as_raw_fd(), ipv6))
+}
+
+/// 在一个临时 socket 上试一次绑定 bind_interface，供启动日志说明是否生效
+pub fn probe_interface_check(ipv6: bool) -> io::Result<()> {
+    let domain = if ipv6 { Domain::IPV6 } else { Domain::IPV4 };
+    let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+    bind_interface(socket.as_raw_fd(), &probe_interface())
+}
+
+fn describe_option(
+    name: &str,
+    value: Option<u8>,
+    result: &Option<io::Result<()>>,
+) -> Option<String> {
+    match (value, result) {
+        (Some(value), Some(Ok(()))) => Some(format!("{}={} ok", name, value)),
+        (Some(value), Some(Err(e))) => Some(format!("{}={} failed ({})", name, value, e)),
+        _ => None,
+    }
+}
+
+fn set_int_option(
+    fd: RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    #[test]
+    fn test_apply_marking() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let marking = Marking {
+            dscp: Some(46),
+            priority: Some(6),
+        };
+        let result = marking.apply(socket.as_raw_fd(), false);
+        assert!(matches!(result.dscp, Some(Ok(()))));
+        assert!(matches!(result.priority, Some(Ok(()))));
+        assert_eq!(result.describe(&marking), "dscp=46 ok, priority=6 ok");
+        let socket = socket2::SockRef::from(&socket);
+        assert_eq!(socket.tos().unwrap(), 46 << 2);
+
+        let result = Marking::default().apply(socket.as_raw_fd(), false);
+        assert!(result.dscp.is_none() && result.priority.is_none());
+
+        // 错误的 fd：设置失败但不影响调用方
+        let result = marking.apply(-1, false);
+        assert!(result.describe(&marking).starts_with("dscp=46 failed ("));
+
+        // 不存在的网卡（没有权限时为 EPERM）
+        assert!(bind_interface(socket.as_raw_fd(), "zxping-none0").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs;
+
+/// /proc/net/sockstat 的快照
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SockStat {
+    pub tcp_inuse: u64,
+    pub tcp_orphan: u64,
+    pub tcp_tw: u64,
+    pub udp_inuse: u64,
+}
+
+impl SockStat {
+    pub fn read() -> Option<SockStat> {
+        parse_sockstat(&fs::read_to_string("/proc/net/sockstat").ok()?)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "tcp_inuse={} tcp_orphan={} tcp_tw={} udp_inuse={}",
+            self.tcp_inuse, self.tcp_orphan, self.tcp_tw, self.udp_inuse
+        )
+    }
+}
+
+/// socket 压力状态变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureEvent {
+    /// 超过阈值，counter 为超出比例最大的计数
+    Enter {
+        counter: &'static str,
+        value: u64,
+        limit: u64,
+    },
+    Exit,
+}
+
+/// 跟踪 TCP socket 使用量，超过阈值时（边沿触发）产生事件
+pub struct SockStatMonitor {
+    pub latest: Option<SockStat>,
+    in_pressure: bool,
+}
+
+impl SockStatMonitor {
+    pub fn new() -> Self {
+        SockStatMonitor {
+            latest: None,
+            in_pressure: false,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        stat: SockStat,
+        tcp_inuse_max: u64,
+        tcp_orphan_max: u64,
+    ) -> Option<PressureEvent> {
+        self.latest = Some(stat);
+        let worst = [
+            ("tcp_inuse", stat.tcp_inuse, tcp_inuse_max),
+            ("tcp_orphan", stat.tcp_orphan, tcp_orphan_max),
+        ]
+        .into_iter()
+        .filter(|(_, value, limit)| *limit > 0 && value > limit)
+        .max_by(|a, b| (a.1 * b.2).cmp(&(b.1 * a.2)));
+
+        match (worst, self.in_pressure) {
+            (Some((counter, value, limit)), false) => {
+                self.in_pressure = true;
+                Some(PressureEvent::Enter {
+                    counter,
+                    value,
+                    limit,
+                })
+            }
+            (None, true) => {
+                self.in_pressure = false;
+                Some(PressureEvent::Exit)
+            }
+            _ => None,
+        }
+    }
+
+    /// 是否处于 socket 压力中
+    pub fn in_pressure(&self) -> bool {
+        self.in_pressure
+    }
+
+    /// STATUS 中的最新快照
+    pub fn status_line(&self) -> String {
+        match &self.latest {
+            Some(stat) => format!("sockstat: {}", stat.summary()),
+            None => "sockstat: -".to_string(),
+        }
+    }
+}
+
+/// 解析 "TCP: inuse 5 orphan 0 tw 2 alloc 7 mem 1" 和 "UDP: inuse 3 mem 2"
+fn parse_sockstat(content: &str) -> Option<SockStat> {
+    let mut stat = SockStat::default();
+    let mut found = false;
+    for line in content.lines() {
+        let Some((proto, fields)) = line.split_once(':') else {
+            continue;
+        };
+        let values: Vec<&str> = fields.split_whitespace().collect();
+        for pair in values.chunks(2) {
+            let [name, value] = pair else {
+                continue;
+            };
+            let Ok(value) = value.parse() else {
+                continue;
+            };
+            match (proto, *name) {
+                ("TCP", "inuse") => stat.tcp_inuse = value,
+                ("TCP", "orphan") => stat.tcp_orphan = value,
+                ("TCP", "tw") => stat.tcp_tw = value,
+                ("UDP", "inuse") => stat.udp_inuse = value,
+                _ => continue,
+            }
+            found = true;
+        }
+    }
+    found.then_some(stat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sockstat() {
+        let content = "sockets: used 123\nTCP: inuse 5 orphan 1 tw 2 alloc 7 mem 1\nUDP: inuse 3 mem 2\nUDPLITE: inuse 0\nRAW: inuse 0\n";
+        assert_eq!(
+            parse_sockstat(content),
+            Some(SockStat {
+                tcp_inuse: 5,
+                tcp_orphan: 1,
+                tcp_tw: 2,
+                udp_inuse: 3
+            })
+        );
+        assert_eq!(parse_sockstat(""), None);
+    }
+
+    #[test]
+    fn test_pressure_worst_counter() {
+        let mut monitor = SockStatMonitor::new();
+        let stat = |tcp_inuse, tcp_orphan| SockStat {
+            tcp_inuse,
+            tcp_orphan,
+            ..SockStat::default()
+        };
+        assert_eq!(monitor.update(stat(100, 10), 500, 64), None);
+        // inuse 超出 20%，orphan 超出 100%，报告 orphan
+        assert_eq!(
+            monitor.update(stat(600, 128), 500, 64),
+            Some(PressureEvent::Enter {
+                counter: "tcp_orphan",
+                value: 128,
+                limit: 64
+            })
+        );
+        assert_eq!(monitor.update(stat(600, 128), 500, 64), None);
+        assert_eq!(
+            monitor.update(stat(100, 10), 500, 64),
+            Some(PressureEvent::Exit)
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 两次写入状态文件的最小间隔
+const MIN_WRITE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 这些字段变化时才重写状态文件（outage、healthy_for 等计时字段不算）
+const KEY_FIELDS: &[&str] = &["health", "severity", "conditions", "default_route"];
+
+/// STATUS 快照中关键字段组成的比较键
+pub fn state_key(lines: &[String]) -> String {
+    lines
+        .iter()
+        .filter(|line| {
+            line.split_once('=')
+                .is_some_and(|(key, _)| KEY_FIELDS.contains(&key))
+        })
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 关键状态变化时写入，且两次写入至少间隔 MIN_WRITE_INTERVAL（间隔内的变化推迟到间隔结束）
+pub struct WriteSchedule {
+    last_key: Option<String>,
+    last_write: Option<Instant>,
+}
+
+impl WriteSchedule {
+    pub fn new() -> Self {
+        WriteSchedule {
+            last_key: None,
+            last_write: None,
+        }
+    }
+
+    pub fn due(&self, key: &str, now: Instant) -> bool {
+        self.last_key.as_deref() != Some(key)
+            && self
+                .last_write
+                .is_none_or(|at| now.duration_since(at) >= MIN_WRITE_INTERVAL)
+    }
+
+    pub fn mark(&mut self, key: String, now: Instant) {
+        self.last_key = Some(key);
+        self.last_write = Some(now);
+    }
+}
+
+/// 供厂商 web 界面读取的状态文件（key=value，内容与 STATUS 回复相同，另加 updated=Unix 秒）。
+/// 写入在后台线程进行，文件系统慢时主循环不等待
+pub struct StatusFile {
+    tx: SyncSender<String>,
+    schedule: WriteSchedule,
+}
+
+impl StatusFile {
+    pub fn start(path: PathBuf) -> StatusFile {
+        // 容量为 1：上一份还没写完时新的快照直接放弃
+        let (tx, rx) = mpsc::sync_channel::<String>(1);
+        thread::spawn(move || {
+            for content in rx {
+                // /tmp 写满等错误忽略，下次状态变化时再试
+                let _ = write_atomic(&path, &content);
+            }
+        });
+        StatusFile {
+            tx,
+            schedule: WriteSchedule::new(),
+        }
+    }
+
+    /// 传入当前的 STATUS 快照，需要时交给后台线程写入
+    pub fn update(&mut self, lines: &[String], now: Instant) {
+        let key = state_key(lines);
+        if !self.schedule.due(&key, now) {
+            return;
+        }
+        let updated = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let content = format!("{}\nupdated={}\n", lines.join("\n"), updated);
+        // 后台线程还卡在上一次写入时不标记，下一轮再试
+        if self.tx.try_send(content).is_ok() {
+            self.schedule.mark(key, now);
+        }
+    }
+}
+
+/// 先写临时文件再 rename，读取方不会看到写了一半的内容；写失败时删除临时文件
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    if let Err(e) = fs::write(&tmp_path, content.as_bytes()) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_state_key() {
+        let snapshot = lines(&[
+            "health=HEALTHY",
+            "outage=0s",
+            "severity=NORMAL",
+            "conditions=- dominant=-",
+            "default_route=10.0.0.1 dev wan1",
+        ]);
+        assert_eq!(
+            state_key(&snapshot),
+            "health=HEALTHY severity=NORMAL conditions=- dominant=- default_route=10.0.0.1 dev wan1"
+        );
+    }
+
+    #[test]
+    fn test_write_schedule() {
+        let start = Instant::now();
+        let mut schedule = WriteSchedule::new();
+        assert!(schedule.due("health=HEALTHY", start));
+        schedule.mark("health=HEALTHY".to_string(), start);
+        // 关键状态不变时不重写
+        assert!(!schedule.due("health=HEALTHY", start + Duration::from_secs(300)));
+        // 变化了，但离上次写入不到 30 秒时推迟
+        assert!(!schedule.due("health=FAILED", start + Duration::from_secs(10)));
+        assert!(schedule.due("health=FAILED", start + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let path = std::env::temp_dir().join(format!("zxping-status-{}", std::process::id()));
+        write_atomic(&path, "health=HEALTHY\n").unwrap();
+        write_atomic(&path, "health=FAILED\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "health=FAILED\n");
+        assert!(!path.with_extension("tmp").exists());
+        let _ = fs::remove_file(&path);
+
+        // 目录不存在：报错且不留下临时文件
+        let missing = std::env::temp_dir().join("zxping-no-such-dir/status");
+        assert!(write_atomic(&missing, "x").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// 首选存储目录不可写时的后备目录（tmpfs，重启后丢失）
+pub const FALLBACK_STORAGE_ROOT: &str = "/tmp";
+/// 降级状态下重新探测首选目录的间隔
+pub const STORAGE_REPROBE_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+/// 统计文件名：断网记录和按小时的失败统计共用（各自的行格式不同，写入时互不覆盖）
+pub const STATS_FILE: &str = "zxping.stats";
+/// 日志清理连续失败这么多次后尝试修复存储（重新挂载为读写，仍不可写则切到后备目录）
+const PRUNE_FAILURE_LIMIT: u32 = 2;
+
+/// 日志和状态文件的存储根目录，所有文件路径都通过 `path()` 构造
+pub struct Storage {
+    preferred: PathBuf,
+    root: PathBuf,
+    last_probe: Instant,
+    /// 首选目录探测失败的原因
+    pub probe_error: Option<String>,
+    /// 日志清理连续失败的次数
+    prune_failures: u32,
+}
+
+impl Storage {
+    /// 探测首选目录是否可写，不可写时退回 /tmp
+    pub fn resolve(preferred: &str) -> Storage {
+        let preferred = PathBuf::from(preferred);
+        let (root, probe_error) = match probe_writable(&preferred) {
+            Ok(()) => (preferred.clone(), None),
+            Err(e) => (PathBuf::from(FALLBACK_STORAGE_ROOT), Some(e.to_string())),
+        };
+        Storage {
+            preferred,
+            root,
+            last_probe: Instant::now(),
+            probe_error,
+            prune_failures: 0,
+        }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn preferred(&self) -> &Path {
+        &self.preferred
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.root != self.preferred
+    }
+
+    /// 记录一次日志清理的结果；使用首选目录时连续失败达到 PRUNE_FAILURE_LIMIT 次返回 true
+    /// （之后重新计数）
+    pub fn record_prune(&mut self, ok: bool) -> bool {
+        if ok {
+            self.prune_failures = 0;
+            return false;
+        }
+        self.prune_failures += 1;
+        if self.is_degraded() || self.prune_failures < PRUNE_FAILURE_LIMIT {
+            return false;
+        }
+        self.prune_failures = 0;
+        true
+    }
+
+    /// 确认首选目录现在是否可写
+    pub fn probe_preferred(&self) -> io::Result<()> {
+        probe_writable(&self.preferred)
+    }
+
+    /// 运行中发现首选目录不可写，切到后备目录（之后按 STORAGE_REPROBE_INTERVAL 重新探测）
+    pub fn degrade(&mut self, now: Instant, error: String) {
+        self.root = PathBuf::from(FALLBACK_STORAGE_ROOT);
+        self.probe_error = Some(error);
+        self.last_probe = now;
+    }
+
+    /// 降级状态下定期重新探测首选目录，恢复可写时切回并返回 true
+    pub fn reprobe(&mut self, now: Instant) -> bool {
+        if !self.is_degraded() || now.duration_since(self.last_probe) < STORAGE_REPROBE_INTERVAL {
+            return false;
+        }
+        self.last_probe = now;
+        match probe_writable(&self.preferred) {
+            Ok(()) => {
+                self.root = self.preferred.clone();
+                self.probe_error = None;
+                true
+            }
+            Err(e) => {
+                self.probe_error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// STATUS 中的存储状态
+    pub fn status_line(&self) -> String {
+        if self.is_degraded() {
+            format!("storage={} (degraded)", self.root.display())
+        } else {
+            format!("storage={}", self.root.display())
+        }
+    }
+}
+
+/// 在 /proc/mounts 的内容中找到 path 所在的挂载点（最长匹配）
+pub fn mount_point(path: &Path, mounts: &str) -> Option<PathBuf> {
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|mount| PathBuf::from(mount.replace("\\040", " ")))
+        .filter(|mount| path.starts_with(mount))
+        .max_by_key(|mount| mount.as_os_str().len())
+}
+
+/// 原子写入几种记录共用的文件：owned 认领的行换成 content，其余的行原样保留
+pub fn write_shared(path: &Path, owned: impl Fn(&str) -> bool, content: &str) -> io::Result<()> {
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let mut merged: String = existing
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !owned(line))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    merged.push_str(content);
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, merged)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// 创建并删除一个临时文件来确认目录可写
+fn probe_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".zxping_probe.{}", std::process::id()));
+    fs::write(&probe, b"probe")?;
+    fs::remove_file(&probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_point() {
+        let mounts = "rootfs / rootfs rw 0 0\n\
+                      /dev/mtdblock5 /etc_rw jffs2 ro,relatime 0 0\n\
+                      tmpfs /tmp tmpfs rw 0 0\n";
+        assert_eq!(
+            mount_point(Path::new("/etc_rw"), mounts),
+            Some(PathBuf::from("/etc_rw"))
+        );
+        assert_eq!(
+            mount_point(Path::new("/etc_rw/zxping"), mounts),
+            Some(PathBuf::from("/etc_rw"))
+        );
+        // /etc_rwx 不在 /etc_rw 下
+        assert_eq!(
+            mount_point(Path::new("/etc_rwx"), mounts),
+            Some(PathBuf::from("/"))
+        );
+        assert_eq!(mount_point(Path::new("/data"), ""), None);
+    }
+
+    #[test]
+    fn test_write_shared() {
+        let path = std::env::temp_dir().join(format!("zxping-shared-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        write_shared(&path, |line| line.starts_with("a:"), "a:1\n").unwrap();
+        write_shared(&path, |line| line.starts_with("b:"), "b:1\nb:2\n").unwrap();
+        write_shared(&path, |line| line.starts_with("a:"), "a:2\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b:1\nb:2\na:2\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prune_failures() {
+        let dir = std::env::temp_dir().join(format!("zxping-storage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut storage = Storage::resolve(dir.to_str().unwrap());
+        assert!(!storage.is_degraded());
+        assert!(!storage.record_prune(false));
+        assert!(!storage.record_prune(true));
+        assert!(!storage.record_prune(false));
+        assert!(storage.record_prune(false));
+        // 达到次数后重新计数
+        assert!(!storage.record_prune(false));
+
+        storage.degrade(Instant::now(), "read-only file system".to_string());
+        assert!(storage.is_degraded());
+        assert_eq!(storage.root(), Path::new(FALLBACK_STORAGE_ROOT));
+        // 已经降级时不再重复修复
+        assert!(!storage.record_prune(false));
+        assert!(!storage.record_prune(false));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+
+/// `echo 5 > /proc/sys/net/ipv4/tcp_retries2` 形式命令的目标路径（只取 /proc 和 /sys 下的）
+pub fn target_path(cmd: &str) -> Option<&str> {
+    let (_, path) = cmd.rsplit_once('>')?;
+    let path = path.trim();
+    (path.starts_with("/proc/") || path.starts_with("/sys/")).then_some(path)
+}
+
+/// SYSCTL_DUMP 的返回内容：每个路径一行 `path=当前值`，
+/// 有记录的原值时为 `path=当前值/原值`，读不到时为 `path=MISSING`（重复的路径只列一次）
+pub fn dump_lines(
+    paths: &[&str],
+    originals: &[(&str, String)],
+    read: impl Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    let mut seen = Vec::new();
+    let mut lines = Vec::new();
+    for path in paths {
+        if seen.contains(path) {
+            continue;
+        }
+        seen.push(path);
+        let line = match read(path) {
+            Some(current) => match originals.iter().find(|(p, _)| p == path) {
+                Some((_, original)) => format!("{}={}/{}", path, current, original),
+                None => format!("{}={}", path, current),
+            },
+            None => format!("{}=MISSING", path),
+        };
+        lines.push(line);
+    }
+    lines
+}
+
+/// 读取当前值，多个值（如 tcp_mem）之间的制表符统一为空格
+pub fn read_current(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_path() {
+        assert_eq!(
+            target_path("echo 5 > /proc/sys/net/ipv4/tcp_retries2"),
+            Some("/proc/sys/net/ipv4/tcp_retries2")
+        );
+        assert_eq!(
+            target_path("echo zixc_ping > /sys/power/wake_lock"),
+            Some("/sys/power/wake_lock")
+        );
+        assert_eq!(target_path("iptables -F"), None);
+        assert_eq!(target_path("echo 1 > /tmp/x"), None);
+    }
+
+    #[test]
+    fn test_dump_lines() {
+        let originals = [("/proc/sys/vm/dirty_ratio", "20".to_string())];
+        let read = |path: &str| match path {
+            "/proc/sys/vm/dirty_ratio" => Some("5".to_string()),
+            "/proc/sys/net/ipv4/tcp_retries2" => Some("3".to_string()),
+            _ => None,
+        };
+        let lines = dump_lines(
+            &[
+                "/proc/sys/net/ipv4/tcp_retries2",
+                "/proc/sys/vm/dirty_ratio",
+                "/proc/sys/net/ipv4/tcp_retries2",
+                "/sys/module/missing",
+            ],
+            &originals,
+            read,
+        );
+        assert_eq!(
+            lines,
+            [
+                "/proc/sys/net/ipv4/tcp_retries2=3",
+                "/proc/sys/vm/dirty_ratio=5/20",
+                "/sys/module/missing=MISSING",
+            ]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::fs;
+
+const UNKNOWN: &str = "unknown";
+
+/// 设备固件/内核信息，启动时读取一次后缓存
+pub struct SystemIdentity {
+    pub kernel: String,
+    pub firmware: String,
+    pub mem_total_kb: Option<u64>,
+    pub cpu_model: String,
+}
+
+impl SystemIdentity {
+    /// 读取系统信息，文件缺失时对应字段为 unknown
+    pub fn collect(firmware_file: &str) -> SystemIdentity {
+        let kernel = fs::read_to_string("/proc/version")
+            .ok()
+            .and_then(|content| parse_kernel_version(&content))
+            .unwrap_or_else(|| UNKNOWN.to_string());
+        let firmware = fs::read_to_string(firmware_file)
+            .ok()
+            .and_then(|content| content.lines().next().map(|l| l.trim().to_string()))
+            .filter(|l| !l.is_empty())
+            .unwrap_or_else(|| UNKNOWN.to_string());
+        let cpu_model = fs::read_to_string("/proc/cpuinfo")
+            .ok()
+            .and_then(|content| parse_cpu_model(&content))
+            .unwrap_or_else(|| UNKNOWN.to_string());
+
+        SystemIdentity {
+            kernel,
+            firmware,
+            mem_total_kb: read_meminfo_kb("MemTotal"),
+            cpu_model,
+        }
+    }
+
+    /// 启动通知中的简短形式
+    pub fn compact(&self) -> String {
+        format!(
+            "KERNEL={} FW={}",
+            self.kernel,
+            self.firmware.replace(' ', "_")
+        )
+    }
+
+    /// SYSINFO 命令的完整返回内容
+    pub fn full_lines(&self) -> Vec<String> {
+        vec![
+            format!("kernel={}", self.kernel),
+            format!("firmware={}", self.firmware),
+            format!(
+                "mem_total_kb={}",
+                self.mem_total_kb
+                    .map(|kb| kb.to_string())
+                    .unwrap_or_else(|| UNKNOWN.to_string())
+            ),
+            format!("cpu_model={}", self.cpu_model),
+        ]
+    }
+}
+
+/// 从 /proc/meminfo 读取指定字段（KB）
+pub fn read_meminfo_kb(key: &str) -> Option<u64> {
+    let content = fs::read_to_string("/proc/meminfo").ok()?;
+    parse_meminfo_kb(&content, key)
+}
+
+/// 解析 meminfo 中形如 "MemTotal:  126304 kB" 的行
+pub fn parse_meminfo_kb(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (name, rest) = line.split_once(':')?;
+        if name.trim() != key {
+            return None;
+        }
+        rest.split_whitespace().next()?.parse().ok()
+    })
+}
+
+/// "Linux version 3.4.110-rt140 (builder@host) ..." -> "3.4.110-rt140"
+fn parse_kernel_version(content: &str) -> Option<String> {
+    let mut parts = content.split_whitespace();
+    while let Some(word) = parts.next() {
+        if word == "version" {
+            return parts.next().map(|v| v.to_string());
+        }
+    }
+    None
+}
+
+/// ARM 上 cpuinfo 的字段名不统一，依次尝试 model name / Processor / Hardware
+fn parse_cpu_model(content: &str) -> Option<String> {
+    for key in ["model name", "Processor", "Hardware"] {
+        let value = content.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if name.trim() == key {
+                Some(value.trim().to_string())
+            } else {
+                None
+            }
+        });
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kernel_version() {
+        let content = "Linux version 3.4.110-rt140 (zte@build) (gcc version 4.9.4) #1 PREEMPT\n";
+        assert_eq!(
+            parse_kernel_version(content).as_deref(),
+            Some("3.4.110-rt140")
+        );
+        assert_eq!(parse_kernel_version(""), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo_kb() {
+        let content = "MemTotal:         126304 kB\nMemFree:            2048 kB\nMemAvailable:      38000 kB\n";
+        assert_eq!(parse_meminfo_kb(content, "MemTotal"), Some(126304));
+        assert_eq!(parse_meminfo_kb(content, "MemAvailable"), Some(38000));
+        assert_eq!(parse_meminfo_kb(content, "SwapTotal"), None);
+    }
+
+    #[test]
+    fn test_parse_cpu_model() {
+        let arm =
+            "Processor\t: ARMv7 Processor rev 5 (v7l)\nBogoMIPS\t: 1.00\nHardware\t: ZX297520V3\n";
+        assert_eq!(
+            parse_cpu_model(arm).as_deref(),
+            Some("ARMv7 Processor rev 5 (v7l)")
+        );
+        let x86 = "processor\t: 0\nmodel name\t: Intel(R) Core(TM)\n";
+        assert_eq!(parse_cpu_model(x86).as_deref(), Some("Intel(R) Core(TM)"));
+        assert_eq!(parse_cpu_model("processor\t: 0\n"), None);
+    }
+}
@@ -0,0 +1,342 @@
+use std::fs;
+use std::io;
+use std::process::{ExitStatus, Output};
+use std::thread;
+use std::time::Duration;
+
+use crate::command::{self, CommandResult};
+use crate::monitor::FailureReason;
+use crate::reboot::REBOOT_CONFIRM_WAIT;
+use crate::{check_connectivity, find_reboot_binary, log_error};
+
+/// 主循环用到的系统操作（读 /proc、连接检查、重启、执行命令），
+/// 测试中用 MockSystem 替换，以便按给定序列驱动决策逻辑
+pub trait SystemOps {
+    /// /proc/stat 的内容
+    fn cpu_stat(&mut self) -> Option<String>;
+    /// TCP 连接目标，返回连接耗时或失败原因
+    fn check_connectivity(&mut self, target: &str) -> Result<Duration, FailureReason>;
+    /// 执行重启；只有重启没有发生时才会返回
+    fn reboot(&mut self);
+    /// 通过 sh -c 执行一条命令
+    fn run_command(&mut self, cmd: &str) -> io::Result<ExitStatus>;
+    /// 通过 sh -c 执行一条命令，同时取得 stdout/stderr
+    fn run_command_output(&mut self, cmd: &str) -> io::Result<Output>;
+    /// 同 run_command_output，stdout 最多保留 limit + 1 字节：长度超过 limit 即为输出被截断
+    fn run_command_output_limit(&mut self, cmd: &str, limit: usize) -> io::Result<Output>;
+}
+
+/// 参数调整、iptables 等 sh -c 命令的超时
+const SHELL_TIMEOUT: Duration = Duration::from_secs(10);
+/// reboot 命令只是通知 init，正常很快返回
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 超时当作执行失败（io::ErrorKind::TimedOut）
+fn check_timeout(result: CommandResult) -> io::Result<CommandResult> {
+    if result.timed_out {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, result.to_string()));
+    }
+    Ok(result)
+}
+
+/// 真实系统
+pub struct RealSystem {
+    is_prod: bool,
+    /// 配置的重启命令（程序和参数），为空时只用内置查找到的 reboot
+    reboot_command: Vec<String>,
+}
+
+impl RealSystem {
+    pub fn new(is_prod: bool, reboot_command: Vec<String>) -> Self {
+        RealSystem {
+            is_prod,
+            reboot_command,
+        }
+    }
+}
+
+impl SystemOps for RealSystem {
+    fn cpu_stat(&mut self) -> Option<String> {
+        fs::read_to_string("/proc/stat").ok()
+    }
+
+    fn check_connectivity(&mut self, target: &str) -> Result<Duration, FailureReason> {
+        check_connectivity(target, self.is_prod)
+    }
+
+    fn reboot(&mut self) {
+        if let Some((program, args)) = self.reboot_command.split_first() {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            match command::run_with_timeout(program, &args, REBOOT_TIMEOUT).and_then(check_timeout)
+            {
+                Ok(result) if result.success() => thread::sleep(REBOOT_CONFIRM_WAIT),
+                Ok(result) => log_error(&format!("{} failed: {}", program, result), self.is_prod),
+                Err(e) => log_error(&format!("{} failed: {}", program, e), self.is_prod),
+            }
+            // 仍在运行：继续尝试内置的 reboot（就是默认的 /sbin/reboot 时不再重复执行）
+        }
+        let builtin = find_reboot_binary();
+        if builtin.is_some_and(|reboot| self.reboot_command == [reboot]) {
+            return;
+        }
+        match builtin {
+            Some(reboot) => {
+                if let Err(e) =
+                    command::run_with_timeout(reboot, &[], REBOOT_TIMEOUT).and_then(check_timeout)
+                {
+                    log_error(&format!("{} failed: {}", reboot, e), self.is_prod);
+                }
+                // reboot 命令只是通知 init，等待一段时间确认系统确实在重启
+                thread::sleep(REBOOT_CONFIRM_WAIT);
+            }
+            None => log_error("No reboot binary found", self.is_prod),
+        }
+    }
+
+    fn run_command(&mut self, cmd: &str) -> io::Result<ExitStatus> {
+        command::run_shell(cmd, SHELL_TIMEOUT)
+            .and_then(check_timeout)
+            .map(|result| result.status)
+    }
+
+    fn run_command_output(&mut self, cmd: &str) -> io::Result<Output> {
+        let result = command::run_shell(cmd, SHELL_TIMEOUT).and_then(check_timeout)?;
+        Ok(Output {
+            status: result.status,
+            stdout: result.stdout,
+            stderr: result.stderr.into_bytes(),
+        })
+    }
+
+    fn run_command_output_limit(&mut self, cmd: &str, limit: usize) -> io::Result<Output> {
+        let result =
+            command::run_shell_capped(cmd, SHELL_TIMEOUT, limit + 1).and_then(check_timeout)?;
+        Ok(Output {
+            status: result.status,
+            stdout: result.stdout,
+            stderr: result.stderr.into_bytes(),
+        })
+    }
+}
+
+/// 按预设序列返回结果并记录所有操作的模拟系统
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockSystem {
+    pub cpu_stats: std::collections::VecDeque<String>,
+    pub connectivity: std::collections::VecDeque<Result<Duration, FailureReason>>,
+    pub reboots: u32,
+    pub commands: Vec<String>,
+    /// 按命令前缀预设的 stdout，run_command_output 取第一个匹配的
+    pub outputs: Vec<(String, String)>,
+    /// 以这些前缀开头的命令返回退出码 1
+    pub failing: Vec<String>,
+    /// 累计 CPU 时间（busy, idle），用于生成递增的 /proc/stat
+    cpu_ticks: (u64, u64),
+}
+
+#[cfg(test)]
+impl MockSystem {
+    /// 追加一次 CPU 采样：下一个 100 tick 中有 busy_percent 为非空闲
+    pub fn push_cpu(&mut self, busy_percent: u64) {
+        self.cpu_ticks.0 += busy_percent;
+        self.cpu_ticks.1 += 100 - busy_percent;
+        self.cpu_stats.push_back(format!(
+            "cpu  {} 0 0 {} 0 0 0 0 0 0\n",
+            self.cpu_ticks.0, self.cpu_ticks.1
+        ));
+    }
+
+    /// 追加一次连接检查结果，rtt_ms 为 None 表示连接失败
+    pub fn push_check(&mut self, rtt_ms: Option<u64>) {
+        self.connectivity.push_back(
+            rtt_ms
+                .map(Duration::from_millis)
+                .ok_or(FailureReason::Timeout),
+        );
+    }
+}
+
+#[cfg(test)]
+impl SystemOps for MockSystem {
+    fn cpu_stat(&mut self) -> Option<String> {
+        self.cpu_stats.pop_front()
+    }
+
+    fn check_connectivity(&mut self, _target: &str) -> Result<Duration, FailureReason> {
+        self.connectivity
+            .pop_front()
+            .unwrap_or(Err(FailureReason::Timeout))
+    }
+
+    fn reboot(&mut self) {
+        self.reboots += 1;
+    }
+
+    fn run_command(&mut self, cmd: &str) -> io::Result<ExitStatus> {
+        use std::os::unix::process::ExitStatusExt;
+        self.commands.push(cmd.to_string());
+        let failed = self
+            .failing
+            .iter()
+            .any(|prefix| cmd.starts_with(prefix.as_str()));
+        Ok(ExitStatus::from_raw(if failed { 1 << 8 } else { 0 }))
+    }
+
+    fn run_command_output(&mut self, cmd: &str) -> io::Result<Output> {
+        let stdout = self
+            .outputs
+            .iter()
+            .find(|(prefix, _)| cmd.starts_with(prefix.as_str()))
+            .map(|(_, stdout)| stdout.clone().into_bytes())
+            .unwrap_or_default();
+        Ok(Output {
+            status: self.run_command(cmd)?,
+            stdout,
+            stderr: Vec::new(),
+        })
+    }
+
+    fn run_command_output_limit(&mut self, cmd: &str, limit: usize) -> io::Result<Output> {
+        let mut output = self.run_command_output(cmd)?;
+        output.stdout.truncate(limit + 1);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::cpu::CpuMonitor;
+    use crate::load::{HighLoad, LoadEvent};
+    use crate::monitor::{step, Action, CycleInputs, MonitorState};
+    use crate::reboot::RebootGuard;
+    use std::time::Instant;
+
+    /// 运行一轮检查，和主循环一样在需要时重启
+    fn run_check(
+        sys: &mut MockSystem,
+        state: &mut MonitorState,
+        guard: &RebootGuard,
+        config: &Config,
+        now: Instant,
+    ) -> Option<Action> {
+        let target = "127.0.0.1:80";
+        let check = sys.check_connectivity(target);
+        let inputs = CycleInputs {
+            target,
+            connected: check.is_ok(),
+            rtt: check.ok(),
+            failure: check.err(),
+            reboot_allowed: guard.allowed(now),
+            probe_running: false,
+            high_load: false,
+            now,
+        };
+        let actions = step(state, config, inputs);
+        if actions
+            .iter()
+            .any(|action| matches!(action, Action::RebootSystem(_)))
+        {
+            sys.reboot();
+        }
+        // 只返回限流相关的动作
+        actions
+            .into_iter()
+            .find(|action| matches!(action, Action::Throttle | Action::Restore))
+    }
+
+    #[test]
+    fn test_high_load_hysteresis() {
+        let mut sys = MockSystem::default();
+        for busy in [10, 90, 95, 50, 20] {
+            sys.push_cpu(busy);
+        }
+        let mut cpu = CpuMonitor::new();
+        let mut load = HighLoad::new(85.0, &Config::default());
+        let start = Instant::now();
+        let events: Vec<Option<LoadEvent>> = (0..5)
+            .map(|i| {
+                let now = start + Duration::from_secs(i * 30);
+                cpu.sample(&mut sys)
+                    .and_then(|usage| load.update(usage, now))
+            })
+            .collect();
+        assert_eq!(events[0], None);
+        assert_eq!(events[1], Some(LoadEvent::Enter(90.0)));
+        assert_eq!(events[2], Some(LoadEvent::Update(95.0)));
+        assert_eq!(
+            events[3],
+            Some(LoadEvent::Exit(50.0, Duration::from_secs(60)))
+        );
+        assert_eq!(events[4], None);
+    }
+
+    #[test]
+    fn test_failures_trigger_reboot() {
+        let config = Config {
+            auto_reboot: true,
+            max_failures: 3,
+            ..Config::default()
+        };
+        let mut sys = MockSystem::default();
+        let mut state = MonitorState::new();
+        let mut guard = RebootGuard::new();
+        let now = Instant::now();
+
+        // 中间一次成功会清零失败计数
+        for rtt in [None, None, Some(50), None, None] {
+            sys.push_check(rtt);
+            run_check(&mut sys, &mut state, &guard, &config, now);
+        }
+        assert_eq!(sys.reboots, 0);
+        sys.push_check(None);
+        run_check(&mut sys, &mut state, &guard, &config, now);
+        assert_eq!(sys.reboots, 1);
+
+        // 重启失败后的退避期内不再重启
+        guard.record_failure(now);
+        sys.push_check(None);
+        run_check(&mut sys, &mut state, &guard, &config, now);
+        assert_eq!(sys.reboots, 1);
+
+        // 未开启 auto_reboot 时从不重启
+        let config = Config::default();
+        let mut state = MonitorState::new();
+        for _ in 0..config.max_failures * 2 {
+            sys.push_check(None);
+            run_check(&mut sys, &mut state, &RebootGuard::new(), &config, now);
+        }
+        assert_eq!(sys.reboots, 1);
+    }
+
+    #[test]
+    fn test_latency_throttle_and_restore() {
+        let config = Config {
+            max_high_latency: 3,
+            ..Config::default()
+        };
+        let mut sys = MockSystem::default();
+        let mut state = MonitorState::new();
+        let guard = RebootGuard::new();
+        let now = Instant::now();
+        let mut run = |rtt_ms| {
+            sys.push_check(Some(rtt_ms));
+            run_check(&mut sys, &mut state, &guard, &config, now)
+        };
+
+        assert_eq!(run(500), None);
+        // 一次正常延迟抵消一次高延迟
+        assert_eq!(run(50), None);
+        assert_eq!(run(500), None);
+        assert_eq!(run(500), None);
+        assert_eq!(run(500), Some(Action::Throttle));
+        // 限流中延迟不够低时保持
+        assert_eq!(run(200), None);
+        assert_eq!(run(50), Some(Action::Restore));
+
+        // 极高延迟直接限流
+        assert_eq!(run(3000), Some(Action::Throttle));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 统计 SLOW_TICK 的时间窗口
+const SLOW_TICK_WINDOW: Duration = Duration::from_secs(600);
+
+/// 主循环一轮中分别计时的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// hooks.poll：回收钩子子进程
+    Hooks,
+    /// notifier.poll：通知应答和退避
+    Notify,
+    /// 控制端口、本机 socket 和 HTTP 接口的命令
+    Commands,
+    /// CPU、socket、文件句柄等定期检查
+    Cpu,
+    /// 连通性检查等待应答（预期内的等待，最长为探测超时，不计入预算）
+    Probe,
+    /// 连通性检查结果的处理和路径探测结果
+    Net,
+    /// 其他定期任务（路由、DNS、SNTP、存储、租约等）
+    Other,
+}
+
+const PHASES: [Phase; 7] = [
+    Phase::Hooks,
+    Phase::Notify,
+    Phase::Commands,
+    Phase::Cpu,
+    Phase::Probe,
+    Phase::Net,
+    Phase::Other,
+];
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Hooks => "hooks",
+            Phase::Notify => "notify",
+            Phase::Commands => "commands",
+            Phase::Cpu => "cpu",
+            Phase::Probe => "probe",
+            Phase::Net => "net",
+            Phase::Other => "other",
+        }
+    }
+}
+
+/// 一轮主循环各阶段的耗时。每个阶段结束时调用 lap，同一阶段可以分几段累计
+pub struct TickTimer {
+    start: Instant,
+    mark: Instant,
+    phases: [Duration; PHASES.len()],
+}
+
+impl TickTimer {
+    pub fn start(now: Instant) -> Self {
+        TickTimer {
+            start: now,
+            mark: now,
+            phases: [Duration::ZERO; PHASES.len()],
+        }
+    }
+
+    /// 上次 lap 以来的时间计入 phase
+    pub fn lap(&mut self, phase: Phase) {
+        self.lap_at(phase, Instant::now());
+    }
+
+    fn lap_at(&mut self, phase: Phase, now: Instant) {
+        let index = PHASES.iter().position(|p| *p == phase).unwrap_or(0);
+        self.phases[index] += now.duration_since(self.mark);
+        self.mark = now;
+    }
+
+    /// 本轮结束（睡眠之前），返回不含 Probe 的总耗时和其余阶段中最慢的
+    pub fn finish(&self) -> (Duration, Phase, Duration) {
+        let (phase, slowest) = PHASES
+            .iter()
+            .zip(self.phases)
+            .filter(|(p, _)| **p != Phase::Probe)
+            .max_by_key(|(_, d)| *d)
+            .map(|(p, d)| (*p, d))
+            .unwrap_or((Phase::Other, Duration::ZERO));
+        let probe = self.phases[PHASES.iter().position(|p| *p == Phase::Probe).unwrap_or(0)];
+        (
+            self.mark.duration_since(self.start).saturating_sub(probe),
+            phase,
+            slowest,
+        )
+    }
+}
+
+/// 一轮超出预算时的处理
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TickEvent {
+    /// 本轮超出预算，记录 Warn
+    Over,
+    /// SLOW_TICK_WINDOW 内超出预算的次数达到 slow_tick_count，发送 SLOW_TICK（之后重新计数）
+    Repeated(usize),
+}
+
+/// tick_budget_ms：检查每轮主循环的耗时，记录最慢的一轮
+pub struct TickBudget {
+    budget: Duration,
+    repeat: usize,
+    over_at: VecDeque<Instant>,
+    over_total: u64,
+    /// 启动以来最慢的一轮和其中最慢的阶段
+    worst: Option<(Duration, Phase)>,
+}
+
+impl TickBudget {
+    /// budget 为 0 时只记录最慢的一轮，不告警
+    pub fn new(budget: Duration, repeat: u32) -> Self {
+        TickBudget {
+            budget,
+            repeat: repeat.max(1) as usize,
+            over_at: VecDeque::new(),
+            over_total: 0,
+            worst: None,
+        }
+    }
+
+    pub fn update(&mut self, total: Duration, slowest: Phase, now: Instant) -> Option<TickEvent> {
+        if self.worst.is_none_or(|(worst, _)| total > worst) {
+            self.worst = Some((total, slowest));
+        }
+        if self.budget.is_zero() || total <= self.budget {
+            return None;
+        }
+        self.over_total += 1;
+        while self
+            .over_at
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= SLOW_TICK_WINDOW)
+        {
+            self.over_at.pop_front();
+        }
+        self.over_at.push_back(now);
+        if self.over_at.len() >= self.repeat {
+            let count = self.over_at.len();
+            self.over_at.clear();
+            return Some(TickEvent::Repeated(count));
+        }
+        Some(TickEvent::Over)
+    }
+
+    /// STATUS 和 STATS 中的一行：`tick_worst_ms=1840 tick_worst_phase=net tick_over_budget=3 tick_budget_ms=1000`
+    pub fn status_line(&self) -> String {
+        let (worst, phase) = self
+            .worst
+            .map(|(d, p)| (d.as_millis().to_string(), p.name()))
+            .unwrap_or_else(|| ("-".to_string(), "-"));
+        format!(
+            "tick_worst_ms={} tick_worst_phase={} tick_over_budget={} tick_budget_ms={}",
+            worst,
+            phase,
+            self.over_total,
+            self.budget.as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_timer() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut timer = TickTimer::start(start);
+        timer.lap_at(Phase::Hooks, start + ms(5));
+        timer.lap_at(Phase::Other, start + ms(300));
+        timer.lap_at(Phase::Net, start + ms(700));
+        timer.lap_at(Phase::Other, start + ms(900));
+        // 同一阶段分段累计
+        assert_eq!(timer.finish(), (ms(900), Phase::Other, ms(495)));
+        // 等待探测应答的时间不计入
+        timer.lap_at(Phase::Probe, start + ms(3900));
+        assert_eq!(timer.finish(), (ms(900), Phase::Other, ms(495)));
+    }
+
+    #[test]
+    fn test_tick_budget() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut budget = TickBudget::new(ms(1000), 3);
+        assert_eq!(
+            budget.status_line(),
+            "tick_worst_ms=- tick_worst_phase=- tick_over_budget=0 tick_budget_ms=1000"
+        );
+        assert_eq!(budget.update(ms(200), Phase::Cpu, start), None);
+        assert_eq!(
+            budget.update(ms(1500), Phase::Net, start),
+            Some(TickEvent::Over)
+        );
+        // 窗口外的超时不计入
+        let later = start + SLOW_TICK_WINDOW;
+        assert_eq!(
+            budget.update(ms(1200), Phase::Commands, later),
+            Some(TickEvent::Over)
+        );
+        assert_eq!(
+            budget.update(ms(1100), Phase::Hooks, later),
+            Some(TickEvent::Over)
+        );
+        assert_eq!(
+            budget.update(ms(1100), Phase::Hooks, later),
+            Some(TickEvent::Repeated(3))
+        );
+        assert_eq!(
+            budget.update(ms(1100), Phase::Hooks, later),
+            Some(TickEvent::Over)
+        );
+        assert_eq!(
+            budget.status_line(),
+            "tick_worst_ms=1500 tick_worst_phase=net tick_over_budget=5 tick_budget_ms=1000"
+        );
+
+        // 预算为 0：只记录最慢的一轮
+        let mut budget = TickBudget::new(Duration::ZERO, 3);
+        assert_eq!(budget.update(ms(5000), Phase::Net, start), None);
+        assert!(budget.status_line().starts_with("tick_worst_ms=5000"));
+    }
+}
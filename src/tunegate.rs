@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+/// 启动时的网络参数优化由什么触发
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuneTrigger {
+    /// 启动 30 秒后直接应用（默认）
+    Startup,
+    /// 第一次检查成功
+    FirstSuccess,
+    /// 等到 tune_deadline_secs 仍没有成功过
+    Deadline,
+}
+
+impl TuneTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TuneTrigger::Startup => "startup",
+            TuneTrigger::FirstSuccess => "first_success",
+            TuneTrigger::Deadline => "deadline",
+        }
+    }
+}
+
+/// tune_after_first_success：推迟网络参数优化，直到确认网络栈已经可用
+pub struct TuneGate {
+    since: Instant,
+    deadline: Duration,
+    /// 已应用时的触发原因和等待时长，None 为仍在等待
+    applied: Option<(TuneTrigger, Duration)>,
+}
+
+impl TuneGate {
+    /// 等待第一次检查成功，最多等 deadline
+    pub fn deferred(now: Instant, deadline: Duration) -> Self {
+        TuneGate {
+            since: now,
+            deadline,
+            applied: None,
+        }
+    }
+
+    /// 启动时已经应用过
+    pub fn applied_at_startup(now: Instant) -> Self {
+        TuneGate {
+            since: now,
+            deadline: Duration::ZERO,
+            applied: Some((TuneTrigger::Startup, Duration::ZERO)),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.applied.is_none()
+    }
+
+    /// 每次检查后调用，需要现在应用时返回触发原因（只返回一次）
+    pub fn update(&mut self, connected: bool, now: Instant) -> Option<TuneTrigger> {
+        if self.applied.is_some() {
+            return None;
+        }
+        let waited = now.duration_since(self.since);
+        let trigger = if connected {
+            TuneTrigger::FirstSuccess
+        } else if waited >= self.deadline {
+            TuneTrigger::Deadline
+        } else {
+            return None;
+        };
+        self.applied = Some((trigger, waited));
+        Some(trigger)
+    }
+
+    /// STATUS 中的一行：`startup_tuning=pending deadline_in=240s` 或 `startup_tuning=first_success after=12s`
+    pub fn status_line(&self, now: Instant) -> String {
+        match self.applied {
+            None => format!(
+                "startup_tuning=pending deadline_in={}s",
+                self.deadline
+                    .saturating_sub(now.duration_since(self.since))
+                    .as_secs()
+            ),
+            Some((trigger, waited)) => {
+                format!(
+                    "startup_tuning={} after={}s",
+                    trigger.as_str(),
+                    waited.as_secs()
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_success() {
+        let start = Instant::now();
+        let mut gate = TuneGate::deferred(start, Duration::from_secs(300));
+        assert_eq!(gate.update(false, start + Duration::from_secs(10)), None);
+        assert_eq!(
+            gate.status_line(start + Duration::from_secs(60)),
+            "startup_tuning=pending deadline_in=240s"
+        );
+        assert_eq!(
+            gate.update(true, start + Duration::from_secs(12)),
+            Some(TuneTrigger::FirstSuccess)
+        );
+        assert!(!gate.is_pending());
+        assert_eq!(gate.update(true, start + Duration::from_secs(20)), None);
+        assert_eq!(
+            gate.status_line(start),
+            "startup_tuning=first_success after=12s"
+        );
+    }
+
+    #[test]
+    fn test_deadline() {
+        let start = Instant::now();
+        let mut gate = TuneGate::deferred(start, Duration::from_secs(300));
+        assert_eq!(gate.update(false, start + Duration::from_secs(299)), None);
+        assert_eq!(
+            gate.update(false, start + Duration::from_secs(300)),
+            Some(TuneTrigger::Deadline)
+        );
+        assert_eq!(gate.update(true, start + Duration::from_secs(301)), None);
+
+        let gate = TuneGate::applied_at_startup(start);
+        assert!(!gate.is_pending());
+        assert_eq!(gate.status_line(start), "startup_tuning=startup after=0s");
+    }
+}
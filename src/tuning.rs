@@ -0,0 +1,362 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// 参数组的目标状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    Throttle,
+    Restore,
+}
+
+impl Intent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Intent::Throttle => "throttled",
+            Intent::Restore => "normal",
+        }
+    }
+}
+
+/// 一组要一起写入的参数（限流、恢复、切换档位）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TuningSet {
+    pub intent: Intent,
+    /// 通知中的阶段名（THROTTLE、RESTORE、SHED_LOAD、PROFILE）
+    pub stage: &'static str,
+    pub profile: &'static str,
+    pub writes: Vec<(&'static str, String)>,
+}
+
+/// 一组参数的执行结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TuningOutcome {
+    pub intent: Intent,
+    pub stage: &'static str,
+    pub profile: &'static str,
+    pub applied: usize,
+    pub failed: Vec<String>,
+    /// 与上一次写入的值完全相同，没有重复写入
+    pub skipped: bool,
+    /// 开始执行前被这一组取代而没有执行的请求数
+    pub superseded: usize,
+}
+
+impl TuningOutcome {
+    /// TUNING 通知：`TUNING: THROTTLE PROFILE=balanced APPLIED=1 FAILED=0`
+    pub fn notification(&self) -> String {
+        format!(
+            "TUNING: {} PROFILE={} APPLIED={} FAILED={}",
+            self.stage,
+            self.profile,
+            self.applied,
+            self.failed.len()
+        )
+    }
+}
+
+type Writer = dyn Fn(&str, &str) -> io::Result<()> + Send + Sync;
+
+fn write_param(path: &str, value: &str) -> io::Result<()> {
+    std::fs::write(path, format!("{}\n", value))
+}
+
+/// 在锁内写入参数组，保证同一时间只有一组在写（后台线程和退出时的恢复共用）
+#[derive(Clone)]
+pub struct Applier {
+    last: Arc<Mutex<Option<TuningSet>>>,
+    writer: Arc<Writer>,
+}
+
+impl Applier {
+    pub fn apply(&self, set: TuningSet) -> TuningOutcome {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        self.apply_locked(&mut last, set, 0)
+    }
+
+    fn apply_locked(
+        &self,
+        last: &mut Option<TuningSet>,
+        set: TuningSet,
+        superseded: usize,
+    ) -> TuningOutcome {
+        let mut outcome = TuningOutcome {
+            intent: set.intent,
+            stage: set.stage,
+            profile: set.profile,
+            applied: 0,
+            failed: Vec::new(),
+            skipped: false,
+            superseded,
+        };
+        // 只比较写入的值：THROTTLE 之后紧接着 SHED_LOAD 也不必重复写
+        if last.as_ref().is_some_and(|last| last.writes == set.writes) {
+            outcome.skipped = true;
+            return outcome;
+        }
+        for (path, value) in &set.writes {
+            match (self.writer)(path, value) {
+                Ok(()) => outcome.applied += 1,
+                Err(e) => outcome.failed.push(format!("{}={} ({})", path, value, e)),
+            }
+        }
+        // 有写入失败时参数处于未知状态，不记录，下一次同样的参数组照常重试
+        *last = outcome.failed.is_empty().then_some(set);
+        outcome
+    }
+}
+
+/// 限流/恢复参数组的串行执行：主循环只提交目标状态，后台线程按提交顺序逐组完整写入。
+/// 还没开始的 RESTORE 在随后又到达限流请求时被取代（其他参数组，如带 hashsize 的 PROFILE，
+/// 总是执行），与上一次写入完全相同的参数组不再重复写入
+pub struct TuningQueue {
+    tx: Sender<TuningSet>,
+    results: Receiver<TuningOutcome>,
+    applier: Applier,
+    /// 置位后后台线程不再写入（退出时恢复前设置）
+    stopped: Arc<AtomicBool>,
+    /// 已提交但还没有返回结果的请求数
+    pending: usize,
+    /// 最近一次写入完成的状态
+    pub state: Option<Intent>,
+    /// 累计被取代而没有执行的请求数
+    superseded: usize,
+    last_failed: usize,
+}
+
+impl TuningQueue {
+    pub fn start() -> Self {
+        Self::with_writer(Arc::new(write_param))
+    }
+
+    fn with_writer(writer: Arc<Writer>) -> Self {
+        let applier = Applier {
+            last: Arc::new(Mutex::new(None)),
+            writer,
+        };
+        let (tx, rx) = mpsc::channel::<TuningSet>();
+        let (results_tx, results) = mpsc::channel();
+        let worker = applier.clone();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker_stopped = Arc::clone(&stopped);
+        thread::spawn(move || {
+            while let Ok(set) = rx.recv() {
+                let mut last = worker.last.lock().unwrap_or_else(|e| e.into_inner());
+                // 拿到锁（真正开始写）之前到达的请求按顺序执行
+                let mut batch = vec![set];
+                batch.extend(rx.try_iter());
+                let mut batch = batch.into_iter().peekable();
+                let mut superseded = 0;
+                while let Some(set) = batch.next() {
+                    if worker_stopped.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    if supersedes(&set, batch.peek()) {
+                        superseded += 1;
+                        continue;
+                    }
+                    let outcome =
+                        worker.apply_locked(&mut last, set, std::mem::take(&mut superseded));
+                    if results_tx.send(outcome).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        TuningQueue {
+            tx,
+            results,
+            applier,
+            stopped,
+            pending: 0,
+            state: None,
+            superseded: 0,
+            last_failed: 0,
+        }
+    }
+
+    pub fn submit(&mut self, set: TuningSet) {
+        if self.tx.send(set).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    /// 取回已完成的结果
+    pub fn poll(&mut self) -> Vec<TuningOutcome> {
+        let outcomes: Vec<TuningOutcome> = self.results.try_iter().collect();
+        for outcome in &outcomes {
+            self.pending = self.pending.saturating_sub(1 + outcome.superseded);
+            self.superseded += outcome.superseded;
+            self.state = Some(outcome.intent);
+            if !outcome.skipped {
+                self.last_failed = outcome.failed.len();
+            }
+        }
+        outcomes
+    }
+
+    /// 停止后台写入：正在写的一组写完后返回，之后排队中的参数组都不再写入
+    pub fn stop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        drop(self.applier.last.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// 同步写入用的句柄（退出时恢复参数，与后台线程互斥）
+    pub fn applier(&self) -> Applier {
+        self.applier.clone()
+    }
+
+    /// STATUS 中的一行：`tuning=throttled pending=0 superseded=2 failed=0`
+    pub fn status_line(&self) -> String {
+        format!(
+            "tuning={} pending={} superseded={} failed={}",
+            self.state.map(|intent| intent.as_str()).unwrap_or("-"),
+            self.pending,
+            self.superseded,
+            self.last_failed
+        )
+    }
+}
+
+/// 还没开始的 RESTORE 后面紧接着限流请求时不必执行
+fn supersedes(set: &TuningSet, next: Option<&TuningSet>) -> bool {
+    set.stage == "RESTORE" && next.is_some_and(|next| next.intent == Intent::Throttle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn set(intent: Intent, value: &str) -> TuningSet {
+        TuningSet {
+            intent,
+            stage: if intent == Intent::Throttle {
+                "THROTTLE"
+            } else {
+                "RESTORE"
+            },
+            profile: "balanced",
+            writes: vec![("/proc/sys/net/nf_conntrack_max", value.to_string())],
+        }
+    }
+
+    fn wait_for(queue: &mut TuningQueue, count: usize) -> Vec<TuningOutcome> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut outcomes = Vec::new();
+        while outcomes.len() < count && Instant::now() < deadline {
+            outcomes.extend(queue.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        outcomes
+    }
+
+    #[test]
+    fn test_serialize_and_supersede() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&written);
+        let mut queue = TuningQueue::with_writer(Arc::new(move |path: &str, value: &str| {
+            log.lock().unwrap().push(format!("{}={}", path, value));
+            Ok(())
+        }));
+
+        // 后台线程拿不到锁时提交的请求按顺序执行，只有还没开始的恢复被随后的限流取代；
+        // 紧接着的相同限流不重复写入
+        let applier = queue.applier();
+        let guard = applier.last.lock().unwrap();
+        queue.submit(set(Intent::Throttle, "2048"));
+        queue.submit(set(Intent::Restore, "4096"));
+        queue.submit(set(Intent::Throttle, "2048"));
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        let outcomes = wait_for(&mut queue, 2);
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].intent, Intent::Throttle);
+        assert_eq!(outcomes[0].superseded, 0);
+        assert_eq!(
+            outcomes[0].notification(),
+            "TUNING: THROTTLE PROFILE=balanced APPLIED=1 FAILED=0"
+        );
+        assert_eq!(outcomes[1].superseded, 1);
+        assert!(outcomes[1].skipped);
+        assert_eq!(
+            *written.lock().unwrap(),
+            vec!["/proc/sys/net/nf_conntrack_max=2048"]
+        );
+        assert_eq!(
+            queue.status_line(),
+            "tuning=throttled pending=0 superseded=1 failed=0"
+        );
+
+        // PROFILE 不会被取代
+        let guard = applier.last.lock().unwrap();
+        let mut profile = set(Intent::Restore, "8192");
+        profile.stage = "PROFILE";
+        profile.writes.push((
+            "/sys/module/nf_conntrack/parameters/hashsize",
+            "4096".to_string(),
+        ));
+        queue.submit(profile);
+        queue.submit(set(Intent::Throttle, "2048"));
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        let outcomes = wait_for(&mut queue, 2);
+        assert_eq!(outcomes[0].stage, "PROFILE");
+        assert_eq!(outcomes[0].applied, 2);
+        assert_eq!(outcomes[1].applied, 1);
+        assert_eq!(written.lock().unwrap().len(), 4);
+
+        // 与上一次相同的参数组不重复写入
+        queue.submit(set(Intent::Throttle, "2048"));
+        let outcomes = wait_for(&mut queue, 1);
+        assert!(outcomes[0].skipped);
+        assert_eq!(written.lock().unwrap().len(), 4);
+
+        // 同步恢复与后台共用状态
+        let outcome = queue.applier().apply(set(Intent::Restore, "4096"));
+        assert_eq!(outcome.applied, 1);
+        assert!(queue.applier().apply(set(Intent::Restore, "4096")).skipped);
+    }
+
+    #[test]
+    fn test_stop_drops_queued() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&written);
+        let mut queue = TuningQueue::with_writer(Arc::new(move |path: &str, value: &str| {
+            log.lock().unwrap().push(format!("{}={}", path, value));
+            Ok(())
+        }));
+        // 退出时的恢复拿着锁，期间排队的限流在停止后不会再写入
+        let applier = queue.applier();
+        let guard = applier.last.lock().unwrap();
+        queue.submit(set(Intent::Throttle, "2048"));
+        thread::sleep(Duration::from_millis(50));
+        queue.stopped.store(true, Ordering::SeqCst);
+        drop(guard);
+        queue.stop();
+        queue.submit(set(Intent::Throttle, "2048"));
+        thread::sleep(Duration::from_millis(50));
+        assert!(written.lock().unwrap().is_empty());
+        assert!(queue.poll().is_empty());
+    }
+
+    #[test]
+    fn test_failed_write() {
+        let mut queue = TuningQueue::with_writer(Arc::new(|_: &str, _: &str| {
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        }));
+        queue.submit(set(Intent::Restore, "4096"));
+        let outcomes = wait_for(&mut queue, 1);
+        assert_eq!(outcomes[0].applied, 0);
+        assert_eq!(outcomes[0].failed.len(), 1);
+        assert!(queue.status_line().ends_with("failed=1"));
+
+        // 失败的参数组再次提交时重试，不当作已写入而跳过
+        queue.submit(set(Intent::Restore, "4096"));
+        let outcomes = wait_for(&mut queue, 1);
+        assert!(!outcomes[0].skipped);
+        assert_eq!(outcomes[0].failed.len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::config::Config;
+use crate::sockmark;
+
+/// UDP echo 负载的最大长度（留出 IP/UDP 头，避免在常见 MTU 上分片）
+pub const MAX_PAYLOAD: usize = 1400;
+
+/// probe=udp 的参数，来自 udp_echo_* 配置项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoSettings {
+    /// 负载长度（字节），用固定图案填充
+    pub payload_size: usize,
+    /// 目标端口，0 为使用检查目标的端口
+    pub port: u16,
+    /// IP TOS 字节（IPv6 为 traffic class），0 为不设置
+    pub tos: u8,
+    /// 回应必须与发出的负载逐字节相同；否则收到目标的任何回应都算成功
+    pub strict: bool,
+}
+
+impl EchoSettings {
+    pub fn from_config(config: &Config) -> Self {
+        EchoSettings {
+            payload_size: config.udp_echo_payload,
+            port: config.udp_echo_port,
+            tos: config.udp_echo_tos,
+            strict: config.udp_echo_strict,
+        }
+    }
+
+    /// 检查目标（IP:端口）换成实际发送的地址
+    pub fn target(&self, check_target: &str) -> Option<SocketAddr> {
+        let mut addr: SocketAddr = check_target.parse().ok()?;
+        if self.port != 0 {
+            addr.set_port(self.port);
+        }
+        Some(addr)
+    }
+
+    /// 启动日志中的实际生效参数，TOS 从 socket 读回（内核可能拒绝或改写）
+    pub fn describe(&self, addr: SocketAddr) -> String {
+        let tos = open_socket(addr, self)
+            .and_then(|socket| read_tos(&socket, addr))
+            .map(|tos| format!("0x{:02x}", tos))
+            .unwrap_or_else(|e| format!("unavailable ({})", e));
+        format!(
+            "UDP echo probe to {}: payload={}B tos={} match={}",
+            addr,
+            self.payload_size,
+            tos,
+            if self.strict { "exact" } else { "any" }
+        )
+    }
+}
+
+/// 一次 echo 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoReply {
+    /// 回应与负载相同
+    Matched(Duration),
+    /// 收到回应但内容不同（多半是运营商 ALG 改写）
+    Mismatched(Duration),
+}
+
+/// 用固定图案填充的负载，两端都能据此校验
+pub fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 251) as u8 ^ 0x5a).collect()
+}
+
+/// 发一个 echo 请求并等待回应。每次使用新的 socket（新的源端口），上次检查迟到的回应不会被误收
+pub fn probe(
+    addr: SocketAddr,
+    settings: &EchoSettings,
+    timeout: Duration,
+) -> io::Result<EchoReply> {
+    let socket = open_socket(addr, settings)?;
+    socket.set_read_timeout(Some(timeout))?;
+    let request = payload(settings.payload_size);
+    let start = Instant::now();
+    socket.send(&request)?;
+    let mut buf = vec![0u8; MAX_PAYLOAD + 1];
+    match socket.recv(&mut buf) {
+        Ok(size) if buf[..size] == request[..] => Ok(EchoReply::Matched(start.elapsed())),
+        Ok(_) => Ok(EchoReply::Mismatched(start.elapsed())),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no echo within {}ms", timeout.as_millis()),
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// 已 connect 到目标的 UDP socket（只收目标发回的包，ICMP 端口不可达会报为 ConnectionRefused）
+fn open_socket(addr: SocketAddr, settings: &EchoSettings) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    sockmark::prepare_probe(socket.as_raw_fd(), addr.is_ipv6());
+    // udp_echo_tos 优先于 probe_dscp
+    if settings.tos != 0 {
+        if addr.is_ipv4() {
+            socket.set_tos(settings.tos as u32)?;
+        } else {
+            socket.set_tclass_v6(settings.tos as u32)?;
+        }
+    }
+    socket.connect(&addr.into())?;
+    Ok(socket.into())
+}
+
+fn read_tos(socket: &UdpSocket, addr: SocketAddr) -> io::Result<u32> {
+    let socket = socket2::SockRef::from(socket);
+    if addr.is_ipv4() {
+        socket.tos()
+    } else {
+        socket.tclass_v6()
+    }
+}
+
+/// probe=udp 的结果统计，回应内容不符与超时分开计数，便于区分 ALG 干扰和丢包
+#[derive(Debug, Default)]
+pub struct EchoStats {
+    pub matched: u64,
+    pub mismatched: u64,
+    pub timeouts: u64,
+    pub errors: u64,
+}
+
+impl EchoStats {
+    pub fn record(&mut self, result: &io::Result<EchoReply>) {
+        match result {
+            Ok(EchoReply::Matched(_)) => self.matched += 1,
+            Ok(EchoReply::Mismatched(_)) => self.mismatched += 1,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => self.timeouts += 1,
+            Err(_) => self.errors += 1,
+        }
+    }
+
+    /// STATUS 中的一行：`udp_echo matched=120 mismatched=3 timeouts=2 errors=0`
+    pub fn status_line(&self) -> String {
+        format!(
+            "udp_echo matched={} mismatched={} timeouts={} errors={}",
+            self.matched, self.mismatched, self.timeouts, self.errors
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn settings(strict: bool) -> EchoSettings {
+        EchoSettings {
+            payload_size: 64,
+            port: 0,
+            tos: 0x88,
+            strict,
+        }
+    }
+
+    #[test]
+    fn test_target_port() {
+        let mut s = settings(true);
+        assert_eq!(s.target("10.0.0.1:80"), "10.0.0.1:80".parse().ok());
+        s.port = 7;
+        assert_eq!(s.target("10.0.0.1:80"), "10.0.0.1:7".parse().ok());
+        assert_eq!(s.target("10.0.0.1"), None);
+        assert_eq!(payload(3), vec![0x5a, 0x5b, 0x58]);
+    }
+
+    #[test]
+    fn test_probe() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let echo = thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            // 第一次原样返回，第二次改写一个字节
+            for corrupt in [false, true] {
+                let (size, from) = server.recv_from(&mut buf).unwrap();
+                if corrupt {
+                    buf[0] ^= 0xff;
+                }
+                server.send_to(&buf[..size], from).unwrap();
+            }
+        });
+        let timeout = Duration::from_secs(5);
+        let mut stats = EchoStats::default();
+        let result = probe(addr, &settings(true), timeout);
+        assert!(matches!(result, Ok(EchoReply::Matched(_))));
+        stats.record(&result);
+        let result = probe(addr, &settings(true), timeout);
+        assert!(matches!(result, Ok(EchoReply::Mismatched(_))));
+        stats.record(&result);
+        echo.join().unwrap();
+
+        // 没有回应：超时
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let result = probe(
+            silent.local_addr().unwrap(),
+            &settings(false),
+            Duration::from_millis(100),
+        );
+        assert_eq!(result.as_ref().unwrap_err().kind(), io::ErrorKind::TimedOut);
+        stats.record(&result);
+        assert_eq!(
+            stats.status_line(),
+            "udp_echo matched=1 mismatched=1 timeouts=1 errors=0"
+        );
+        assert!(settings(true).describe(addr).contains("tos=0x88"));
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+
+/// 限流时调整的 vm 参数：(路径, 限流值)
+/// 闪存写入是瓶颈时，降低脏页比例让回写更早、更小批地进行，并更积极地回收 dentry/inode 缓存
+const VM_THROTTLE: &[(&str, &str)] = &[
+    ("/proc/sys/vm/dirty_ratio", "5"),
+    ("/proc/sys/vm/dirty_background_ratio", "2"),
+    ("/proc/sys/vm/vfs_cache_pressure", "200"),
+];
+
+/// 限流时会修改的所有路径
+pub fn managed_paths() -> impl Iterator<Item = &'static str> {
+    VM_THROTTLE.iter().map(|(path, _)| *path)
+}
+
+/// 一次参数修改（用于写入和记录日志）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmChange {
+    pub path: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+impl VmChange {
+    /// 日志中的形式：`dirty_ratio 20 -> 5`
+    pub fn describe(&self) -> String {
+        let name = self.path.rsplit('/').next().unwrap_or(self.path);
+        format!("{} {} -> {}", name, self.from, self.to)
+    }
+}
+
+/// 限流期间的 vm 参数：进入时保存原值，退出时恢复
+pub struct VmThrottle {
+    saved: Vec<(&'static str, String)>,
+}
+
+impl VmThrottle {
+    pub fn new() -> Self {
+        VmThrottle { saved: Vec::new() }
+    }
+
+    /// 进入限流：记录原值并返回需要写入的限流值（已在限流中时返回空）
+    pub fn enter(&mut self, read: impl Fn(&str) -> Option<String>) -> Vec<VmChange> {
+        if !self.saved.is_empty() {
+            return Vec::new();
+        }
+        let mut changes = Vec::new();
+        for (path, value) in VM_THROTTLE {
+            // 读不到的参数（内核不支持）不修改，也不恢复
+            let Some(original) = read(path) else {
+                continue;
+            };
+            self.saved.push((path, original.clone()));
+            changes.push(VmChange {
+                path,
+                from: original,
+                to: value.to_string(),
+            });
+        }
+        changes
+    }
+
+    /// 限流中记录的原值（未在限流中时为空）
+    pub fn originals(&self) -> &[(&'static str, String)] {
+        &self.saved
+    }
+
+    /// 退出限流：返回恢复原值需要的修改
+    pub fn exit(&mut self, read: impl Fn(&str) -> Option<String>) -> Vec<VmChange> {
+        self.saved
+            .drain(..)
+            .map(|(path, original)| VmChange {
+                path,
+                from: read(path).unwrap_or_else(|| "?".to_string()),
+                to: original,
+            })
+            .collect()
+    }
+}
+
+/// 读取 /proc/sys 下的单个值
+pub fn read_value(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter_and_exit() {
+        let mut throttle = VmThrottle::new();
+        let current = |path: &str| match path {
+            "/proc/sys/vm/vfs_cache_pressure" => None,
+            _ => Some("20".to_string()),
+        };
+        let changes = throttle.enter(current);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].describe(), "dirty_ratio 20 -> 5");
+        // 已在限流中不重复记录（否则会把限流值当成原值）
+        assert!(throttle.enter(|_| Some("5".to_string())).is_empty());
+
+        let restored = throttle.exit(|_| Some("5".to_string()));
+        assert_eq!(
+            restored.iter().map(VmChange::describe).collect::<Vec<_>>(),
+            ["dirty_ratio 5 -> 20", "dirty_background_ratio 5 -> 20"]
+        );
+        assert!(throttle.exit(current).is_empty());
+    }
+}
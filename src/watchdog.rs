@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::boot::BootRecord;
+use crate::config::{Config, StallAction};
+use crate::notify::Notifier;
+use crate::system::{RealSystem, SystemOps};
+use crate::{log_error, log_message, log_warn};
+
+/// 卡住后退出时的退出码（由 init/supervisor 重新拉起）
+const STALL_EXIT_CODE: i32 = 3;
+
+/// 主循环卡住/恢复
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallEvent {
+    /// 超过 timeout 没有喂狗，附距上次喂狗的时间
+    Stalled(Duration),
+    Recovered(Duration),
+}
+
+/// 判断是否卡住，每次卡住只报告一次
+pub struct StallCheck {
+    timeout: Duration,
+    stalled: bool,
+}
+
+impl StallCheck {
+    pub fn new(timeout: Duration) -> Self {
+        StallCheck {
+            timeout,
+            stalled: false,
+        }
+    }
+
+    /// since_pet 为距上次喂狗的时间；stalled 期间的最长时间在恢复时报告
+    pub fn update(&mut self, since_pet: Duration, longest: Duration) -> Option<StallEvent> {
+        match (since_pet >= self.timeout, self.stalled) {
+            (true, false) => {
+                self.stalled = true;
+                Some(StallEvent::Stalled(since_pet))
+            }
+            (false, true) => {
+                self.stalled = false;
+                Some(StallEvent::Recovered(longest))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 主循环最近一次喂狗时的状态
+struct Pet {
+    at: Instant,
+    /// 主循环此时是否允许主动重启（维护模式、重启失败退避、重启循环保守模式下不允许）
+    reboot_allowed: bool,
+    /// 启动记录文件，重启前在其中记录原因
+    boot_record: PathBuf,
+}
+
+/// 主循环看门狗：主循环每轮调用 pet，独立线程发现超过 loop_stall_timeout_secs 没有喂狗时
+/// 记录日志、发送 LOOP_STALL，并按 loop_stall_action 退出进程或重启系统。
+/// 重启与 reboot_system 受同样的限制（按卡住前最后一次喂狗时的许可），并同样记录重启原因。
+/// 通知使用单独的 Notifier（主循环卡住时它的 Notifier 可能正被占用）
+pub struct Watchdog {
+    last_pet: Arc<Mutex<Pet>>,
+}
+
+impl Watchdog {
+    pub fn start(
+        config: &Config,
+        notifier: Notifier,
+        system: RealSystem,
+        boot_record: PathBuf,
+        is_prod: bool,
+    ) -> Self {
+        let last_pet = Arc::new(Mutex::new(Pet {
+            at: Instant::now(),
+            reboot_allowed: false,
+            boot_record,
+        }));
+        let shared = Arc::clone(&last_pet);
+        let timeout = config.loop_stall_timeout;
+        let action = config.loop_stall_action;
+        thread::spawn(move || {
+            let mut system = system;
+            let mut check = StallCheck::new(timeout);
+            let mut longest = Duration::ZERO;
+            let interval = (timeout / 4).max(Duration::from_secs(1));
+            loop {
+                thread::sleep(interval);
+                let (since_pet, reboot_allowed, boot_path) = match shared.lock() {
+                    Ok(pet) => (
+                        pet.at.elapsed(),
+                        pet.reboot_allowed,
+                        pet.boot_record.clone(),
+                    ),
+                    Err(_) => continue,
+                };
+                longest = longest.max(since_pet);
+                match check.update(since_pet, longest) {
+                    Some(StallEvent::Stalled(stalled)) => {
+                        let action_name = match action {
+                            StallAction::Reboot if !reboot_allowed => "reboot_suppressed",
+                            _ => action.name(),
+                        };
+                        log_error(
+                            &format!(
+                                "Monitor loop stalled: no iteration for {}s (action: {})",
+                                stalled.as_secs(),
+                                action_name
+                            ),
+                            is_prod,
+                        );
+                        notifier.send_critical(
+                            &format!(
+                                "LOOP_STALL: STALLED={}s ACTION={}",
+                                stalled.as_secs(),
+                                action_name
+                            ),
+                            is_prod,
+                        );
+                        match action {
+                            StallAction::Log => {}
+                            StallAction::Exit => std::process::exit(STALL_EXIT_CODE),
+                            StallAction::Reboot if !reboot_allowed => log_warn(
+                                "Loop stall reboot suppressed (maintenance, reboot backoff or boot loop protection)",
+                                is_prod,
+                            ),
+                            StallAction::Reboot => {
+                                // 与 reboot_system 一样记录原因，下次启动时计入重启循环检测
+                                let mut record = BootRecord::load(boot_path);
+                                record.mark_reboot("loop_stall");
+                                system.reboot();
+                                record.clear_clean_shutdown();
+                                log_error("Loop stall reboot failed", is_prod);
+                            }
+                        }
+                    }
+                    Some(StallEvent::Recovered(longest)) => {
+                        log_message(
+                            &format!(
+                                "Monitor loop resumed after stalling for {}s",
+                                longest.as_secs()
+                            ),
+                            is_prod,
+                        );
+                        notifier.send(
+                            &format!("LOOP_RECOVERED: STALLED={}s", longest.as_secs()),
+                            is_prod,
+                        );
+                    }
+                    None => {}
+                }
+                if since_pet < timeout {
+                    longest = Duration::ZERO;
+                }
+            }
+        });
+        Watchdog { last_pet }
+    }
+
+    /// 主循环每轮调用一次，reboot_allowed 为此时是否允许主动重启
+    pub fn pet(&self, reboot_allowed: bool) {
+        if let Ok(mut pet) = self.last_pet.lock() {
+            pet.at = Instant::now();
+            pet.reboot_allowed = reboot_allowed;
+        }
+    }
+
+    /// 存储目录切换后启动记录的新路径
+    pub fn relocate(&self, boot_record: PathBuf) {
+        if let Ok(mut pet) = self.last_pet.lock() {
+            pet.boot_record = boot_record;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_check() {
+        let secs = Duration::from_secs;
+        let mut check = StallCheck::new(secs(60));
+        assert_eq!(check.update(secs(10), secs(10)), None);
+        assert_eq!(
+            check.update(secs(60), secs(60)),
+            Some(StallEvent::Stalled(secs(60)))
+        );
+        // 持续卡住时只报告一次
+        assert_eq!(check.update(secs(120), secs(120)), None);
+        assert_eq!(
+            check.update(secs(1), secs(135)),
+            Some(StallEvent::Recovered(secs(135)))
+        );
+        assert_eq!(check.update(secs(1), secs(1)), None);
+    }
+}